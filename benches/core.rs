@@ -0,0 +1,126 @@
+//! Benchmarks of the core operations, so performance regressions are caught
+//! over time.
+//!
+//! Run with `cargo bench --bench core`. The groups measure decoding with and
+//! without the decode cache, the step throughput on representative ROMs,
+//! drawing-heavy workloads and the conversion of the framebuffer into the
+//! RGBA texture frontends upload.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use chip8::{
+    display::{DisplayOptions, Filter},
+    octo,
+    processor::StepResult,
+    rng::Rng,
+    roms, Chip8,
+};
+
+/// The amount of instructions executed per iteration of the step
+/// benchmarks.
+const INSTRUCTIONS: u64 = 10_000;
+
+/// A loop of arithmetic, index and skip instructions, as found in the inner
+/// loops of most programs.
+const ALU_LOOP: &[u8] = &[
+    0x60, 0x01, // 6001: V0 = 1
+    0x71, 0x03, // 7103: V1 += 3
+    0x82, 0x14, // 8214: V2 += V1
+    0x83, 0x22, // 8322: V3 &= V2
+    0xA3, 0x00, // A300: I = 0x300
+    0xF0, 0x1E, // F01E: I += V0
+    0x31, 0x00, // 3100: skip if V1 == 0
+    0x84, 0x06, // 8406: V4 >>= 1
+    0x12, 0x00, // 1200: jump to 0x200
+];
+
+/// A loop drawing the 16 font digits across the display.
+const DRAW_LOOP: &[u8] = &[
+    0x63, 0x0F, // 630F: V3 = 0x0F
+    0xF2, 0x29, // F229: I = digit V2
+    0xD0, 0x15, // D015: draw it at (V0, V1)
+    0x70, 0x05, // 7005: V0 += 5
+    0x72, 0x01, // 7201: V2 += 1
+    0x82, 0x32, // 8232: V2 &= V3
+    0x12, 0x02, // 1202: jump to 0x202
+];
+
+/// Creates a system running the given ROM with a fixed seed and without the
+/// rewind history, so only the core is measured.
+fn system(rom: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::new_with_rng(Rng::new(0));
+    chip8.history.set_depth(0);
+    chip8.load_rom_data(rom.to_vec()).expect("the ROM fits");
+    chip8
+}
+
+/// Executes [`INSTRUCTIONS`] instructions, restarting the program whenever
+/// it halts or ends so every iteration does the same amount of work.
+fn run(chip8: &mut Chip8) {
+    for _ in 0..INSTRUCTIONS {
+        match chip8.step() {
+            Ok(StepResult::Continue | StepResult::WaitingForKey | StepResult::WaitingForVblank) => {
+            }
+            Ok(StepResult::Loop | StepResult::End) | Err(_) => {
+                chip8.reset_keep_rom().expect("the ROM fits");
+            }
+        }
+    }
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+    for (name, cached) in [("uncached", false), ("cached", true)] {
+        let mut chip8 = system(ALU_LOOP);
+        chip8.processor.set_decode_cache(cached);
+        group.bench_function(name, |b| b.iter(|| run(&mut chip8)));
+    }
+    group.finish();
+}
+
+fn step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("step");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+    let selftest = octo::assemble(include_str!("../roms/selftest/opcodes.8o"))
+        .expect("the test ROM assembles");
+    let noise = roms::find("noise").expect("the ROM is built in").data;
+    for (name, rom) in [("selftest", selftest.as_slice()), ("noise", noise)] {
+        let mut chip8 = system(rom);
+        group.bench_function(name, |b| b.iter(|| run(&mut chip8)));
+    }
+    group.finish();
+}
+
+fn draw(c: &mut Criterion) {
+    let mut group = c.benchmark_group("draw");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+    for (name, hires) in [("lores", false), ("hires", true)] {
+        let mut chip8 = system(DRAW_LOOP);
+        chip8.bus.graphics.set_hires(hires);
+        group.bench_function(name, |b| b.iter(|| run(&mut chip8)));
+    }
+    group.finish();
+}
+
+fn framebuffer(c: &mut Criterion) {
+    let mut chip8 = system(DRAW_LOOP);
+    run(&mut chip8);
+    let graphics = chip8.bus.graphics;
+
+    let mut group = c.benchmark_group("framebuffer");
+    group.bench_function("as_rgb8", |b| b.iter(|| graphics.as_rgb8()));
+    group.bench_function("to_rgba", |b| b.iter(|| graphics.to_rgba(1)));
+    for filter in Filter::ALL {
+        let options = DisplayOptions {
+            filter,
+            ..DisplayOptions::default()
+        };
+        let name = format!("render {}", filter.name().to_lowercase());
+        group.bench_function(name, |b| b.iter(|| options.render(&graphics, 8)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode, step, draw, framebuffer);
+criterion_main!(benches);
@@ -0,0 +1,39 @@
+//! Feeds arbitrary opcodes to the disassembler and executes each of them once
+//! from an arbitrary register state, including index registers pointing past
+//! the end of memory. Neither may panic.
+//!
+//! The input starts with the 16 general purpose registers and the index
+//! register as a big-endian `u16`, followed by the opcodes as big-endian
+//! `u16`s.
+
+#![no_main]
+
+use chip8::{
+    disassembler::{self, Syntax},
+    labels::Labels,
+    rng::Rng,
+    Chip8,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((state, opcodes)) = data.split_first_chunk::<18>() else {
+        return;
+    };
+    let labels = Labels::default();
+    for opcode in opcodes.chunks_exact(2) {
+        let opcode = u16::from_be_bytes([opcode[0], opcode[1]]);
+        for syntax in Syntax::ALL {
+            let _ = disassembler::disassemble_as(opcode, syntax, &labels);
+        }
+
+        let mut chip8 = Chip8::new_with_rng(Rng::new(0));
+        chip8.history.set_depth(0);
+        chip8
+            .load_rom_data(opcode.to_be_bytes().to_vec())
+            .expect("two bytes always fit");
+        chip8.processor.v.copy_from_slice(&state[..16]);
+        chip8.processor.i = usize::from(u16::from_be_bytes([state[16], state[17]]));
+        let _ = chip8.step();
+    }
+});
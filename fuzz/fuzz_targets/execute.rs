@@ -0,0 +1,42 @@
+//! Runs arbitrary ROMs for a bounded amount of instructions under every
+//! variant, with and without the Mega-Chip extensions and the decode cache.
+//! Programs may fail with a [`chip8::error::Chip8Error`], but never panic.
+//!
+//! The input starts with a byte selecting the variant and the options, and a
+//! big-endian `u16` of held keys, followed by the ROM.
+
+#![no_main]
+
+use chip8::{processor::BatchStop, quirks::Variant, rng::Rng, Chip8};
+use libfuzzer_sys::fuzz_target;
+
+/// The amount of instructions a ROM runs for at most.
+const MAX_INSTRUCTIONS: u32 = 10_000;
+
+/// The amount of instructions executed at once before the timers tick.
+const FRAME: u32 = 100;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&[options, keys_hi, keys_lo], rom)) = data.split_first_chunk::<3>() else {
+        return;
+    };
+    let mut chip8 = Chip8::new_with_rng(Rng::new(0));
+    chip8.history.set_depth(0);
+    chip8.set_variant(Variant::ALL[usize::from(options) % Variant::ALL.len()]);
+    chip8.set_megachip(options & 0x80 != 0);
+    chip8.processor.set_decode_cache(options & 0x40 != 0);
+    if chip8.load_rom_data(rom.to_vec()).is_err() {
+        return;
+    }
+    chip8.set_keys(u16::from_be_bytes([keys_hi, keys_lo]));
+
+    let mut executed = 0;
+    while executed < MAX_INSTRUCTIONS {
+        let batch = chip8.step_n(FRAME);
+        if batch.executed == 0 && batch.stop != BatchStop::WaitingForVblank {
+            break;
+        }
+        executed += batch.executed;
+        chip8.tick_timers();
+    }
+});
@@ -0,0 +1,75 @@
+/*
+ * C interface to the Chip8 interpreter core, exported by the `ffi` feature.
+ *
+ * Build the shared library with
+ *
+ *     cargo rustc --lib --release --features ffi --crate-type cdylib
+ *
+ * A system is created with chip8_new() and released with chip8_free(). All
+ * functions accept a null system and fail gracefully.
+ */
+
+#ifndef CHIP8_H
+#define CHIP8_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Results of chip8_step(). */
+#define CHIP8_CONTINUE 0
+#define CHIP8_WAITING_FOR_KEY 1
+#define CHIP8_LOOP 2
+#define CHIP8_END 3
+#define CHIP8_WAITING_FOR_VBLANK 4
+
+/* Returned by the functions below when they fail. */
+#define CHIP8_ERROR (-1)
+
+/* An opaque Chip8 system. */
+typedef struct Chip8 Chip8;
+
+/* Creates a new system, which must be released with chip8_free(). Its timers
+ * only tick through chip8_tick_60hz(). */
+Chip8 *chip8_new(void);
+
+/* Releases a system created by chip8_new(). */
+void chip8_free(Chip8 *chip8);
+
+/* Resets the system and loads the ROM of len bytes at data. Returns 0 on
+ * success, or CHIP8_ERROR if the ROM does not fit into memory. */
+int chip8_load_rom(Chip8 *chip8, const uint8_t *data, size_t len);
+
+/* Executes one instruction cycle. Returns one of the CHIP8_CONTINUE,
+ * CHIP8_WAITING_FOR_KEY, CHIP8_LOOP, CHIP8_END and CHIP8_WAITING_FOR_VBLANK
+ * results, or CHIP8_ERROR if the instruction cannot be executed. */
+int chip8_step(Chip8 *chip8);
+
+/* Decrements the delay and sound timers once and raises the vblank
+ * interrupt. chip8_step() never does, so call this at 60Hz. */
+void chip8_tick_60hz(Chip8 *chip8);
+
+/* Sets the state of all 16 keys at once, one bit per key with key 0 in the
+ * lowest bit. */
+void chip8_set_keys(Chip8 *chip8, uint16_t keys);
+
+/* Returns the width of the display in pixels. */
+size_t chip8_display_width(void);
+
+/* Returns the current height of the display in pixels, which changes with
+ * the hires mode. */
+size_t chip8_display_height(const Chip8 *chip8);
+
+/* Copies the palette index of every pixel into out, row by row, writing at
+ * most len bytes. Returns the amount of pixels of the display, which is
+ * larger than len if out was too small. */
+size_t chip8_get_framebuffer(const Chip8 *chip8, uint8_t *out, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CHIP8_H */
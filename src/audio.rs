@@ -0,0 +1,288 @@
+//! This module synthesizes the sound of the buzzer, which plays while the
+//! sound timer is non-zero.
+//!
+//! By default the buzzer is a tone of the configured [`Waveform`] and
+//! frequency. Once a program loads an XO-CHIP audio pattern with `F002`, the
+//! 128 bits of the pattern are played back instead, at the rate set by
+//! `Fx3A`.
+
+#[cfg(feature = "std")]
+use core::f32::consts::TAU;
+
+/// The length of an XO-CHIP audio pattern in bytes.
+pub const PATTERN_SIZE: usize = 16;
+
+/// The amount of bits in an XO-CHIP audio pattern.
+#[cfg(feature = "std")]
+const PATTERN_BITS: f32 = 128.0;
+
+/// The pitch register value at which a pattern plays at 4000 bits per second.
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// The shape of the buzzer tone.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Waveform {
+    /// A square wave, like the original buzzer.
+    #[default]
+    Square,
+    /// A sine wave.
+    Sine,
+    /// A triangle wave.
+    Triangle,
+}
+
+impl Waveform {
+    /// All waveforms, in the order they should be offered to the user.
+    pub const ALL: [Self; 3] = [Self::Square, Self::Sine, Self::Triangle];
+
+    /// Returns the display name of the waveform.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Square => "Square",
+            Self::Sine => "Sine",
+            Self::Triangle => "Triangle",
+        }
+    }
+
+    /// Returns the value of the waveform at the given phase, from `0.0` to
+    /// `1.0`, ranging from `-1.0` to `1.0`.
+    #[cfg(feature = "std")]
+    fn sample(self, phase: f32) -> f32 {
+        match self {
+            Self::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Self::Sine => (phase * TAU).sin(),
+            Self::Triangle => 4.0f32.mul_add(-(phase - 0.5).abs(), 1.0),
+        }
+    }
+}
+
+/// The XO-CHIP audio registers, written by `F002` and `Fx3A`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Audio {
+    /// The loaded audio pattern, or [`None`] if the program did not load one
+    /// and the buzzer tone is played.
+    pub pattern: Option<[u8; PATTERN_SIZE]>,
+
+    /// The pitch register.
+    pub pitch: u8,
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Self {
+            pattern: None,
+            pitch: DEFAULT_PITCH,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Audio {
+    /// Returns the rate at which the bits of the pattern are played, in bits
+    /// per second.
+    #[must_use]
+    pub fn playback_rate(&self) -> f32 {
+        4000.0 * ((f32::from(self.pitch) - 64.0) / 48.0).exp2()
+    }
+}
+
+/// The length of a key click in seconds, see [`Synth::click`].
+pub const CLICK_DURATION: f32 = 0.015;
+
+/// The pitch of a key click in Hz.
+pub const CLICK_FREQUENCY: f32 = 2000.0;
+
+/// Generates the samples of the buzzer.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Synth {
+    /// The shape of the buzzer tone.
+    pub waveform: Waveform,
+
+    /// The frequency of the buzzer tone in Hz.
+    pub frequency: f32,
+
+    /// The volume, from `0.0` for muted to `1.0`.
+    pub volume: f32,
+
+    /// Whether the buzzer is muted, independently of the volume.
+    pub muted: bool,
+
+    /// Whether [`Synth::click`] plays a short click, as an audible cue for
+    /// key presses.
+    pub key_clicks: bool,
+
+    /// The position within the current period of the tone, from `0.0` to
+    /// `1.0`, or within the pattern, from `0.0` to the amount of bits.
+    phase: f32,
+
+    /// The time left of the current click in seconds.
+    click: f32,
+}
+
+#[cfg(feature = "std")]
+impl Default for Synth {
+    fn default() -> Self {
+        Self {
+            waveform: Waveform::default(),
+            frequency: 440.0,
+            volume: 0.5,
+            muted: false,
+            key_clicks: false,
+            phase: 0.0,
+            click: 0.0,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Synth {
+    /// Creates a new [`Synth`] playing a square wave at 440 Hz.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts a short click that is mixed into the next samples, if
+    /// [`Synth::key_clicks`] is enabled. Call this whenever a key is pressed.
+    pub const fn click(&mut self) {
+        if self.key_clicks {
+            self.click = CLICK_DURATION;
+        }
+    }
+
+    /// Fills the given buffer with mono samples at the given sample rate.
+    /// While `playing` is [`false`], e.g. because the sound timer is zero,
+    /// the buffer is filled with silence, apart from a pending click. While
+    /// muted, it is filled with silence.
+    pub fn fill(&mut self, samples: &mut [f32], sample_rate: u32, audio: &Audio, playing: bool) {
+        if self.muted {
+            samples.fill(0.0);
+            self.phase = 0.0;
+            self.click = 0.0;
+            return;
+        }
+
+        #[allow(clippy::cast_precision_loss)] // sample rates are far below 2^24
+        let sample_rate = sample_rate as f32;
+        if !playing {
+            samples.fill(0.0);
+            self.phase = 0.0;
+        } else if let Some(pattern) = &audio.pattern {
+            let step = audio.playback_rate() / sample_rate;
+            for sample in samples.iter_mut() {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let bit = self.phase as usize;
+                let set = pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+                *sample = if set { self.volume } else { -self.volume };
+                self.phase = (self.phase + step) % PATTERN_BITS;
+            }
+        } else {
+            let step = self.frequency / sample_rate;
+            for sample in samples.iter_mut() {
+                *sample = self.waveform.sample(self.phase) * self.volume;
+                self.phase = (self.phase + step).fract();
+            }
+        }
+        self.mix_click(samples, sample_rate);
+    }
+
+    /// Adds the rest of the current click to the given samples: a square
+    /// wave at [`CLICK_FREQUENCY`] fading out over [`CLICK_DURATION`].
+    fn mix_click(&mut self, samples: &mut [f32], sample_rate: f32) {
+        for sample in samples {
+            if self.click <= 0.0 {
+                self.click = 0.0;
+                return;
+            }
+            let elapsed = CLICK_DURATION - self.click;
+            let level = if (elapsed * CLICK_FREQUENCY).fract() < 0.5 {
+                1.0
+            } else {
+                -1.0
+            };
+            let click = level * self.volume * self.click / CLICK_DURATION;
+            *sample = (*sample + click).clamp(-1.0, 1.0);
+            self.click -= 1.0 / sample_rate;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    fn assert_samples(samples: &[f32], expected: &[f32]) {
+        for (sample, expected) in samples.iter().zip(expected) {
+            assert!(
+                (sample - expected).abs() < 1e-4,
+                "{samples:?} != {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_tone() {
+        let mut synth = Synth::new();
+        synth.volume = 1.0;
+        let mut samples = [0.0; 4];
+
+        // 440 Hz at 1760 Hz sample rate is one period per 4 samples
+        synth.fill(&mut samples, 1760, &Audio::default(), true);
+        assert_samples(&samples, &[1.0, 1.0, -1.0, -1.0]);
+
+        synth.waveform = Waveform::Triangle;
+        synth.fill(&mut samples, 1760, &Audio::default(), true);
+        assert_samples(&samples, &[-1.0, 0.0, 1.0, 0.0]);
+
+        synth.fill(&mut samples, 1760, &Audio::default(), false);
+        assert_samples(&samples, &[0.0; 4]);
+    }
+
+    #[test]
+    fn test_click() {
+        let mut synth = Synth::new();
+        let mut samples = [0.0; 4];
+
+        // Clicks are off by default
+        synth.click();
+        synth.fill(&mut samples, 8000, &Audio::default(), false);
+        assert_samples(&samples, &[0.0; 4]);
+
+        // A click starts at full volume and fades out over 120 samples at
+        // 8000 Hz
+        synth.key_clicks = true;
+        synth.click();
+        let mut click = [0.0; 124];
+        synth.fill(&mut click, 8000, &Audio::default(), false);
+        assert_samples(&click, &[0.5]);
+        assert!(click[60].abs() < 0.3);
+        assert_samples(&click[120..], &[0.0; 4]);
+    }
+
+    #[test]
+    fn test_pattern() {
+        let mut synth = Synth::new();
+        let mut pattern = [0; PATTERN_SIZE];
+        pattern[0] = 0b1010_0000;
+        let audio = Audio {
+            pattern: Some(pattern),
+            pitch: DEFAULT_PITCH,
+        };
+        assert!((audio.playback_rate() - 4000.0).abs() < f32::EPSILON);
+
+        // One bit per sample at 4000 Hz
+        let mut samples = [0.0; 4];
+        synth.fill(&mut samples, 4000, &audio, true);
+        assert_samples(&samples, &[0.5, -0.5, 0.5, -0.5]);
+    }
+}
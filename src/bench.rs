@@ -0,0 +1,97 @@
+//! This module provides a headless benchmark of the emulator core, measuring
+//! how many instructions per second it can execute.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::{error::Chip8Error, processor::StepResult, Chip8};
+
+/// The result of a [`run`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BenchReport {
+    /// The amount of cycles that were executed.
+    pub instructions: u64,
+
+    /// The wall-clock time the cycles took.
+    pub elapsed: Duration,
+
+    /// The error that ended the benchmark early, if any.
+    pub error: Option<Chip8Error>,
+}
+
+impl BenchReport {
+    /// Returns the achieved amount of instructions per second.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn ips(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} instructions in {:.3}s ({:.0} instructions/sec)",
+            self.instructions,
+            self.elapsed.as_secs_f64(),
+            self.ips()
+        )?;
+        if let Some(error) = self.error {
+            write!(f, ", stopped by: {error}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Executes up to `instructions` cycles of the given [`Chip8`] as fast as
+/// possible, without any rate limiting.
+///
+/// The rewind history is disabled while the benchmark runs, so only the core
+/// itself is measured. The benchmark stops early when the program runs past the end of memory or
+/// raises an error. Programs that halt by jumping to themselves keep running,
+/// since every loop iteration still executes an instruction.
+pub fn run(chip8: &mut Chip8, instructions: u64) -> BenchReport {
+    let depth = chip8.history.depth();
+    chip8.history.set_depth(0);
+
+    let mut executed = 0;
+    let mut error = None;
+    let start = Instant::now();
+    while executed < instructions {
+        match chip8.step() {
+            Ok(StepResult::End) => break,
+            Ok(_) => executed += 1,
+            Err(err) => {
+                error = Some(err);
+                break;
+            }
+        }
+    }
+    let elapsed = start.elapsed();
+
+    chip8.history.set_depth(depth);
+    BenchReport {
+        instructions: executed,
+        elapsed,
+        error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let mut chip8 = Chip8::new();
+        // 7001: V0 += 1, 1200: jump to 0x200
+        chip8.load_rom_data(vec![0x70, 0x01, 0x12, 0x00]).unwrap();
+
+        let report = run(&mut chip8, 1000);
+        assert_eq!(report.instructions, 1000);
+        assert_eq!(report.error, None);
+        assert_eq!(chip8.processor.v[0], 244);
+        assert!(chip8.history.is_empty());
+    }
+}
@@ -0,0 +1,281 @@
+//! Runs a ROM without any frontend until an exit condition is met, then
+//! prints why it stopped, the registers and the display, e.g. for testing
+//! ROMs in CI.
+//!
+//! ```text
+//! chip8-headless <ROM> [--max-instructions=N] [--timeout=SECONDS] [--exit-on-loop]
+//!                [--dump-display=PATH] [--dump-state=PATH] [--trace]
+//!                [--trace-format=text|json] [--load-address=ADDR] [--log-level=FILTER]
+//!                [--log-file=PATH] [--crash-dir=PATH]
+//! chip8-headless selftest
+//! chip8-headless check <ROM> [--variant=NAME] [--megachip]
+//! ```
+//!
+//! `--load-address` loads the ROM at the given address, e.g. `0x600` for
+//! ETI-660 ROMs. Without it, ROMs that look like they expect a different load
+//! address are pointed out.
+//!
+//! With `--dump-display`, the display is written to the given file instead of
+//! being printed. `--dump-state` writes the full machine state as JSON once
+//! the program stopped. `--trace` prints the state before every instruction,
+//! one line each, as text or as JSON objects with `--trace-format=json`. The
+//! exit code is non-zero if the ROM cannot be loaded, the program raised an
+//! error or the timeout passed.
+//!
+//! `--log-level` sets the levels of the log messages by module, e.g.
+//! `processor=trace`, see [`chip8::logging::LogFilter`], and `--log-file`
+//! writes them to the given file instead of stderr. With `--crash-dir`, a
+//! [`chip8::fault::CrashReport`] is written to the given directory if the
+//! program raised an error.
+//!
+//! `selftest` runs the bundled test ROMs under every variant instead and
+//! prints which checks passed, see [`chip8::selftest`]. The exit code is
+//! non-zero if any check failed.
+//!
+//! `check` does not run the ROM, but prints a compatibility report for the
+//! variant given by its display name, e.g. `SCHIP`, or CHIP-8 by default,
+//! see [`chip8::compat`]. `--megachip` enables the Mega-Chip extensions. The
+//! exit code is non-zero if the ROM uses instructions the variant lacks.
+//!
+//! Messages about invalid arguments are shown in the language selected by
+//! `LANG`, see [`chip8::i18n::Language::from_env`].
+
+use std::{env, fs, fs::File, path::PathBuf, process::ExitCode, time::Duration};
+
+use chip8::{
+    compat::CompatReport,
+    fault::CrashReport,
+    headless::{self, ExitReason, HeadlessOptions},
+    i18n::Language,
+    logging::{self, LogFilter},
+    quirks::Variant,
+    rom,
+    selftest::Report,
+    trace::{self, TraceEntry, TraceFormat},
+    Chip8,
+};
+
+/// The command line usage.
+const USAGE: &str = "chip8-headless <ROM> [--max-instructions=N] [--timeout=SECONDS] \
+                     [--exit-on-loop] [--dump-display=PATH] [--dump-state=PATH] [--trace] \
+                     [--trace-format=text|json] [--load-address=ADDR] [--log-level=FILTER] \
+                     [--log-file=PATH] [--crash-dir=PATH]\n       \
+                     chip8-headless selftest\n       \
+                     chip8-headless check <ROM> [--variant=NAME] [--megachip]";
+
+fn main() -> ExitCode {
+    let language = Language::from_env();
+    if env::args().nth(1).as_deref() == Some("selftest") {
+        let report = Report::run();
+        print!("{report}");
+        return if report.passed() {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        };
+    }
+    if env::args().nth(1).as_deref() == Some("check") {
+        return check(language);
+    }
+
+    let mut rom = None;
+    let mut options = HeadlessOptions::default();
+    let mut dump_display = None;
+    let mut dump_state = None;
+    let mut trace = None;
+    let mut load_address = None;
+    let mut log_filter = LogFilter::default();
+    let mut log_file = None;
+    let mut crash_dir = None;
+    for arg in env::args().skip(1) {
+        if let Some(max) = arg.strip_prefix("--max-instructions=") {
+            let Ok(max) = max.parse() else {
+                eprintln!("{} {max}", language.translate("invalid instruction count"));
+                return ExitCode::FAILURE;
+            };
+            options.max_instructions = Some(max);
+            continue;
+        }
+        if let Some(seconds) = arg.strip_prefix("--timeout=") {
+            let timeout = seconds.parse().ok().map(Duration::try_from_secs_f64);
+            let Some(Ok(timeout)) = timeout else {
+                eprintln!("{} {seconds}", language.translate("invalid timeout"));
+                return ExitCode::FAILURE;
+            };
+            options.timeout = Some(timeout);
+            continue;
+        }
+        if let Some(path) = arg.strip_prefix("--dump-display=") {
+            dump_display = Some(path.to_string());
+            continue;
+        }
+        if let Some(path) = arg.strip_prefix("--dump-state=") {
+            dump_state = Some(path.to_string());
+            continue;
+        }
+        if let Some(addr) = arg.strip_prefix("--load-address=") {
+            let Some(addr) = parse_address(addr) else {
+                eprintln!("{} {addr}", language.translate("invalid load address"));
+                return ExitCode::FAILURE;
+            };
+            load_address = Some(addr);
+            continue;
+        }
+        if let Some(filter) = arg.strip_prefix("--log-level=") {
+            let Some(filter) = LogFilter::parse(filter) else {
+                eprintln!("{} {filter}", language.translate("invalid log level"));
+                return ExitCode::FAILURE;
+            };
+            log_filter = filter;
+            continue;
+        }
+        if let Some(path) = arg.strip_prefix("--log-file=") {
+            log_file = Some(path.to_string());
+            continue;
+        }
+        if let Some(path) = arg.strip_prefix("--crash-dir=") {
+            crash_dir = Some(PathBuf::from(path));
+            continue;
+        }
+        if let Some(name) = arg.strip_prefix("--trace-format=") {
+            let Some(format) = TraceFormat::from_name(name) else {
+                eprintln!("{} {name}", language.translate("unknown trace format"));
+                return ExitCode::FAILURE;
+            };
+            trace = Some(format);
+            continue;
+        }
+        match arg.as_str() {
+            "--exit-on-loop" => options.exit_on_loop = true,
+            "--trace" => trace = trace.or(Some(TraceFormat::Text)),
+            _ => rom = Some(arg),
+        }
+    }
+    let Some(rom) = rom else {
+        eprintln!("{}: {USAGE}", language.translate("usage"));
+        return ExitCode::FAILURE;
+    };
+    if let Some(path) = log_file {
+        match File::create(&path) {
+            Ok(file) => logging::logger().set_output(Some(Box::new(file))),
+            Err(err) => {
+                eprintln!("{} {path}: {err}", language.translate("cannot create"));
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    logging::init(log_filter).expect("no other logger is installed");
+
+    let mut chip8 = Chip8::new();
+    if let Some(addr) = load_address {
+        if let Err(err) = chip8.set_load_address(addr) {
+            log::error!("{err}");
+            return ExitCode::FAILURE;
+        }
+    }
+    if let Err(err) = chip8.load_rom_file(&rom) {
+        log::error!("cannot load {rom}: {err}");
+        return ExitCode::FAILURE;
+    }
+    let detected = rom::detect_load_address(chip8.rom());
+    if load_address.is_none() && detected != chip8.load_address() {
+        log::warn!(
+            "{rom} looks like it loads at {detected:#05X}, try --load-address={detected:#05X}"
+        );
+    }
+    let report = headless::run_traced(&mut chip8, &options, |chip8| {
+        if let Some(format) = trace {
+            println!("{}", TraceEntry::capture(chip8).format(format));
+        }
+    });
+    println!(
+        "stopped after {} instructions: {}",
+        report.instructions, report.reason
+    );
+    println!("{}", headless::dump_registers(&chip8));
+    if let (ExitReason::Error(err), Some(dir)) = (report.reason, &crash_dir) {
+        match CrashReport::capture(&chip8, err).save_to(dir) {
+            Ok(path) => println!(
+                "{} {}",
+                language.translate("wrote a crash report to"),
+                path.display()
+            ),
+            Err(err) => log::error!("cannot write a crash report: {err}"),
+        }
+    }
+
+    let display = headless::dump_display(&chip8.bus.graphics);
+    match dump_display {
+        Some(path) => {
+            if let Err(err) = fs::write(&path, display) {
+                log::error!("cannot write {path}: {err}");
+                return ExitCode::FAILURE;
+            }
+        }
+        None => print!("{display}"),
+    }
+
+    if let Some(path) = dump_state {
+        if let Err(err) = fs::write(&path, trace::dump_state(&chip8)) {
+            log::error!("cannot write {path}: {err}");
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if report.reason.is_success() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Runs the `check` command, printing the compatibility report of the ROM.
+fn check(language: Language) -> ExitCode {
+    let mut rom = None;
+    let mut variant = Variant::Chip8;
+    let mut megachip = false;
+    for arg in env::args().skip(2) {
+        if let Some(name) = arg.strip_prefix("--variant=") {
+            let Some(found) = Variant::ALL
+                .into_iter()
+                .find(|variant| variant.name().eq_ignore_ascii_case(name))
+            else {
+                eprintln!("{} {name}", language.translate("unknown variant"));
+                return ExitCode::FAILURE;
+            };
+            variant = found;
+            continue;
+        }
+        match arg.as_str() {
+            "--megachip" => megachip = true,
+            _ => rom = Some(arg),
+        }
+    }
+    let Some(rom) = rom else {
+        eprintln!("{}: {USAGE}", language.translate("usage"));
+        return ExitCode::FAILURE;
+    };
+
+    // Loading assembles Octo sources, and checks the size against the memory
+    let mut chip8 = Chip8::new();
+    chip8.set_megachip(megachip);
+    if let Err(err) = chip8.load_rom_file(&rom) {
+        eprintln!("{} {rom}: {err}", language.translate("cannot load"));
+        return ExitCode::FAILURE;
+    }
+    let report = CompatReport::new(chip8.rom(), variant, megachip);
+    println!("{report}");
+    if report.is_compatible() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Parses an address given in hex with a `0x` prefix, or in decimal.
+fn parse_address(text: &str) -> Option<usize> {
+    match text.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
@@ -0,0 +1,490 @@
+//! A lightweight frontend built on `winit` and `pixels`, for low-latency
+//! (fullscreen) play on weak hardware. It also serves as a reference
+//! implementation of [`Chip8Frontend`].
+//!
+//! ```text
+//! chip8-pixels <ROM> [--fullscreen] [--scanlines] [--fit] [--phosphor=FRAMES] [--theme=NAME]
+//!              [--pause-in-background] [--watch] [--load-address=ADDR]
+//!              [--save-region=ADDR:LEN] [--log-level=FILTER] [--log-file=PATH]
+//!              [--crash-dir=PATH]
+//! ```
+//!
+//! The display is scaled by whole factors and letterboxed, or as large as
+//! fits while keeping its aspect ratio with `--fit`. The window opens at a
+//! size that covers every display pixel with a whole number of physical
+//! pixels, also on high-DPI screens, and keeps doing so when it moves to a
+//! screen with another scale factor. With
+//! `--phosphor`, turned-off pixels fade out over the given amount of frames.
+//! `--theme` selects one of the preset [`THEMES`]. While the window is not
+//! focused, the emulator is throttled, or paused with
+//! `--pause-in-background`. With `--watch`, the ROM is reloaded whenever its
+//! file changes. `--load-address` loads the ROM at the given address, e.g.
+//! `0x600` for ETI-660 ROMs. `--save-region` reserves a memory region whose
+//! contents are kept in the ROM's save file, next to its RPL flags.
+//! `--log-level` sets the levels of the log messages by module, e.g.
+//! `info,processor=trace`, see [`LogFilter`], and `--log-file` writes them to
+//! the given file instead of stderr. Whenever the program raises an error, a
+//! [`CrashReport`] is written to `--crash-dir`, which defaults to the
+//! `chip8/crashes` directory in the platform data directory. While the
+//! program waits for a key press, the window is
+//! redrawn at 60 Hz at most and wakes up on key events. The keypad is bound
+//! to the default [`QWERTY`] layout and the emulator controls to the default
+//! [`Hotkeys`]. F11 toggles fullscreen and Escape quits. Messages about
+//! invalid arguments are shown in the language selected by `LANG`, see
+//! [`Language::from_env`].
+//!
+//! [`CrashReport`]: chip8::fault::CrashReport
+//! [`Hotkeys`]: chip8::hotkeys::Hotkeys
+//! [`Language::from_env`]: chip8::i18n::Language::from_env
+//! [`LogFilter`]: chip8::logging::LogFilter
+//! [`QWERTY`]: chip8::keymap::QWERTY
+//! [`THEMES`]: chip8::theme::THEMES
+
+use std::{
+    env,
+    fs::File,
+    ops::Range,
+    path::PathBuf,
+    process::ExitCode,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chip8::{
+    display::{DisplayOptions, Filter, Phosphor, Scaling},
+    frontend::Chip8Frontend,
+    graphics::{Framebuffer, HEIGHT, WIDTH},
+    hotkeys::Hotkeys,
+    i18n::Language,
+    keymap::{Keymap, KEY_COUNT},
+    logging::{self, LogFilter},
+    runner::{Chip8Runner, FocusBehavior},
+    storage::Storage,
+    watch::RomWatcher,
+    Chip8,
+};
+use pixels::{Pixels, ScalingMode, SurfaceTexture};
+use winit::{
+    application::ApplicationHandler,
+    dpi::PhysicalSize,
+    event::{ElementState, StartCause, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::{Key, NamedKey},
+    window::{Fullscreen, Window, WindowId},
+};
+
+/// The command line usage.
+const USAGE: &str = "chip8-pixels <ROM> [--fullscreen] [--scanlines] [--fit] \
+                     [--phosphor=FRAMES] [--theme=NAME] [--pause-in-background] [--watch] \
+                     [--load-address=ADDR] [--save-region=ADDR:LEN] [--log-level=FILTER] \
+                     [--log-file=PATH] [--crash-dir=PATH]";
+
+/// The title of the window.
+const TITLE: &str = "Chip8";
+
+/// The initial size of a display pixel in logical window pixels.
+const SCALE: u32 = 10;
+
+/// The size of a display pixel in the pixel buffer, which leaves room for
+/// the scanline filter.
+const BUFFER_SCALE: usize = 4;
+
+/// The time between redraws while the program sleeps until a key press.
+const SLEEP_FRAME: Duration = Duration::from_millis(16);
+
+/// Presents the emulator in a window.
+struct PixelsFrontend {
+    window: Arc<Window>,
+    pixels: Pixels<'static>,
+    options: DisplayOptions,
+    phosphor: Phosphor,
+    keys: [bool; KEY_COUNT],
+    beeping: bool,
+}
+
+impl Chip8Frontend for PixelsFrontend {
+    fn draw(&mut self, fb: &Framebuffer) {
+        #[allow(clippy::cast_possible_truncation)] // the display is small
+        let (width, height) = (
+            (WIDTH * BUFFER_SCALE) as u32,
+            (fb.height() * BUFFER_SCALE) as u32,
+        );
+        if self.pixels.texture().height() != height {
+            // The program entered or left the two-page hires mode
+            if let Err(err) = self.pixels.resize_buffer(width, height) {
+                log::error!("cannot resize: {err}");
+                return;
+            }
+        }
+        let rgb = self.phosphor.apply(&fb.as_rgb8());
+        let rgba = self.options.render_rgb(rgb, WIDTH, BUFFER_SCALE);
+        self.pixels.frame_mut().copy_from_slice(&rgba);
+        if let Err(err) = self.pixels.render() {
+            log::error!("cannot render: {err}");
+        }
+    }
+
+    fn keys(&self) -> [bool; KEY_COUNT] {
+        self.keys
+    }
+
+    fn beep(&mut self, on: bool) {
+        // There is no audio output, so the buzzer is shown in the title
+        if on != self.beeping {
+            self.beeping = on;
+            self.window
+                .set_title(if on { "Chip8 \u{266A}" } else { TITLE });
+        }
+    }
+}
+
+/// The state of the application.
+struct App {
+    runner: Chip8Runner,
+    keymap: Keymap,
+    hotkeys: Hotkeys,
+    shift: bool,
+    options: DisplayOptions,
+    watcher: Option<RomWatcher>,
+    #[cfg(feature = "persistence")]
+    rom: String,
+    frontend: Option<PixelsFrontend>,
+}
+
+impl App {
+    /// Creates the window and its pixel buffer.
+    fn create_frontend(&self, event_loop: &ActiveEventLoop) -> Result<PixelsFrontend, String> {
+        let scale_factor = event_loop
+            .primary_monitor()
+            .map_or(1.0, |monitor| monitor.scale_factor());
+        let attributes = Window::default_attributes()
+            .with_title(TITLE)
+            .with_inner_size(window_size(scale_factor))
+            .with_fullscreen(
+                self.options
+                    .fullscreen
+                    .then_some(Fullscreen::Borderless(None)),
+            );
+        let window = Arc::new(
+            event_loop
+                .create_window(attributes)
+                .map_err(|err| err.to_string())?,
+        );
+        let inner = window.inner_size();
+        let surface = SurfaceTexture::new(inner.width, inner.height, window.clone());
+        #[allow(clippy::cast_possible_truncation)]
+        let mut pixels = Pixels::new(
+            (WIDTH * BUFFER_SCALE) as u32,
+            (HEIGHT * BUFFER_SCALE) as u32,
+            surface,
+        )
+        .map_err(|err| err.to_string())?;
+        pixels.set_scaling_mode(match self.options.scaling {
+            Scaling::Integer => ScalingMode::PixelPerfect,
+            Scaling::Fit | Scaling::Stretch => ScalingMode::Fill,
+        });
+        Ok(PixelsFrontend {
+            window,
+            pixels,
+            options: self.options,
+            phosphor: Phosphor::new(self.options.phosphor_frames),
+            keys: [false; KEY_COUNT],
+            beeping: false,
+        })
+    }
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.frontend.is_some() {
+            return;
+        }
+        match self.create_frontend(event_loop) {
+            Ok(frontend) => {
+                frontend.window.request_redraw();
+                self.frontend = Some(frontend);
+            }
+            Err(err) => {
+                log::error!("cannot create window: {err}");
+                event_loop.exit();
+            }
+        }
+    }
+
+    fn new_events(&mut self, event_loop: &ActiveEventLoop, cause: StartCause) {
+        if let (StartCause::ResumeTimeReached { .. }, Some(frontend)) = (cause, &self.frontend) {
+            event_loop.set_control_flow(ControlFlow::Wait);
+            frontend.window.request_redraw();
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        let Some(frontend) = self.frontend.as_mut() else {
+            return;
+        };
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Focused(focused) => self.runner.set_focused(focused),
+            WindowEvent::Resized(size) => {
+                if let Err(err) = frontend.pixels.resize_surface(size.width, size.height) {
+                    log::error!("cannot resize: {err}");
+                }
+            }
+            WindowEvent::ScaleFactorChanged {
+                scale_factor,
+                mut inner_size_writer,
+            } if !self.options.fullscreen => {
+                if let Err(err) = inner_size_writer.request_inner_size(window_size(scale_factor)) {
+                    log::warn!("cannot resize: {err}");
+                }
+            }
+            WindowEvent::ModifiersChanged(modifiers) => self.shift = modifiers.state().shift_key(),
+            WindowEvent::KeyboardInput { event, .. } => {
+                let pressed = event.state == ElementState::Pressed;
+                let name = match &event.logical_key {
+                    Key::Named(NamedKey::Escape) => return event_loop.exit(),
+                    Key::Named(NamedKey::F11) if pressed && !event.repeat => {
+                        self.options.fullscreen = !self.options.fullscreen;
+                        frontend.window.set_fullscreen(
+                            self.options
+                                .fullscreen
+                                .then_some(Fullscreen::Borderless(None)),
+                        );
+                        return;
+                    }
+                    Key::Named(key) => format!("{key:?}"),
+                    Key::Character(text) => text.to_string(),
+                    _ => return,
+                };
+                if let Some(action) = self.hotkeys.action(&name, self.shift) {
+                    if pressed && !event.repeat {
+                        frontend.window.request_redraw();
+                        self.runner.perform(action);
+                    }
+                    return;
+                }
+                if let Some(key_code) = self.keymap.key_code(&name) {
+                    frontend.keys[usize::from(key_code)] = pressed;
+                    frontend.window.request_redraw();
+                }
+            }
+            WindowEvent::RedrawRequested => {
+                if let Some(watcher) = &self.watcher {
+                    match watcher.reload(&mut self.runner.chip8) {
+                        Ok(true) => self.runner.resume(),
+                        Ok(false) => {}
+                        Err(err) => log::error!("cannot reload: {err}"),
+                    }
+                }
+                if let Some(event) = self.runner.run_frame(frontend) {
+                    log::info!("{event:?}");
+                }
+                #[cfg(feature = "persistence")]
+                save_data(&mut self.runner.chip8, &self.rom);
+                if self.runner.is_sleeping() {
+                    event_loop
+                        .set_control_flow(ControlFlow::WaitUntil(Instant::now() + SLEEP_FRAME));
+                } else {
+                    event_loop.set_control_flow(ControlFlow::Wait);
+                    frontend.window.request_redraw();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Saves the RPL flags and the storage of the ROM at the given path to their
+/// sidecar files once the program changed them.
+#[cfg(feature = "persistence")]
+fn save_data(chip8: &mut Chip8, rom: &str) {
+    if chip8.bus.flags.take_changed() {
+        if let Err(err) = chip8.bus.flags.save_for_rom(rom) {
+            log::error!("cannot save flags: {err}");
+        }
+    }
+    chip8.sync_storage();
+    if chip8.storage.take_changed() {
+        if let Err(err) = chip8.storage.save_for_rom(rom) {
+            log::error!("cannot save data: {err}");
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let language = Language::from_env();
+    let mut rom = None;
+    let mut options = DisplayOptions::default();
+    let mut theme = None;
+    let mut focus_behavior = FocusBehavior::Throttle;
+    let mut watch = false;
+    let mut load_address = None;
+    let mut save_region = None;
+    let mut log_filter = LogFilter::default();
+    let mut log_file = None;
+    #[cfg(feature = "persistence")]
+    let mut crash_dir = chip8::fault::CrashReport::default_dir();
+    #[cfg(not(feature = "persistence"))]
+    let mut crash_dir = None;
+    for arg in env::args().skip(1) {
+        if let Some(frames) = arg.strip_prefix("--phosphor=") {
+            options.phosphor_frames = frames.parse().unwrap_or_default();
+            continue;
+        }
+        if let Some(addr) = arg.strip_prefix("--load-address=") {
+            let Some(addr) = parse_address(addr) else {
+                eprintln!("{} {addr}", language.translate("invalid load address"));
+                return ExitCode::FAILURE;
+            };
+            load_address = Some(addr);
+            continue;
+        }
+        if let Some(region) = arg.strip_prefix("--save-region=") {
+            let Some(region) = parse_region(region) else {
+                eprintln!("{} {region}", language.translate("invalid save region"));
+                return ExitCode::FAILURE;
+            };
+            save_region = Some(region);
+            continue;
+        }
+        if let Some(filter) = arg.strip_prefix("--log-level=") {
+            let Some(filter) = LogFilter::parse(filter) else {
+                eprintln!("{} {filter}", language.translate("invalid log level"));
+                return ExitCode::FAILURE;
+            };
+            log_filter = filter;
+            continue;
+        }
+        if let Some(path) = arg.strip_prefix("--log-file=") {
+            log_file = Some(path.to_string());
+            continue;
+        }
+        if let Some(path) = arg.strip_prefix("--crash-dir=") {
+            crash_dir = Some(PathBuf::from(path));
+            continue;
+        }
+        if let Some(name) = arg.strip_prefix("--theme=") {
+            theme = chip8::theme::find(name);
+            if theme.is_none() {
+                eprintln!("{} {name}", language.translate("unknown theme"));
+            }
+            continue;
+        }
+        match arg.as_str() {
+            "--fullscreen" => options.fullscreen = true,
+            "--scanlines" => options.filter = Filter::Scanlines,
+            "--fit" => options.scaling = Scaling::Fit,
+            "--pause-in-background" => focus_behavior = FocusBehavior::Pause,
+            "--watch" => watch = true,
+            _ => rom = Some(arg),
+        }
+    }
+    let Some(rom) = rom else {
+        eprintln!("{}: {USAGE}", language.translate("usage"));
+        return ExitCode::FAILURE;
+    };
+    if let Some(path) = log_file {
+        match File::create(&path) {
+            Ok(file) => logging::logger().set_output(Some(Box::new(file))),
+            Err(err) => {
+                eprintln!("{} {path}: {err}", language.translate("cannot create"));
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    logging::init(log_filter).expect("no other logger is installed");
+
+    let mut chip8 = Chip8::new();
+    if let Some(addr) = load_address {
+        if let Err(err) = chip8.set_load_address(addr) {
+            log::error!("{err}");
+            return ExitCode::FAILURE;
+        }
+    }
+    if let Err(err) = chip8.load_rom_file(&rom) {
+        log::error!("cannot load {rom}: {err}");
+        return ExitCode::FAILURE;
+    }
+    let detected = chip8::rom::detect_load_address(chip8.rom());
+    if load_address.is_none() && detected != chip8.load_address() {
+        log::warn!(
+            "{rom} looks like it loads at {detected:#05X}, try --load-address={detected:#05X}"
+        );
+    }
+    #[cfg(feature = "persistence")]
+    match chip8::flags::RplFlags::load_for_rom(&rom) {
+        Ok(flags) => chip8.bus.flags = flags,
+        Err(err) => log::warn!("cannot load flags: {err}"),
+    }
+    #[cfg(feature = "persistence")]
+    let mut storage = Storage::load_for_rom(&rom).unwrap_or_else(|err| {
+        log::warn!("cannot load data: {err}");
+        Storage::new()
+    });
+    #[cfg(not(feature = "persistence"))]
+    let mut storage = Storage::new();
+    if save_region.is_some() {
+        storage.set_region(save_region);
+    }
+    chip8.set_storage(storage);
+    if let Some(theme) = theme {
+        chip8.bus.graphics.set_palette(theme.palette);
+    }
+    let watcher = match watch.then(|| RomWatcher::new(&rom)).transpose() {
+        Ok(watcher) => watcher,
+        Err(err) => {
+            log::error!("cannot watch {rom}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut runner = Chip8Runner::new(chip8);
+    runner.set_focus_behavior(focus_behavior);
+    runner.set_power_saving(true);
+    runner.set_crash_dir(crash_dir);
+    let mut app = App {
+        runner,
+        keymap: Keymap::new(),
+        hotkeys: Hotkeys::new(),
+        shift: false,
+        options,
+        watcher,
+        #[cfg(feature = "persistence")]
+        rom,
+        frontend: None,
+    };
+    let result = EventLoop::new().and_then(|event_loop| event_loop.run_app(&mut app));
+    if let Err(err) = result {
+        log::error!("{err}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// Returns the physical size of the window for a screen with the given scale
+/// factor, so every display pixel covers about [`SCALE`] logical pixels and
+/// the pixel buffer is scaled by a whole factor.
+fn window_size(scale_factor: f64) -> PhysicalSize<u32> {
+    #[allow(clippy::cast_possible_truncation)] // the display is small
+    let (width, height) = DisplayOptions::window_size(
+        (WIDTH * BUFFER_SCALE) as u32,
+        (HEIGHT * BUFFER_SCALE) as u32,
+        f64::from(SCALE) / BUFFER_SCALE as f64,
+        scale_factor,
+    );
+    PhysicalSize::new(width, height)
+}
+
+/// Parses a memory region given as `ADDR:LEN`.
+fn parse_region(text: &str) -> Option<Range<usize>> {
+    let (addr, len) = text.split_once(':')?;
+    let addr = parse_address(addr)?;
+    Some(addr..addr.checked_add(parse_address(len)?)?)
+}
+
+/// Parses an address given in hex with a `0x` prefix, or in decimal.
+fn parse_address(text: &str) -> Option<usize> {
+    match text.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
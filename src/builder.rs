@@ -0,0 +1,162 @@
+//! This module provides [`Chip8Builder`], which configures a new [`Chip8`]
+//! system and loads a ROM into it in one validated step.
+//!
+//! ```
+//! use chip8::{quirks::Variant, Chip8};
+//!
+//! let chip8 = Chip8::builder()
+//!     .rom(&[0x60, 0x05])
+//!     .variant(Variant::SuperChip)
+//!     .seed(42)
+//!     .build()
+//!     .unwrap();
+//! assert_eq!(chip8.processor.pc, 0x200);
+//! ```
+
+use alloc::vec::Vec;
+
+use crate::{
+    error::Chip8Error,
+    memory::PROGRAM_START,
+    quirks::{Quirks, Variant},
+    rng::Rng,
+    Chip8,
+};
+
+/// Configures and creates a [`Chip8`] system.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct Chip8Builder {
+    rom: Option<Vec<u8>>,
+    quirks: Quirks,
+    seed: Option<u64>,
+    load_address: usize,
+    megachip: bool,
+    paused: bool,
+}
+
+impl Default for Chip8Builder {
+    fn default() -> Self {
+        Self {
+            rom: None,
+            quirks: Quirks::default(),
+            seed: None,
+            load_address: PROGRAM_START,
+            megachip: false,
+            paused: false,
+        }
+    }
+}
+
+impl Chip8Builder {
+    /// Creates a new [`Chip8Builder`] for a system without a ROM, with the
+    /// default quirks and a random seed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the ROM loaded into the system.
+    pub fn rom(mut self, data: &[u8]) -> Self {
+        self.rom = Some(data.to_vec());
+        self
+    }
+
+    /// Sets the quirks to the profile of the given [`Variant`].
+    pub const fn variant(mut self, variant: Variant) -> Self {
+        self.quirks = variant.quirks();
+        self
+    }
+
+    /// Sets the quirks of the emulated interpreter.
+    pub const fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Seeds the random number generator, so runs are reproducible. Without
+    /// a seed, it is seeded from the operating system's entropy source, or
+    /// with 0 without the `std` feature.
+    pub const fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Sets the address the ROM is loaded at and execution starts from, see
+    /// [`Chip8::set_load_address`].
+    pub const fn start_address(mut self, addr: usize) -> Self {
+        self.load_address = addr;
+        self
+    }
+
+    /// Enables the Mega-Chip extensions, see [`Chip8::set_megachip`].
+    pub const fn megachip(mut self, enabled: bool) -> Self {
+        self.megachip = enabled;
+        self
+    }
+
+    /// Starts the system paused through [`Chip8::controls`], e.g. to step
+    /// through the program from its first instruction.
+    pub const fn paused(mut self, paused: bool) -> Self {
+        self.paused = paused;
+        self
+    }
+
+    /// Creates the configured system.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::EmptyRom`] if an empty ROM was given,
+    /// [`Chip8Error::InvalidLoadAddress`] if the start address lies before
+    /// [`PROGRAM_START`] or outside of memory, and
+    /// [`Chip8Error::RomTooLarge`] if the ROM does not fit into memory.
+    pub fn build(self) -> Result<Chip8, Chip8Error> {
+        #[cfg(feature = "std")]
+        let rng = self.seed.map_or_else(Rng::from_entropy, Rng::new);
+        #[cfg(not(feature = "std"))]
+        let rng = Rng::new(self.seed.unwrap_or_default());
+        let mut chip8 = Chip8::new_with_rng(rng);
+        chip8.processor.quirks = self.quirks;
+        chip8.set_megachip(self.megachip);
+        chip8.set_load_address(self.load_address)?;
+        match self.rom {
+            Some(rom) if rom.is_empty() => return Err(Chip8Error::EmptyRom),
+            Some(rom) => chip8.reset_and_load(rom)?,
+            None => chip8.reset(),
+        }
+        if self.paused {
+            chip8.controls.pause();
+        }
+        Ok(chip8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MAX_ROM_SIZE;
+
+    #[test]
+    fn test_build() {
+        let chip8 = Chip8::builder()
+            .rom(&[0x60, 0x05])
+            .start_address(0x600)
+            .seed(7)
+            .paused(true)
+            .build()
+            .unwrap();
+        assert_eq!(chip8.processor.pc, 0x600);
+        assert_eq!(chip8.bus.memory[0x601], 0x05);
+        assert_eq!(chip8.processor.rng.seed(), 7);
+        assert!(chip8.controls.is_paused());
+
+        let result = Chip8::builder().rom(&[]).build();
+        assert_eq!(result.err(), Some(Chip8Error::EmptyRom));
+        let result = Chip8::builder().rom(&[0; MAX_ROM_SIZE + 1]).build();
+        assert!(matches!(result, Err(Chip8Error::RomTooLarge { .. })));
+        let result = Chip8::builder().start_address(0x100).build();
+        assert_eq!(
+            result.err(),
+            Some(Chip8Error::InvalidLoadAddress { addr: 0x100 })
+        );
+    }
+}
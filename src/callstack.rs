@@ -0,0 +1,99 @@
+//! This module reconstructs the subroutine call stack of the [`super::Chip8`]
+//! from the return addresses on its stack.
+
+use crate::{
+    disassembler::{self, Syntax},
+    labels::Labels,
+    memory::Memory,
+    processor::Cpu,
+};
+use alloc::{format, string::String, vec::Vec};
+
+/// A subroutine call that has not returned yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallFrame {
+    /// The address of the `2nnn` instruction that made the call.
+    pub call_site: usize,
+
+    /// The address the call will return to.
+    pub return_address: usize,
+
+    /// The opcode at the call site, if it lies within memory. This is
+    /// normally the `2nnn` instruction, unless the program modified it since.
+    pub opcode: Option<u16>,
+}
+
+impl CallFrame {
+    /// Returns the address of the called subroutine, if the call site still
+    /// holds a `2nnn` instruction.
+    #[must_use]
+    pub fn target(&self) -> Option<usize> {
+        self.opcode
+            .filter(|opcode| opcode & 0xF000 == 0x2000)
+            .map(|opcode| usize::from(opcode & 0x0FFF))
+    }
+
+    /// Describes the call as its call site disassembled in the given
+    /// [`Syntax`] and its return address, using the given labels for
+    /// addresses.
+    #[must_use]
+    pub fn describe(&self, labels: &Labels, syntax: Syntax) -> String {
+        let instruction = self
+            .opcode
+            .and_then(|opcode| disassembler::disassemble_as(opcode, syntax, labels))
+            .unwrap_or_else(|| "???".into());
+        format!(
+            "{}  {instruction}  -> {}",
+            labels.format_address(self.call_site),
+            labels.format_address(self.return_address)
+        )
+    }
+}
+
+/// Returns the pending subroutine calls of the given [`Cpu`], with the most
+/// recent call first.
+#[must_use]
+pub fn call_stack(cpu: &Cpu, memory: &Memory) -> Vec<CallFrame> {
+    cpu.stack[..cpu.sp.min(cpu.stack.len())]
+        .iter()
+        .rev()
+        .map(|&return_address| {
+            let call_site = return_address.wrapping_sub(2);
+            let opcode = memory.read_opcode(call_site).ok();
+            CallFrame {
+                call_site,
+                return_address,
+                opcode,
+            }
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{disassembler::Syntax, Chip8};
+
+    #[test]
+    fn test_call_stack() {
+        let mut chip8 = Chip8::new();
+        // 2204: call 0x204, 0000, 2208: call 0x208, 0000, 00EE: return
+        chip8
+            .load_rom_data(vec![
+                0x22, 0x04, 0x00, 0x00, 0x22, 0x08, 0x00, 0x00, 0x00, 0xEE,
+            ])
+            .unwrap();
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        chip8.labels.set(0x208, "inner");
+
+        let frames = chip8.call_stack();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].call_site, 0x204);
+        assert_eq!(frames[0].target(), Some(0x208));
+        assert_eq!(frames[1].return_address, 0x202);
+        assert_eq!(
+            frames[0].describe(&chip8.labels, Syntax::Cowgod),
+            "0x0204  CALL inner  -> 0x0206"
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! This module provides cheats that freeze a memory address or register at a
+//! fixed value, e.g. to keep the lives counter of a game at 3.
+//!
+//! The [`super::Chip8`] applies all enabled cheats before every instruction.
+//! With the `persistence` feature enabled, the cheat list of a ROM can be
+//! stored in a JSON sidecar file next to it.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+#[cfg(feature = "persistence")]
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "persistence")]
+use crate::sidecar;
+use crate::{memory::Memory, processor::Cpu};
+
+/// The extension of the sidecar file holding the cheats of a ROM.
+#[cfg(feature = "persistence")]
+pub const SIDECAR_EXTENSION: &str = "cht";
+
+/// The location a [`Cheat`] writes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Target {
+    /// The byte at the given memory address.
+    Memory(usize),
+    /// The register `Vx` with the given index.
+    Register(u8),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Memory(address) => write!(f, "mem[{address:#06X}]"),
+            Self::Register(x) => write!(f, "V{x:X}"),
+        }
+    }
+}
+
+/// A memory address or register that is frozen at a value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cheat {
+    /// A user-chosen description, e.g. "Infinite lives".
+    pub name: String,
+
+    /// The location the value is written to.
+    pub target: Target,
+
+    /// The value the target is frozen at.
+    pub value: u8,
+
+    /// Whether the cheat is applied.
+    pub enabled: bool,
+}
+
+impl Cheat {
+    /// Creates a new, enabled [`Cheat`].
+    #[must_use]
+    pub fn new(name: impl Into<String>, target: Target, value: u8) -> Self {
+        Self {
+            name: name.into(),
+            target,
+            value,
+            enabled: true,
+        }
+    }
+
+    /// Writes the value to the target. Targets outside of memory or the
+    /// register file are ignored.
+    fn apply(&self, cpu: &mut Cpu, memory: &mut Memory) {
+        match self.target {
+            Target::Memory(address) => {
+                let _ = memory.write(address, self.value);
+            }
+            Target::Register(x) => {
+                if let Some(register) = cpu.v.get_mut(usize::from(x)) {
+                    *register = self.value;
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Cheat {
+    /// Formats the cheat like `Infinite lives: mem[0x03A0] = 0x03`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} = {:#04X}", self.name, self.target, self.value)
+    }
+}
+
+/// The list of cheats of a ROM.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+    /// Creates an empty list of [`Cheats`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a cheat to the list.
+    pub fn push(&mut self, cheat: Cheat) {
+        self.cheats.push(cheat);
+    }
+
+    /// Removes and returns the cheat at the given index, if any.
+    pub fn remove(&mut self, index: usize) -> Option<Cheat> {
+        (index < self.cheats.len()).then(|| self.cheats.remove(index))
+    }
+
+    /// Enables or disables the cheat at the given index. Returns whether the
+    /// cheat exists.
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        self.cheats
+            .get_mut(index)
+            .map(|cheat| cheat.enabled = enabled)
+            .is_some()
+    }
+
+    /// Returns the cheat at the given index, if any.
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&Cheat> {
+        self.cheats.get(index)
+    }
+
+    /// Returns an iterator over all cheats, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = &Cheat> {
+        self.cheats.iter()
+    }
+
+    /// Returns the amount of cheats.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.cheats.len()
+    }
+
+    /// Returns whether there are no cheats.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.cheats.is_empty()
+    }
+
+    /// Removes all cheats.
+    pub fn clear(&mut self) {
+        self.cheats.clear();
+    }
+
+    /// Writes the values of all enabled cheats to their targets.
+    pub fn apply(&self, cpu: &mut Cpu, memory: &mut Memory) {
+        for cheat in self.cheats.iter().filter(|cheat| cheat.enabled) {
+            cheat.apply(cpu, memory);
+        }
+    }
+
+    /// Deserializes the [`Cheats`] of the ROM with the given hash from a
+    /// sidecar JSON string. Returns [`None`] if the sidecar belongs to a
+    /// different ROM.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the string is not a valid sidecar.
+    #[cfg(feature = "persistence")]
+    pub fn from_json(json: &str, rom_hash: &str) -> Result<Option<Self>, serde_json::Error> {
+        sidecar::from_json(json, rom_hash)
+    }
+
+    /// Serializes the [`Cheats`] into a sidecar JSON string for the ROM with
+    /// the given hash.
+    ///
+    /// # Panics
+    ///
+    /// Never panics in practice, since [`Cheats`] always serialize to JSON.
+    #[cfg(feature = "persistence")]
+    #[must_use]
+    pub fn to_json(&self, rom_hash: &str) -> String {
+        sidecar::to_json(self, rom_hash).expect("cheats are always serializable")
+    }
+
+    /// Returns the path of the sidecar file for the ROM at the given path.
+    #[cfg(feature = "persistence")]
+    #[must_use]
+    pub fn sidecar_path(rom_path: impl AsRef<Path>) -> PathBuf {
+        sidecar::path(rom_path, SIDECAR_EXTENSION)
+    }
+
+    /// Loads the [`Cheats`] of the ROM at the given path from its sidecar
+    /// file, or returns an empty list if there is none for this ROM.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM or an existing sidecar cannot be read, or
+    /// if the sidecar is invalid.
+    #[cfg(feature = "persistence")]
+    pub fn load_for_rom(rom_path: impl AsRef<Path>) -> io::Result<Self> {
+        sidecar::load(rom_path, SIDECAR_EXTENSION)
+    }
+
+    /// Saves the [`Cheats`] to the sidecar file of the ROM at the given path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM cannot be read or the sidecar cannot be
+    /// written.
+    #[cfg(feature = "persistence")]
+    pub fn save_for_rom(&self, rom_path: impl AsRef<Path>) -> io::Result<()> {
+        sidecar::save(self, rom_path, SIDECAR_EXTENSION)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    #[test]
+    fn test_freeze() {
+        let mut chip8 = Chip8::new();
+        // 7001: V0 += 1, A3A0: I = 0x3A0, F055: store V0, 1200: jump to start
+        chip8
+            .load_rom_data(vec![0x70, 0x01, 0xA3, 0xA0, 0xF0, 0x55, 0x12, 0x00])
+            .unwrap();
+        chip8
+            .cheats
+            .push(Cheat::new("Frozen V0", Target::Register(0), 3));
+        chip8
+            .cheats
+            .push(Cheat::new("Unused", Target::Memory(0x3A1), 7));
+        chip8.cheats.set_enabled(1, false);
+
+        for _ in 0..8 {
+            chip8.step().unwrap();
+        }
+        // The cheat is applied before every instruction, so V0 is stored as 3
+        assert_eq!(chip8.bus.memory.read(0x3A0), Ok(3));
+        assert_eq!(chip8.bus.memory.read(0x3A1), Ok(0));
+        assert_eq!(
+            chip8.cheats.get(0).unwrap().to_string(),
+            "Frozen V0: V0 = 0x03"
+        );
+    }
+}
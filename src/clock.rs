@@ -1,35 +1,194 @@
 //! This module provides a clock for updating the sound and delay timers of
-//! a Chip8 emulator. The [`Clock`] struct keeps track of the current value of
-//! the delay timer, the sound timer, and whether a vblank interrupt has occurred.
+//! a Chip8 emulator.
+//!
+//! The [`Clock`] struct keeps track of the current value of the delay timer,
+//! the sound timer, and whether a vblank interrupt has occurred.
 //!
 //! The delay timer and the sound timer are decremented at a rate of 60Hz, which is
 //! the frequency at which the timers are updated.
+//!
+//! The timers only tick when the host calls [`Clock::tick`], usually through
+//! [`super::Chip8::tick_timers`] at 60Hz: [`crate::runner::Chip8Runner`]
+//! does so in step with the emulated time, and frontends driving a
+//! [`super::Chip8`] themselves call it e.g. once per
+//! `requestAnimationFrame`. Executing instructions never ticks the timers by
+//! itself, unless a [`TimeSource`] like [`FixedStep`] is selected, which
+//! ticks after a fixed amount of instructions for deterministic and headless
+//! runs, or [`RealTime`], which follows the wall clock.
 
-use std::sync::{
-    atomic::{AtomicU8, Ordering},
-    Arc,
-};
+use alloc::boxed::Box;
+use core::fmt;
 
-#[cfg(not(target_arch = "wasm32"))]
+use portable_atomic::{AtomicU8, Ordering};
+use portable_atomic_util::Arc;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 use std::time::{Duration, Instant};
 
+/// Decides when the timers of a [`Clock`] tick while executing instructions.
+pub trait TimeSource: fmt::Debug + Send {
+    /// Returns whether a timer tick is due. This is called before every
+    /// instruction.
+    fn tick_due(&mut self) -> bool;
+
+    /// Changes the frequency (in Hz) at which ticks become due, for sources
+    /// following the wall clock. Other sources ignore it.
+    fn set_frequency(&mut self, _frequency: f64) {}
+}
+
+/// Ticks the timers following the wall clock, at [`Clock::TIMER_FREQUENCY_HZ`]
+/// unless changed through [`Clock::set_timer_frequency`]. Available with the
+/// `std` feature.
+///
+/// The timers then tick independently of the emulated time, so prefer
+/// [`crate::runner::Chip8Runner`], which ticks them in step with it.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+pub struct RealTime {
+    /// The frequency (in Hz) at which the timers tick.
+    frequency: f64,
+    /// The time at which the last tick occurred.
+    #[cfg(not(target_arch = "wasm32"))]
+    last_tick: Instant,
+    /// The time in milliseconds at which the last tick occurred.
+    #[cfg(target_arch = "wasm32")]
+    last_tick: f64,
+}
+
+#[cfg(feature = "std")]
+impl Default for RealTime {
+    fn default() -> Self {
+        Self {
+            frequency: Clock::TIMER_FREQUENCY_HZ,
+            #[cfg(not(target_arch = "wasm32"))]
+            last_tick: Instant::now(),
+            #[cfg(target_arch = "wasm32")]
+            last_tick: js_sys::Date::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl TimeSource for RealTime {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn tick_due(&mut self) -> bool {
+        if self.frequency <= 0.0 {
+            return false;
+        }
+        let period = 1.0 / self.frequency;
+        if self.last_tick.elapsed().as_secs_f64() < period {
+            return false;
+        }
+        self.last_tick += Duration::from_secs_f64(period);
+        true
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn tick_due(&mut self) -> bool {
+        let now = js_sys::Date::now();
+        if self.frequency <= 0.0 || now - self.last_tick < 1000.0 / self.frequency {
+            return false;
+        }
+        self.last_tick = now;
+        true
+    }
+
+    fn set_frequency(&mut self, frequency: f64) {
+        self.frequency = frequency;
+    }
+}
+
+/// Ticks the timers once every `instructions` instructions, independently of
+/// the wall clock, so runs are reproducible and can be fast-forwarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedStep {
+    /// The amount of instructions per timer tick.
+    pub instructions: u32,
+    /// The amount of instructions since the last tick.
+    elapsed: u32,
+}
+
+impl FixedStep {
+    /// Creates a new [`FixedStep`] source ticking once every `instructions`
+    /// instructions, e.g. 11 for the classic 660 instructions per second.
+    #[must_use]
+    pub const fn new(instructions: u32) -> Self {
+        Self {
+            instructions,
+            elapsed: 0,
+        }
+    }
+}
+
+impl TimeSource for FixedStep {
+    fn tick_due(&mut self) -> bool {
+        self.elapsed += 1;
+        if self.elapsed < self.instructions {
+            return false;
+        }
+        self.elapsed = 0;
+        true
+    }
+}
+
+/// Never ticks the timers by itself. The host calls [`Clock::tick`] instead,
+/// e.g. from a 60Hz timer interrupt or once per displayed frame. This is the
+/// source of a new [`Clock`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Manual;
+
+impl TimeSource for Manual {
+    fn tick_due(&mut self) -> bool {
+        false
+    }
+}
+
+/// Returns the [`TimeSource`] of a new [`Clock`], which is [`Manual`].
+fn default_source() -> Box<dyn TimeSource> {
+    Box::new(Manual)
+}
+
+/// The most recent load of a timer by the program, through `Fx15` or `Fx18`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimerLoad {
+    /// The value the timer was loaded with.
+    pub value: u8,
+    /// The amount of loads so far, wrapping around. It tells apart two loads
+    /// of the same value, which leave the timer unchanged.
+    pub count: u32,
+}
+
+impl TimerLoad {
+    /// Records a load of the given value.
+    pub(crate) const fn record(&mut self, value: u8) {
+        self.value = value;
+        self.count = self.count.wrapping_add(1);
+    }
+}
+
 /// Handles the updating of the [`super::Chip8`] sound and delay timers. The `delay_timer` and
 /// the `sound_timer` are decremented by `1` at a rate of `60Hz`.
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Clock {
     /// The current value of the delay timer.
     pub delay_timer: u8,
     /// The current value of the sound timer, stored in an atomic variable for thread-safety.
-    #[serde(skip)]
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub sound_timer: Arc<AtomicU8>,
-    /// A flag indicating whether a vblank interrupt has occurred.
+    /// A flag indicating whether a vblank interrupt has occurred since the
+    /// last sprite drawn while waiting for it, see
+    /// [`crate::quirks::Quirks::vblank_wait`].
     pub vblank_interrupt: bool,
-    /// The time at which the last delay timer update occurred.
-    #[cfg_attr(not(target_arch = "wasm32"), serde(skip, default = "Instant::now"))]
-    #[cfg(not(target_arch = "wasm32"))]
-    last_delay: Instant,
-    #[cfg(target_arch = "wasm32")]
-    last_delay: f64,
+    /// The most recent load of the delay timer.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub delay_load: TimerLoad,
+    /// The most recent load of the sound timer.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sound_load: TimerLoad,
+    /// Decides when the timers tick.
+    #[cfg_attr(feature = "serde", serde(skip, default = "default_source"))]
+    source: Box<dyn TimeSource>,
 }
 
 impl Default for Clock {
@@ -37,18 +196,17 @@ impl Default for Clock {
         Self {
             delay_timer: Default::default(),
             sound_timer: Arc::default(),
-            #[cfg(not(target_arch = "wasm32"))]
-            last_delay: Instant::now(),
-            #[cfg(target_arch = "wasm32")]
-            last_delay: f64::default(),
             vblank_interrupt: Default::default(),
+            delay_load: TimerLoad::default(),
+            sound_load: TimerLoad::default(),
+            source: default_source(),
         }
     }
 }
 
 impl Clock {
-    /// The frequency (in Hz) at which the timers are updated.
-    const TIMER_FREQUENCY_HZ: f64 = 60.0;
+    /// The default frequency (in Hz) at which the timers are updated.
+    pub const TIMER_FREQUENCY_HZ: f64 = 60.0;
 
     /// Create a new `Clock`.
     #[must_use]
@@ -56,40 +214,51 @@ impl Clock {
         Self::default()
     }
 
-    #[cfg(not(target_arch = "wasm32"))]
-    pub fn update(&mut self) {
-        let elapsed_time = self.last_delay.elapsed().as_secs_f64();
-
-        if elapsed_time >= 1.0 / Self::TIMER_FREQUENCY_HZ {
-            self.delay_timer = self.delay_timer.saturating_sub(1);
-            self.sound_timer
-                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| {
-                    Some(x.saturating_sub(1))
-                })
-                .unwrap_or_default();
-            self.vblank_interrupt = true;
-            self.last_delay += Duration::from_secs_f64(1.0 / Self::TIMER_FREQUENCY_HZ);
-        } else {
-            self.vblank_interrupt = false;
-        }
+    /// Sets the frequency (in Hz) at which the timers are decremented. A
+    /// frequency of `0` stops the timers. Only [`RealTime`] follows the
+    /// frequency.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frequency` is negative or not a number.
+    #[deprecated(note = "use `Chip8Runner::set_timer_frequency` instead")]
+    pub fn set_timer_frequency(&mut self, frequency: f64) {
+        assert!(frequency >= 0.0, "timer frequency must not be negative");
+        self.source.set_frequency(frequency);
     }
 
-    #[cfg(target_arch = "wasm32")]
+    /// Replaces the [`TimeSource`] deciding when the timers tick.
+    pub fn set_source(&mut self, source: impl TimeSource + 'static) {
+        self.source = Box::new(source);
+    }
+
+    /// Takes the [`TimeSource`], leaving the default source in its place.
+    pub(crate) fn take_source(&mut self) -> Box<dyn TimeSource> {
+        core::mem::replace(&mut self.source, default_source())
+    }
+
+    /// Puts back a [`TimeSource`] returned by [`Clock::take_source`].
+    pub(crate) fn restore_source(&mut self, source: Box<dyn TimeSource>) {
+        self.source = source;
+    }
+
+    /// Decrements both timers once and raises the vblank interrupt, regardless
+    /// of the [`TimeSource`].
+    pub fn tick(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| {
+                Some(x.saturating_sub(1))
+            })
+            .unwrap_or_default();
+        self.vblank_interrupt = true;
+    }
+
+    /// Ticks the timers if the [`TimeSource`] says a tick is due. This is
+    /// called before every instruction.
     pub fn update(&mut self) {
-        let current_time = js_sys::Date::now();
-        let elapsed_time = current_time - self.last_delay;
-
-        if elapsed_time >= 1.0 / Self::TIMER_FREQUENCY_HZ {
-            self.delay_timer = self.delay_timer.saturating_sub(1);
-            self.sound_timer
-                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| {
-                    Some(x.saturating_sub(1))
-                })
-                .unwrap_or_default();
-            self.vblank_interrupt = true;
-            self.last_delay = current_time;
-        } else {
-            self.vblank_interrupt = false;
+        if self.source.tick_due() {
+            self.tick();
         }
     }
 }
@@ -97,33 +266,49 @@ impl Clock {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::thread;
-    use std::time::Duration;
 
     #[test]
-    fn test_update() {
+    fn test_sources() {
         let mut clock = Clock::new();
+        clock.delay_timer = 10;
+
+        // Without a source, executing instructions leaves the timers alone
+        for _ in 0..100 {
+            clock.update();
+        }
+        assert_eq!(clock.delay_timer, 10);
+        clock.tick();
+        assert_eq!(clock.delay_timer, 9);
+        assert!(clock.vblank_interrupt);
 
-        // Set initial values for the timers
+        clock.set_source(FixedStep::new(3));
+        for _ in 0..7 {
+            clock.update();
+        }
+        assert_eq!(clock.delay_timer, 7);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    #[allow(deprecated)]
+    fn test_real_time() {
+        let mut clock = Clock::new();
         clock.delay_timer = 10;
-        clock.sound_timer.store(10, Ordering::SeqCst);
+        clock.set_source(RealTime::default());
 
-        // Call the update method
+        // Not enough time has passed for a tick yet
         clock.update();
-
-        // The timers should not have changed yet because not enough time has passed
         assert_eq!(clock.delay_timer, 10);
-        assert_eq!(clock.sound_timer.load(Ordering::SeqCst), 10);
 
         // Sleep for a bit more than 1/60th of a second
-        thread::sleep(Duration::from_millis(17));
-
-        // Call the update method again
+        std::thread::sleep(std::time::Duration::from_millis(17));
         clock.update();
+        assert_eq!(clock.delay_timer, 9);
 
-        // Now the timers should have decremented
+        // A frequency of 0 stops the timers
+        clock.set_timer_frequency(0.0);
+        std::thread::sleep(std::time::Duration::from_millis(17));
+        clock.update();
         assert_eq!(clock.delay_timer, 9);
-        assert_eq!(clock.sound_timer.load(Ordering::SeqCst), 9);
     }
 }
-
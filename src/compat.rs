@@ -0,0 +1,325 @@
+//! This module checks a ROM for compatibility with a [`Variant`] before it is
+//! run, for a `check` command.
+//!
+//! The check is static: like [`RomInfo`], it follows the jumps, calls and
+//! skips from the entry point, and reports the reachable instructions the
+//! original platform of the variant cannot execute. It also points out runs
+//! of valid instructions ending in a return or jump that are never reached,
+//! e.g. a subroutine nobody calls, and `Annn` loads pointing the index
+//! register at code while the program writes memory through it. The targets
+//! of `Bnnn` jumps depend on a register, so code only reached through them
+//! shows up as unreachable.
+
+use std::{collections::BTreeSet, fmt};
+
+use crate::{
+    disassembler, memory,
+    quirks::Variant,
+    rom::{self, Extension, RomInfo},
+};
+
+/// The minimum amount of instructions of an unreachable run of code that is
+/// reported, so short runs of sprite data that happen to decode are not.
+pub const MIN_UNREACHABLE: usize = 3;
+
+/// A finding of a [`CompatReport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Issue {
+    /// A reachable instruction belongs to an extension the variant lacks.
+    Unsupported {
+        /// The address of the instruction.
+        address: usize,
+        /// The opcode of the instruction.
+        opcode: u16,
+        /// The extension the instruction belongs to.
+        extension: Extension,
+    },
+    /// A reachable opcode is not an instruction of any platform.
+    Invalid {
+        /// The address of the opcode.
+        address: usize,
+        /// The opcode.
+        opcode: u16,
+    },
+    /// A run of valid instructions that is never reached.
+    Unreachable {
+        /// The address of the first instruction.
+        start: usize,
+        /// The amount of instructions.
+        instructions: usize,
+    },
+    /// An `Annn` points the index register at code, which the program may
+    /// overwrite through `Fx55`, `Fx33` or `5xy2`.
+    SelfModifying {
+        /// The address of the `Annn`.
+        address: usize,
+        /// The address of the code the index register points at.
+        target: usize,
+    },
+}
+
+impl Issue {
+    /// Returns the address the issue was found at.
+    #[must_use]
+    pub const fn address(&self) -> usize {
+        match *self {
+            Self::Unsupported { address, .. }
+            | Self::Invalid { address, .. }
+            | Self::SelfModifying { address, .. } => address,
+            Self::Unreachable { start, .. } => start,
+        }
+    }
+
+    /// Returns whether the issue keeps the ROM from running on the variant,
+    /// rather than being worth a look.
+    #[must_use]
+    pub const fn is_error(&self) -> bool {
+        matches!(self, Self::Unsupported { .. } | Self::Invalid { .. })
+    }
+}
+
+impl fmt::Display for Issue {
+    /// Formats the issue as its address followed by a description.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#05X}  ", self.address())?;
+        match *self {
+            Self::Unsupported {
+                opcode, extension, ..
+            } => write!(f, "{opcode:04X}  {extension} instruction"),
+            Self::Invalid { opcode, .. } => write!(f, "{opcode:04X}  invalid instruction"),
+            Self::Unreachable { instructions, .. } => {
+                write!(f, "unreachable code, {instructions} instructions")
+            }
+            Self::SelfModifying { target, .. } => {
+                write!(f, "A{target:03X}  points I at code that may be overwritten")
+            }
+        }
+    }
+}
+
+/// The result of checking a ROM for compatibility with a [`Variant`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatReport {
+    /// The variant the ROM was checked against.
+    pub variant: Variant,
+    /// Whether the Mega-Chip extensions are enabled.
+    pub megachip: bool,
+    /// The issues found, by address.
+    pub issues: Vec<Issue>,
+    /// Whether the reachable code jumps through `Bnnn`, whose targets cannot
+    /// be followed.
+    pub computed_jumps: bool,
+    /// The variant the ROM most likely expects, see
+    /// [`RomInfo::suggested_variant`].
+    pub suggested_variant: Variant,
+}
+
+impl CompatReport {
+    /// Checks the given ROM data against the given variant, with or without
+    /// the Mega-Chip extensions.
+    #[must_use]
+    pub fn new(data: &[u8], variant: Variant, megachip: bool) -> Self {
+        let info = RomInfo::new(data);
+        let start = info.load_address;
+        let reachable = rom::reachable(data, start);
+        let opcodes: Vec<(usize, u16)> = reachable
+            .iter()
+            .filter_map(|&address| Some((address, rom::opcode_at(data, start, address)?)))
+            .collect();
+
+        let mut issues = Vec::new();
+        if rom::is_hires(data, start) && !Extension::Hires.supported_by(variant) {
+            issues.push(Issue::Unsupported {
+                address: start,
+                opcode: 0x1260,
+                extension: Extension::Hires,
+            });
+        }
+        for &(address, opcode) in &opcodes {
+            match Extension::of(opcode) {
+                Some(Extension::MegaChip) if megachip => {}
+                Some(extension) if !extension.supported_by(variant) => {
+                    issues.push(Issue::Unsupported {
+                        address,
+                        opcode,
+                        extension,
+                    });
+                }
+                None if disassembler::disassemble(opcode).is_none() => {
+                    issues.push(Issue::Invalid { address, opcode });
+                }
+                _ => {}
+            }
+        }
+
+        // Every byte of a reachable instruction, including the address
+        // following `F000`
+        let code: BTreeSet<usize> = opcodes
+            .iter()
+            .flat_map(|&(address, opcode)| {
+                let len = if opcode == 0xF000 { 4 } else { 2 };
+                address..address + len
+            })
+            .collect();
+        let writes = opcodes.iter().any(|&(_, opcode)| {
+            matches!(opcode & 0xF0FF, 0xF055 | 0xF033) || opcode & 0xF00F == 0x5002
+        });
+        if writes {
+            for &(address, opcode) in &opcodes {
+                let target = match opcode >> 12 {
+                    0xA => usize::from(opcode & 0x0FFF),
+                    _ if opcode == 0xF000 => match rom::opcode_at(data, start, address + 2) {
+                        Some(target) => usize::from(target),
+                        None => continue,
+                    },
+                    _ => continue,
+                };
+                if code.contains(&target) {
+                    issues.push(Issue::SelfModifying { address, target });
+                }
+            }
+        }
+
+        issues.extend(unreachable(data, start, &code));
+        issues.sort_by_key(Issue::address);
+        Self {
+            variant,
+            megachip,
+            issues,
+            computed_jumps: opcodes.iter().any(|&(_, opcode)| opcode >> 12 == 0xB),
+            suggested_variant: info.suggested_variant(),
+        }
+    }
+
+    /// Returns whether the ROM runs on the variant, i.e. no reachable
+    /// instruction is unsupported or invalid.
+    #[must_use]
+    pub fn is_compatible(&self) -> bool {
+        !self.issues.iter().any(Issue::is_error)
+    }
+}
+
+impl fmt::Display for CompatReport {
+    /// Formats the report as one line per issue, followed by the verdict.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let megachip = if self.megachip { " with Mega-Chip" } else { "" };
+        writeln!(f, "Compatibility with {}{megachip}:", self.variant)?;
+        for issue in &self.issues {
+            writeln!(f, "  {issue}")?;
+        }
+        if self.issues.is_empty() {
+            writeln!(f, "  no issues found")?;
+        }
+        if self.computed_jumps {
+            writeln!(
+                f,
+                "Note: the ROM jumps through Bnnn, so some code may only look unreachable"
+            )?;
+        }
+        if self.is_compatible() {
+            write!(f, "Compatible")
+        } else {
+            write!(
+                f,
+                "Incompatible, suggested variant: {}",
+                self.suggested_variant
+            )
+        }
+    }
+}
+
+/// Finds the runs of at least [`MIN_UNREACHABLE`] valid instructions of the
+/// ROM loaded at `start` that end in a return or jump, without any of their
+/// bytes being part of the reachable `code`. The interpreter patch of a
+/// hires ROM is skipped.
+fn unreachable(data: &[u8], start: usize, code: &BTreeSet<usize>) -> Vec<Issue> {
+    let first = if rom::is_hires(data, start) {
+        memory::HIRES_START
+    } else {
+        start
+    };
+    let mut issues = Vec::new();
+    let mut run: Option<(usize, usize)> = None;
+    for address in (first..start + data.len()).step_by(2) {
+        let opcode = rom::opcode_at(data, start, address);
+        let Some(opcode) = opcode.filter(|&opcode| {
+            !code.contains(&address) && !code.contains(&(address + 1)) && is_instruction(opcode)
+        }) else {
+            run = None;
+            continue;
+        };
+        let (run_start, instructions) = run.get_or_insert((address, 0));
+        *instructions += 1;
+        if opcode == 0x00EE || opcode >> 12 == 0x1 {
+            if *instructions >= MIN_UNREACHABLE {
+                issues.push(Issue::Unreachable {
+                    start: *run_start,
+                    instructions: *instructions,
+                });
+            }
+            run = None;
+        }
+    }
+    issues
+}
+
+/// Returns whether the opcode is a valid instruction. Machine code calls
+/// (`0nnn`) are left out, since zeroed data decodes as them.
+fn is_instruction(opcode: u16) -> bool {
+    match opcode >> 12 {
+        0x0 => matches!(opcode, 0x00E0 | 0x00EE) || Extension::of(opcode).is_some(),
+        _ => Extension::of(opcode).is_some() || disassembler::disassemble(opcode).is_some(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        // 00FF: high resolution, A208: I = 0x208, F055: store V0 at I,
+        // 1208: jump to 0x208, then a subroutine nobody calls: 6001, 7101,
+        // 00EE, followed by sprite data
+        let rom = [
+            0x00, 0xFF, 0xA2, 0x08, 0xF0, 0x55, 0x12, 0x08, 0x12, 0x08, 0x60, 0x01, 0x71, 0x01,
+            0x00, 0xEE, 0xF0, 0x90,
+        ];
+        let report = CompatReport::new(&rom, Variant::Chip8, false);
+        assert_eq!(
+            report.issues,
+            [
+                Issue::Unsupported {
+                    address: 0x200,
+                    opcode: 0x00FF,
+                    extension: Extension::SuperChip
+                },
+                Issue::SelfModifying {
+                    address: 0x202,
+                    target: 0x208
+                },
+                Issue::Unreachable {
+                    start: 0x20A,
+                    instructions: 3
+                },
+            ]
+        );
+        assert!(!report.is_compatible());
+        assert!(report
+            .to_string()
+            .ends_with("Incompatible, suggested variant: SCHIP"));
+
+        let report = CompatReport::new(&rom, Variant::SuperChip, false);
+        assert!(report.is_compatible());
+
+        // 800F: not an instruction
+        let report = CompatReport::new(&[0x80, 0x0F, 0x12, 0x02], Variant::XoChip, false);
+        assert_eq!(
+            report.issues,
+            [Issue::Invalid {
+                address: 0x200,
+                opcode: 0x800F
+            }]
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! This module stores the user preferences of a frontend, so colors, key
+//! bindings, quirks and speed survive a restart.
+//!
+//! A [`Config`] is stored as TOML in the platform configuration directory,
+//! e.g. `~/.config/chip8/config.toml` on Linux. Missing fields fall back to
+//! their defaults, so configuration files of older versions keep loading.
+
+use std::{fs, io, path::PathBuf};
+
+use crate::{
+    audio::{Synth, Waveform},
+    disassembler::Syntax,
+    display::DisplayOptions,
+    graphics::{Rgb, DEFAULT_BACKGROUND, DEFAULT_FOREGROUND},
+    hotkeys::Hotkeys,
+    i18n::Language,
+    keymap::Keymap,
+    layout::DockLayout,
+    quirks::Quirks,
+    runner::{Chip8Runner, FocusBehavior, DEFAULT_IPS},
+    theme::{Palette, DEFAULT_PALETTE},
+};
+
+/// The name of the directory holding the configuration file, inside the
+/// platform configuration directory.
+pub const APP_DIR: &str = "chip8";
+
+/// The name of the configuration file.
+pub const FILE_NAME: &str = "config.toml";
+
+/// The smallest factor the user interface can be scaled by.
+pub const MIN_UI_SCALE: f32 = 0.5;
+
+/// The largest factor the user interface can be scaled by.
+pub const MAX_UI_SCALE: f32 = 3.0;
+
+/// The position, size and debugger panels of the main window.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct WindowLayout {
+    /// The width of the window in logical pixels.
+    pub width: u32,
+
+    /// The height of the window in logical pixels.
+    pub height: u32,
+
+    /// The position of the window, or [`None`] to let the platform choose.
+    pub position: Option<(i32, i32)>,
+
+    /// Whether the window is maximized.
+    pub maximized: bool,
+
+    /// The arrangement of the debugger panels, and whether they are hidden
+    /// in play mode.
+    pub dock: DockLayout,
+}
+
+impl Default for WindowLayout {
+    fn default() -> Self {
+        Self {
+            width: 640,
+            height: 320,
+            position: None,
+            maximized: false,
+            dock: DockLayout::default(),
+        }
+    }
+}
+
+/// The user preferences of a frontend.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The color of set pixels.
+    pub foreground: Rgb,
+
+    /// The color of unset pixels.
+    pub background: Rgb,
+
+    /// The colors of pixels set in the second plane only and in both planes,
+    /// shown by XO-CHIP programs.
+    pub plane_colors: [Rgb; 2],
+
+    /// The keyboard bindings of the Chip8 keypad.
+    pub keymap: Keymap,
+
+    /// The keyboard shortcuts of the emulator controls.
+    pub hotkeys: Hotkeys,
+
+    /// The quirks of the emulated interpreter.
+    pub quirks: Quirks,
+
+    /// The target amount of instructions per second.
+    pub ips: u64,
+
+    /// The volume of the buzzer, from `0.0` for muted to `1.0`.
+    pub volume: f32,
+
+    /// The shape of the buzzer tone.
+    pub waveform: Waveform,
+
+    /// The frequency of the buzzer tone in Hz.
+    pub tone_frequency: f32,
+
+    /// Whether key presses are confirmed with a short click, see
+    /// [`Synth::click`].
+    pub key_clicks: bool,
+
+    /// The layout of the main window.
+    pub window: WindowLayout,
+
+    /// The fullscreen, scaling and filter options of the display.
+    pub display: DisplayOptions,
+
+    /// What the emulator does while the window is not focused.
+    pub focus_behavior: FocusBehavior,
+
+    /// The syntax the debugger writes instructions in.
+    pub syntax: Syntax,
+
+    /// The language of the user interface.
+    pub language: Language,
+
+    /// The factor the user interface is scaled by, from [`MIN_UI_SCALE`] to
+    /// [`MAX_UI_SCALE`], on top of the scale factor of the screen.
+    pub ui_scale: f32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            foreground: DEFAULT_FOREGROUND,
+            background: DEFAULT_BACKGROUND,
+            plane_colors: [DEFAULT_PALETTE[2], DEFAULT_PALETTE[3]],
+            keymap: Keymap::default(),
+            hotkeys: Hotkeys::default(),
+            quirks: Quirks::new(),
+            ips: DEFAULT_IPS,
+            volume: 0.5,
+            waveform: Waveform::default(),
+            tone_frequency: 440.0,
+            key_clicks: false,
+            window: WindowLayout::default(),
+            display: DisplayOptions::default(),
+            focus_behavior: FocusBehavior::default(),
+            syntax: Syntax::default(),
+            language: Language::default(),
+            ui_scale: 1.0,
+        }
+    }
+}
+
+impl Config {
+    /// Returns the path of the configuration file in the platform
+    /// configuration directory, or [`None`] if the platform has none.
+    #[must_use]
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(APP_DIR).join(FILE_NAME))
+    }
+
+    /// Parses a [`Config`] from a TOML string.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the string is not a valid configuration.
+    pub fn from_toml(toml: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(toml)
+    }
+
+    /// Serializes the [`Config`] into a TOML string.
+    ///
+    /// # Panics
+    ///
+    /// Never panics in practice, since a [`Config`] always serializes to TOML.
+    #[must_use]
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("config is always serializable")
+    }
+
+    /// Loads the [`Config`] from the platform configuration directory. Returns
+    /// the default configuration if none was saved yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an existing file cannot be read or is not a valid
+    /// configuration.
+    pub fn load() -> io::Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        match fs::read_to_string(path) {
+            Ok(contents) => Self::from_toml(&contents)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Saves the [`Config`] to the platform configuration directory, creating
+    /// the directory if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the platform has no configuration directory or the
+    /// file cannot be written.
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no configuration directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_toml())
+    }
+
+    /// Returns the configured colors as a [`Palette`].
+    #[must_use]
+    pub const fn palette(&self) -> Palette {
+        [
+            self.background,
+            self.foreground,
+            self.plane_colors[0],
+            self.plane_colors[1],
+        ]
+    }
+
+    /// Sets all colors from the given [`Palette`], e.g. a preset theme.
+    pub const fn set_palette(&mut self, palette: Palette) {
+        self.background = palette[0];
+        self.foreground = palette[1];
+        self.plane_colors = [palette[2], palette[3]];
+    }
+
+    /// Returns a [`Synth`] with the configured waveform, frequency, volume
+    /// and key clicks.
+    #[must_use]
+    pub fn synth(&self) -> Synth {
+        let mut synth = Synth::new();
+        synth.waveform = self.waveform;
+        synth.frequency = self.tone_frequency;
+        synth.volume = self.volume;
+        synth.key_clicks = self.key_clicks;
+        synth
+    }
+
+    /// Returns the configured [`Config::ui_scale`], clamped to the supported
+    /// range, e.g. to set the zoom of a user interface toolkit.
+    #[must_use]
+    pub const fn ui_scale(&self) -> f32 {
+        self.ui_scale.clamp(MIN_UI_SCALE, MAX_UI_SCALE)
+    }
+
+    /// Applies the colors, quirks, speed and focus behavior to the given
+    /// [`Chip8Runner`]. The key bindings, sound and window layout are left to
+    /// the frontend.
+    pub fn apply(&self, runner: &mut Chip8Runner) {
+        runner.chip8.bus.graphics.set_palette(self.palette());
+        runner.chip8.processor.quirks = self.quirks;
+        runner.set_ips(self.ips);
+        runner.set_focus_behavior(self.focus_behavior);
+    }
+
+    /// Updates the colors, quirks, speed and focus behavior from the current
+    /// state of the given [`Chip8Runner`], e.g. before saving on exit.
+    pub fn capture(&mut self, runner: &Chip8Runner) {
+        self.set_palette(runner.chip8.bus.graphics.palette);
+        self.quirks = runner.chip8.processor.quirks;
+        self.ips = runner.ips();
+        self.focus_behavior = runner.focus_behavior();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        layout::{Dock, Panel},
+        quirks::Variant,
+        Chip8,
+    };
+
+    #[test]
+    fn test_round_trip() {
+        let mut runner = Chip8Runner::new(Chip8::new());
+        runner.chip8.set_variant(Variant::SuperChip);
+        runner.set_ips(1000);
+
+        let mut config = Config::default();
+        config.capture(&runner);
+        config.window.dock.set_dock(Panel::Memory, Dock::Floating);
+        config.window.dock.play_mode = true;
+        config.language = Language::German;
+        let config = Config::from_toml(&config.to_toml()).unwrap();
+        assert_eq!(config.quirks, Variant::SuperChip.quirks());
+        assert_eq!(config.ips, 1000);
+        assert_eq!(config.window.dock.state(Panel::Memory).dock, Dock::Floating);
+        assert!(config.window.dock.play_mode);
+        assert_eq!(config.language, Language::German);
+
+        let mut runner = Chip8Runner::new(Chip8::new());
+        config.apply(&mut runner);
+        assert_eq!(runner.ips(), 1000);
+
+        // Missing fields fall back to their defaults
+        let config = Config::from_toml("ips = 500").unwrap();
+        assert_eq!(config.ips, 500);
+        assert_eq!(config.keymap, Keymap::default());
+        assert_eq!(config.hotkeys, Hotkeys::default());
+
+        let config = Config::from_toml("ui_scale = 10.0").unwrap();
+        assert!((config.ui_scale() - MAX_UI_SCALE).abs() < f32::EPSILON);
+    }
+}
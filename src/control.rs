@@ -0,0 +1,116 @@
+//! This module provides a thread-safe control handle for pausing and
+//! single-stepping a running [`super::Chip8`].
+//!
+//! A [`Controls`] handle is cheap to clone. A frontend keeps one clone to
+//! toggle execution or request steps, while the thread that drives the CPU
+//! asks [`Controls::should_step`] before executing each instruction.
+
+use portable_atomic::{AtomicBool, AtomicU32, Ordering};
+use portable_atomic_util::Arc;
+
+/// The shared state behind a [`Controls`] handle.
+#[derive(Debug, Default)]
+struct ControlState {
+    /// Whether execution is currently paused.
+    paused: AtomicBool,
+    /// The amount of instructions to execute while paused.
+    pending_steps: AtomicU32,
+}
+
+/// A shared handle used to pause, resume and single-step the CPU.
+#[derive(Debug, Default, Clone)]
+pub struct Controls {
+    state: Arc<ControlState>,
+}
+
+impl Controls {
+    /// Creates a new [`Controls`] handle in the running state.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether execution is currently paused.
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.state.paused.load(Ordering::SeqCst)
+    }
+
+    /// Pauses execution. Any pending steps are discarded.
+    pub fn pause(&self) {
+        self.state.paused.store(true, Ordering::SeqCst);
+        self.state.pending_steps.store(0, Ordering::SeqCst);
+    }
+
+    /// Resumes execution.
+    pub fn resume(&self) {
+        self.state.paused.store(false, Ordering::SeqCst);
+        self.state.pending_steps.store(0, Ordering::SeqCst);
+    }
+
+    /// Toggles between the paused and running state.
+    pub fn toggle(&self) {
+        if self.is_paused() {
+            self.resume();
+        } else {
+            self.pause();
+        }
+    }
+
+    /// Requests a single instruction to be executed while paused. This has no
+    /// effect while the CPU is running.
+    pub fn step(&self) {
+        self.step_n(1);
+    }
+
+    /// Requests `n` instructions to be executed while paused. This has no
+    /// effect while the CPU is running.
+    pub fn step_n(&self, n: u32) {
+        if self.is_paused() {
+            self.state.pending_steps.fetch_add(n, Ordering::SeqCst);
+        }
+    }
+
+    /// Returns the amount of requested steps that have not been executed yet.
+    #[must_use]
+    pub fn pending_steps(&self) -> u32 {
+        self.state.pending_steps.load(Ordering::SeqCst)
+    }
+
+    /// Returns whether the CPU should execute its next instruction. While
+    /// paused, this consumes one of the pending steps.
+    #[must_use]
+    pub fn should_step(&self) -> bool {
+        if !self.is_paused() {
+            return true;
+        }
+        self.state
+            .pending_steps
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pause_and_step() {
+        let controls = Controls::new();
+        let handle = controls.clone();
+        assert!(controls.should_step());
+
+        handle.pause();
+        assert!(!controls.should_step());
+
+        handle.step_n(2);
+        assert!(controls.should_step());
+        assert!(controls.should_step());
+        assert!(!controls.should_step());
+
+        handle.toggle();
+        assert!(!controls.is_paused());
+        assert!(controls.should_step());
+    }
+}
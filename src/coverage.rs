@@ -0,0 +1,233 @@
+//! This module classifies the bytes of a ROM as code or data, based on what a
+//! [`Profiler`] observed during a run.
+//!
+//! A byte is code if it was part of an executed instruction and data if an
+//! instruction read it. Bytes that were both usually indicate self-modifying
+//! code or a misaligned jump. The resulting [`Coverage`] can be exported as
+//! JSON with the `persistence` feature, or printed as a colored listing.
+
+#[cfg(feature = "persistence")]
+use std::{fs, io, path::Path};
+
+use std::fmt::Write;
+
+use crate::{memory::Memory, profiler::Profiler};
+
+/// How a byte was used during a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Usage {
+    /// The byte was neither executed nor read.
+    #[default]
+    Unused,
+    /// The byte was part of an executed instruction.
+    Code,
+    /// The byte was read as data, e.g. as a sprite.
+    Data,
+    /// The byte was both executed and read.
+    Both,
+}
+
+impl Usage {
+    /// Returns whether the byte was executed.
+    #[must_use]
+    pub const fn is_code(self) -> bool {
+        matches!(self, Self::Code | Self::Both)
+    }
+
+    /// Returns whether the byte was read.
+    #[must_use]
+    pub const fn is_data(self) -> bool {
+        matches!(self, Self::Data | Self::Both)
+    }
+
+    /// Returns the ANSI escape sequence used to color the byte in a listing.
+    const fn color(self) -> &'static str {
+        match self {
+            Self::Unused => "\x1b[90m",
+            Self::Code => "\x1b[32m",
+            Self::Data => "\x1b[36m",
+            Self::Both => "\x1b[33m",
+        }
+    }
+}
+
+/// The usage of every byte of an address range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    start: usize,
+    usage: Vec<Usage>,
+}
+
+/// The JSON representation of a [`Coverage`], with the usage condensed into
+/// inclusive address ranges.
+#[cfg(feature = "persistence")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+struct Report {
+    start: usize,
+    len: usize,
+    code_bytes: usize,
+    data_bytes: usize,
+    code: Vec<(usize, usize)>,
+    data: Vec<(usize, usize)>,
+}
+
+impl Coverage {
+    /// Collects the coverage of `len` bytes starting at `start` from the
+    /// counts of the given [`Profiler`]. An executed instruction covers both
+    /// of its bytes.
+    #[must_use]
+    pub fn from_profiler(profiler: &Profiler, start: usize, len: usize) -> Self {
+        let usage = (start..start + len)
+            .map(|address| {
+                let executed = profiler.executions(address) > 0
+                    || address
+                        .checked_sub(1)
+                        .is_some_and(|address| profiler.executions(address) > 0);
+                match (executed, profiler.reads(address) > 0) {
+                    (false, false) => Usage::Unused,
+                    (true, false) => Usage::Code,
+                    (false, true) => Usage::Data,
+                    (true, true) => Usage::Both,
+                }
+            })
+            .collect();
+        Self { start, usage }
+    }
+
+    /// Returns the address of the first covered byte.
+    #[must_use]
+    pub const fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the amount of covered bytes.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.usage.len()
+    }
+
+    /// Returns whether the coverage spans no bytes.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.usage.is_empty()
+    }
+
+    /// Returns the usage of the byte at the given address, or
+    /// [`Usage::Unused`] if it is outside of the covered range.
+    #[must_use]
+    pub fn usage(&self, address: usize) -> Usage {
+        address
+            .checked_sub(self.start)
+            .and_then(|offset| self.usage.get(offset))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the amount of executed bytes.
+    #[must_use]
+    pub fn code_bytes(&self) -> usize {
+        self.usage.iter().filter(|usage| usage.is_code()).count()
+    }
+
+    /// Returns the amount of bytes read as data.
+    #[must_use]
+    pub fn data_bytes(&self) -> usize {
+        self.usage.iter().filter(|usage| usage.is_data()).count()
+    }
+
+    /// Returns the inclusive address ranges of the bytes matching the given
+    /// predicate.
+    #[cfg(any(test, feature = "persistence"))]
+    fn ranges(&self, predicate: impl Fn(Usage) -> bool) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for (offset, &usage) in self.usage.iter().enumerate() {
+            if !predicate(usage) {
+                continue;
+            }
+            let address = self.start + offset;
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == address => *end = address,
+                _ => ranges.push((address, address)),
+            }
+        }
+        ranges
+    }
+
+    /// Serializes the [`Coverage`] into a JSON object holding the inclusive
+    /// address ranges of code and data.
+    ///
+    /// # Panics
+    ///
+    /// Never panics in practice, since the report always serializes to JSON.
+    #[cfg(feature = "persistence")]
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let report = Report {
+            start: self.start,
+            len: self.len(),
+            code_bytes: self.code_bytes(),
+            data_bytes: self.data_bytes(),
+            code: self.ranges(Usage::is_code),
+            data: self.ranges(Usage::is_data),
+        };
+        serde_json::to_string_pretty(&report).expect("coverage is always serializable")
+    }
+
+    /// Saves the [`Coverage`] as JSON to the file at the given path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    #[cfg(feature = "persistence")]
+    pub fn save_json(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_json())
+    }
+
+    /// Formats the covered bytes of the given [`Memory`] as a hex listing of
+    /// 16 bytes per line, colored with ANSI escape sequences: green for code,
+    /// cyan for data, yellow for both and gray for unused bytes.
+    #[must_use]
+    pub fn listing(&self, memory: &Memory) -> String {
+        let mut listing = String::new();
+        for (line, usage) in self.usage.chunks(16).enumerate() {
+            let address = self.start + line * 16;
+            let _ = write!(listing, "{address:#06X} ");
+            for (offset, usage) in usage.iter().enumerate() {
+                let byte = memory.read(address + offset).unwrap_or_default();
+                let _ = write!(listing, " {}{byte:02X}\x1b[0m", usage.color());
+            }
+            listing.push('\n');
+        }
+        listing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    #[test]
+    fn test_coverage() {
+        let mut chip8 = Chip8::new();
+        chip8.profiler.set_enabled(true);
+        // A206: I = 0x206, D001: draw 1 byte sprite, 1204: loop, F0: sprite
+        chip8
+            .load_rom_data(vec![0xA2, 0x06, 0xD0, 0x01, 0x12, 0x04, 0xF0, 0x00])
+            .unwrap();
+        for _ in 0..3 {
+            chip8.step().unwrap();
+        }
+
+        let coverage = Coverage::from_profiler(&chip8.profiler, 0x200, 8);
+        assert_eq!(coverage.usage(0x201), Usage::Code);
+        assert_eq!(coverage.usage(0x206), Usage::Data);
+        assert_eq!(coverage.usage(0x207), Usage::Unused);
+        assert_eq!(coverage.code_bytes(), 6);
+        assert_eq!(coverage.data_bytes(), 1);
+        assert_eq!(coverage.ranges(Usage::is_code), vec![(0x200, 0x205)]);
+        #[cfg(feature = "persistence")]
+        assert!(coverage.to_json().contains("\"data_bytes\": 1"));
+        assert!(coverage.listing(&chip8.bus.memory).starts_with("0x0200 "));
+    }
+}
@@ -0,0 +1,241 @@
+//! This module provides a cache of decoded instructions, which speeds up the
+//! inner loops of programs at high instruction rates.
+//!
+//! Without the cache, every cycle decodes its opcode again and writes an
+//! explanation of what it did for the debugger. With the cache enabled
+//! through [`crate::processor::Cpu::set_decode_cache`], the simple
+//! instructions that make up most inner loops (jumps, skips, arithmetic on
+//! registers and loading the index register) are decoded once per address
+//! and then executed directly, without an explanation. Their entries in
+//! [`crate::processor::Cpu::instructions`] are disassembled instead, see
+//! [`crate::processor::Instruction::text`]. All other instructions take the
+//! regular path.
+//!
+//! Every entry remembers the opcode it was decoded from and is only used
+//! while memory still holds that opcode, so self-modifying programs and
+//! loading another ROM invalidate the affected entries without any
+//! bookkeeping on memory writes.
+
+use alloc::vec::Vec;
+
+use crate::{
+    memory,
+    processor::{Cpu, ProgramCounterUpdate},
+};
+
+/// A decoded instruction that is executed without the regular decoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Op {
+    /// `1nnn`: jump to the address.
+    Jump(usize),
+    /// `3xnn`: skip if VX equals the byte.
+    SkipIfEqual(usize, u8),
+    /// `4xnn`: skip if VX does not equal the byte.
+    SkipIfNotEqual(usize, u8),
+    /// `5xy0`: skip if VX equals VY.
+    SkipIfRegistersEqual(usize, usize),
+    /// `9xy0`: skip if VX does not equal VY.
+    SkipIfRegistersNotEqual(usize, usize),
+    /// `6xnn`: set VX to the byte.
+    Load(usize, u8),
+    /// `7xnn`: add the byte to VX without carry.
+    Add(usize, u8),
+    /// `8xy0`: set VX to VY.
+    Move(usize, usize),
+    /// `8xy1`: set VX to VX OR VY.
+    Or(usize, usize),
+    /// `8xy2`: set VX to VX AND VY.
+    And(usize, usize),
+    /// `8xy3`: set VX to VX XOR VY.
+    Xor(usize, usize),
+    /// `8xy4`: add VY to VX with carry.
+    AddRegisters(usize, usize),
+    /// `8xy5`: subtract VY from VX with borrow.
+    Sub(usize, usize),
+    /// `8xy7`: set VX to VY minus VX with borrow.
+    SubReversed(usize, usize),
+    /// `Annn`: set I to the address.
+    LoadIndex(usize),
+}
+
+impl Op {
+    /// Decodes the given opcode, or returns [`None`] if it has to take the
+    /// regular path.
+    const fn decode(opcode: u16) -> Option<Self> {
+        let x = (opcode as usize & 0x0F00) >> 8;
+        let y = (opcode as usize & 0x00F0) >> 4;
+        let nn = (opcode & 0x00FF) as u8;
+        let nnn = opcode as usize & 0x0FFF;
+        Some(match opcode >> 12 {
+            // 1260 starts hires programs on the COSMAC VIP
+            0x1 if opcode != 0x1260 => Self::Jump(nnn),
+            0x3 => Self::SkipIfEqual(x, nn),
+            0x4 => Self::SkipIfNotEqual(x, nn),
+            0x5 => match opcode & 0x000F {
+                0x0 => Self::SkipIfRegistersEqual(x, y),
+                _ => return None,
+            },
+            0x6 => Self::Load(x, nn),
+            0x7 => Self::Add(x, nn),
+            0x8 => match opcode & 0x000F {
+                0x0 => Self::Move(x, y),
+                0x1 => Self::Or(x, y),
+                0x2 => Self::And(x, y),
+                0x3 => Self::Xor(x, y),
+                0x4 => Self::AddRegisters(x, y),
+                0x5 => Self::Sub(x, y),
+                0x7 => Self::SubReversed(x, y),
+                _ => return None,
+            },
+            0x9 => Self::SkipIfRegistersNotEqual(x, y),
+            0xA => Self::LoadIndex(nnn),
+            _ => return None,
+        })
+    }
+
+    /// Executes the instruction like the regular decoder would.
+    pub(crate) fn execute(self, cpu: &mut Cpu) -> ProgramCounterUpdate {
+        let skip_if = |condition: bool| {
+            if condition {
+                ProgramCounterUpdate::SkipNext
+            } else {
+                ProgramCounterUpdate::Next
+            }
+        };
+        let v = &mut cpu.v;
+        match self {
+            Self::Jump(nnn) => return ProgramCounterUpdate::Jump(nnn),
+            Self::SkipIfEqual(x, nn) => return skip_if(v[x] == nn),
+            Self::SkipIfNotEqual(x, nn) => return skip_if(v[x] != nn),
+            Self::SkipIfRegistersEqual(x, y) => return skip_if(v[x] == v[y]),
+            Self::SkipIfRegistersNotEqual(x, y) => return skip_if(v[x] != v[y]),
+            Self::Load(x, nn) => v[x] = nn,
+            Self::Add(x, nn) => v[x] = v[x].wrapping_add(nn),
+            Self::Move(x, y) => v[x] = v[y],
+            Self::Or(x, y) | Self::And(x, y) | Self::Xor(x, y) => {
+                match self {
+                    Self::Or(..) => v[x] |= v[y],
+                    Self::And(..) => v[x] &= v[y],
+                    _ => v[x] ^= v[y],
+                }
+                if cpu.quirks.vf_reset {
+                    v[0xF] = 0;
+                }
+            }
+            Self::AddRegisters(x, y) => {
+                let (result, overflow) = v[x].overflowing_add(v[y]);
+                v[x] = result;
+                v[0xF] = u8::from(overflow);
+            }
+            Self::Sub(x, y) => {
+                let (result, overflow) = v[x].overflowing_sub(v[y]);
+                v[x] = result;
+                v[0xF] = u8::from(!overflow);
+            }
+            Self::SubReversed(x, y) => {
+                let (result, overflow) = v[y].overflowing_sub(v[x]);
+                v[x] = result;
+                v[0xF] = u8::from(!overflow);
+            }
+            Self::LoadIndex(nnn) => cpu.i = nnn,
+        }
+        ProgramCounterUpdate::Next
+    }
+}
+
+/// A decoded address: the opcode it was decoded from, and the instruction if
+/// it can skip the regular decoder.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    /// The opcode the entry was decoded from.
+    opcode: u16,
+    /// The decoded instruction.
+    op: Option<Op>,
+}
+
+/// The decoded instructions of the addresses executed so far, see the
+/// [module documentation](self).
+#[derive(Debug, Clone, Default)]
+pub struct DecodeCache {
+    /// The entry of every address, grown up to the highest address executed
+    /// below [`memory::MEMORY_SIZE`].
+    entries: Vec<Option<Entry>>,
+}
+
+impl DecodeCache {
+    /// Creates an empty [`DecodeCache`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the decoded instruction for the given opcode at the given
+    /// address, decoding it if the address was not executed before or held
+    /// another opcode then. Returns [`None`] if the instruction has to take
+    /// the regular path. The instructions of the larger Mega-Chip memory
+    /// are decoded without being cached, so the cache stays small.
+    pub(crate) fn get(&mut self, address: usize, opcode: u16) -> Option<Op> {
+        if address >= memory::MEMORY_SIZE {
+            return Op::decode(opcode);
+        }
+        if address >= self.entries.len() {
+            self.entries.resize(address + 1, None);
+        }
+        let entry = &mut self.entries[address];
+        match entry {
+            Some(entry) if entry.opcode == opcode => entry.op,
+            _ => {
+                let op = Op::decode(opcode);
+                *entry = Some(Entry { opcode, op });
+                op
+            }
+        }
+    }
+
+    /// Discards all decoded instructions.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{quirks::Variant, rng::Rng, Chip8};
+    use alloc::vec;
+
+    #[test]
+    fn test_decode_cache() {
+        // Every cached instruction, with both outcomes of the skips:
+        // 6005 6103 7101 8010 8211 8312 8413 8F14 8015 8107 A123 3005 4005
+        // 5010 9010 3006 4006 8FF5 1200
+        let rom = vec![
+            0x60, 0x05, 0x61, 0x03, 0x71, 0x01, 0x80, 0x10, 0x82, 0x11, 0x83, 0x12, 0x84, 0x13,
+            0x8F, 0x14, 0x80, 0x15, 0x81, 0x07, 0xA1, 0x23, 0x30, 0x05, 0x40, 0x05, 0x50, 0x10,
+            0x90, 0x10, 0x30, 0x06, 0x40, 0x06, 0x8F, 0xF5, 0x12, 0x00,
+        ];
+        for variant in Variant::ALL {
+            let run = |cached| {
+                let mut chip8 = Chip8::new_with_rng(Rng::new(0));
+                chip8.set_variant(variant);
+                chip8.processor.set_decode_cache(cached);
+                chip8.load_rom_data(rom.clone()).unwrap();
+                for _ in 0..100 {
+                    chip8.step().unwrap();
+                }
+                (chip8.processor.v, chip8.processor.i, chip8.processor.pc)
+            };
+            assert_eq!(run(true), run(false), "{variant}");
+        }
+
+        // Entries follow changes of memory
+        let mut cache = DecodeCache::new();
+        assert_eq!(cache.get(0x200, 0x6005), Some(Op::Load(0, 5)));
+        assert_eq!(cache.get(0x200, 0x7005), Some(Op::Add(0, 5)));
+        assert_eq!(cache.get(0x200, 0xD005), None);
+
+        // Addresses beyond the Chip8 memory are decoded, but not cached
+        assert_eq!(cache.get(0xFF_FFFE, 0x6005), Some(Op::Load(0, 5)));
+        assert_eq!(cache.entries.len(), 0x201);
+    }
+}
@@ -0,0 +1,176 @@
+//! This module provides the difference between the machine state before and
+//! after the most recent instruction, so a debugger can highlight what an
+//! instruction changed.
+//!
+//! A [`StateDiff`] is computed from the latest snapshot of the
+//! [`crate::history::History`], so it is only available while the history
+//! is enabled. Next to the changes, it holds the [`Accesses`] of the executed
+//! instruction: the registers and memory it read and wrote, whether or not
+//! their value changed.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// The registers and memory an instruction reads and writes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Accesses {
+    /// A bitmask of the V registers read, where bit `n` stands for `Vn`.
+    pub reads: u16,
+    /// A bitmask of the V registers written, where bit `n` stands for `Vn`.
+    pub writes: u16,
+    /// Whether the index register is read.
+    pub reads_i: bool,
+    /// Whether the index register is written.
+    pub writes_i: bool,
+    /// The memory addresses read.
+    pub memory_reads: Range<usize>,
+    /// The memory addresses written.
+    pub memory_writes: Range<usize>,
+}
+
+impl Accesses {
+    /// Decodes the accesses of the given opcode, executed with the index
+    /// register set to `i`. Memory ranges are not wrapped around.
+    #[must_use]
+    pub fn of(opcode: usize, i: usize) -> Self {
+        let x = (opcode & 0x0F00) >> 8;
+        let y = (opcode & 0x00F0) >> 4;
+        let (vx, vy, vf) = (1 << x, 1 << y, 1 << 0xF);
+        let mut accesses = Self::default();
+        match (opcode & 0xF000, opcode & 0x000F, opcode & 0x00FF) {
+            (0x3000 | 0x4000 | 0xE000, ..) | (0xF000, _, 0x15 | 0x18 | 0x29 | 0x30) => {
+                accesses.reads = vx;
+            }
+            (0x5000, 0x2, _) => {
+                accesses.reads = registers_between(x, y);
+                accesses.reads_i = true;
+                accesses.memory_writes = i..i + x.abs_diff(y) + 1;
+            }
+            (0x5000, 0x3, _) => {
+                accesses.writes = registers_between(x, y);
+                accesses.reads_i = true;
+                accesses.memory_reads = i..i + x.abs_diff(y) + 1;
+            }
+            (0x5000 | 0x9000, ..) => accesses.reads = vx | vy,
+            (0x6000 | 0xC000, ..) | (0xF000, _, 0x07 | 0x0A) => accesses.writes = vx,
+            (0x7000, ..) => (accesses.reads, accesses.writes) = (vx, vx),
+            (0x8000, 0x0, _) => (accesses.reads, accesses.writes) = (vy, vx),
+            (0x8000, ..) => (accesses.reads, accesses.writes) = (vx | vy, vx | vf),
+            (0xA000, ..) | (0xF000, _, 0x00) => accesses.writes_i = true,
+            (0xB000, ..) => accesses.reads = 1,
+            (0xD000, n, _) => {
+                let len = if n == 0 { 32 } else { n };
+                (accesses.reads, accesses.writes) = (vx | vy, vf);
+                accesses.reads_i = true;
+                accesses.memory_reads = i..i + len;
+            }
+            (0xF000, _, 0x1E) => {
+                accesses.reads = vx;
+                (accesses.reads_i, accesses.writes_i) = (true, true);
+            }
+            (0xF000, _, 0x33) => {
+                accesses.reads = vx;
+                accesses.reads_i = true;
+                accesses.memory_writes = i..i + 3;
+            }
+            (0xF000, _, 0x55) => {
+                accesses.reads = registers_up_to(x);
+                accesses.reads_i = true;
+                accesses.memory_writes = i..i + x + 1;
+            }
+            (0xF000, _, 0x65) => {
+                accesses.writes = registers_up_to(x);
+                accesses.reads_i = true;
+                accesses.memory_reads = i..i + x + 1;
+            }
+            _ => {}
+        }
+        accesses
+    }
+}
+
+/// Returns the bitmask of the registers `V0` to `Vx`.
+const fn registers_up_to(x: usize) -> u16 {
+    (((1u32 << (x + 1)) - 1) & 0xFFFF) as u16
+}
+
+/// Returns the bitmask of the registers from `Vx` to `Vy`, in either order.
+const fn registers_between(x: usize, y: usize) -> u16 {
+    let (from, to) = if x < y { (x, y) } else { (y, x) };
+    registers_up_to(to) & !(registers_up_to(from) >> 1)
+}
+
+/// The changes made by the most recent instruction.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    /// The address of the instruction.
+    pub pc: usize,
+    /// The opcode of the instruction.
+    pub opcode: usize,
+    /// The registers and memory the instruction reads and writes.
+    pub accesses: Accesses,
+    /// A bitmask of the V registers whose value changed.
+    pub registers: u16,
+    /// Whether the index register changed.
+    pub i: bool,
+    /// Whether the stack pointer changed.
+    pub sp: bool,
+    /// Whether the delay timer changed.
+    pub delay_timer: bool,
+    /// Whether the sound timer changed.
+    pub sound_timer: bool,
+    /// The addresses of the memory bytes that changed, in ascending order.
+    pub memory: Vec<usize>,
+    /// The positions of the pixels that changed, row by row.
+    pub pixels: Vec<(usize, usize)>,
+}
+
+impl StateDiff {
+    /// Returns whether the given register changed.
+    #[must_use]
+    pub const fn register_changed(&self, register: usize) -> bool {
+        self.registers & 1 << register != 0
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::Chip8;
+
+    #[test]
+    fn test_diff() {
+        let mut chip8 = Chip8::new();
+        chip8.history.set_depth(1);
+        // 6005: V0 = 5, A300: I = 0x300, F033: BCD of V0, A302: I = 0x302,
+        // D001: draw
+        let rom = vec![0x60, 0x05, 0xA3, 0x00, 0xF0, 0x33, 0xA3, 0x02, 0xD0, 0x01];
+        chip8.load_rom_data(rom).unwrap();
+        assert_eq!(chip8.diff(), None);
+
+        chip8.step().unwrap();
+        let diff = chip8.diff().unwrap();
+        assert_eq!((diff.pc, diff.opcode), (0x200, 0x6005));
+        assert!(diff.register_changed(0) && !diff.i);
+        assert_eq!(diff.accesses.writes, 0b1);
+
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        let diff = chip8.diff().unwrap();
+        // 5 is stored as 0, 0, 5, so only the last byte changed
+        assert_eq!(diff.memory, [0x302]);
+        assert_eq!(diff.accesses.memory_writes, 0x300..0x303);
+
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        let diff = chip8.diff().unwrap();
+        // 0b101 drawn at (V0, V0) = (5, 5)
+        assert_eq!(diff.pixels, [(10, 5), (12, 5)]);
+        assert!(diff.accesses.reads_i);
+
+        // 5422: store V4 down to V2 at I
+        let accesses = super::Accesses::of(0x5422, 0x300);
+        assert_eq!(accesses.reads, 0b1_1100);
+        assert_eq!(accesses.memory_writes, 0x300..0x303);
+    }
+}
@@ -0,0 +1,259 @@
+//! This module translates opcodes into assembly mnemonics, independently of
+//! executing them.
+//!
+//! By default, the mnemonics follow the widely used syntax of Cowgod's Chip-8
+//! technical reference, e.g. `LD V0, 0x05` or `CALL 0x2A0`. The [`Syntax`] of
+//! Octo, e.g. `v0 := 0x05`, can be selected instead so the output can be
+//! pasted into Octo. Address operands can be replaced with the names assigned
+//! in [`Labels`].
+
+use crate::labels::Labels;
+use alloc::{format, string::String};
+
+/// The syntax instructions are written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Syntax {
+    /// An explanation of what the instruction did, including the values it
+    /// used, e.g. `Set V0 to 5`. This needs the executed state, so only the
+    /// trace of executed instructions can show it, and the disassembler falls
+    /// back to [`Syntax::Cowgod`].
+    #[default]
+    Description,
+    /// The mnemonics of Cowgod's Chip-8 technical reference, e.g.
+    /// `LD V0, 0x05`.
+    Cowgod,
+    /// The statements of the Octo assembly language, e.g. `v0 := 0x05`.
+    Octo,
+}
+
+impl Syntax {
+    /// All syntaxes, in the order they should be offered to the user.
+    pub const ALL: [Self; 3] = [Self::Description, Self::Cowgod, Self::Octo];
+
+    /// Returns the display name of the syntax.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Description => "Description",
+            Self::Cowgod => "Cowgod",
+            Self::Octo => "Octo",
+        }
+    }
+}
+
+/// Disassembles the given opcode, or returns [`None`] if it is not a valid
+/// Chip8 instruction.
+#[must_use]
+pub fn disassemble(opcode: u16) -> Option<String> {
+    disassemble_with_labels(opcode, &Labels::default())
+}
+
+/// Disassembles the given opcode like [`disassemble`], writing address
+/// operands as their label if one is assigned.
+#[must_use]
+pub fn disassemble_with_labels(opcode: u16, labels: &Labels) -> Option<String> {
+    disassemble_as(opcode, Syntax::Cowgod, labels)
+}
+
+/// Disassembles the given opcode in the given [`Syntax`], writing address
+/// operands as their label if one is assigned.
+#[must_use]
+pub fn disassemble_as(opcode: u16, syntax: Syntax, labels: &Labels) -> Option<String> {
+    match syntax {
+        Syntax::Description | Syntax::Cowgod => cowgod(opcode, labels),
+        Syntax::Octo => octo(opcode, labels),
+    }
+}
+
+/// Disassembles the given opcode in the syntax of Cowgod's reference.
+fn cowgod(opcode: u16, labels: &Labels) -> Option<String> {
+    let x = (opcode & 0x0F00) >> 8;
+    let y = (opcode & 0x00F0) >> 4;
+    let n = opcode & 0x000F;
+    let nn = opcode & 0x00FF;
+    let nnn = usize::from(opcode & 0x0FFF);
+    let addr = labels.format_address(nnn);
+
+    let text = match (opcode & 0xF000) >> 12 {
+        0x0 => match opcode {
+            0x00C0..=0x00CF => format!("SCD {n}"),
+            0x00D0..=0x00DF => format!("SCU {n}"),
+            0x00E0 => "CLS".into(),
+            0x00EE => "RET".into(),
+            0x00FB => "SCR".into(),
+            0x00FC => "SCL".into(),
+            _ => format!("SYS {addr}"),
+        },
+        0x1 => format!("JP {addr}"),
+        0x2 => format!("CALL {addr}"),
+        0x3 => format!("SE V{x:X}, {nn:#04X}"),
+        0x4 => format!("SNE V{x:X}, {nn:#04X}"),
+        0x5 if n == 0 => format!("SE V{x:X}, V{y:X}"),
+        0x6 => format!("LD V{x:X}, {nn:#04X}"),
+        0x7 => format!("ADD V{x:X}, {nn:#04X}"),
+        0x8 => {
+            let mnemonic = match n {
+                0x0 => "LD",
+                0x1 => "OR",
+                0x2 => "AND",
+                0x3 => "XOR",
+                0x4 => "ADD",
+                0x5 => "SUB",
+                0x6 => "SHR",
+                0x7 => "SUBN",
+                0xE => "SHL",
+                _ => return None,
+            };
+            format!("{mnemonic} V{x:X}, V{y:X}")
+        }
+        0x9 if n == 0 => format!("SNE V{x:X}, V{y:X}"),
+        0xA => format!("LD I, {addr}"),
+        0xB => format!("JP V0, {addr}"),
+        0xC => format!("RND V{x:X}, {nn:#04X}"),
+        0xD => format!("DRW V{x:X}, V{y:X}, {n}"),
+        0xE => match nn {
+            0x9E => format!("SKP V{x:X}"),
+            0xA1 => format!("SKNP V{x:X}"),
+            _ => return None,
+        },
+        0xF => match nn {
+            0x07 => format!("LD V{x:X}, DT"),
+            0x0A => format!("LD V{x:X}, K"),
+            0x15 => format!("LD DT, V{x:X}"),
+            0x18 => format!("LD ST, V{x:X}"),
+            0x1E => format!("ADD I, V{x:X}"),
+            0x29 => format!("LD F, V{x:X}"),
+            0x33 => format!("LD B, V{x:X}"),
+            0x55 => format!("LD [I], V{x:X}"),
+            0x65 => format!("LD V{x:X}, [I]"),
+            0x75 => format!("LD R, V{x:X}"),
+            0x85 => format!("LD V{x:X}, R"),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(text)
+}
+
+/// Disassembles the given opcode as an Octo statement. Skip instructions
+/// become `if ... then` with the inverted condition, and system calls, which
+/// Octo has no statement for, become their two bytes.
+fn octo(opcode: u16, labels: &Labels) -> Option<String> {
+    let x = (opcode & 0x0F00) >> 8;
+    let y = (opcode & 0x00F0) >> 4;
+    let n = opcode & 0x000F;
+    let nn = opcode & 0x00FF;
+    let nnn = usize::from(opcode & 0x0FFF);
+    let addr = labels.format_address(nnn);
+
+    let text = match (opcode & 0xF000) >> 12 {
+        0x0 => match opcode {
+            0x00C0..=0x00CF => format!("scroll-down {n}"),
+            0x00D0..=0x00DF => format!("scroll-up {n}"),
+            0x00E0 => "clear".into(),
+            0x00EE => "return".into(),
+            0x00FB => "scroll-right".into(),
+            0x00FC => "scroll-left".into(),
+            _ => format!("{:#04X} {nn:#04X}", opcode >> 8),
+        },
+        0x1 => format!("jump {addr}"),
+        0x2 if labels.get(nnn).is_some() => addr,
+        0x2 => format!(":call {addr}"),
+        0x3 => format!("if v{x:x} != {nn:#04X} then"),
+        0x4 => format!("if v{x:x} == {nn:#04X} then"),
+        0x5 if n == 0 => format!("if v{x:x} != v{y:x} then"),
+        0x6 => format!("v{x:x} := {nn:#04X}"),
+        0x7 => format!("v{x:x} += {nn:#04X}"),
+        0x8 => {
+            let operator = match n {
+                0x0 => ":=",
+                0x1 => "|=",
+                0x2 => "&=",
+                0x3 => "^=",
+                0x4 => "+=",
+                0x5 => "-=",
+                0x6 => ">>=",
+                0x7 => "=-",
+                0xE => "<<=",
+                _ => return None,
+            };
+            format!("v{x:x} {operator} v{y:x}")
+        }
+        0x9 if n == 0 => format!("if v{x:x} == v{y:x} then"),
+        0xA => format!("i := {addr}"),
+        0xB => format!("jump0 {addr}"),
+        0xC => format!("v{x:x} := random {nn:#04X}"),
+        0xD => format!("sprite v{x:x} v{y:x} {n}"),
+        0xE => match nn {
+            0x9E => format!("if v{x:x} -key then"),
+            0xA1 => format!("if v{x:x} key then"),
+            _ => return None,
+        },
+        0xF => match nn {
+            0x07 => format!("v{x:x} := delay"),
+            0x0A => format!("v{x:x} := key"),
+            0x15 => format!("delay := v{x:x}"),
+            0x18 => format!("buzzer := v{x:x}"),
+            0x1E => format!("i += v{x:x}"),
+            0x29 => format!("i := hex v{x:x}"),
+            0x33 => format!("bcd v{x:x}"),
+            0x55 => format!("save v{x:x}"),
+            0x65 => format!("load v{x:x}"),
+            0x75 => format!("saveflags v{x:x}"),
+            0x85 => format!("loadflags v{x:x}"),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disassemble() {
+        assert_eq!(disassemble(0x00E0).as_deref(), Some("CLS"));
+        assert_eq!(disassemble(0x00C4).as_deref(), Some("SCD 4"));
+        assert_eq!(disassemble(0x22A0).as_deref(), Some("CALL 0x02A0"));
+        assert_eq!(disassemble(0x6A05).as_deref(), Some("LD VA, 0x05"));
+        assert_eq!(disassemble(0x8124).as_deref(), Some("ADD V1, V2"));
+        assert_eq!(disassemble(0xD125).as_deref(), Some("DRW V1, V2, 5"));
+        assert_eq!(disassemble(0xF355).as_deref(), Some("LD [I], V3"));
+        assert_eq!(disassemble(0xF375).as_deref(), Some("LD R, V3"));
+        assert_eq!(disassemble(0x8008), None);
+        assert_eq!(disassemble(0x5001), None);
+
+        let mut labels = Labels::new();
+        labels.set(0x2A0, "draw_player");
+        assert_eq!(
+            disassemble_with_labels(0x22A0, &labels).as_deref(),
+            Some("CALL draw_player")
+        );
+
+        let octo = |opcode| disassemble_as(opcode, Syntax::Octo, &labels);
+        assert_eq!(octo(0x22A0).as_deref(), Some("draw_player"));
+        assert_eq!(octo(0x2300).as_deref(), Some(":call 0x0300"));
+        assert_eq!(octo(0x6A05).as_deref(), Some("va := 0x05"));
+        assert_eq!(octo(0x3105).as_deref(), Some("if v1 != 0x05 then"));
+        assert_eq!(octo(0xF329).as_deref(), Some("i := hex v3"));
+        assert_eq!(octo(0xF385).as_deref(), Some("loadflags v3"));
+        assert_eq!(octo(0x00FB).as_deref(), Some("scroll-right"));
+        assert_eq!(octo(0x0123).as_deref(), Some("0x01 0x23"));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_octo_round_trip() {
+        // The Octo syntax assembles back into the same opcodes, including
+        // calls of addresses without a label through `:call`
+        let labels = Labels::new();
+        for opcode in [0x2300, 0x6A05, 0x8124, 0xD125, 0xF329, 0x00FB, 0x0123] {
+            let text = disassemble_as(opcode, Syntax::Octo, &labels).unwrap();
+            let rom = crate::octo::assemble(&format!(": main {text}")).unwrap();
+            assert_eq!(rom[2..], opcode.to_be_bytes(), "{text}");
+        }
+    }
+}
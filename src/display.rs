@@ -0,0 +1,381 @@
+//! This module provides the display options of a frontend: fullscreen, how
+//! the display is scaled into the window, and an optional CRT-style filter.
+//!
+//! Frontends ask [`DisplayOptions::viewport`] for the rectangle of the window
+//! the display should be drawn into whenever the window is resized, and fill
+//! the rest with the letterbox color. Frontends showing the display in a
+//! panel next to others use [`DisplayOptions::viewport_in`] with the area of
+//! the panel instead. All sizes are in physical pixels, so whole scaling
+//! factors stay pixel-perfect on high-DPI screens; [`to_physical`] and
+//! [`DisplayOptions::window_size`] convert from the logical sizes windowing
+//! systems work with. [`DisplayOptions::render`] returns the
+//! display scaled up and filtered, ready to be uploaded as a texture.
+//!
+//! Programs draw with XOR, so moving sprites are briefly erased and flicker
+//! heavily. A [`Phosphor`] layer emulates the persistence of a CRT phosphor by
+//! letting turned-off pixels fade out over a few frames.
+
+use crate::graphics::{Framebuffer, WIDTH};
+
+/// How the display is scaled to fit the window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Scaling {
+    /// Scale by the largest whole factor that fits, letterboxing the rest, so
+    /// every display pixel has the same size.
+    #[default]
+    Integer,
+    /// Scale as large as fits while keeping the aspect ratio.
+    Fit,
+    /// Fill the whole window, distorting the aspect ratio.
+    Stretch,
+}
+
+impl Scaling {
+    /// All scaling modes, in the order they should be offered to the user.
+    pub const ALL: [Self; 3] = [Self::Integer, Self::Fit, Self::Stretch];
+
+    /// Returns the display name of the scaling mode.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Integer => "Integer",
+            Self::Fit => "Fit",
+            Self::Stretch => "Stretch",
+        }
+    }
+}
+
+/// A filter applied to the scaled display.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Filter {
+    /// Show the pixels as they are.
+    #[default]
+    None,
+    /// Darken the bottom row of every display pixel, like the scanlines of a
+    /// CRT monitor. Needs a scale of at least 2.
+    Scanlines,
+}
+
+impl Filter {
+    /// All filters, in the order they should be offered to the user.
+    pub const ALL: [Self; 2] = [Self::None, Self::Scanlines];
+
+    /// Returns the display name of the filter.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::None => "None",
+            Self::Scanlines => "Scanlines",
+        }
+    }
+
+    /// Applies the filter to an RGBA image that is `width` pixels wide, in
+    /// which every display pixel was scaled up to `scale` x `scale` pixels.
+    pub fn apply(self, rgba: &mut [u8], width: usize, scale: usize) {
+        match self {
+            Self::None => {}
+            Self::Scanlines => {
+                if scale < 2 {
+                    return;
+                }
+                for (y, row) in rgba.chunks_exact_mut(width * 4).enumerate() {
+                    if y % scale == scale - 1 {
+                        for pixel in row.chunks_exact_mut(4) {
+                            for channel in &mut pixel[..3] {
+                                *channel /= 2;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Converts a length in logical pixels into physical pixels on a screen with
+/// the given scale factor, e.g. `window.devicePixelRatio` in the browser,
+/// rounded to the nearest pixel.
+#[must_use]
+pub fn to_physical(logical: u32, scale_factor: f64) -> u32 {
+    // lengths on screen are far below 2^32
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let physical = (f64::from(logical) * scale_factor).round().max(0.0) as u32;
+    physical
+}
+
+/// The rectangle of the window the display is drawn into, in physical
+/// pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    /// The distance from the left edge of the window.
+    pub x: u32,
+    /// The distance from the top edge of the window.
+    pub y: u32,
+    /// The width of the display.
+    pub width: u32,
+    /// The height of the display.
+    pub height: u32,
+}
+
+/// The display options of a frontend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct DisplayOptions {
+    /// Whether the window covers the whole screen.
+    pub fullscreen: bool,
+
+    /// How the display is scaled to fit the window.
+    pub scaling: Scaling,
+
+    /// The filter applied to the scaled display.
+    pub filter: Filter,
+
+    /// The amount of frames turned-off pixels take to fade out, see
+    /// [`Phosphor`]. 0 disables the effect.
+    pub phosphor_frames: u32,
+}
+
+impl DisplayOptions {
+    /// Returns the rectangle a display of `width` x `height` pixels is drawn
+    /// into, centered in a window of `window_width` x `window_height` pixels.
+    #[must_use]
+    pub fn viewport(
+        &self,
+        width: u32,
+        height: u32,
+        window_width: u32,
+        window_height: u32,
+    ) -> Viewport {
+        let (width, height) = match self.scaling {
+            Scaling::Integer => {
+                let scale = (window_width / width.max(1))
+                    .min(window_height / height.max(1))
+                    .max(1);
+                (width * scale, height * scale)
+            }
+            Scaling::Fit => {
+                // Compare window_width / width with window_height / height
+                let (w, h) = (u64::from(width.max(1)), u64::from(height.max(1)));
+                let (window_w, window_h) = (u64::from(window_width), u64::from(window_height));
+                if window_w * h <= window_h * w {
+                    (
+                        window_width,
+                        u32::try_from(window_w * h / w).unwrap_or(window_height),
+                    )
+                } else {
+                    (
+                        u32::try_from(window_h * w / h).unwrap_or(window_width),
+                        window_height,
+                    )
+                }
+            }
+            Scaling::Stretch => (window_width, window_height),
+        };
+        Viewport {
+            x: window_width.saturating_sub(width) / 2,
+            y: window_height.saturating_sub(height) / 2,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the rectangle a display of `width` x `height` pixels is drawn
+    /// into, centered in the given area of the window, e.g. the panel left
+    /// between the debugger panels.
+    #[must_use]
+    pub fn viewport_in(&self, width: u32, height: u32, area: Viewport) -> Viewport {
+        let viewport = self.viewport(width, height, area.width, area.height);
+        Viewport {
+            x: area.x + viewport.x,
+            y: area.y + viewport.y,
+            ..viewport
+        }
+    }
+
+    /// Returns the physical size of a window showing a display of `width` x
+    /// `height` pixels, with every display pixel about `scale` logical pixels
+    /// wide on a screen with the given scale factor. The factor is rounded
+    /// to whole physical pixels, so the display fills the window exactly.
+    #[must_use]
+    pub fn window_size(width: u32, height: u32, scale: f64, scale_factor: f64) -> (u32, u32) {
+        // scaling factors are small and positive
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let scale = (scale * scale_factor).round().max(1.0) as u32;
+        (width * scale, height * scale)
+    }
+
+    /// Returns the given display as RGBA pixels, with every pixel scaled up
+    /// to a `scale` x `scale` square and the [`Filter`] applied.
+    #[must_use]
+    pub fn render(&self, fb: &Framebuffer, scale: usize) -> Vec<u8> {
+        self.render_rgb(&fb.as_rgb8(), WIDTH, scale)
+    }
+
+    /// Returns an RGB image that is `width` pixels wide as RGBA pixels, with
+    /// every pixel scaled up to a `scale` x `scale` square and the [`Filter`]
+    /// applied. This also covers displays of other sizes, e.g. Mega-Chip.
+    #[must_use]
+    pub fn render_rgb(&self, rgb: &[u8], width: usize, scale: usize) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(rgb.len() / 3 * scale * scale * 4);
+        for row in rgb.chunks_exact(width * 3) {
+            for _ in 0..scale {
+                for pixel in row.chunks_exact(3) {
+                    for _ in 0..scale {
+                        rgba.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 0xFF]);
+                    }
+                }
+            }
+        }
+        self.filter.apply(&mut rgba, width * scale, scale);
+        rgba
+    }
+}
+
+/// Emulates phosphor persistence over a sequence of displayed frames.
+///
+/// Every color channel that gets darker fades out linearly over
+/// [`Phosphor::frames`] frames instead of switching off at once, while
+/// channels that get brighter follow immediately. This suits palettes with a
+/// foreground that is brighter than the background.
+#[derive(Debug, Clone, Default)]
+pub struct Phosphor {
+    /// The amount of frames a pixel takes to fade out, or 0 to disable the
+    /// effect.
+    pub frames: u32,
+    /// The RGB image shown for the previous frame.
+    glow: Vec<u8>,
+}
+
+impl Phosphor {
+    /// Creates a new [`Phosphor`] layer fading pixels out over the given
+    /// amount of frames.
+    #[must_use]
+    pub const fn new(frames: u32) -> Self {
+        Self {
+            frames,
+            glow: Vec::new(),
+        }
+    }
+
+    /// Returns whether the effect is enabled.
+    #[must_use]
+    pub const fn is_enabled(&self) -> bool {
+        self.frames > 0
+    }
+
+    /// Feeds the RGB image of the next frame and returns the image to show.
+    /// Call this once per displayed frame.
+    pub fn apply(&mut self, rgb: &[u8]) -> &[u8] {
+        if self.frames == 0 || self.glow.len() != rgb.len() {
+            // Disabled, or the display changed its size
+            self.glow.clear();
+            self.glow.extend_from_slice(rgb);
+            return &self.glow;
+        }
+        let step = u8::try_from(255u32.div_ceil(self.frames)).unwrap_or(u8::MAX);
+        for (glow, &target) in self.glow.iter_mut().zip(rgb) {
+            *glow = target.max(glow.saturating_sub(step));
+        }
+        &self.glow
+    }
+
+    /// Returns the image returned by the previous [`Phosphor::apply`].
+    #[must_use]
+    pub fn output(&self) -> &[u8] {
+        &self.glow
+    }
+
+    /// Discards the fading pixels, e.g. after loading another program.
+    pub fn reset(&mut self) {
+        self.glow.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_viewport() {
+        let mut options = DisplayOptions::default();
+        // 650 x 400 fits 10x, leaving a letterbox around 640 x 320
+        assert_eq!(
+            options.viewport(64, 32, 650, 400),
+            Viewport {
+                x: 5,
+                y: 40,
+                width: 640,
+                height: 320
+            }
+        );
+
+        options.scaling = Scaling::Fit;
+        assert_eq!(
+            options.viewport(64, 32, 650, 400),
+            Viewport {
+                x: 0,
+                y: 37,
+                width: 650,
+                height: 325
+            }
+        );
+
+        options.scaling = Scaling::Stretch;
+        assert_eq!(options.viewport(64, 32, 650, 400).height, 400);
+
+        // A panel right of a 200 pixels wide sidebar
+        options.scaling = Scaling::Integer;
+        let area = Viewport {
+            x: 200,
+            y: 20,
+            width: 650,
+            height: 400,
+        };
+        assert_eq!(
+            options.viewport_in(64, 32, area),
+            Viewport {
+                x: 205,
+                y: 60,
+                width: 640,
+                height: 320
+            }
+        );
+
+        // 10 logical pixels are 15 physical pixels at 150%, and round to 13
+        // at 125%
+        assert_eq!(to_physical(10, 1.5), 15);
+        assert_eq!(DisplayOptions::window_size(64, 32, 10.0, 1.5), (960, 480));
+        assert_eq!(DisplayOptions::window_size(64, 32, 10.0, 1.25), (832, 416));
+        assert_eq!(DisplayOptions::window_size(64, 32, 10.0, 0.01), (64, 32));
+    }
+
+    #[test]
+    fn test_scanlines() {
+        let mut fb = Framebuffer::new();
+        fb.draw_byte(0, 0, 0x80);
+        let options = DisplayOptions {
+            filter: Filter::Scanlines,
+            ..DisplayOptions::default()
+        };
+        let rgba = options.render(&fb, 2);
+        let row = WIDTH * 2 * 4;
+        assert_eq!(rgba[..4], [255, 255, 255, 255]);
+        assert_eq!(rgba[row..row + 4], [127, 127, 127, 255]);
+    }
+
+    #[test]
+    fn test_phosphor() {
+        let mut phosphor = Phosphor::new(3);
+        assert_eq!(phosphor.apply(&[255, 0]), [255, 0]);
+        // The turned-off channel fades out over three frames
+        assert_eq!(phosphor.apply(&[0, 255]), [170, 255]);
+        assert_eq!(phosphor.apply(&[0, 0]), [85, 170]);
+        assert_eq!(phosphor.apply(&[0, 0]), [0, 85]);
+
+        phosphor.frames = 0;
+        assert_eq!(phosphor.apply(&[0, 0]), [0, 0]);
+    }
+}
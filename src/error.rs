@@ -0,0 +1,78 @@
+//! This module provides the [`Chip8Error`] type, describing the ways in which
+//! executing a Chip8 program can fail.
+
+use core::fmt;
+
+/// An error raised while executing a Chip8 program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Chip8Error {
+    /// The opcode at `pc` does not decode to any known instruction.
+    InvalidOpcode {
+        /// The address of the invalid opcode.
+        pc: usize,
+        /// The invalid opcode.
+        opcode: usize,
+    },
+
+    /// A return instruction at `pc` was executed with an empty stack.
+    StackUnderflow {
+        /// The address of the return instruction.
+        pc: usize,
+    },
+
+    /// A call instruction at `pc` was executed with a full stack.
+    StackOverflow {
+        /// The address of the call instruction.
+        pc: usize,
+    },
+
+    /// An instruction tried to access memory outside of the address space.
+    MemoryOutOfBounds {
+        /// The address that was accessed.
+        addr: usize,
+    },
+
+    /// A ROM of `size` bytes does not fit into the `max` bytes of program
+    /// memory.
+    RomTooLarge {
+        /// The size of the ROM in bytes.
+        size: usize,
+        /// The maximum size of a ROM in bytes.
+        max: usize,
+    },
+
+    /// A ROM without any bytes was given.
+    EmptyRom,
+
+    /// ROMs cannot be loaded at `addr`, because it lies inside the
+    /// interpreter area or outside of memory.
+    InvalidLoadAddress {
+        /// The rejected address.
+        addr: usize,
+    },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidOpcode { pc, opcode } => {
+                write!(f, "invalid opcode {opcode:#06X} at {pc:#06X}")
+            }
+            Self::StackUnderflow { pc } => write!(f, "stack underflow at {pc:#06X}"),
+            Self::StackOverflow { pc } => write!(f, "stack overflow at {pc:#06X}"),
+            Self::MemoryOutOfBounds { addr } => {
+                write!(f, "memory access out of bounds at {addr:#06X}")
+            }
+            Self::RomTooLarge { size, max } => {
+                write!(f, "ROM of {size} bytes exceeds the maximum of {max} bytes")
+            }
+            Self::EmptyRom => write!(f, "the ROM is empty"),
+            Self::InvalidLoadAddress { addr } => {
+                write!(f, "cannot load a ROM at {addr:#06X}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for Chip8Error {}
@@ -0,0 +1,107 @@
+//! This module provides the events a [`crate::runner::Chip8Runner`] publishes
+//! about the system, so frontends react to the core instead of polling its
+//! state.
+//!
+//! Every layer on top of the core, e.g. a GUI, a TUI, a script or a network
+//! session, subscribes to the same [`EventBus`] and receives every
+//! [`EmulatorEvent`] on its own channel. Subscribers that dropped their
+//! receiver are forgotten on the next event.
+
+use std::sync::mpsc;
+
+use crate::{error::Chip8Error, runner::RunnerEvent};
+
+/// Something that happened to the system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmulatorEvent {
+    /// The display changed and should be presented again.
+    DisplayUpdated,
+    /// The sound timer became non-zero, so the buzzer should sound.
+    SoundStarted,
+    /// The sound timer ran out, so the buzzer should be silent.
+    SoundStopped,
+    /// The system was reset and a new ROM was loaded.
+    RomLoaded,
+    /// Execution was paused at the given address by a breakpoint, a
+    /// breakpoint on an event or the address to run to.
+    Breakpoint {
+        /// The address of the program counter.
+        pc: usize,
+    },
+    /// The program raised a [`Chip8Error`].
+    Error(Chip8Error),
+    /// The program halted or ran past the end of memory.
+    Ended,
+}
+
+impl From<RunnerEvent> for EmulatorEvent {
+    fn from(event: RunnerEvent) -> Self {
+        match event {
+            RunnerEvent::End | RunnerEvent::Loop { .. } => Self::Ended,
+            RunnerEvent::Error(err) => Self::Error(err),
+            RunnerEvent::Reached { pc }
+            | RunnerEvent::Breakpoint { pc }
+            | RunnerEvent::Event { pc, .. } => Self::Breakpoint { pc },
+        }
+    }
+}
+
+/// Hands out [`EmulatorEvent`]s to any amount of subscribers.
+#[derive(Debug, Default)]
+pub struct EventBus {
+    /// The channels of all subscribers.
+    subscribers: Vec<mpsc::Sender<EmulatorEvent>>,
+}
+
+impl EventBus {
+    /// Creates an [`EventBus`] without any subscribers.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribes to all events published from now on.
+    pub fn subscribe(&mut self) -> mpsc::Receiver<EmulatorEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    /// Returns whether anyone listens, so publishers can skip the work of
+    /// detecting events otherwise.
+    #[must_use]
+    pub const fn has_subscribers(&self) -> bool {
+        !self.subscribers.is_empty()
+    }
+
+    /// Sends the given event to every subscriber, and forgets the ones that
+    /// dropped their receiver.
+    pub fn publish(&mut self, event: EmulatorEvent) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_bus() {
+        let mut bus = EventBus::new();
+        assert!(!bus.has_subscribers());
+        let first = bus.subscribe();
+        let second = bus.subscribe();
+        bus.publish(EmulatorEvent::RomLoaded);
+        assert_eq!(first.try_recv(), Ok(EmulatorEvent::RomLoaded));
+        assert_eq!(second.try_recv(), Ok(EmulatorEvent::RomLoaded));
+
+        drop(first);
+        bus.publish(RunnerEvent::Breakpoint { pc: 0x202 }.into());
+        assert_eq!(
+            second.try_recv(),
+            Ok(EmulatorEvent::Breakpoint { pc: 0x202 })
+        );
+        assert_eq!(bus.subscribers.len(), 1);
+    }
+}
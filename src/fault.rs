@@ -0,0 +1,336 @@
+//! This module provides the [`Fault`] report, describing a [`Chip8Error`]
+//! together with the machine state it occurred in.
+//!
+//! Frontends receive faults through [`super::runner::Chip8Runner::subscribe_faults`]
+//! and can present them to the user, who may then reset the system or ignore
+//! the faulting instruction with [`super::Chip8::skip_instruction`].
+//!
+//! A [`CrashReport`] extends a fault with the full machine state: the
+//! registers, the stack, the memory around the program counter and the index
+//! register, and the recently executed instructions. The runner keeps the
+//! report of the latest fault and writes it to a file once a crash directory
+//! is set, so users can attach it to bug reports.
+
+use std::fmt;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{error::Chip8Error, processor::Instruction, roms, Chip8};
+
+/// The amount of recently executed instructions included in a [`Fault`].
+const CONTEXT_LENGTH: usize = 8;
+
+/// The amount of bytes of memory shown before the address of interest in a
+/// [`CrashReport`].
+const MEMORY_BEFORE: usize = 0x20;
+
+/// The amount of bytes of memory shown in total in a [`CrashReport`].
+const MEMORY_LENGTH: usize = 0x40;
+
+/// The amount of bytes in a line of a memory dump.
+const MEMORY_LINE: usize = 16;
+
+/// A report of a [`Chip8Error`] raised while executing a program.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Fault {
+    /// The error that was raised.
+    pub error: Chip8Error,
+
+    /// The address of the faulting instruction.
+    pub pc: usize,
+
+    /// The opcode of the faulting instruction, if the program counter points
+    /// into memory.
+    pub opcode: Option<usize>,
+
+    /// The instructions executed right before the fault, with the most recent
+    /// one first.
+    pub context: Vec<Instruction>,
+}
+
+impl Fault {
+    /// Captures a [`Fault`] for the given error from the current state of the
+    /// given [`Chip8`].
+    #[must_use]
+    pub fn capture(chip8: &Chip8, error: Chip8Error) -> Self {
+        Self {
+            error,
+            pc: chip8.processor.pc,
+            opcode: chip8.current_opcode(),
+            context: chip8
+                .processor
+                .instructions
+                .iter()
+                .take(CONTEXT_LENGTH)
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.error)?;
+        match self.opcode {
+            Some(opcode) => writeln!(f, "PC: {:#06X}  opcode: {opcode:#06X}", self.pc)?,
+            None => writeln!(f, "PC: {:#06X}", self.pc)?,
+        }
+        for instruction in &self.context {
+            writeln!(
+                f,
+                "  {:#06X}  {:04X}  {instruction}",
+                instruction.address, instruction.opcode
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A window of memory included in a [`CrashReport`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryWindow {
+    /// The address of the first byte.
+    pub start: usize,
+
+    /// The bytes of memory.
+    pub bytes: Vec<u8>,
+}
+
+impl MemoryWindow {
+    /// Captures the memory of the given [`Chip8`] around the given address,
+    /// aligned to lines of 16 bytes and limited to the size of memory.
+    fn capture(chip8: &Chip8, addr: usize) -> Self {
+        let memory = &chip8.bus.memory;
+        let start = (addr.min(memory.len()) & !(MEMORY_LINE - 1)).saturating_sub(MEMORY_BEFORE);
+        let end = (start + MEMORY_LENGTH).min(memory.len());
+        Self {
+            start,
+            bytes: (start..end).map(|addr| memory[addr]).collect(),
+        }
+    }
+}
+
+impl fmt::Display for MemoryWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (line, bytes) in self.bytes.chunks(MEMORY_LINE).enumerate() {
+            write!(f, "  {:#06X} ", self.start + line * MEMORY_LINE)?;
+            for byte in bytes {
+                write!(f, " {byte:02X}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// A [`Fault`] together with the full machine state it occurred in, see the
+/// [module documentation](self).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CrashReport {
+    /// The fault, with the instructions executed right before it.
+    pub fault: Fault,
+
+    /// The SHA-1 hash of the loaded ROM.
+    pub rom_hash: String,
+
+    /// The size of the loaded ROM in bytes.
+    pub rom_size: usize,
+
+    /// The general purpose registers V0 to VF.
+    pub v: [u8; 16],
+
+    /// The index register.
+    pub i: usize,
+
+    /// The return addresses on the stack, with the outermost call first.
+    pub stack: Vec<usize>,
+
+    /// The delay timer.
+    pub delay_timer: u8,
+
+    /// The sound timer.
+    pub sound_timer: u8,
+
+    /// The quirks the program ran with, in their debug representation.
+    pub quirks: String,
+
+    /// The memory around the program counter.
+    pub memory_at_pc: MemoryWindow,
+
+    /// The memory around the index register.
+    pub memory_at_i: MemoryWindow,
+
+    /// All recently executed instructions, with the most recent one first.
+    pub trace: Vec<Instruction>,
+}
+
+impl CrashReport {
+    /// Captures a [`CrashReport`] for the given error from the current state
+    /// of the given [`Chip8`].
+    #[must_use]
+    pub fn capture(chip8: &Chip8, error: Chip8Error) -> Self {
+        let cpu = &chip8.processor;
+        Self {
+            fault: Fault::capture(chip8, error),
+            rom_hash: roms::hash(chip8.rom()),
+            rom_size: chip8.rom().len(),
+            v: cpu.v,
+            i: cpu.i,
+            stack: cpu.stack[..cpu.sp.min(cpu.stack.len())].to_vec(),
+            delay_timer: chip8.bus.clock.delay_timer,
+            sound_timer: chip8
+                .bus
+                .clock
+                .sound_timer
+                .load(std::sync::atomic::Ordering::SeqCst),
+            quirks: format!("{:?}", cpu.quirks),
+            memory_at_pc: MemoryWindow::capture(chip8, cpu.pc),
+            memory_at_i: MemoryWindow::capture(chip8, cpu.i),
+            trace: cpu.instructions.iter().cloned().collect(),
+        }
+    }
+
+    /// Returns whether the report describes the same error at the same
+    /// address as the given one, e.g. because a program keeps running into
+    /// it.
+    #[must_use]
+    pub fn same_fault(&self, other: &Self) -> bool {
+        self.fault.error == other.fault.error && self.fault.pc == other.fault.pc
+    }
+
+    /// Writes the report into a new file in the given directory, creating the
+    /// directory if needed, and returns the path of the file. Files are named
+    /// after the time of writing, e.g. `crash-1700000000.txt`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_to(&self, dir: &Path) -> io::Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let mut path = dir.join(format!("crash-{secs}.txt"));
+        for n in 1.. {
+            if !path.exists() {
+                break;
+            }
+            path = dir.join(format!("crash-{secs}-{n}.txt"));
+        }
+        fs::write(&path, self.to_string())?;
+        Ok(path)
+    }
+
+    /// Returns the directory crash reports are written to by default, inside
+    /// the platform data directory, or [`None`] if the platform has none.
+    #[cfg(all(feature = "persistence", not(target_arch = "wasm32")))]
+    #[must_use]
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join(crate::config::APP_DIR).join("crashes"))
+    }
+}
+
+impl fmt::Display for CrashReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fault = &self.fault;
+        writeln!(f, "Crash report: {}", fault.error)?;
+        writeln!(f, "ROM: {} ({} bytes)", self.rom_hash, self.rom_size)?;
+        writeln!(f, "Quirks: {}", self.quirks)?;
+        writeln!(f)?;
+        writeln!(f, "Registers:")?;
+        write!(f, " ")?;
+        for (x, value) in self.v.iter().enumerate() {
+            write!(f, " V{x:X}={value:02X}")?;
+        }
+        writeln!(f)?;
+        write!(f, "  PC={:04X} I={:04X}", fault.pc, self.i)?;
+        if let Some(opcode) = fault.opcode {
+            write!(f, " opcode={opcode:04X}")?;
+        }
+        writeln!(
+            f,
+            " DT={:02X} ST={:02X}",
+            self.delay_timer, self.sound_timer
+        )?;
+        writeln!(f)?;
+        writeln!(f, "Stack:")?;
+        if self.stack.is_empty() {
+            writeln!(f, "  (empty)")?;
+        }
+        for addr in self.stack.iter().rev() {
+            writeln!(f, "  {addr:#06X}")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Memory at PC:")?;
+        write!(f, "{}", self.memory_at_pc)?;
+        writeln!(f)?;
+        writeln!(f, "Memory at I:")?;
+        write!(f, "{}", self.memory_at_i)?;
+        writeln!(f)?;
+        writeln!(f, "Recent instructions, most recent first:")?;
+        for instruction in &self.trace {
+            writeln!(
+                f,
+                "  {:#06X}  {:04X}  {instruction}",
+                instruction.address, instruction.opcode
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture() {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_data(vec![0x60, 0x05, 0x80, 0x08]).unwrap();
+        chip8.step().unwrap();
+        let error = chip8.step().unwrap_err();
+
+        let fault = Fault::capture(&chip8, error);
+        assert_eq!(fault.pc, 0x202);
+        assert_eq!(fault.opcode, Some(0x8008));
+        assert_eq!(fault.context.len(), 1);
+        assert_eq!(fault.context[0].opcode, 0x6005);
+
+        // The crash report holds the full state
+        let report = CrashReport::capture(&chip8, error);
+        assert_eq!(report.v[0], 5);
+        assert_eq!(report.memory_at_pc.start, 0x1E0);
+        assert_eq!(report.memory_at_pc.bytes.len(), 0x40);
+        assert_eq!(
+            report.memory_at_pc.bytes[0x20..0x24],
+            [0x60, 0x05, 0x80, 0x08]
+        );
+        assert_eq!(report.memory_at_i.start, 0);
+        let text = report.to_string();
+        assert!(text.starts_with("Crash report: invalid opcode 0x8008 at 0x0202\n"));
+        assert!(text.contains("  0x0200  60 05 80 08 00"));
+        assert!(report.same_fault(&CrashReport::capture(&chip8, error)));
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let dir = std::env::temp_dir().join(format!("chip8-crash-{}", std::process::id()));
+            let first = report.save_to(&dir).unwrap();
+            let second = report.save_to(&dir).unwrap();
+            assert_ne!(first, second);
+            assert_eq!(fs::read_to_string(&first).unwrap(), text);
+            fs::remove_dir_all(dir).unwrap();
+        }
+
+        // Ignoring the fault continues after the faulting instruction
+        chip8.skip_instruction();
+        assert_eq!(chip8.processor.pc, 0x204);
+    }
+}
@@ -0,0 +1,193 @@
+//! This module exports the interpreter core through a C ABI, so it can be
+//! embedded in frontends not written in Rust, e.g. C programs, Python through
+//! `ctypes` or game engines.
+//!
+//! The declarations are in `include/chip8.h`. A system is created with
+//! [`chip8_new`], driven through the other functions and released with
+//! [`chip8_free`]. All functions accept a null pointer and fail gracefully.
+
+use core::{ffi::c_int, ptr, slice};
+
+use crate::{clock, graphics::WIDTH, processor::StepResult, Chip8};
+
+/// Returned by [`chip8_step`] when an instruction was executed.
+pub const CHIP8_CONTINUE: c_int = 0;
+
+/// Returned by [`chip8_step`] when the processor waits for a key press.
+pub const CHIP8_WAITING_FOR_KEY: c_int = 1;
+
+/// Returned by [`chip8_step`] when the program jumped to its own address.
+pub const CHIP8_LOOP: c_int = 2;
+
+/// Returned by [`chip8_step`] when the program counter ran past the end of
+/// memory.
+pub const CHIP8_END: c_int = 3;
+
+/// Returned by [`chip8_step`] when the processor waits for the next
+/// [`chip8_tick_60hz`] before drawing a sprite.
+pub const CHIP8_WAITING_FOR_VBLANK: c_int = 4;
+
+/// Returned by the functions of this module when they fail.
+pub const CHIP8_ERROR: c_int = -1;
+
+/// Creates a new system. The returned pointer must be released with
+/// [`chip8_free`]. Its timers only tick through [`chip8_tick_60hz`].
+#[no_mangle]
+pub extern "C" fn chip8_new() -> *mut Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.set_time_source(clock::Manual);
+    Box::into_raw(Box::new(chip8))
+}
+
+/// Releases a system created by [`chip8_new`].
+///
+/// # Safety
+///
+/// `chip8` must be null or a pointer returned by [`chip8_new`] that was not
+/// released yet.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        drop(Box::from_raw(chip8));
+    }
+}
+
+/// Resets the system and loads the ROM of `len` bytes at `data`. Returns `0`
+/// on success, or [`CHIP8_ERROR`] if the ROM does not fit into memory.
+///
+/// # Safety
+///
+/// `chip8` must be null or a live pointer returned by [`chip8_new`], and
+/// `data` must be null or point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(chip8: *mut Chip8, data: *const u8, len: usize) -> c_int {
+    let (Some(chip8), false) = (chip8.as_mut(), data.is_null()) else {
+        return CHIP8_ERROR;
+    };
+    let rom = slice::from_raw_parts(data, len).to_vec();
+    match chip8.reset_and_load(rom) {
+        Ok(()) => 0,
+        Err(_) => CHIP8_ERROR,
+    }
+}
+
+/// Executes one instruction cycle.
+///
+/// Returns one of [`CHIP8_CONTINUE`], [`CHIP8_WAITING_FOR_KEY`],
+/// [`CHIP8_LOOP`], [`CHIP8_END`] and [`CHIP8_WAITING_FOR_VBLANK`], or
+/// [`CHIP8_ERROR`] if the instruction cannot be executed.
+///
+/// # Safety
+///
+/// `chip8` must be null or a live pointer returned by [`chip8_new`].
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(chip8: *mut Chip8) -> c_int {
+    let Some(chip8) = chip8.as_mut() else {
+        return CHIP8_ERROR;
+    };
+    match chip8.step() {
+        Ok(StepResult::Continue) => CHIP8_CONTINUE,
+        Ok(StepResult::WaitingForKey) => CHIP8_WAITING_FOR_KEY,
+        Ok(StepResult::Loop) => CHIP8_LOOP,
+        Ok(StepResult::End) => CHIP8_END,
+        Ok(StepResult::WaitingForVblank) => CHIP8_WAITING_FOR_VBLANK,
+        Err(_) => CHIP8_ERROR,
+    }
+}
+
+/// Decrements the timers once, see [`Chip8::tick_timers`]. Call this at 60Hz.
+///
+/// # Safety
+///
+/// `chip8` must be null or a live pointer returned by [`chip8_new`].
+#[no_mangle]
+pub unsafe extern "C" fn chip8_tick_60hz(chip8: *mut Chip8) {
+    if let Some(chip8) = chip8.as_mut() {
+        chip8.tick_timers();
+    }
+}
+
+/// Sets the state of all 16 keys at once, one bit per key with key `0` in the
+/// lowest bit.
+///
+/// # Safety
+///
+/// `chip8` must be null or a live pointer returned by [`chip8_new`].
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_keys(chip8: *mut Chip8, keys: u16) {
+    if let Some(chip8) = chip8.as_mut() {
+        chip8.set_keys(keys);
+    }
+}
+
+/// Returns the width of the display in pixels.
+#[no_mangle]
+pub const extern "C" fn chip8_display_width() -> usize {
+    WIDTH
+}
+
+/// Returns the current height of the display in pixels, which changes with
+/// the hires mode.
+///
+/// # Safety
+///
+/// `chip8` must be null or a live pointer returned by [`chip8_new`].
+#[no_mangle]
+pub unsafe extern "C" fn chip8_display_height(chip8: *const Chip8) -> usize {
+    chip8
+        .as_ref()
+        .map_or(0, |chip8| chip8.bus.graphics.height())
+}
+
+/// Copies the palette index of every pixel into `out`, row by row, writing
+/// at most `len` bytes. Returns the amount of pixels of the display, which is
+/// larger than `len` if `out` was too small.
+///
+/// # Safety
+///
+/// `chip8` must be null or a live pointer returned by [`chip8_new`], and
+/// `out` must be null or point to `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_framebuffer(
+    chip8: *const Chip8,
+    out: *mut u8,
+    len: usize,
+) -> usize {
+    let Some(chip8) = chip8.as_ref() else {
+        return 0;
+    };
+    let graphics = &chip8.bus.graphics;
+    if !out.is_null() {
+        for (index, (_, _, pixel)) in graphics.iter().take(len).enumerate() {
+            ptr::write(out.add(index), pixel);
+        }
+    }
+    WIDTH * graphics.height()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffi() {
+        unsafe {
+            let chip8 = chip8_new();
+            // A000: I = 0x000, D001: draw the first row of the digit 0
+            let rom = [0xA0, 0x00, 0xD0, 0x01];
+            assert_eq!(chip8_load_rom(chip8, rom.as_ptr(), rom.len()), 0);
+            assert_eq!(chip8_step(chip8), CHIP8_CONTINUE);
+            assert_eq!(chip8_step(chip8), CHIP8_CONTINUE);
+
+            let mut pixels = vec![0xFF; chip8_display_width() * chip8_display_height(chip8)];
+            let len = chip8_get_framebuffer(chip8, pixels.as_mut_ptr(), pixels.len());
+            assert_eq!(len, pixels.len());
+            assert_eq!(pixels[..5], [1, 1, 1, 1, 0]);
+
+            chip8_set_keys(chip8, 0b10);
+            assert!((*chip8).bus.input.is_key_pressed(1));
+            assert_eq!(chip8_step(ptr::null_mut()), CHIP8_ERROR);
+            chip8_free(chip8);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! This module provides the HP-48 RPL user flags, which SUPER-CHIP programs
+//! write with `Fx75` and read with `Fx85`, typically to keep high scores.
+//!
+//! On the HP-48, the flags survive turning the calculator off, so the
+//! [`RplFlags`] are kept across [`crate::Chip8::reset`]. With the
+//! `persistence` feature enabled, they can be stored in a JSON sidecar file
+//! next to the ROM, or in the browser, so they also survive a restart.
+
+#[cfg(feature = "persistence")]
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "persistence")]
+use crate::sidecar;
+
+/// The amount of flag registers. SUPER-CHIP has 8, XO-CHIP extends them to
+/// 16.
+pub const FLAG_COUNT: usize = 16;
+
+/// The extension of the sidecar file holding the flags of a ROM.
+#[cfg(feature = "persistence")]
+pub const SIDECAR_EXTENSION: &str = "flags";
+
+/// The RPL user flag registers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RplFlags {
+    /// The values of the flag registers.
+    values: [u8; FLAG_COUNT],
+
+    /// Whether the flags were written since [`RplFlags::take_changed`] was
+    /// last called.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    changed: bool,
+}
+
+impl RplFlags {
+    /// Creates a new set of [`RplFlags`], all cleared.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            values: [0; FLAG_COUNT],
+            changed: false,
+        }
+    }
+
+    /// Returns the values of the flag registers.
+    #[must_use]
+    pub const fn values(&self) -> &[u8; FLAG_COUNT] {
+        &self.values
+    }
+
+    /// Writes the given values into the first flag registers, as done by
+    /// `Fx75`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if more than [`FLAG_COUNT`] values are given.
+    pub fn store(&mut self, values: &[u8]) {
+        self.values[..values.len()].copy_from_slice(values);
+        self.changed = true;
+    }
+
+    /// Returns whether the flags were written since the last call, and
+    /// resets that state, so a frontend knows when to save them.
+    pub const fn take_changed(&mut self) -> bool {
+        let changed = self.changed;
+        self.changed = false;
+        changed
+    }
+
+    /// Returns the path of the sidecar file for the ROM at the given path,
+    /// i.e. the ROM path with its extension replaced by
+    /// [`SIDECAR_EXTENSION`].
+    #[cfg(feature = "persistence")]
+    #[must_use]
+    pub fn sidecar_path(rom_path: impl AsRef<Path>) -> PathBuf {
+        sidecar::path(rom_path, SIDECAR_EXTENSION)
+    }
+
+    /// Deserializes the [`RplFlags`] of the ROM with the given hash from a
+    /// sidecar JSON string. Returns [`None`] if the sidecar belongs to a
+    /// different ROM.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the string is not a valid sidecar.
+    #[cfg(feature = "persistence")]
+    pub fn from_json(json: &str, rom_hash: &str) -> Result<Option<Self>, serde_json::Error> {
+        sidecar::from_json(json, rom_hash)
+    }
+
+    /// Serializes the [`RplFlags`] into a sidecar JSON string for the ROM with
+    /// the given hash.
+    ///
+    /// # Panics
+    ///
+    /// Never panics in practice, since [`RplFlags`] always serialize to JSON.
+    #[cfg(feature = "persistence")]
+    #[must_use]
+    pub fn to_json(&self, rom_hash: &str) -> String {
+        sidecar::to_json(self, rom_hash).expect("flags are always serializable")
+    }
+
+    /// Loads the [`RplFlags`] of the ROM at the given path from its sidecar
+    /// file. Returns cleared flags if there is no sidecar file, or if it was
+    /// saved for a different version of the ROM.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM or an existing sidecar cannot be read, or
+    /// if the sidecar is invalid.
+    #[cfg(feature = "persistence")]
+    pub fn load_for_rom(rom_path: impl AsRef<Path>) -> io::Result<Self> {
+        sidecar::load(rom_path, SIDECAR_EXTENSION)
+    }
+
+    /// Saves the [`RplFlags`] to the sidecar file of the ROM at the given
+    /// path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM cannot be read or the sidecar cannot be
+    /// written.
+    #[cfg(feature = "persistence")]
+    pub fn save_for_rom(&self, rom_path: impl AsRef<Path>) -> io::Result<()> {
+        sidecar::save(self, rom_path, SIDECAR_EXTENSION)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::Chip8;
+
+    #[test]
+    fn test_flags() {
+        let mut chip8 = Chip8::new();
+        // 6007: V0 = 7, 6109: V1 = 9, F175: save V0 and V1 to the flags,
+        // 00E0: clear, F185: load V0 and V1 from the flags
+        let rom = vec![0x60, 0x07, 0x61, 0x09, 0xF1, 0x75, 0x00, 0xE0, 0xF1, 0x85];
+        chip8.load_rom_data(rom).unwrap();
+        for _ in 0..3 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.bus.flags.values()[..3], [7, 9, 0]);
+        assert!(chip8.bus.flags.take_changed());
+        assert!(!chip8.bus.flags.take_changed());
+
+        // The flags survive a reset
+        chip8.reset_keep_rom().unwrap();
+        chip8.processor.pc = 0x208;
+        chip8.step().unwrap();
+        assert_eq!(chip8.processor.v[..2], [7, 9]);
+
+        #[cfg(feature = "persistence")]
+        {
+            use super::RplFlags;
+            let json = chip8.bus.flags.to_json("hash");
+            let flags = RplFlags::from_json(&json, "hash").unwrap();
+            assert_eq!(flags, Some(chip8.bus.flags));
+            assert_eq!(RplFlags::from_json(&json, "other").unwrap(), None);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! This module defines the interface between the emulator core and the
+//! frontends presenting it, so SDL, terminal, wasm canvas or test frontends
+//! can plug in without changes to the core.
+//!
+//! A frontend implements [`Chip8Frontend`] and hands itself to
+//! [`Chip8Runner::run_frame`] once per displayed frame. The runner reads the
+//! key state from the frontend, executes the instructions that are due and
+//! then lets the frontend draw the display and play the buzzer.
+//!
+//! [`Chip8Runner::run_frame`]: crate::runner::Chip8Runner::run_frame
+
+use crate::{graphics::Framebuffer, keymap::KEY_COUNT};
+
+/// A frontend presenting a [`crate::Chip8`] to the user.
+pub trait Chip8Frontend {
+    /// Presents the given display contents.
+    fn draw(&mut self, fb: &Framebuffer);
+
+    /// Returns the current state of the 16 keys of the keypad.
+    fn keys(&self) -> [bool; KEY_COUNT];
+
+    /// Turns the buzzer on or off. Called once per frame, whether the state
+    /// changed or not.
+    fn beep(&mut self, on: bool);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{runner::Chip8Runner, Chip8};
+
+    /// A frontend that records what it was asked to present.
+    #[derive(Debug, Default)]
+    struct TestFrontend {
+        keys: [bool; KEY_COUNT],
+        frames: Vec<Framebuffer>,
+        beeps: Vec<bool>,
+    }
+
+    impl Chip8Frontend for TestFrontend {
+        fn draw(&mut self, fb: &Framebuffer) {
+            self.frames.push(*fb);
+        }
+
+        fn keys(&self) -> [bool; KEY_COUNT] {
+            self.keys
+        }
+
+        fn beep(&mut self, on: bool) {
+            self.beeps.push(on);
+        }
+    }
+
+    #[test]
+    fn test_run_frame() {
+        let mut chip8 = Chip8::new();
+        // F00A: wait for a key in V0, F018: sound timer = V0, 1204: loop
+        chip8
+            .load_rom_data(vec![0xF0, 0x0A, 0xF0, 0x18, 0x12, 0x04])
+            .unwrap();
+        let mut runner = Chip8Runner::new(chip8);
+        // Execute some instructions during the few microseconds between
+        // frames
+        runner.set_ips(1_000_000_000);
+        let mut frontend = TestFrontend::default();
+
+        runner.run_frame(&mut frontend);
+        assert!(runner.chip8.bus.input.waiting());
+        assert_eq!(frontend.beeps, [false]);
+
+        // Press and release key 9 to answer the key request
+        frontend.keys[9] = true;
+        runner.run_frame(&mut frontend);
+        frontend.keys[9] = false;
+        runner.run_frame(&mut frontend);
+        runner.run_frame(&mut frontend);
+        assert_eq!(runner.chip8.processor.v[0], 9);
+        assert_eq!(frontend.beeps.last(), Some(&true));
+        assert_eq!(frontend.frames.len(), 4);
+        assert_eq!(runner.stats().frames, 4);
+    }
+}
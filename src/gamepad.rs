@@ -0,0 +1,336 @@
+//! This module maps gamepad buttons to the keys of the Chip8 keypad.
+//!
+//! Most Chip8 games only use two to four keys, which play far better on a
+//! d-pad than on the keyboard. A [`GamepadMap`] binds every [`Button`] to at
+//! most one Chip8 key, and several buttons may share a key. Since games
+//! disagree on which keys they use, the mapping of a ROM can be stored in a
+//! JSON sidecar file next to it with the `persistence` feature enabled.
+//!
+//! With the `gamepad` feature enabled, [`Gamepads`] reads connected
+//! controllers through `gilrs` and forwards their buttons to a
+//! [`super::Chip8`].
+
+use std::{collections::BTreeMap, fmt};
+#[cfg(feature = "persistence")]
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "persistence")]
+use crate::sidecar;
+#[cfg(feature = "gamepad")]
+use crate::Chip8;
+
+/// The extension of the sidecar file holding the gamepad mapping of a ROM.
+#[cfg(feature = "persistence")]
+pub const SIDECAR_EXTENSION: &str = "pad";
+
+/// A button of a standard gamepad, named by its position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Button {
+    /// Up on the d-pad.
+    DPadUp,
+    /// Down on the d-pad.
+    DPadDown,
+    /// Left on the d-pad.
+    DPadLeft,
+    /// Right on the d-pad.
+    DPadRight,
+    /// The bottom face button, e.g. A on Xbox controllers.
+    South,
+    /// The right face button, e.g. B on Xbox controllers.
+    East,
+    /// The top face button, e.g. Y on Xbox controllers.
+    North,
+    /// The left face button, e.g. X on Xbox controllers.
+    West,
+    /// The left shoulder button.
+    LeftShoulder,
+    /// The right shoulder button.
+    RightShoulder,
+    /// The select or back button.
+    Select,
+    /// The start button.
+    Start,
+}
+
+impl Button {
+    /// All buttons, e.g. to list them in a mapping dialog.
+    pub const ALL: [Self; 12] = [
+        Self::DPadUp,
+        Self::DPadDown,
+        Self::DPadLeft,
+        Self::DPadRight,
+        Self::South,
+        Self::East,
+        Self::North,
+        Self::West,
+        Self::LeftShoulder,
+        Self::RightShoulder,
+        Self::Select,
+        Self::Start,
+    ];
+
+    /// Returns the human-readable name of the button.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::DPadUp => "D-pad up",
+            Self::DPadDown => "D-pad down",
+            Self::DPadLeft => "D-pad left",
+            Self::DPadRight => "D-pad right",
+            Self::South => "South",
+            Self::East => "East",
+            Self::North => "North",
+            Self::West => "West",
+            Self::LeftShoulder => "Left shoulder",
+            Self::RightShoulder => "Right shoulder",
+            Self::Select => "Select",
+            Self::Start => "Start",
+        }
+    }
+
+    /// Converts the index of a button in the standard layout of the browser
+    /// Gamepad API, or returns [`None`] for buttons without an equivalent.
+    #[must_use]
+    pub const fn from_standard_index(index: usize) -> Option<Self> {
+        Some(match index {
+            0 => Self::South,
+            1 => Self::East,
+            2 => Self::West,
+            3 => Self::North,
+            4 => Self::LeftShoulder,
+            5 => Self::RightShoulder,
+            8 => Self::Select,
+            9 => Self::Start,
+            12 => Self::DPadUp,
+            13 => Self::DPadDown,
+            14 => Self::DPadLeft,
+            15 => Self::DPadRight,
+            _ => return None,
+        })
+    }
+
+    /// Converts a `gilrs` button, or returns [`None`] for buttons without an
+    /// equivalent.
+    #[cfg(feature = "gamepad")]
+    #[must_use]
+    pub const fn from_gilrs(button: gilrs::Button) -> Option<Self> {
+        Some(match button {
+            gilrs::Button::DPadUp => Self::DPadUp,
+            gilrs::Button::DPadDown => Self::DPadDown,
+            gilrs::Button::DPadLeft => Self::DPadLeft,
+            gilrs::Button::DPadRight => Self::DPadRight,
+            gilrs::Button::South => Self::South,
+            gilrs::Button::East => Self::East,
+            gilrs::Button::North => Self::North,
+            gilrs::Button::West => Self::West,
+            gilrs::Button::LeftTrigger => Self::LeftShoulder,
+            gilrs::Button::RightTrigger => Self::RightShoulder,
+            gilrs::Button::Select => Self::Select,
+            gilrs::Button::Start => Self::Start,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for Button {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A table of gamepad bindings, translating buttons into Chip8 key codes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GamepadMap {
+    /// The Chip8 key code bound to each bound button.
+    bindings: BTreeMap<Button, u8>,
+}
+
+impl Default for GamepadMap {
+    /// Binds the d-pad to the `5`, `8`, `7` and `9` keys, which most games
+    /// use as up, down, left and right, and the face buttons to the nearby
+    /// `6`, `4`, `E` and `A` keys.
+    fn default() -> Self {
+        Self {
+            bindings: BTreeMap::from([
+                (Button::DPadUp, 0x5),
+                (Button::DPadDown, 0x8),
+                (Button::DPadLeft, 0x7),
+                (Button::DPadRight, 0x9),
+                (Button::South, 0x6),
+                (Button::East, 0x4),
+                (Button::North, 0xE),
+                (Button::West, 0xA),
+            ]),
+        }
+    }
+}
+
+impl GamepadMap {
+    /// Creates a new [`GamepadMap`] with the default bindings.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the Chip8 key code bound to the given button, if any.
+    #[must_use]
+    pub fn key_code(&self, button: Button) -> Option<u8> {
+        self.bindings.get(&button).copied()
+    }
+
+    /// Binds the given button to a Chip8 key code, replacing its previous
+    /// binding.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key_code` is not a valid Chip8 key (`0x0..=0xF`).
+    pub fn bind(&mut self, button: Button, key_code: u8) {
+        assert!(
+            usize::from(key_code) < crate::keymap::KEY_COUNT,
+            "invalid key code {key_code:#X}"
+        );
+        self.bindings.insert(button, key_code);
+    }
+
+    /// Removes the binding of the given button.
+    pub fn unbind(&mut self, button: Button) {
+        self.bindings.remove(&button);
+    }
+
+    /// Returns an iterator over all bound buttons and their Chip8 key codes.
+    pub fn iter(&self) -> impl Iterator<Item = (Button, u8)> + '_ {
+        self.bindings
+            .iter()
+            .map(|(&button, &key_code)| (button, key_code))
+    }
+
+    /// Deserializes the [`GamepadMap`] of the ROM with the given hash from a
+    /// sidecar JSON string. Returns [`None`] if the sidecar belongs to a
+    /// different ROM.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the string is not a valid sidecar.
+    #[cfg(feature = "persistence")]
+    pub fn from_json(json: &str, rom_hash: &str) -> Result<Option<Self>, serde_json::Error> {
+        sidecar::from_json(json, rom_hash)
+    }
+
+    /// Serializes the [`GamepadMap`] into a sidecar JSON string for the ROM
+    /// with the given hash.
+    ///
+    /// # Panics
+    ///
+    /// Never panics in practice, since a [`GamepadMap`] always serializes to
+    /// JSON.
+    #[cfg(feature = "persistence")]
+    #[must_use]
+    pub fn to_json(&self, rom_hash: &str) -> String {
+        sidecar::to_json(self, rom_hash).expect("gamepad map is always serializable")
+    }
+
+    /// Returns the path of the sidecar file for the ROM at the given path.
+    #[cfg(feature = "persistence")]
+    #[must_use]
+    pub fn sidecar_path(rom_path: impl AsRef<Path>) -> PathBuf {
+        sidecar::path(rom_path, SIDECAR_EXTENSION)
+    }
+
+    /// Loads the [`GamepadMap`] of the ROM at the given path from its sidecar
+    /// file, or returns the default bindings if there is none for this ROM.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM or an existing sidecar cannot be read, or
+    /// if the sidecar is invalid.
+    #[cfg(feature = "persistence")]
+    pub fn load_for_rom(rom_path: impl AsRef<Path>) -> io::Result<Self> {
+        sidecar::load(rom_path, SIDECAR_EXTENSION)
+    }
+
+    /// Saves the [`GamepadMap`] to the sidecar file of the ROM at the given
+    /// path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM cannot be read or the sidecar cannot be
+    /// written.
+    #[cfg(feature = "persistence")]
+    pub fn save_for_rom(&self, rom_path: impl AsRef<Path>) -> io::Result<()> {
+        sidecar::save(self, rom_path, SIDECAR_EXTENSION)
+    }
+}
+
+/// The connected gamepads, read through `gilrs`.
+#[cfg(feature = "gamepad")]
+pub struct Gamepads {
+    gilrs: gilrs::Gilrs,
+}
+
+#[cfg(feature = "gamepad")]
+impl fmt::Debug for Gamepads {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Gamepads").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "gamepad")]
+impl Gamepads {
+    /// Starts listening for gamepads.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the platform does not support gamepads.
+    pub fn new() -> Result<Self, Box<gilrs::Error>> {
+        Ok(Self {
+            gilrs: gilrs::Gilrs::new().map_err(Box::new)?,
+        })
+    }
+
+    /// Forwards the button presses and releases of all gamepads since the
+    /// last call to the given [`Chip8`], using the given [`GamepadMap`].
+    /// Call this once per frame.
+    pub fn poll(&mut self, map: &GamepadMap, chip8: &mut Chip8) {
+        while let Some(gilrs::Event { event, .. }) = self.gilrs.next_event() {
+            let (button, pressed) = match event {
+                gilrs::EventType::ButtonPressed(button, _) => (button, true),
+                gilrs::EventType::ButtonReleased(button, _) => (button, false),
+                _ => continue,
+            };
+            if let Some(key_code) = Button::from_gilrs(button).and_then(|b| map.key_code(b)) {
+                chip8.update_key_state(key_code, pressed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bindings() {
+        let mut map = GamepadMap::new();
+        assert_eq!(map.key_code(Button::DPadUp), Some(0x5));
+        assert_eq!(map.key_code(Button::Start), None);
+
+        // Several buttons may share a key
+        map.bind(Button::Start, 0x5);
+        assert_eq!(map.key_code(Button::Start), Some(0x5));
+        assert_eq!(map.key_code(Button::DPadUp), Some(0x5));
+
+        map.unbind(Button::DPadUp);
+        assert_eq!(map.key_code(Button::DPadUp), None);
+
+        #[cfg(feature = "persistence")]
+        assert_eq!(
+            GamepadMap::from_json(&map.to_json("abc"), "abc").unwrap(),
+            Some(map)
+        );
+    }
+}
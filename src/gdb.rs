@@ -0,0 +1,528 @@
+//! This module provides a remote debugging server speaking a minimal subset
+//! of the GDB remote serial protocol, so external tools can drive the
+//! emulator over TCP.
+//!
+//! Packets have the form `$<data>#<checksum>` and are acknowledged with `+`.
+//! The supported commands are:
+//!
+//! | Packet               | Meaning                                    |
+//! |----------------------|--------------------------------------------|
+//! | `?`                  | report why execution stopped               |
+//! | `g` / `G<regs>`      | read / write all registers                 |
+//! | `m<addr>,<len>`      | read memory                                |
+//! | `M<addr>,<len>:<hex>`| write memory                               |
+//! | `s` / `c`            | step one instruction / continue            |
+//! | `Z0,<addr>,<kind>`   | add a breakpoint                           |
+//! | `z0,<addr>,<kind>`   | remove a breakpoint                        |
+//! | `D` / `k`            | detach / kill, ending the session          |
+//! | `qRcmd,<hex>`        | run a `monitor` command, see below         |
+//!
+//! A `0x03` byte interrupts a running program. The registers are sent as
+//! `V0` to `VF`, then `I` and `PC` as little-endian 16-bit values, then `SP`,
+//! the delay timer and the sound timer as single bytes.
+//!
+//! The `monitor memlog` command controls the [`crate::memlog::MemoryLog`]:
+//! `memlog on` and `memlog off` start and stop recording, `memlog clear`
+//! discards the entries, `memlog filter <start> <end>` limits recording to a
+//! range of hex addresses (`memlog filter` removes the limit), and `memlog`
+//! or `memlog <start> <end>` lists the recorded accesses. With the
+//! `persistence` feature enabled, `monitor dump-state` prints the full machine
+//! state as JSON. `monitor reset` restarts the loaded ROM, and `monitor
+//! hard-reset` also reseeds the random number generator, like a power cycle.
+
+use std::fmt::Write as _;
+use std::ops::Range;
+use std::sync::atomic::Ordering;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use crate::rng::Rng;
+use crate::runner::{Chip8Runner, RunnerEvent};
+
+/// The size of the register block sent by `g`, in bytes.
+const REGISTERS_SIZE: usize = 16 + 2 + 2 + 3;
+
+/// The byte sent by a client to interrupt a running program.
+const INTERRUPT: u8 = 0x03;
+
+/// An item received from a debugging client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Incoming {
+    /// A packet with a valid checksum, without its framing.
+    Packet(String),
+
+    /// A packet with an invalid checksum, which should be retransmitted.
+    Corrupt,
+
+    /// A request to interrupt the running program.
+    Interrupt,
+}
+
+/// Takes the next complete item from the front of the given buffer of
+/// received bytes. Acknowledgements and stray bytes are skipped. Returns
+/// [`None`] if the buffer holds no complete item yet.
+pub fn parse_incoming(buffer: &mut Vec<u8>) -> Option<Incoming> {
+    loop {
+        match buffer.first()? {
+            b'$' => break,
+            &INTERRUPT => {
+                buffer.remove(0);
+                return Some(Incoming::Interrupt);
+            }
+            _ => {
+                buffer.remove(0);
+            }
+        }
+    }
+    let end = buffer.iter().position(|&byte| byte == b'#')?;
+    if buffer.len() < end + 3 {
+        return None;
+    }
+    let frame: Vec<u8> = buffer.drain(..end + 3).collect();
+    let data = &frame[1..end];
+    let checksum = std::str::from_utf8(&frame[end + 1..])
+        .ok()
+        .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+    if checksum != Some(checksum_of(data)) {
+        return Some(Incoming::Corrupt);
+    }
+    Some(Incoming::Packet(String::from_utf8_lossy(data).into_owned()))
+}
+
+/// Frames the given packet data with its checksum.
+#[must_use]
+pub fn frame_packet(data: &str) -> String {
+    format!("${data}#{:02x}", checksum_of(data.as_bytes()))
+}
+
+/// Returns the checksum of packet data: the sum of its bytes modulo 256.
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
+
+/// What the server should do after handling a packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    /// Send the given packet data.
+    Packet(String),
+
+    /// Resume execution until a breakpoint, an event or an interrupt, then
+    /// send a stop reply.
+    Continue,
+
+    /// Send `OK` and end the session.
+    Detach,
+}
+
+/// Handles a packet from a debugging client, applying its effects on the
+/// given [`Chip8Runner`].
+pub fn handle_packet(runner: &mut Chip8Runner, packet: &str) -> Reply {
+    let (command, args) = packet.split_at(packet.len().min(1));
+    let reply = match command {
+        "?" => "S05".to_string(),
+        "g" => read_registers(runner),
+        "G" => status(write_registers(runner, args)),
+        "m" => read_memory(runner, args).unwrap_or_else(|| "E01".into()),
+        "M" => status(write_memory(runner, args)),
+        "s" => stop_reply(runner.step_n(1)),
+        "c" => return Reply::Continue,
+        // only software breakpoints are supported
+        "Z" | "z" => parse_breakpoint(args).map_or_else(String::new, |address| {
+            if command == "Z" {
+                runner.add_breakpoint(address);
+            } else {
+                runner.remove_breakpoint(address);
+            }
+            "OK".into()
+        }),
+        "D" | "k" => return Reply::Detach,
+        "q" if args.starts_with("Supported") => "PacketSize=1000".into(),
+        "q" if args == "Attached" => "1".into(),
+        "q" if args.starts_with("Rcmd,") => monitor(runner, &args["Rcmd,".len()..]),
+        _ => String::new(),
+    };
+    Reply::Packet(reply)
+}
+
+/// Returns the stop reply for the event that stopped execution, if any.
+#[must_use]
+pub fn stop_reply(event: Option<RunnerEvent>) -> String {
+    match event {
+        Some(RunnerEvent::End) => "W00",
+        Some(RunnerEvent::Error(_)) => "S04",
+        None
+        | Some(
+            RunnerEvent::Loop { .. }
+            | RunnerEvent::Reached { .. }
+            | RunnerEvent::Breakpoint { .. }
+            | RunnerEvent::Event { .. },
+        ) => "S05",
+    }
+    .into()
+}
+
+/// Runs the hex-encoded `monitor` command and returns its hex-encoded
+/// output, or an error reply if the command is unknown.
+fn monitor(runner: &mut Chip8Runner, hex: &str) -> String {
+    let Some(command) = decode_hex(hex).and_then(|bytes| String::from_utf8(bytes).ok()) else {
+        return "E01".into();
+    };
+    let mut words = command.split_whitespace();
+    match words.next() {
+        #[cfg(feature = "persistence")]
+        Some("dump-state") => {
+            let state = crate::trace::dump_state(&runner.chip8);
+            return encode_hex(format!("{state}\n").into_bytes());
+        }
+        Some(command @ ("reset" | "hard-reset")) => {
+            let result = if command == "reset" {
+                runner.chip8.reset_keep_rom()
+            } else {
+                runner.chip8.power_cycle(Rng::from_entropy().seed())
+            };
+            return match result {
+                Ok(()) => encode_hex(*b"program restarted\n"),
+                Err(_) => "E01".into(),
+            };
+        }
+        Some("memlog") => {}
+        _ => return String::new(),
+    }
+    let words: Vec<&str> = words.collect();
+    let log = &mut runner.chip8.memory_log;
+    let output = match words.as_slice() {
+        ["on"] => {
+            log.set_enabled(true);
+            "memory log enabled\n".into()
+        }
+        ["off"] => {
+            log.set_enabled(false);
+            "memory log disabled\n".into()
+        }
+        ["clear"] => {
+            log.clear();
+            "memory log cleared\n".into()
+        }
+        ["filter"] => {
+            log.set_filter(None);
+            "logging all addresses\n".into()
+        }
+        ["filter", start, end] => {
+            let Some(range) = parse_addresses(start, end) else {
+                return "E01".into();
+            };
+            let output = format!("logging {:#06X}..{:#06X}\n", range.start, range.end);
+            log.set_filter(Some(range));
+            output
+        }
+        [] => log.iter().fold(String::new(), |mut output, entry| {
+            let _ = writeln!(output, "{entry}");
+            output
+        }),
+        [start, end] => {
+            let Some(range) = parse_addresses(start, end) else {
+                return "E01".into();
+            };
+            log.in_range(range)
+                .fold(String::new(), |mut output, entry| {
+                    let _ = writeln!(output, "{entry}");
+                    output
+                })
+        }
+        _ => return "E01".into(),
+    };
+    encode_hex(output.into_bytes())
+}
+
+/// Parses a pair of hex addresses into a range.
+fn parse_addresses(start: &str, end: &str) -> Option<Range<usize>> {
+    let parse = |hex: &str| usize::from_str_radix(hex.trim_start_matches("0x"), 16).ok();
+    Some(parse(start)?..parse(end)?)
+}
+
+/// Returns `OK` if an operation succeeded, or an error reply otherwise.
+fn status(ok: Option<()>) -> String {
+    if ok.is_some() { "OK" } else { "E01" }.into()
+}
+
+/// Encodes the given bytes as lowercase hex.
+fn encode_hex(bytes: impl IntoIterator<Item = u8>) -> String {
+    bytes.into_iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// Decodes a string of hex digit pairs.
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Parses an `<addr>,<len>` pair of hex numbers.
+fn parse_range(args: &str) -> Option<(usize, usize)> {
+    let (addr, len) = args.split_once(',')?;
+    Some((
+        usize::from_str_radix(addr, 16).ok()?,
+        usize::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+/// Parses the arguments of a software breakpoint packet, returning its
+/// address.
+fn parse_breakpoint(args: &str) -> Option<usize> {
+    let mut fields = args.split(',');
+    if fields.next()? != "0" {
+        return None;
+    }
+    usize::from_str_radix(fields.next()?, 16).ok()
+}
+
+/// Encodes the register block of the `g` packet.
+fn read_registers(runner: &Chip8Runner) -> String {
+    let chip8 = &runner.chip8;
+    let cpu = &chip8.processor;
+    let mut bytes = cpu.v.to_vec();
+    #[allow(clippy::cast_possible_truncation)] // addresses fit into 16 bits
+    {
+        bytes.extend((cpu.i as u16).to_le_bytes());
+        bytes.extend((cpu.pc as u16).to_le_bytes());
+        bytes.push(cpu.sp as u8);
+    }
+    bytes.push(chip8.bus.clock.delay_timer);
+    bytes.push(chip8.bus.clock.sound_timer.load(Ordering::SeqCst));
+    encode_hex(bytes)
+}
+
+/// Decodes the register block of the `G` packet and writes it.
+fn write_registers(runner: &mut Chip8Runner, hex: &str) -> Option<()> {
+    let bytes = decode_hex(hex)?;
+    if bytes.len() != REGISTERS_SIZE {
+        return None;
+    }
+    let chip8 = &mut runner.chip8;
+    chip8.processor.v.copy_from_slice(&bytes[..16]);
+    chip8.processor.i = usize::from(u16::from_le_bytes([bytes[16], bytes[17]]));
+    chip8.processor.pc = usize::from(u16::from_le_bytes([bytes[18], bytes[19]]));
+    chip8.processor.sp = usize::from(bytes[20]);
+    chip8.bus.clock.delay_timer = bytes[21];
+    chip8
+        .bus
+        .clock
+        .sound_timer
+        .store(bytes[22], Ordering::SeqCst);
+    Some(())
+}
+
+/// Encodes the memory range requested by the `m` packet.
+fn read_memory(runner: &Chip8Runner, args: &str) -> Option<String> {
+    let (addr, len) = parse_range(args)?;
+    let memory = &runner.chip8.bus.memory;
+    let bytes: Option<Vec<u8>> = (addr..addr.checked_add(len)?)
+        .map(|addr| memory.read(addr).ok())
+        .collect();
+    bytes.map(encode_hex)
+}
+
+/// Writes the memory range of the `M` packet.
+fn write_memory(runner: &mut Chip8Runner, args: &str) -> Option<()> {
+    let (range, hex) = args.split_once(':')?;
+    let (addr, len) = parse_range(range)?;
+    let bytes = decode_hex(hex)?;
+    let memory = &mut runner.chip8.bus.memory;
+    if bytes.len() != len || addr.checked_add(len)? > memory.len() {
+        return None;
+    }
+    for (offset, byte) in bytes.into_iter().enumerate() {
+        memory[addr + offset] = byte;
+    }
+    Some(())
+}
+
+/// Waits for a debugging client to connect to the given address and serves
+/// it until it detaches. Execution is paused while the client is connected,
+/// except while it continues the program.
+///
+/// # Errors
+///
+/// Returns an error if the address cannot be bound or the connection fails.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn serve(addr: impl ToSocketAddrs, runner: &mut Chip8Runner) -> io::Result<()> {
+    let (stream, _) = TcpListener::bind(addr)?.accept()?;
+    serve_client(stream, runner)
+}
+
+/// Serves a connected debugging client until it detaches.
+///
+/// # Errors
+///
+/// Returns an error if the connection fails.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn serve_client(mut stream: TcpStream, runner: &mut Chip8Runner) -> io::Result<()> {
+    runner.pause();
+    let mut buffer = Vec::new();
+    loop {
+        let Some(incoming) = receive(&mut stream, &mut buffer, None)? else {
+            continue;
+        };
+        let packet = match incoming {
+            Incoming::Packet(packet) => packet,
+            Incoming::Corrupt => {
+                stream.write_all(b"-")?;
+                continue;
+            }
+            Incoming::Interrupt => {
+                send(&mut stream, "S02")?;
+                continue;
+            }
+        };
+        stream.write_all(b"+")?;
+        match handle_packet(runner, &packet) {
+            Reply::Packet(reply) => send(&mut stream, &reply)?,
+            Reply::Continue => {
+                let reply = run_until_stopped(&mut stream, &mut buffer, runner)?;
+                send(&mut stream, &reply)?;
+            }
+            Reply::Detach => {
+                send(&mut stream, "OK")?;
+                runner.resume();
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Resumes execution until the program stops or the client interrupts it,
+/// returning the stop reply.
+#[cfg(not(target_arch = "wasm32"))]
+fn run_until_stopped(
+    stream: &mut TcpStream,
+    buffer: &mut Vec<u8>,
+    runner: &mut Chip8Runner,
+) -> io::Result<String> {
+    runner.resume();
+    loop {
+        if let Some(event) = runner.update() {
+            runner.pause();
+            return Ok(stop_reply(Some(event)));
+        }
+        let timeout = Some(Duration::from_millis(1));
+        if receive(stream, buffer, timeout)? == Some(Incoming::Interrupt) {
+            runner.pause();
+            return Ok("S02".into());
+        }
+    }
+}
+
+/// Reads from the client until an item is complete, or until the timeout
+/// expired if one is given.
+#[cfg(not(target_arch = "wasm32"))]
+fn receive(
+    stream: &mut TcpStream,
+    buffer: &mut Vec<u8>,
+    timeout: Option<Duration>,
+) -> io::Result<Option<Incoming>> {
+    if let Some(incoming) = parse_incoming(buffer) {
+        return Ok(Some(incoming));
+    }
+    stream.set_read_timeout(timeout)?;
+    let mut chunk = [0; 1024];
+    match stream.read(&mut chunk) {
+        Ok(0) => Err(io::ErrorKind::UnexpectedEof.into()),
+        Ok(n) => {
+            buffer.extend_from_slice(&chunk[..n]);
+            Ok(parse_incoming(buffer))
+        }
+        Err(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Sends a framed packet to the client.
+#[cfg(not(target_arch = "wasm32"))]
+fn send(stream: &mut TcpStream, data: &str) -> io::Result<()> {
+    stream.write_all(frame_packet(data).as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    fn packet(runner: &mut Chip8Runner, packet: &str) -> String {
+        match handle_packet(runner, packet) {
+            Reply::Packet(reply) => reply,
+            reply => panic!("unexpected {reply:?}"),
+        }
+    }
+
+    #[test]
+    fn test_packets() {
+        let mut buffer = b"+$m200,2#5d$g#00\x03".to_vec();
+        assert_eq!(
+            parse_incoming(&mut buffer),
+            Some(Incoming::Packet("m200,2".into()))
+        );
+        assert_eq!(parse_incoming(&mut buffer), Some(Incoming::Corrupt));
+        assert_eq!(parse_incoming(&mut buffer), Some(Incoming::Interrupt));
+        assert_eq!(parse_incoming(&mut buffer), None);
+        assert_eq!(frame_packet("OK"), "$OK#9a");
+
+        let mut chip8 = Chip8::new();
+        // 6005: V0 = 5, 1202: loop
+        chip8.load_rom_data(vec![0x60, 0x05, 0x12, 0x02]).unwrap();
+        let mut runner = Chip8Runner::new(chip8);
+
+        assert_eq!(packet(&mut runner, "m200,4"), "60051202");
+        assert_eq!(packet(&mut runner, "s"), "S05");
+        let registers = packet(&mut runner, "g");
+        assert_eq!(&registers[..2], "05");
+        assert_eq!(&registers[36..40], "0202");
+
+        // Writing the registers back changes V1
+        let registers = format!("{}07{}", &registers[..2], &registers[4..]);
+        assert_eq!(packet(&mut runner, &format!("G{registers}")), "OK");
+        assert_eq!(runner.chip8.processor.v[1], 7);
+
+        assert_eq!(packet(&mut runner, "M300,2:abcd"), "OK");
+        assert_eq!(runner.chip8.bus.memory[0x301], 0xCD);
+        assert_eq!(packet(&mut runner, "mfff,2"), "E01");
+        // Ranges past the end of the address space are rejected as well
+        assert_eq!(packet(&mut runner, "mffffffffffffffff,2"), "E01");
+        assert_eq!(packet(&mut runner, "Mffffffffffffffff,1:ab"), "E01");
+
+        assert_eq!(packet(&mut runner, "Z0,202,2"), "OK");
+        assert_eq!(runner.breakpoints().collect::<Vec<_>>(), [0x202]);
+        assert_eq!(handle_packet(&mut runner, "c"), Reply::Continue);
+        assert_eq!(handle_packet(&mut runner, "D"), Reply::Detach);
+        assert_eq!(packet(&mut runner, "vMustReplyEmpty"), "");
+
+        let command = format!("qRcmd,{}", encode_hex(*b"memlog on"));
+        let reply = decode_hex(&packet(&mut runner, &command)).unwrap();
+        assert_eq!(reply, b"memory log enabled\n");
+        assert!(runner.chip8.memory_log.is_enabled());
+
+        // Resetting restarts the ROM that was loaded
+        let command = format!("qRcmd,{}", encode_hex(*b"reset"));
+        let reply = decode_hex(&packet(&mut runner, &command)).unwrap();
+        assert_eq!(reply, b"program restarted\n");
+        assert_eq!(runner.chip8.processor.pc, 0x200);
+        assert_eq!(packet(&mut runner, "m200,4"), "60051202");
+    }
+}
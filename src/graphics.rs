@@ -1,6 +1,14 @@
-//! This module provides a simple graphics buffer implementation with a fixed resolution of 64x32 pixels.
+//! This module provides a simple graphics buffer implementation with a resolution of 64x32 pixels,
+//! or 64x64 pixels in the two-page hires mode of some historical CHIP-8 programs.
+//!
+//! The [`Framebuffer`] stores palette indices rather than colors, so programs
+//! using several bit planes can be displayed with up to four colors.
 
-use std::mem;
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "std")]
+use std::{fs, io, path::Path};
+
+use crate::theme::{self, Palette};
 
 /// The height of the graphics buffer in pixels. This is a constant value
 /// set to 32.
@@ -11,6 +19,12 @@ pub const WIDTH: usize = 64;
 /// The total number of pixels in the graphics buffer. This is calculated
 /// as the product of [`WIDTH`] and [`HEIGHT`].
 pub const PIXEL_COUNT: usize = WIDTH * HEIGHT;
+/// The height of the graphics buffer in pixels in the two-page hires mode,
+/// which shows two pages of [`HEIGHT`] rows below each other.
+pub const HIRES_HEIGHT: usize = 2 * HEIGHT;
+/// The number of pixels the graphics buffer can hold, which is the size of
+/// the display in the hires mode.
+pub const MAX_PIXEL_COUNT: usize = WIDTH * HIRES_HEIGHT;
 /// The default foreground color for the graphics buffer. This is an [`Rgb`]
 /// struct with the value `[255, 255, 255]`, representing white.
 pub const DEFAULT_FOREGROUND: Rgb = Rgb {
@@ -29,7 +43,8 @@ pub const DEFAULT_BACKGROUND: Rgb = Rgb {
 /// A struct representing an RGB color with 8 bits per channel. This struct
 /// holds 3 fields of [`u8`] values representing the red, green, and blue
 /// channels of the color.
-#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rgb {
     /// Red color
     pub red: u8,
@@ -57,114 +72,337 @@ impl Rgb {
     }
 }
 
-/// A struct representing the graphics buffer. This struct holds a 2D array
-/// of [`Rgb`] colors representing the graphics buffer, as well as foreground
-/// and background colors. The buffer supports drawing single bytes (8 pixels)
-/// with a given position and data, and keeps track of collisions between
-/// active pixels.
-#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy)]
-pub struct Buffer {
-    #[serde(with = "serde_big_array::BigArray")]
-    vram: [Rgb; PIXEL_COUNT],
-    /// An [`Rgb`] value that represents the color used for drawing active pixels.
-    pub foreground_rgb: Rgb,
-    /// An [`Rgb`] value that represents the color used for drawing inactive
-    /// pixels (i.e., the background color).
-    pub background_rgb: Rgb,
+/// The amount of bit planes of the [`Framebuffer`]. Every pixel stores one bit
+/// per plane, which together form an index into the palette of four colors.
+pub const PLANE_COUNT: usize = 2;
+
+/// The amount of colors in the palette of the [`Framebuffer`].
+pub const COLOR_COUNT: usize = 1 << PLANE_COUNT;
+
+/// A bitmask with the bits of all planes set.
+const ALL_PLANES: u8 = (1 << PLANE_COUNT) - 1;
+
+/// A struct representing the display of the Chip8.
+///
+/// Every pixel is stored as a palette index made up of one bit per plane.
+/// Programs draw into the selected planes only; plain Chip8 programs just use
+/// the first plane, so their pixels are either the background color (index
+/// `0`) or the foreground color (index `1`). Drawing keeps track of collisions
+/// between active pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Framebuffer {
+    #[cfg_attr(feature = "serde", serde(with = "serde_big_array::BigArray"))]
+    pixels: [u8; MAX_PIXEL_COUNT],
+    /// The amount of rows in use, either [`HEIGHT`] or [`HIRES_HEIGHT`].
+    #[cfg_attr(feature = "serde", serde(default = "Framebuffer::default_height"))]
+    height: usize,
+    /// The colors of the palette indices. Index `0` is the background color
+    /// and index `1` the foreground color.
+    pub palette: [Rgb; COLOR_COUNT],
+    /// A bitmask of the planes that drawing, clearing and scrolling affect.
+    planes: u8,
 }
 
-impl Default for Buffer {
+impl Default for Framebuffer {
     fn default() -> Self {
         Self {
-            vram: [DEFAULT_BACKGROUND; PIXEL_COUNT],
-            foreground_rgb: DEFAULT_FOREGROUND,
-            background_rgb: DEFAULT_BACKGROUND,
+            pixels: [0; MAX_PIXEL_COUNT],
+            height: Self::default_height(),
+            palette: theme::DEFAULT_PALETTE,
+            planes: 1,
         }
     }
 }
 
-impl Buffer {
-    /// Creates a new [`Buffer`] instance with the default background and
-    /// foreground colors.
+impl Framebuffer {
+    /// Creates a new [`Framebuffer`] instance with the default palette, drawing
+    /// into the first plane.
     #[must_use]
     pub fn new() -> Self {
         Self::default()
     }
 
-    /// Draws a byte (8 pixels) with the given position and data. Returns a
-    /// [`bool`] indicating whether any active pixels in the byte collided
-    /// with active pixels already present in the buffer.
-    pub fn draw_byte(&mut self, x: usize, y: usize, data: u8) -> bool {
-        if y >= PIXEL_COUNT / WIDTH {
+    /// Returns [`HEIGHT`], used when deserializing a [`Framebuffer`] that was
+    /// stored without a height.
+    const fn default_height() -> usize {
+        HEIGHT
+    }
+
+    /// Returns the height of the display in pixels, which is [`HIRES_HEIGHT`]
+    /// in the hires mode and [`HEIGHT`] otherwise.
+    #[must_use]
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns whether the two-page hires mode is active.
+    #[must_use]
+    pub const fn is_hires(&self) -> bool {
+        self.height == HIRES_HEIGHT
+    }
+
+    /// Switches between the 64x32 display and the 64x64 display of the
+    /// two-page hires mode. Switching modes clears all planes.
+    pub fn set_hires(&mut self, hires: bool) {
+        self.height = if hires { HIRES_HEIGHT } else { HEIGHT };
+        self.pixels.fill(0);
+    }
+
+    /// Returns the pixels of the rows in use.
+    fn active(&self) -> &[u8] {
+        &self.pixels[..WIDTH * self.height]
+    }
+
+    /// Returns the bitmask of the planes that drawing, clearing and
+    /// scrolling affect.
+    #[must_use]
+    pub const fn selected_planes(&self) -> u8 {
+        self.planes
+    }
+
+    /// Selects the planes that drawing, clearing and scrolling affect, as a
+    /// bitmask. Bits beyond [`PLANE_COUNT`] are ignored.
+    pub const fn select_planes(&mut self, planes: u8) {
+        self.planes = planes & ALL_PLANES;
+    }
+
+    /// Returns the palette index of the pixel at the given position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside of the display.
+    #[must_use]
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        assert!(
+            x < WIDTH && y < self.height,
+            "pixel is outside of the display"
+        );
+        self.pixels[y * WIDTH + x]
+    }
+
+    /// Flips the pixel at the given position in all selected planes. Returns a
+    /// [`bool`] indicating whether the pixel was active in any of them before.
+    /// Pixels outside of the display are ignored.
+    pub const fn xor_pixel(&mut self, x: usize, y: usize) -> bool {
+        if x >= WIDTH || y >= self.height {
             return false;
         }
+        let pixel = &mut self.pixels[y * WIDTH + x];
+        let collision = *pixel & self.planes != 0;
+        *pixel ^= self.planes;
+        collision
+    }
 
-        let max_x = (WIDTH - x).min(8);
-        let bitmasks: [u8; 8] = [0x80, 0x40, 0x20, 0x10, 0x08, 0x04, 0x02, 0x01];
-
+    /// Draws a byte (8 pixels) with the given position and data, like
+    /// [`Framebuffer::draw_byte`], but pixels past the right or bottom edge
+    /// wrap around to the opposite edge instead of being clipped.
+    pub fn draw_byte_wrapping(&mut self, x: usize, y: usize, data: u8) -> bool {
+        let y = y % self.height;
         let mut collision = false;
+        for b in 0..8 {
+            if data & (0x80 >> b) != 0 {
+                collision |= self.xor_pixel((x + b) % WIDTH, y);
+            }
+        }
+        collision
+    }
 
-        for (b, &mask) in bitmasks.iter().enumerate().take(max_x) {
-            let pos = (WIDTH * y) + x + b;
-            let new_pixel_active = (data & mask) != 0;
-            let old_pixel_active = self.vram[pos] == self.foreground_rgb;
-            if new_pixel_active && old_pixel_active {
-                collision = true;
+    /// Draws a byte (8 pixels) with the given position and data. Returns a
+    /// [`bool`] indicating whether any active pixels in the byte collided
+    /// with active pixels already present in the buffer. Pixels past the
+    /// right or bottom edge are clipped.
+    pub fn draw_byte(&mut self, x: usize, y: usize, data: u8) -> bool {
+        let mut collision = false;
+        for b in 0..8 {
+            if data & (0x80 >> b) != 0 {
+                collision |= self.xor_pixel(x + b, y);
             }
-            self.vram[pos] = if new_pixel_active ^ old_pixel_active {
-                self.foreground_rgb
-            } else {
-                self.background_rgb
-            };
         }
         collision
     }
 
     /// Sets the foreground color of the buffer to the given [`Rgb`]
-    /// value, and updates the colors of all active foreground pixels in the
-    /// buffer accordingly.
+    /// value, which changes the color of all active pixels in the first
+    /// plane.
     #[inline]
-    pub fn set_foreground_color(&mut self, foreground: Rgb) {
-        let old_color = mem::replace(&mut self.foreground_rgb, foreground);
-
-        for color in &mut self.vram {
-            if *color == old_color {
-                *color = foreground;
-            }
-        }
+    pub const fn set_foreground_color(&mut self, foreground: Rgb) {
+        self.palette[1] = foreground;
     }
 
     /// Sets the background color of the buffer to the given [`Rgb`]
-    /// value,and updates the colors of all background pixels in the buffer
-    /// accordingly.
+    /// value, which changes the color of all inactive pixels.
     #[inline]
-    pub fn set_background_color(&mut self, background: Rgb) {
-        let old_color = mem::replace(&mut self.background_rgb, background);
+    pub const fn set_background_color(&mut self, background: Rgb) {
+        self.palette[0] = background;
+    }
 
-        for color in &mut self.vram {
-            if *color == old_color {
-                *color = background;
-            }
+    /// Replaces all colors of the buffer with the given [`Palette`], e.g. a
+    /// preset [`theme::Theme`].
+    #[inline]
+    pub const fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// Returns an iterator over the position and palette index of every
+    /// pixel, row by row.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, u8)> + '_ {
+        self.active()
+            .iter()
+            .enumerate()
+            .map(|(i, &index)| (i % WIDTH, i / WIDTH, index))
+    }
+
+    /// Returns an iterator over the color of every pixel, row by row.
+    pub fn colors(&self) -> impl Iterator<Item = Rgb> + '_ {
+        self.active()
+            .iter()
+            .map(|&index| self.palette[usize::from(index)])
+    }
+
+    /// Returns whether every pixel is active in any plane, as a grid of
+    /// [`bool`]s with one row per display row. This is the display of a plain
+    /// Chip8.
+    #[must_use]
+    pub fn as_mono(&self) -> Vec<[bool; WIDTH]> {
+        let mut mono = vec![[false; WIDTH]; self.height];
+        for (x, y, index) in self.iter() {
+            mono[y][x] = index != 0;
         }
+        mono
     }
 
-    /// Returns the graphics buffer as a flat array of [`Rgb`] values.
+    /// Returns the graphics buffer as a flat array of [`Rgb`] values, row by
+    /// row.
     #[must_use]
-    pub fn as_rgb8(&self) -> [u8; PIXEL_COUNT * 3] {
-        let mut data = [0; PIXEL_COUNT * 3];
-        for (i, pixel) in self.vram.iter().enumerate() {
-            let offset = i * 3;
-            data[offset] = pixel.red;
-            data[offset + 1] = pixel.green;
-            data[offset + 2] = pixel.blue;
+    pub fn as_rgb8(&self) -> Vec<u8> {
+        let mut data = vec![0; self.active().len() * 3];
+        for (pixel, rgb) in self.colors().zip(data.chunks_exact_mut(3)) {
+            rgb.copy_from_slice(&pixel.as_array());
         }
         data
     }
 
-    /// Clears the graphics buffer by setting all pixels to the current background color.
+    /// Returns the graphics buffer as a flat array of RGBA values, where every
+    /// pixel is scaled up to a `scale` x `scale` square. The resulting image is
+    /// `WIDTH * scale` pixels wide and [`Framebuffer::height`] times `scale`
+    /// pixels high.
+    #[must_use]
+    pub fn to_rgba(&self, scale: usize) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.active().len() * scale * scale * 4);
+        for row in self.active().chunks_exact(WIDTH) {
+            for _ in 0..scale {
+                for &index in row {
+                    let pixel = self.palette[usize::from(index)];
+                    for _ in 0..scale {
+                        data.extend_from_slice(&[pixel.red, pixel.green, pixel.blue, 0xFF]);
+                    }
+                }
+            }
+        }
+        data
+    }
+
+    /// Saves the graphics buffer as a PNG image to the given path, scaling
+    /// every pixel up to a `scale` x `scale` square.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the scaled image is too large for a PNG image.
+    #[cfg(feature = "std")]
+    pub fn save_png(&self, path: impl AsRef<Path>, scale: usize) -> io::Result<()> {
+        let width = u32::try_from(WIDTH * scale).expect("image width fits into u32");
+        let height = u32::try_from(self.height * scale).expect("image height fits into u32");
+
+        let file = io::BufWriter::new(fs::File::create(path)?);
+        let mut encoder = png::Encoder::new(file, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.to_rgba(scale))?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Clears the selected planes of the graphics buffer.
     #[inline]
     pub fn clear(&mut self) {
-        self.vram = [self.background_rgb; PIXEL_COUNT];
+        let planes = self.planes;
+        for pixel in &mut self.pixels {
+            *pixel &= !planes;
+        }
+    }
+
+    /// Scrolls the selected planes down by `n` pixels. Pixels scrolled off
+    /// the bottom are lost, and the uncovered rows are cleared.
+    pub fn scroll_down(&mut self, n: usize) {
+        let len = WIDTH * self.height;
+        shift(&mut self.pixels[..len], n * WIDTH, true, self.planes);
+    }
+
+    /// Scrolls the selected planes up by `n` pixels. Pixels scrolled off the
+    /// top are lost, and the uncovered rows are cleared.
+    pub fn scroll_up(&mut self, n: usize) {
+        let len = WIDTH * self.height;
+        shift(&mut self.pixels[..len], n * WIDTH, false, self.planes);
+    }
+
+    /// Scrolls the selected planes left by `n` pixels. Pixels scrolled off
+    /// the left edge are lost rather than wrapped around, and the uncovered
+    /// columns are cleared.
+    pub fn scroll_left(&mut self, n: usize) {
+        let len = WIDTH * self.height;
+        for row in self.pixels[..len].chunks_exact_mut(WIDTH) {
+            shift(row, n, false, self.planes);
+        }
+    }
+
+    /// Scrolls the selected planes right by `n` pixels. Pixels scrolled off
+    /// the right edge are lost rather than wrapped around, and the uncovered
+    /// columns are cleared.
+    pub fn scroll_right(&mut self, n: usize) {
+        let len = WIDTH * self.height;
+        for row in self.pixels[..len].chunks_exact_mut(WIDTH) {
+            shift(row, n, true, self.planes);
+        }
+    }
+}
+
+/// Moves the given planes of the pixels by `n` positions towards the end of
+/// the slice, or towards its start if `forward` is false. Pixels moved past
+/// either end are lost, and uncovered pixels are cleared.
+///
+/// When all planes are selected, this is a plain memory move. Otherwise the
+/// pixels are visited in the direction of the move, so every pixel is read
+/// before it is overwritten.
+fn shift(pixels: &mut [u8], n: usize, forward: bool, planes: u8) {
+    let len = pixels.len();
+    let n = n.min(len);
+    if planes == ALL_PLANES {
+        if forward {
+            pixels.copy_within(..len - n, n);
+            pixels[..n].fill(0);
+        } else {
+            pixels.copy_within(n.., 0);
+            pixels[len - n..].fill(0);
+        }
+        return;
+    }
+    let merge = |pixel: &mut u8, moved: u8| *pixel = (*pixel & !planes) | (moved & planes);
+    if forward {
+        for i in (0..len).rev() {
+            let moved = i.checked_sub(n).map_or(0, |from| pixels[from]);
+            merge(&mut pixels[i], moved);
+        }
+    } else {
+        for i in 0..len {
+            let moved = pixels.get(i + n).copied().unwrap_or(0);
+            merge(&mut pixels[i], moved);
+        }
     }
 }
 
@@ -174,7 +412,7 @@ mod tests {
 
     #[test]
     fn test_draw_byte() {
-        let mut buffer = Buffer::new();
+        let mut buffer = Framebuffer::new();
 
         // Draw a byte at position (0, 0) with data 0b10000000
         let collision = buffer.draw_byte(0, 0, 0b1000_0000);
@@ -183,10 +421,10 @@ mod tests {
         assert!(!collision);
 
         // The first pixel should be the foreground color
-        assert_eq!(buffer.vram[0], buffer.foreground_rgb);
+        assert_eq!(buffer.pixel(0, 0), 1);
 
         // The rest of the pixels should be the background color
-        assert_eq!(buffer.vram[1..8], [buffer.background_rgb; 7]);
+        assert!((1..8).all(|x| buffer.pixel(x, 0) == 0));
 
         // Draw another byte at the same position with data 0b10000000
         let collision = buffer.draw_byte(0, 0, 0b1000_0000);
@@ -195,12 +433,84 @@ mod tests {
         assert!(collision);
 
         // All pixels should now be the background color
-        assert_eq!(buffer.vram[0..8], [buffer.background_rgb; 8]);
+        assert!((0..8).all(|x| buffer.pixel(x, 0) == 0));
+    }
+
+    #[test]
+    fn test_planes() {
+        let mut buffer = Framebuffer::new();
+        buffer.draw_byte(0, 0, 0b1100_0000);
+
+        // Drawing into the second plane only collides with that plane
+        buffer.select_planes(0b10);
+        assert!(!buffer.draw_byte(1, 0, 0b1000_0000));
+        assert_eq!(buffer.pixel(0, 0), 0b01);
+        assert_eq!(buffer.pixel(1, 0), 0b11);
+        assert_eq!(buffer.colors().nth(1), Some(buffer.palette[3]));
+
+        // Clearing only affects the selected planes
+        buffer.clear();
+        assert_eq!(buffer.pixel(1, 0), 0b01);
+        assert!(buffer.as_mono()[0][0]);
+    }
+
+    #[test]
+    fn test_scroll() {
+        let mut buffer = Framebuffer::new();
+        buffer.draw_byte(0, 0, 0b1000_0000);
+
+        buffer.scroll_down(2);
+        buffer.scroll_right(4);
+        assert_eq!(buffer.pixel(4, 2), 1);
+        assert_eq!(buffer.pixel(0, 0), 0);
+
+        buffer.scroll_left(4);
+        buffer.scroll_up(2);
+        assert_eq!(buffer.pixel(0, 0), 1);
+
+        // Pixels scrolled off the display are lost rather than wrapped
+        // around, also at the end of a row
+        buffer.scroll_up(1);
+        buffer.scroll_down(1);
+        assert!(buffer.iter().all(|(_, _, index)| index == 0));
+        buffer.draw_byte(WIDTH - 8, 0, 0b0000_0001);
+        buffer.scroll_right(4);
+        assert!(buffer.iter().all(|(_, _, index)| index == 0));
+        buffer.draw_byte(0, 1, 0b1000_0000);
+        buffer.scroll_left(1);
+        assert_eq!(buffer.pixel(WIDTH - 1, 0), 0);
+        buffer.scroll_down(HEIGHT + 1);
+        assert!(buffer.iter().all(|(_, _, index)| index == 0));
+
+        // Only the selected planes move
+        buffer.select_planes(0b11);
+        buffer.draw_byte(0, 0, 0b1000_0000);
+        buffer.select_planes(0b10);
+        buffer.scroll_right(1);
+        assert_eq!((buffer.pixel(0, 0), buffer.pixel(1, 0)), (0b01, 0b10));
+        buffer.scroll_up(1);
+        assert_eq!(buffer.pixel(1, 0), 0);
+    }
+
+    #[test]
+    fn test_to_rgba() {
+        let mut buffer = Framebuffer::new();
+        buffer.draw_byte(1, 0, 0b1000_0000);
+
+        let data = buffer.to_rgba(2);
+        assert_eq!(data.len(), PIXEL_COUNT * 4 * 4);
+
+        // The second pixel covers columns 2 and 3 of the first two rows
+        let row = WIDTH * 2 * 4;
+        assert_eq!(data[0..4], [0, 0, 0, 255]);
+        assert_eq!(data[8..16], [255, 255, 255, 255, 255, 255, 255, 255]);
+        assert_eq!(data[row + 8..row + 16], data[8..16]);
+        assert_eq!(data[16..20], [0, 0, 0, 255]);
     }
 
     #[test]
     fn test_clear() {
-        let mut buffer = Buffer::new();
+        let mut buffer = Framebuffer::new();
 
         // Draw a byte at position (0, 0) with data 0b11111111
         buffer.draw_byte(0, 0, 0b1111_1111);
@@ -209,6 +519,30 @@ mod tests {
         buffer.clear();
 
         // All pixels should now be the background color
-        assert_eq!(buffer.vram, [buffer.background_rgb; PIXEL_COUNT]);
+        assert_eq!(buffer.pixels, [0; MAX_PIXEL_COUNT]);
+    }
+
+    #[test]
+    fn test_hires() {
+        let mut buffer = Framebuffer::new();
+        buffer.set_hires(true);
+        assert_eq!(buffer.height(), HIRES_HEIGHT);
+        buffer.draw_byte(0, HIRES_HEIGHT - 1, 0b1000_0000);
+        assert_eq!(buffer.as_mono().len(), HIRES_HEIGHT);
+        assert!(buffer.as_mono()[HIRES_HEIGHT - 1][0]);
+        assert_eq!(buffer.to_rgba(1).len(), MAX_PIXEL_COUNT * 4);
+
+        // The second page scrolls like the first one, and loses pixels at the
+        // bottom of the taller display
+        buffer.scroll_up(HEIGHT);
+        assert_eq!(buffer.pixel(0, HEIGHT - 1), 1);
+        buffer.scroll_down(HEIGHT + 1);
+        assert!(buffer.iter().all(|(_, _, index)| index == 0));
+
+        buffer.draw_byte(0, 0, 0b1000_0000);
+        buffer.set_hires(false);
+        assert_eq!(buffer.height(), HEIGHT);
+        assert!(!buffer.xor_pixel(0, HEIGHT));
+        assert!(buffer.iter().all(|(_, _, index)| index == 0));
     }
 }
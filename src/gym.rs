@@ -0,0 +1,209 @@
+//! This module wraps a [`Chip8`] into a Gym-style environment, so agents can
+//! learn to play ROMs, e.g. in reinforcement-learning experiments.
+//!
+//! A [`Chip8Env`] advances in whole frames: every [`Chip8Env::step`] sets the
+//! pressed keys, executes [`Chip8Env::instructions_per_frame`] instructions
+//! and ticks the timers once. The timers never tick by themselves and the
+//! random number generator is seeded, so an episode replays exactly for the
+//! same actions.
+
+use alloc::vec::Vec;
+use core::sync::atomic::Ordering;
+
+use crate::{error::Chip8Error, processor::StepResult, rng::Rng, Chip8};
+
+/// The default amount of instructions executed per frame, i.e. 600
+/// instructions per second at 60 frames per second.
+pub const DEFAULT_INSTRUCTIONS_PER_FRAME: u32 = 10;
+
+/// What an agent sees of the system after a reset or step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Observation {
+    /// The palette index of every pixel, row by row.
+    pub pixels: Vec<u8>,
+    /// The width of the display in pixels.
+    pub width: usize,
+    /// The height of the display in pixels.
+    pub height: usize,
+    /// The general purpose registers `V0` to `VF`.
+    pub v: [u8; 16],
+    /// The index register.
+    pub i: usize,
+    /// The program counter.
+    pub pc: usize,
+    /// The value of the delay timer.
+    pub delay_timer: u8,
+    /// The value of the sound timer.
+    pub sound_timer: u8,
+}
+
+impl Observation {
+    /// Captures the observation of the given system.
+    #[must_use]
+    pub fn capture(chip8: &Chip8) -> Self {
+        let graphics = &chip8.bus.graphics;
+        Self {
+            pixels: graphics.iter().map(|(_, _, pixel)| pixel).collect(),
+            width: crate::graphics::WIDTH,
+            height: graphics.height(),
+            v: chip8.processor.v,
+            i: chip8.processor.i,
+            pc: chip8.processor.pc,
+            delay_timer: chip8.bus.clock.delay_timer,
+            sound_timer: chip8.bus.clock.sound_timer.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// A Gym-style environment running a ROM.
+#[derive(Debug)]
+pub struct Chip8Env {
+    /// The system running the ROM.
+    chip8: Chip8,
+
+    /// The seed of the random number generator at the start of an episode.
+    seed: u64,
+
+    /// The amount of instructions executed per frame.
+    instructions_per_frame: u32,
+
+    /// The amount of frames after which an episode ends, if any.
+    max_frames: Option<u64>,
+
+    /// The amount of frames of the current episode.
+    frame: u64,
+
+    /// Whether the current episode ended.
+    done: bool,
+}
+
+impl Chip8Env {
+    /// Creates an environment running the given ROM, with the random number
+    /// generator seeded by `seed` at the start of every episode.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::RomTooLarge`] if the ROM does not fit into
+    /// memory.
+    pub fn new(rom: Vec<u8>, seed: u64) -> Result<Self, Chip8Error> {
+        let mut chip8 = Chip8::new_with_rng(Rng::new(seed));
+        chip8.history.set_depth(0);
+        chip8.load_rom_data(rom)?;
+        Ok(Self {
+            chip8,
+            seed,
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+            max_frames: None,
+            frame: 0,
+            done: false,
+        })
+    }
+
+    /// Returns the system running the ROM, e.g. to read memory for a reward.
+    #[must_use]
+    pub const fn chip8(&self) -> &Chip8 {
+        &self.chip8
+    }
+
+    /// Returns the system running the ROM mutably, e.g. to select a
+    /// [`crate::quirks::Variant`] before the first [`Chip8Env::reset`].
+    pub const fn chip8_mut(&mut self) -> &mut Chip8 {
+        &mut self.chip8
+    }
+
+    /// Returns the amount of instructions executed per frame.
+    #[must_use]
+    pub const fn instructions_per_frame(&self) -> u32 {
+        self.instructions_per_frame
+    }
+
+    /// Sets the amount of instructions executed per frame.
+    pub const fn set_instructions_per_frame(&mut self, instructions: u32) {
+        self.instructions_per_frame = instructions;
+    }
+
+    /// Ends every episode after the given amount of frames, or never if
+    /// [`None`] is given.
+    pub const fn set_max_frames(&mut self, frames: Option<u64>) {
+        self.max_frames = frames;
+    }
+
+    /// Returns the amount of frames of the current episode.
+    #[must_use]
+    pub const fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Starts a new episode by power-cycling the system with the seed given
+    /// to [`Chip8Env::new`], and returns the first observation.
+    ///
+    /// # Panics
+    ///
+    /// Never panics in practice, since the ROM already fit into memory when
+    /// the environment was created.
+    pub fn reset(&mut self) -> Observation {
+        self.chip8
+            .power_cycle(self.seed)
+            .expect("the ROM fit into memory before");
+        self.frame = 0;
+        self.done = false;
+        Observation::capture(&self.chip8)
+    }
+
+    /// Runs one frame with the given keys pressed, one bit per key with key
+    /// `0` in the lowest bit. Returns the observation after the frame, and
+    /// whether the episode ended: the program halted, ran past the end of
+    /// memory, raised an error or reached the frame limit. Once an episode
+    /// ended, further steps do nothing until the next [`Chip8Env::reset`].
+    pub fn step(&mut self, keys: u16) -> (Observation, bool) {
+        if !self.done {
+            self.chip8.set_keys(keys);
+            for _ in 0..self.instructions_per_frame {
+                match self.chip8.step() {
+                    Ok(
+                        StepResult::Continue
+                        | StepResult::WaitingForKey
+                        | StepResult::WaitingForVblank,
+                    ) => {}
+                    Ok(StepResult::Loop | StepResult::End) | Err(_) => {
+                        self.done = true;
+                        break;
+                    }
+                }
+            }
+            self.chip8.tick_timers();
+            self.frame += 1;
+            self.done |= self.max_frames.is_some_and(|max| self.frame >= max);
+        }
+        (Observation::capture(&self.chip8), self.done)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_env() {
+        // 6105: V1 = 5, F10A: V1 = key, 1204: halt
+        let rom = vec![0x61, 0x05, 0xF1, 0x0A, 0x12, 0x04];
+        let mut env = Chip8Env::new(rom, 0).unwrap();
+        let observation = env.reset();
+        assert_eq!((observation.pc, observation.pixels.len()), (0x200, 64 * 32));
+
+        // The program waits for a key, then halts
+        assert!(!env.step(0).1);
+        let (observation, done) = env.step(1 << 7);
+        assert!(done);
+        assert_eq!(observation.v[1], 7);
+        assert!(env.step(0).1);
+        assert_eq!(env.frame(), 2);
+
+        let observation = env.reset();
+        assert_eq!((observation.v[1], env.frame()), (0, 0));
+        env.set_max_frames(Some(2));
+        assert!(!env.step(0).1);
+        assert!(env.step(0).1);
+    }
+}
@@ -0,0 +1,255 @@
+//! This module provides a headless run of a ROM without any frontend, e.g.
+//! for testing ROMs in CI.
+//!
+//! The program runs as fast as possible until an exit condition is met: it
+//! runs past the end of memory, raises an error, waits for a key that will
+//! never be pressed, halts or idles in a loop (if enabled), or reaches the instruction
+//! cap or timeout. Since there is no wall clock to keep up with, the timers
+//! tick every [`HeadlessOptions::instructions_per_tick`] instructions through
+//! [`FixedStep`], which also makes runs reproducible.
+
+use std::fmt::{self, Write as _};
+use std::time::{Duration, Instant};
+
+use crate::{
+    clock::FixedStep, error::Chip8Error, graphics, processor::StepResult, runner::DEFAULT_IPS,
+    Chip8,
+};
+
+/// The exit conditions of a headless [`run`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeadlessOptions {
+    /// The amount of instructions to execute at most.
+    pub max_instructions: Option<u64>,
+    /// The wall-clock time to run for at most.
+    pub timeout: Option<Duration>,
+    /// Whether to stop once the program jumps to its own address, or spins in
+    /// a loop only a key press can end.
+    pub exit_on_loop: bool,
+    /// The amount of instructions per timer tick.
+    pub instructions_per_tick: u32,
+}
+
+impl Default for HeadlessOptions {
+    fn default() -> Self {
+        Self {
+            max_instructions: None,
+            timeout: None,
+            exit_on_loop: false,
+            #[allow(clippy::cast_possible_truncation)] // 700 / 60 fits
+            instructions_per_tick: (DEFAULT_IPS / 60) as u32,
+        }
+    }
+}
+
+/// Why a headless [`run`] stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitReason {
+    /// The program jumped to its own address at `pc`, or spins in an idle
+    /// loop closed by the instruction at `pc`, see [`crate::idle`].
+    Loop {
+        /// The address of the looping instruction.
+        pc: usize,
+    },
+    /// The program counter ran past the end of memory.
+    End,
+    /// The program waits for a key press, which never comes.
+    WaitingForKey,
+    /// The program raised a [`Chip8Error`].
+    Error(Chip8Error),
+    /// [`HeadlessOptions::max_instructions`] were executed.
+    InstructionLimit,
+    /// [`HeadlessOptions::timeout`] passed.
+    Timeout,
+}
+
+impl ExitReason {
+    /// Returns whether the run ended as intended, rather than by an error or
+    /// the timeout.
+    #[must_use]
+    pub const fn is_success(&self) -> bool {
+        !matches!(self, Self::Error(_) | Self::Timeout)
+    }
+}
+
+impl fmt::Display for ExitReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Loop { pc } => write!(f, "loop at {pc:#06X}"),
+            Self::End => write!(f, "end of memory"),
+            Self::WaitingForKey => write!(f, "waiting for a key"),
+            Self::Error(err) => write!(f, "error: {err}"),
+            Self::InstructionLimit => write!(f, "instruction limit"),
+            Self::Timeout => write!(f, "timeout"),
+        }
+    }
+}
+
+/// The result of a headless [`run`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeadlessReport {
+    /// The amount of instructions that were executed.
+    pub instructions: u64,
+    /// Why the run stopped.
+    pub reason: ExitReason,
+}
+
+/// Runs the given [`Chip8`] until one of the exit conditions in `options` is
+/// met. The rewind history is disabled while running.
+pub fn run(chip8: &mut Chip8, options: &HeadlessOptions) -> HeadlessReport {
+    run_traced(chip8, options, |_| {})
+}
+
+/// Like [`run`], but calls `on_step` with the system before every
+/// instruction, e.g. to write a [`crate::trace::TraceEntry`].
+pub fn run_traced(
+    chip8: &mut Chip8,
+    options: &HeadlessOptions,
+    mut on_step: impl FnMut(&Chip8),
+) -> HeadlessReport {
+    let depth = chip8.history.depth();
+    chip8.history.set_depth(0);
+    chip8.set_time_source(FixedStep::new(options.instructions_per_tick));
+    let idle = chip8.idle.is_enabled();
+    chip8.idle.set_enabled(options.exit_on_loop);
+
+    let start = Instant::now();
+    let mut instructions = 0;
+    let reason = loop {
+        if options
+            .max_instructions
+            .is_some_and(|max| instructions >= max)
+        {
+            break ExitReason::InstructionLimit;
+        }
+        // checking the time is slow compared to an instruction
+        if instructions % 1024 == 0 && options.timeout.is_some_and(|t| start.elapsed() >= t) {
+            break ExitReason::Timeout;
+        }
+        on_step(chip8);
+        let pc = chip8.processor.pc;
+        match chip8.step() {
+            Ok(StepResult::Continue) => {
+                instructions += 1;
+                if let Some(pc) = chip8.idle.idle_pc() {
+                    break ExitReason::Loop { pc };
+                }
+            }
+            Ok(StepResult::Loop) => {
+                instructions += 1;
+                if options.exit_on_loop {
+                    break ExitReason::Loop { pc };
+                }
+            }
+            // the next timer tick lets the program draw again
+            Ok(StepResult::WaitingForVblank) => {}
+            Ok(StepResult::WaitingForKey) => break ExitReason::WaitingForKey,
+            Ok(StepResult::End) => break ExitReason::End,
+            Err(err) => break ExitReason::Error(err),
+        }
+    };
+
+    chip8.history.set_depth(depth);
+    chip8.idle.set_enabled(idle);
+    HeadlessReport {
+        instructions,
+        reason,
+    }
+}
+
+/// Renders the display as text, one line per row, with `#` for lit and `.`
+/// for unlit pixels.
+#[must_use]
+pub fn dump_display(graphics: &graphics::Framebuffer) -> String {
+    let mut text = String::with_capacity((graphics::WIDTH + 1) * graphics.height());
+    for y in 0..graphics.height() {
+        text.extend((0..graphics::WIDTH).map(
+            |x| {
+                if graphics.pixel(x, y) == 0 {
+                    '.'
+                } else {
+                    '#'
+                }
+            },
+        ));
+        text.push('\n');
+    }
+    text
+}
+
+/// Renders the registers and timers as text, e.g.
+/// `V0=05 V1=00 ... VF=00 I=0300 PC=0202 SP=0 DT=00 ST=00`.
+#[must_use]
+pub fn dump_registers(chip8: &Chip8) -> String {
+    let cpu = &chip8.processor;
+    let mut text = String::new();
+    for (x, value) in cpu.v.iter().enumerate() {
+        let _ = write!(text, "V{x:X}={value:02X} ");
+    }
+    let _ = write!(
+        text,
+        "I={:04X} PC={:04X} SP={} DT={:02X} ST={:02X}",
+        cpu.i,
+        cpu.pc,
+        cpu.sp,
+        chip8.bus.clock.delay_timer,
+        chip8
+            .bus
+            .clock
+            .sound_timer
+            .load(std::sync::atomic::Ordering::SeqCst)
+    );
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        // 6010: V0 = 16, F015: delay timer = V0, F107: V1 = delay timer,
+        // 3100: skip if V1 == 0, 1204: loop back, 00E0: clear, 120C: halt
+        let rom = [
+            0x60, 0x10, 0xF0, 0x15, 0xF1, 0x07, 0x31, 0x00, 0x12, 0x04, 0x00, 0xE0, 0x12, 0x0C,
+        ];
+        let chip8 = || Chip8::builder().rom(&rom).build().unwrap();
+
+        let options = HeadlessOptions {
+            exit_on_loop: true,
+            ..HeadlessOptions::default()
+        };
+        let report = run(&mut chip8(), &options);
+        assert_eq!(report.reason, ExitReason::Loop { pc: 0x20C });
+        assert!(report.reason.is_success());
+
+        let mut chip8 = chip8();
+        let options = HeadlessOptions {
+            max_instructions: Some(10),
+            ..HeadlessOptions::default()
+        };
+        assert_eq!(
+            run(&mut chip8, &options),
+            HeadlessReport {
+                instructions: 10,
+                reason: ExitReason::InstructionLimit
+            }
+        );
+
+        let display = dump_display(&chip8.bus.graphics);
+        assert_eq!(display.lines().count(), graphics::HEIGHT);
+        assert!(dump_registers(&chip8).starts_with("V0=10 V1="));
+
+        // 6005: V0 = 5, E09E: skip if key 5 pressed, 1202: jump back
+        let rom = [0x60, 0x05, 0xE0, 0x9E, 0x12, 0x02];
+        let mut chip8 = Chip8::builder().rom(&rom).build().unwrap();
+        let options = HeadlessOptions {
+            exit_on_loop: true,
+            ..HeadlessOptions::default()
+        };
+        assert_eq!(
+            run(&mut chip8, &options).reason,
+            ExitReason::Loop { pc: 0x204 }
+        );
+    }
+}
@@ -0,0 +1,408 @@
+//! This module provides a bounded history of machine snapshots, allowing the
+//! [`super::Chip8`] to step backwards.
+//!
+//! Recording is opt-in, since every snapshot copies the whole memory. Once
+//! enabled through [`History::set_depth`], a snapshot is recorded before
+//! every executed instruction, and the oldest snapshot is discarded once the
+//! configured depth is reached. The same
+//! snapshots back the [`SaveState`]s taken through
+//! [`super::Chip8::save_state`].
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::sync::atomic::Ordering;
+
+use crate::{
+    audio::Audio,
+    clock::TimerLoad,
+    diff::{Accesses, StateDiff},
+    flags::RplFlags,
+    graphics, input,
+    megachip::MegaChip,
+    memory,
+    processor::Cpu,
+    rng::Rng,
+    Bus,
+};
+
+/// The default amount of snapshots kept by a [`History`], which disables
+/// recording.
+pub const DEFAULT_HISTORY_DEPTH: usize = 0;
+
+/// An amount of snapshots suited to stepping back through the recent
+/// instructions of a paused program.
+pub const REWIND_DEPTH: usize = 64;
+
+/// The state of the machine right before an instruction was executed.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Snapshot {
+    v: [u8; 16],
+    i: usize,
+    pc: usize,
+    sp: usize,
+    stack: Vec<usize>,
+    rng: Rng,
+    memory: memory::Memory,
+    graphics: graphics::Framebuffer,
+    megachip: Option<MegaChip>,
+    audio: Audio,
+    input: input::Input,
+    delay_timer: u8,
+    sound_timer: u8,
+    #[cfg_attr(feature = "serde", serde(default))]
+    delay_load: TimerLoad,
+    #[cfg_attr(feature = "serde", serde(default))]
+    sound_load: TimerLoad,
+    #[cfg_attr(feature = "serde", serde(default))]
+    flags: RplFlags,
+}
+
+impl Snapshot {
+    /// Captures the current state of the given [`Cpu`] and [`Bus`].
+    fn capture(cpu: &Cpu, bus: &Bus) -> Self {
+        Self {
+            v: cpu.v,
+            i: cpu.i,
+            pc: cpu.pc,
+            sp: cpu.sp,
+            stack: cpu.stack.clone(),
+            rng: cpu.rng,
+            memory: bus.memory.clone(),
+            graphics: bus.graphics,
+            megachip: bus.megachip.clone(),
+            audio: bus.audio,
+            input: bus.input.clone(),
+            delay_timer: bus.clock.delay_timer,
+            sound_timer: bus.clock.sound_timer.load(Ordering::SeqCst),
+            delay_load: bus.clock.delay_load,
+            sound_load: bus.clock.sound_load,
+            flags: bus.flags,
+        }
+    }
+
+    /// Compares the snapshot with the current state of the given [`Cpu`] and
+    /// [`Bus`].
+    fn diff(&self, cpu: &Cpu, bus: &Bus) -> StateDiff {
+        let opcode = if self.pc + 1 < self.memory.len() {
+            (usize::from(self.memory[self.pc]) << 8) | usize::from(self.memory[self.pc + 1])
+        } else {
+            0
+        };
+        let registers = (0..16)
+            .filter(|&x| self.v[x] != cpu.v[x])
+            .fold(0, |mask, x| mask | 1 << x);
+        let memory = (0..self.memory.len().min(bus.memory.len()))
+            .filter(|&address| self.memory[address] != bus.memory[address])
+            .collect();
+        let height = self.graphics.height().min(bus.graphics.height());
+        let pixels = (0..height)
+            .flat_map(|y| (0..graphics::WIDTH).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.graphics.pixel(x, y) != bus.graphics.pixel(x, y))
+            .collect();
+        StateDiff {
+            pc: self.pc,
+            opcode,
+            accesses: Accesses::of(opcode, self.i),
+            registers,
+            i: self.i != cpu.i,
+            sp: self.sp != cpu.sp,
+            delay_timer: self.delay_timer != bus.clock.delay_timer,
+            sound_timer: self.sound_timer != bus.clock.sound_timer.load(Ordering::SeqCst),
+            memory,
+            pixels,
+        }
+    }
+
+    /// Writes the snapshot back into the given [`Cpu`] and [`Bus`]. The most
+    /// recent entry of the instruction buffer is dropped, since it belongs to
+    /// the instruction that is being undone.
+    fn restore(self, cpu: &mut Cpu, bus: &mut Bus) {
+        cpu.instructions.pop_front();
+        self.apply(cpu, bus);
+    }
+
+    /// Writes the snapshot into the given [`Cpu`] and [`Bus`].
+    fn apply(self, cpu: &mut Cpu, bus: &mut Bus) {
+        cpu.v = self.v;
+        cpu.i = self.i;
+        cpu.pc = self.pc;
+        cpu.sp = self.sp;
+        cpu.stack = self.stack;
+        cpu.rng = self.rng;
+        bus.memory = self.memory;
+        bus.graphics = self.graphics;
+        bus.megachip = self.megachip;
+        bus.audio = self.audio;
+        bus.input = self.input;
+        bus.clock.delay_timer = self.delay_timer;
+        bus.clock
+            .sound_timer
+            .store(self.sound_timer, Ordering::SeqCst);
+        bus.clock.delay_load = self.delay_load;
+        bus.clock.sound_load = self.sound_load;
+        bus.flags = self.flags;
+    }
+}
+
+/// A copy of the machine state that can be loaded again later, taken
+/// through [`super::Chip8::save_state`].
+///
+/// With the `serde` feature enabled, save states can be serialized, e.g. to
+/// keep them in browser storage.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SaveState(Snapshot);
+
+impl SaveState {
+    /// Captures the current state of the given [`Cpu`] and [`Bus`].
+    pub(crate) fn capture(cpu: &Cpu, bus: &Bus) -> Self {
+        Self(Snapshot::capture(cpu, bus))
+    }
+
+    /// Writes the state into the given [`Cpu`] and [`Bus`].
+    pub(crate) fn load(&self, cpu: &mut Cpu, bus: &mut Bus) {
+        self.0.clone().apply(cpu, bus);
+    }
+
+    /// Returns the program counter the state resumes at.
+    #[must_use]
+    pub const fn pc(&self) -> usize {
+        self.0.pc
+    }
+}
+
+/// A bounded history of snapshots used to rewind the [`super::Chip8`].
+#[derive(Debug)]
+pub struct History {
+    /// The recorded snapshots, with the most recent one at the front.
+    snapshots: VecDeque<Snapshot>,
+    /// The maximum amount of snapshots to keep. A depth of `0` disables
+    /// recording entirely.
+    depth: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_DEPTH)
+    }
+}
+
+impl History {
+    /// Creates a new, empty [`History`] that keeps at most `depth` snapshots.
+    #[must_use]
+    pub fn new(depth: usize) -> Self {
+        Self {
+            snapshots: VecDeque::with_capacity(depth),
+            depth,
+        }
+    }
+
+    /// Returns the maximum amount of snapshots that are kept.
+    #[must_use]
+    pub const fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Sets the maximum amount of snapshots that are kept. If the history
+    /// currently holds more snapshots than `depth`, the oldest ones are
+    /// discarded.
+    pub fn set_depth(&mut self, depth: usize) {
+        self.depth = depth;
+        self.snapshots.truncate(depth);
+    }
+
+    /// Returns the amount of instructions that can currently be undone.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Returns whether there is nothing left to undo.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// Discards all recorded snapshots.
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    /// Captures the current state of the given [`Cpu`] and [`Bus`], or
+    /// returns [`None`] if recording is disabled. The snapshot is recorded
+    /// through [`History::push`] once the instruction was executed.
+    pub(crate) fn capture(&self, cpu: &Cpu, bus: &Bus) -> Option<Snapshot> {
+        (self.depth > 0).then(|| Snapshot::capture(cpu, bus))
+    }
+
+    /// Records a snapshot taken through [`History::capture`].
+    pub(crate) fn push(&mut self, snapshot: Snapshot) {
+        if self.snapshots.len() == self.depth {
+            self.snapshots.pop_back();
+        }
+        self.snapshots.push_front(snapshot);
+    }
+
+    /// Compares the most recent snapshot with the current state of the given
+    /// [`Cpu`] and [`Bus`]. Returns [`None`] if the history is empty.
+    pub(crate) fn diff(&self, cpu: &Cpu, bus: &Bus) -> Option<StateDiff> {
+        self.snapshots
+            .front()
+            .map(|snapshot| snapshot.diff(cpu, bus))
+    }
+
+    /// Restores the most recent snapshot into the given [`Cpu`] and [`Bus`].
+    /// Returns `false` if there was nothing to restore.
+    pub(crate) fn rewind(&mut self, cpu: &mut Cpu, bus: &mut Bus) -> bool {
+        let Some(snapshot) = self.snapshots.pop_front() else {
+            return false;
+        };
+        snapshot.restore(cpu, bus);
+        true
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::REWIND_DEPTH;
+    use crate::{clock::TimerLoad, processor::StepResult, Chip8};
+
+    #[test]
+    fn test_step_back() {
+        let mut chip8 = Chip8::new();
+        chip8.history.set_depth(REWIND_DEPTH);
+        // 6005: V0 = 5, 7001: V0 += 1
+        chip8.load_rom_data(vec![0x60, 0x05, 0x70, 0x01]).unwrap();
+
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.processor.v[0], 6);
+        assert_eq!(chip8.history.len(), 2);
+
+        // Undo the add
+        assert!(chip8.step_back());
+        assert_eq!(chip8.processor.v[0], 5);
+        assert_eq!(chip8.processor.pc, 0x202);
+
+        // Undo the load
+        assert!(chip8.step_back());
+        assert_eq!(chip8.processor.v[0], 0);
+        assert_eq!(chip8.processor.pc, 0x200);
+        assert!(chip8.processor.instructions.is_empty());
+
+        // Nothing left to undo
+        assert!(!chip8.step_back());
+    }
+
+    #[test]
+    fn test_step_back_after_stop() {
+        let mut chip8 = Chip8::new();
+        chip8.history.set_depth(4);
+        // 6005: V0 = 5, 7001: V0 += 1, 8008: invalid
+        chip8
+            .load_rom_data(vec![0x60, 0x05, 0x70, 0x01, 0x80, 0x08])
+            .unwrap();
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert!(chip8.step().is_err());
+
+        // The failed step is not recorded, so the add is undone
+        assert_eq!(chip8.history.len(), 2);
+        assert!(chip8.step_back());
+        assert_eq!((chip8.processor.v[0], chip8.processor.pc), (5, 0x202));
+        assert_eq!(chip8.processor.instructions.len(), 1);
+
+        // 6005: V0 = 5, 7001: V0 += 1, F00A: wait for a key
+        chip8
+            .reset_and_load(vec![0x60, 0x05, 0x70, 0x01, 0xF0, 0x0A])
+            .unwrap();
+        for _ in 0..3 {
+            chip8.step().unwrap();
+        }
+        for _ in 0..4 {
+            assert_eq!(chip8.step(), Ok(StepResult::WaitingForKey));
+        }
+
+        // Waiting is not recorded either, so every step back undoes one
+        // instruction
+        assert_eq!(chip8.history.len(), 3);
+        assert!(chip8.step_back());
+        assert_eq!((chip8.processor.v[0], chip8.processor.pc), (6, 0x204));
+        assert!(chip8.step_back());
+        assert!(chip8.step_back());
+        assert_eq!((chip8.processor.v[0], chip8.processor.pc), (0, 0x200));
+        assert!(!chip8.step_back());
+    }
+
+    #[test]
+    fn test_step_back_flags_and_timer_loads() {
+        let mut chip8 = Chip8::new();
+        chip8.history.set_depth(4);
+        // 6005: V0 = 5, F075: flags = V0, F015: DT = V0
+        chip8
+            .load_rom_data(vec![0x60, 0x05, 0xF0, 0x75, 0xF0, 0x15])
+            .unwrap();
+        for _ in 0..3 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.bus.flags.values()[0], 5);
+        assert_eq!(chip8.bus.clock.delay_load.count, 1);
+
+        assert!(chip8.step_back());
+        assert_eq!(chip8.bus.clock.delay_load, TimerLoad::default());
+        assert!(chip8.step_back());
+        assert_eq!(chip8.bus.flags.values()[0], 0);
+    }
+
+    #[test]
+    fn test_save_state() {
+        let mut chip8 = Chip8::new();
+        // 6005: V0 = 5, 7001: V0 += 1
+        chip8.load_rom_data(vec![0x60, 0x05, 0x70, 0x01]).unwrap();
+        chip8.step().unwrap();
+
+        let state = chip8.save_state();
+        chip8.step().unwrap();
+        assert_eq!(chip8.processor.v[0], 6);
+
+        // Loading discards the rewind history, and can be repeated
+        chip8.load_state(&state);
+        assert_eq!((chip8.processor.v[0], chip8.processor.pc), (5, 0x202));
+        assert!(chip8.history.is_empty());
+        chip8.step().unwrap();
+        chip8.load_state(&state);
+        assert_eq!(state.pc(), 0x202);
+        assert_eq!(chip8.processor.v[0], 5);
+
+        #[cfg(feature = "persistence")]
+        {
+            let json = serde_json::to_string(&state).unwrap();
+            let state: super::SaveState = serde_json::from_str(&json).unwrap();
+            chip8.step().unwrap();
+            chip8.load_state(&state);
+            assert_eq!((chip8.processor.v[0], chip8.processor.pc), (5, 0x202));
+        }
+    }
+
+    #[test]
+    fn test_history_depth() {
+        let mut chip8 = Chip8::new();
+        // Nothing is recorded by default
+        chip8.load_rom_data(vec![0x60, 0x05]).unwrap();
+        chip8.step().unwrap();
+        assert!(chip8.history.is_empty());
+
+        chip8.history.set_depth(1);
+        chip8.load_rom_data(vec![0x60, 0x05, 0x70, 0x01]).unwrap();
+
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.history.len(), 1);
+
+        chip8.history.set_depth(0);
+        assert!(chip8.history.is_empty());
+        chip8.step().unwrap();
+        assert!(!chip8.step_back());
+    }
+}
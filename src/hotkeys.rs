@@ -0,0 +1,222 @@
+//! This module provides configurable keyboard shortcuts for the emulator
+//! controls, so a frontend can pause, step, reset, save and load states or
+//! change the speed without the mouse.
+//!
+//! Like the [`crate::keymap::Keymap`], hotkeys are identified by host key
+//! names, optionally prefixed with `Shift+`, so the table can be used by any
+//! frontend. Actions are carried out by [`crate::runner::Chip8Runner::perform`].
+
+use std::fmt;
+
+/// The amount of save state slots.
+pub const SLOTS: usize = 4;
+
+/// An emulator control that can be bound to a hotkey.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Pause or resume execution.
+    TogglePause,
+    /// Pause and execute a single instruction.
+    Step,
+    /// Restart the program.
+    Reset,
+    /// Save the machine state into the slot with the given index.
+    SaveState(usize),
+    /// Load the machine state from the slot with the given index.
+    LoadState(usize),
+    /// Double the emulation speed.
+    SpeedUp,
+    /// Halve the emulation speed.
+    SlowDown,
+    /// Mute or unmute the buzzer.
+    ToggleMute,
+    /// Switch between player mode and developer mode.
+    ToggleMode,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TogglePause => f.write_str("pause"),
+            Self::Step => f.write_str("step"),
+            Self::Reset => f.write_str("reset"),
+            Self::SaveState(slot) => write!(f, "save-state-{}", slot + 1),
+            Self::LoadState(slot) => write!(f, "load-state-{}", slot + 1),
+            Self::SpeedUp => f.write_str("speed-up"),
+            Self::SlowDown => f.write_str("slow-down"),
+            Self::ToggleMute => f.write_str("mute"),
+            Self::ToggleMode => f.write_str("mode"),
+        }
+    }
+}
+
+/// A table of hotkeys, binding each [`Action`] to one host key name. An
+/// empty name leaves the action unbound.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Hotkeys {
+    /// The key bound to [`Action::TogglePause`].
+    pub pause: String,
+    /// The key bound to [`Action::Step`].
+    pub step: String,
+    /// The key bound to [`Action::Reset`].
+    pub reset: String,
+    /// The keys bound to [`Action::SaveState`], by slot.
+    pub save_state: [String; SLOTS],
+    /// The keys bound to [`Action::LoadState`], by slot.
+    pub load_state: [String; SLOTS],
+    /// The key bound to [`Action::SpeedUp`].
+    pub speed_up: String,
+    /// The key bound to [`Action::SlowDown`].
+    pub slow_down: String,
+    /// The key bound to [`Action::ToggleMute`].
+    pub mute: String,
+    /// The key bound to [`Action::ToggleMode`].
+    pub mode: String,
+}
+
+impl Default for Hotkeys {
+    /// P pauses, F6 steps, F5 resets, F1 to F4 load and Shift+F1 to Shift+F4
+    /// save states, + and - change the speed, M mutes and F8 switches between
+    /// player and developer mode.
+    fn default() -> Self {
+        Self {
+            pause: "P".to_string(),
+            step: "F6".to_string(),
+            reset: "F5".to_string(),
+            save_state: ["Shift+F1", "Shift+F2", "Shift+F3", "Shift+F4"].map(String::from),
+            load_state: ["F1", "F2", "F3", "F4"].map(String::from),
+            speed_up: "+".to_string(),
+            slow_down: "-".to_string(),
+            mute: "M".to_string(),
+            mode: "F8".to_string(),
+        }
+    }
+}
+
+impl Hotkeys {
+    /// Creates a new [`Hotkeys`] table with the default bindings.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the action bound to the given host key, if any. While Shift is
+    /// held, a binding with the `Shift+` prefix takes precedence over one
+    /// without, so keys that need Shift on some layouts, such as `+`, work
+    /// either way. Key names are compared case-insensitively.
+    #[must_use]
+    pub fn action(&self, key: &str, shift: bool) -> Option<Action> {
+        let find = |name: &str| {
+            self.iter()
+                .find(|(_, binding)| !binding.is_empty() && binding.eq_ignore_ascii_case(name))
+                .map(|(action, _)| action)
+        };
+        shift
+            .then(|| find(&format!("Shift+{key}")))
+            .flatten()
+            .or_else(|| find(key))
+    }
+
+    /// Returns the name of the host key bound to the given action.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the action refers to a slot beyond [`SLOTS`].
+    #[must_use]
+    pub fn binding(&self, action: Action) -> &str {
+        match action {
+            Action::TogglePause => &self.pause,
+            Action::Step => &self.step,
+            Action::Reset => &self.reset,
+            Action::SaveState(slot) => &self.save_state[slot],
+            Action::LoadState(slot) => &self.load_state[slot],
+            Action::SpeedUp => &self.speed_up,
+            Action::SlowDown => &self.slow_down,
+            Action::ToggleMute => &self.mute,
+            Action::ToggleMode => &self.mode,
+        }
+    }
+
+    /// Binds the given host key to an action. Any other action bound to the
+    /// same key is unbound.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the action refers to a slot beyond [`SLOTS`].
+    pub fn rebind(&mut self, action: Action, key: &str) {
+        for binding in self.bindings_mut() {
+            if !key.is_empty() && binding.eq_ignore_ascii_case(key) {
+                binding.clear();
+            }
+        }
+        let binding = match action {
+            Action::TogglePause => &mut self.pause,
+            Action::Step => &mut self.step,
+            Action::Reset => &mut self.reset,
+            Action::SaveState(slot) => &mut self.save_state[slot],
+            Action::LoadState(slot) => &mut self.load_state[slot],
+            Action::SpeedUp => &mut self.speed_up,
+            Action::SlowDown => &mut self.slow_down,
+            Action::ToggleMute => &mut self.mute,
+            Action::ToggleMode => &mut self.mode,
+        };
+        key.clone_into(binding);
+    }
+
+    /// Returns an iterator over all actions and the host key they are bound
+    /// to.
+    pub fn iter(&self) -> impl Iterator<Item = (Action, &str)> {
+        let slots = (0..SLOTS).flat_map(|slot| [Action::SaveState(slot), Action::LoadState(slot)]);
+        [Action::TogglePause, Action::Step, Action::Reset]
+            .into_iter()
+            .chain(slots)
+            .chain([
+                Action::SpeedUp,
+                Action::SlowDown,
+                Action::ToggleMute,
+                Action::ToggleMode,
+            ])
+            .map(|action| (action, self.binding(action)))
+    }
+
+    /// Returns mutable references to all bindings.
+    fn bindings_mut(&mut self) -> impl Iterator<Item = &mut String> {
+        [
+            &mut self.pause,
+            &mut self.step,
+            &mut self.reset,
+            &mut self.speed_up,
+            &mut self.slow_down,
+            &mut self.mute,
+            &mut self.mode,
+        ]
+        .into_iter()
+        .chain(&mut self.save_state)
+        .chain(&mut self.load_state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action() {
+        let mut hotkeys = Hotkeys::new();
+        assert_eq!(hotkeys.action("p", false), Some(Action::TogglePause));
+        assert_eq!(hotkeys.action("F2", false), Some(Action::LoadState(1)));
+        assert_eq!(hotkeys.action("F2", true), Some(Action::SaveState(1)));
+        // + needs Shift on US layouts
+        assert_eq!(hotkeys.action("+", true), Some(Action::SpeedUp));
+        assert_eq!(hotkeys.action("X", false), None);
+        assert_eq!(hotkeys.action("F8", false), Some(Action::ToggleMode));
+
+        // Rebinding a key in use unbinds the previous action
+        hotkeys.rebind(Action::Step, "P");
+        assert_eq!(hotkeys.action("P", false), Some(Action::Step));
+        assert_eq!(hotkeys.binding(Action::TogglePause), "");
+        assert_eq!(Action::SaveState(0).to_string(), "save-state-1");
+    }
+}
@@ -0,0 +1,164 @@
+//! This module translates the labels of the frontends and the messages of
+//! the command line tools.
+//!
+//! Texts are looked up by their English wording, so the English strings
+//! used throughout the crate, e.g. [`crate::display::Scaling::name`], double
+//! as keys and need no table of their own. [`Language::translate`] returns
+//! the text unchanged if a language has no translation for it yet.
+//!
+//! Frontends store the chosen [`Language`] in their settings. The command
+//! line tools follow the environment, see [`Language::from_env`].
+
+/// A language the user interface can be shown in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Language {
+    /// English, the language of the texts in the source code.
+    #[default]
+    English,
+    /// German.
+    German,
+}
+
+/// The German translations, by English text.
+const GERMAN: &[(&str, &str)] = &[
+    // Display options
+    ("Integer", "Ganzzahlig"),
+    ("Fit", "Einpassen"),
+    ("Stretch", "Strecken"),
+    ("None", "Keiner"),
+    ("Scanlines", "Rasterzeilen"),
+    // Debugger panels and docks
+    ("Control", "Steuerung"),
+    ("General State", "Allgemeiner Zustand"),
+    ("Memory", "Speicher"),
+    ("Disassembly", "Disassemblierung"),
+    ("Trace", "Ablaufverfolgung"),
+    ("Timers", "Timer"),
+    ("Left", "Links"),
+    ("Right", "Rechts"),
+    ("Bottom", "Unten"),
+    ("Floating", "Schwebend"),
+    // Sound
+    ("Square", "Rechteck"),
+    ("Sine", "Sinus"),
+    ("Triangle", "Dreieck"),
+    // Focus behavior
+    ("Keep running", "Weiterlaufen"),
+    ("Pause", "Pausieren"),
+    ("Throttle", "Drosseln"),
+    // Themes
+    ("Classic", "Klassisch"),
+    ("Green phosphor", "Grüner Phosphor"),
+    ("Amber", "Bernstein"),
+    ("High contrast", "Hoher Kontrast"),
+    ("High contrast light", "Hoher Kontrast hell"),
+    ("Colorblind safe", "Farbenblind-sicher"),
+    // Gamepad buttons
+    ("D-pad up", "Steuerkreuz oben"),
+    ("D-pad down", "Steuerkreuz unten"),
+    ("D-pad left", "Steuerkreuz links"),
+    ("D-pad right", "Steuerkreuz rechts"),
+    ("South", "Unten"),
+    ("East", "Rechts"),
+    ("North", "Oben"),
+    ("West", "Links"),
+    ("Left shoulder", "Linke Schultertaste"),
+    ("Right shoulder", "Rechte Schultertaste"),
+    ("Select", "Auswahl"),
+    // Disassembler syntax
+    ("Description", "Beschreibung"),
+    // Command line messages
+    ("usage", "Aufruf"),
+    ("invalid instruction count", "ungültige Anzahl an Befehlen"),
+    ("invalid timeout", "ungültige Zeitbegrenzung"),
+    ("invalid load address", "ungültige Ladeadresse"),
+    ("invalid log level", "ungültige Protokollstufe"),
+    ("invalid save region", "ungültiger Speicherbereich"),
+    ("unknown theme", "unbekanntes Farbschema"),
+    ("unknown trace format", "unbekanntes Ablaufformat"),
+    ("unknown variant", "unbekannte Variante"),
+    ("cannot load", "Fehler beim Laden von"),
+    ("cannot create", "Fehler beim Anlegen von"),
+    ("wrote a crash report to", "Absturzbericht geschrieben nach"),
+];
+
+impl Language {
+    /// All languages, in the order they should be offered to the user.
+    pub const ALL: [Self; 2] = [Self::English, Self::German];
+
+    /// Returns the name of the language in the language itself, so users can
+    /// find theirs whatever the current language is.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::English => "English",
+            Self::German => "Deutsch",
+        }
+    }
+
+    /// Returns the ISO 639-1 code of the language, e.g. `de`.
+    #[must_use]
+    pub const fn code(self) -> &'static str {
+        match self {
+            Self::English => "en",
+            Self::German => "de",
+        }
+    }
+
+    /// Returns the language with the given code. Region and encoding
+    /// suffixes are ignored, so `de-AT` as in `navigator.language` and
+    /// `de_DE.UTF-8` as in `LANG` both select German.
+    #[must_use]
+    pub fn from_code(code: &str) -> Option<Self> {
+        let code = code.split(['-', '_', '.']).next().unwrap_or_default();
+        Self::ALL
+            .into_iter()
+            .find(|language| language.code().eq_ignore_ascii_case(code))
+    }
+
+    /// Returns the language selected by the `LC_ALL`, `LC_MESSAGES` or
+    /// `LANG` environment variable, in that order, or English.
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Self::from_code(&value))
+            .unwrap_or_default()
+    }
+
+    /// Returns the given English text in the language, or the text itself if
+    /// there is no translation for it.
+    #[must_use]
+    pub fn translate(self, text: &str) -> &str {
+        let table = match self {
+            Self::English => return text,
+            Self::German => GERMAN,
+        };
+        table
+            .iter()
+            .find(|(english, _)| *english == text)
+            .map_or(text, |(_, translated)| translated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate() {
+        assert_eq!(Language::English.translate("Memory"), "Memory");
+        assert_eq!(Language::German.translate("Memory"), "Speicher");
+        // Untranslated texts stay English
+        assert_eq!(Language::German.translate("Octo"), "Octo");
+
+        assert_eq!(Language::from_code("de-AT"), Some(Language::German));
+        assert_eq!(Language::from_code("de_DE.UTF-8"), Some(Language::German));
+        assert_eq!(Language::from_code("EN"), Some(Language::English));
+        assert_eq!(Language::from_code("fr"), None);
+    }
+}
@@ -0,0 +1,135 @@
+//! This module provides the detection of idle loops that span several
+//! instructions, such as a program polling a key by jumping back two
+//! instructions.
+//!
+//! [`crate::processor::StepResult::Loop`] only reports a jump to the jumping
+//! instruction itself. The [`IdleDetector`] instead hashes the program
+//! counter, registers and pressed keys after every instruction, and reports
+//! an idle loop once the machine returns to a state it was in a few
+//! instructions earlier without having changed memory, the display or the
+//! timers in between. From then on, only a key press can get the program out
+//! of the loop. Loops that read the delay timer are not reported, since the
+//! timer ends them.
+
+use crate::{processor::Cpu, Bus};
+
+/// The amount of recent states compared, which bounds the length of the
+/// loops that are detected.
+pub const WINDOW: usize = 32;
+
+/// Detects idle loops, see the [module documentation](self).
+#[derive(Debug, Clone, Default)]
+pub struct IdleDetector {
+    /// Whether loops are detected.
+    enabled: bool,
+    /// The hashes of the most recent states, as a ring buffer.
+    hashes: [u64; WINDOW],
+    /// The amount of valid entries in `hashes`.
+    len: usize,
+    /// The index the next hash is written to.
+    next: usize,
+    /// The address of the instruction that closed the detected loop, if the
+    /// program is idle.
+    idle_pc: Option<usize>,
+}
+
+impl IdleDetector {
+    /// Returns whether loops are detected.
+    #[must_use]
+    pub const fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Starts or stops detecting loops.
+    pub const fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.reset();
+    }
+
+    /// Returns the address of the instruction that closed the idle loop the
+    /// program is spinning in, if any.
+    #[must_use]
+    pub const fn idle_pc(&self) -> Option<usize> {
+        self.idle_pc
+    }
+
+    /// Forgets all recorded states.
+    pub const fn reset(&mut self) {
+        self.len = 0;
+        self.next = 0;
+        self.idle_pc = None;
+    }
+
+    /// Records the state after the given opcode, executed at `pc`, and checks
+    /// whether it closed an idle loop.
+    pub(crate) fn record(&mut self, pc: usize, opcode: usize, cpu: &Cpu, bus: &Bus) {
+        if has_side_effects(opcode) {
+            self.reset();
+            return;
+        }
+        // the pressed keys are part of the state, so a key press leaves
+        // the loop
+        let hash = hash_state(cpu, bus.input.keys());
+        if self.hashes[..self.len].contains(&hash) {
+            self.idle_pc = Some(pc);
+            return;
+        }
+        self.idle_pc = None;
+        self.hashes[self.next] = hash;
+        self.next = (self.next + 1) % WINDOW;
+        self.len = (self.len + 1).min(WINDOW);
+    }
+}
+
+/// Returns whether the opcode changes something outside of the registers,
+/// or reads the delay timer.
+const fn has_side_effects(opcode: usize) -> bool {
+    matches!(
+        opcode & 0xF0FF,
+        0x00E0 | 0x00FB | 0x00FC | 0x00FD | 0xF007 | 0xF015 | 0xF018 | 0xF033 | 0xF055 | 0xF075
+    ) || matches!(opcode & 0xFFF0, 0x00C0 | 0x00D0)
+        || opcode & 0xF00F == 0x5002
+        || opcode & 0xF000 == 0xD000
+        || opcode & 0xF000 == 0xC000
+}
+
+/// Hashes the registers, the program counter and the pressed keys with
+/// FNV-1a.
+fn hash_state(cpu: &Cpu, keys: u16) -> u64 {
+    const PRIME: u64 = 0x0100_0000_01B3;
+    let words = [cpu.pc as u64, cpu.i as u64, cpu.sp as u64, u64::from(keys)];
+    cpu.v
+        .iter()
+        .map(|&v| u64::from(v))
+        .chain(words)
+        .fold(0xCBF2_9CE4_8422_2325, |hash, word| {
+            (hash ^ word).wrapping_mul(PRIME)
+        })
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::Chip8;
+
+    #[test]
+    fn test_idle_loop() {
+        let mut chip8 = Chip8::new();
+        // 6005: V0 = 5, E09E: skip if key 5 pressed, 1202: jump back,
+        // 7101: V1 += 1, 1206: loop back
+        let rom = vec![0x60, 0x05, 0xE0, 0x9E, 0x12, 0x02, 0x71, 0x01, 0x12, 0x06];
+        chip8.load_rom_data(rom).unwrap();
+        chip8.idle.set_enabled(true);
+        // the jump back returns to the state after 6005
+        for _ in 0..3 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.idle.idle_pc(), Some(0x204));
+
+        // the counting loop changes V1, so it is not idle
+        chip8.press(0x5);
+        for _ in 0..16 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.idle.idle_pc(), None);
+    }
+}
@@ -1,12 +1,22 @@
 //! This module provides the input system for the Chip8 emulator. It keeps
 //! track of the state of all 16 keys and handles any key press requests
 //! from programs.
+//!
+//! Frontends report key changes one at a time through [`Input::press`] and
+//! [`Input::release`], or as a whole through [`Input::set_keys`]. Either way
+//! only changes are applied, so `Fx0A` sees the edge of a key going down
+//! instead of a key that was already held. A [`KeyQueue`] spreads changes that
+//! happened between two frames over the instructions of the next one, so a
+//! quick tap is not lost.
+
+use alloc::collections::VecDeque;
 
 /// A response for a requested key press by the processor.
 ///
 /// Contains the key code of the pressed key and the register where
 /// the processor should store it in.
-#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KeyRequestResponse {
     /// The key code of the pressed key.
     pub key_code: u8,
@@ -16,7 +26,8 @@ pub struct KeyRequestResponse {
 
 /// Input system for the [`super::Chip8`]. Keeps track of the state of all 16 keys
 /// and any key press requests from programs.
-#[derive(Debug, serde::Serialize, serde::Deserialize, Default)]
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Input {
     /// The current state of all 16 keys.
     state: [bool; 16],
@@ -41,7 +52,7 @@ impl Input {
     ///
     /// * `key_code`: The key code of the key that was pressed or released.
     /// * `pressed`: A boolean indicating whether the key was pressed (true)
-    ///              or released (false).
+    ///   or released (false).
     pub fn update(&mut self, key_code: u8, pressed: bool) {
         let key_index = usize::from(key_code);
         if self.state[key_index] == pressed {
@@ -58,12 +69,41 @@ impl Input {
         }
     }
 
+    /// Presses the given key.
+    pub fn press(&mut self, key_code: u8) {
+        self.update(key_code, true);
+    }
+
+    /// Releases the given key.
+    pub fn release(&mut self, key_code: u8) {
+        self.update(key_code, false);
+    }
+
+    /// Sets the state of all 16 keys from a bitmask, where bit `n` is set if
+    /// key `n` is pressed. Only keys whose state changed are updated.
+    pub fn set_keys(&mut self, keys: u16) {
+        for key_code in 0..16 {
+            self.update(key_code, keys & 1 << key_code != 0);
+        }
+    }
+
+    /// Returns the state of all 16 keys as a bitmask, where bit `n` is set if
+    /// key `n` is pressed.
+    #[must_use]
+    pub fn keys(&self) -> u16 {
+        self.state
+            .iter()
+            .enumerate()
+            .filter(|(_, &pressed)| pressed)
+            .fold(0, |keys, (key_code, _)| keys | 1 << key_code)
+    }
+
     /// Requests a single key press from the user.
     ///
     /// # Arguments
     ///
     /// * `register`: The index of the register where the key code should be stored.
-    pub fn request_key_press(&mut self, register: usize) {
+    pub const fn request_key_press(&mut self, register: usize) {
         self.waiting = true;
         self.request_reg = register;
     }
@@ -72,23 +112,153 @@ impl Input {
     ///
     /// This will be `None` if no key press was requested or if the key press
     /// was already consumed.
-    pub fn request_response(&mut self) -> Option<KeyRequestResponse> {
+    pub const fn request_response(&mut self) -> Option<KeyRequestResponse> {
         self.request_response.take()
     }
 
+    /// Returns the response to a previous key press request without
+    /// consuming it.
+    #[must_use]
+    pub const fn peek_response(&self) -> Option<KeyRequestResponse> {
+        self.request_response
+    }
+
     /// Returns whether the input system is currently waiting for user input.
     #[must_use]
     pub const fn waiting(&self) -> bool {
         self.waiting
     }
 
-    /// Returns whether the given key is currently pressed.
+    /// Returns whether the given key is currently pressed. Like the keypad
+    /// of the COSMAC VIP, only the low four bits of the key code are decoded,
+    /// so programs testing larger values do not crash.
     ///
     /// # Arguments
     ///
     /// * `key_code`: The key code of the key to check.
     #[must_use]
     pub fn is_key_pressed(&self, key_code: u8) -> bool {
-        self.state[usize::from(key_code)]
+        self.state[usize::from(key_code & 0xF)]
+    }
+}
+
+/// A key state change scheduled at a point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimedKey {
+    /// The amount of instructions executed by the [`KeyQueue`] before the
+    /// change is applied.
+    pub at: u64,
+    /// The key code of the changed key.
+    pub key_code: u8,
+    /// Whether the key is pressed ([`true`]) or released ([`false`]).
+    pub pressed: bool,
+}
+
+/// A queue of timestamped key state changes, applied by
+/// [`super::Chip8::step`] once their time has come.
+///
+/// Time is counted in executed instructions, so queued changes are applied
+/// at the same point of a run regardless of the speed of the host.
+#[derive(Debug, Clone, Default)]
+pub struct KeyQueue {
+    /// The pending changes, in chronological order.
+    events: VecDeque<TimedKey>,
+    /// The amount of instructions executed since the queue was created.
+    now: u64,
+}
+
+impl KeyQueue {
+    /// Returns the amount of instructions executed since the queue was
+    /// created.
+    #[must_use]
+    pub const fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// Returns the amount of pending changes.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Returns whether no change is pending.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Schedules a key state change. Changes scheduled in the past are
+    /// applied before the next instruction, and changes for the same time
+    /// are applied one instruction apart, in the order they were pushed, so
+    /// a press and release within a single frame are both seen.
+    pub fn push(&mut self, event: TimedKey) {
+        let index = self.events.partition_point(|queued| queued.at <= event.at);
+        self.events.insert(index, event);
+    }
+
+    /// Discards all pending changes.
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// Returns the change due before the next instruction, if any. At most
+    /// one change is returned per instruction.
+    pub(crate) fn pop_due(&mut self) -> Option<TimedKey> {
+        self.events
+            .front()
+            .is_some_and(|event| event.at <= self.now)
+            .then(|| self.events.pop_front())
+            .flatten()
+    }
+
+    /// Counts an executed instruction.
+    pub(crate) const fn advance(&mut self) {
+        self.now += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_set_keys() {
+        let mut input = Input::new();
+        input.press(0x2);
+        input.request_key_press(3);
+        // an already held key does not answer the request
+        input.set_keys(0b0000_0000_0000_0100);
+        assert!(input.waiting());
+        input.set_keys(0b1000_0000_0000_0100);
+        assert_eq!(input.keys(), 0b1000_0000_0000_0100);
+        let response = input.request_response().unwrap();
+        assert_eq!((response.key_code, response.register), (0xF, 3));
+    }
+
+    #[test]
+    fn test_key_queue() {
+        let mut queue = KeyQueue::default();
+        for (at, pressed) in [(5, false), (0, true)] {
+            queue.push(TimedKey {
+                at,
+                key_code: 0x1,
+                pressed,
+            });
+        }
+        queue.push(TimedKey {
+            at: 0,
+            key_code: 0x2,
+            pressed: true,
+        });
+        let mut applied = Vec::new();
+        for _ in 0..8 {
+            if let Some(event) = queue.pop_due() {
+                applied.push((queue.now(), event.key_code, event.pressed));
+            }
+            queue.advance();
+        }
+        assert_eq!(applied, [(0, 0x1, true), (1, 0x2, true), (5, 0x1, false)]);
+        assert!(queue.is_empty());
     }
 }
@@ -0,0 +1,220 @@
+//! This module provides a remappable table of key bindings, translating host
+//! keyboard keys into the 16 keys of the Chip8 keypad.
+//!
+//! Keys are identified by their name (for example `"Q"` or `"Space"`), so the
+//! table can be used by any frontend. With the `persistence` feature enabled,
+//! a [`Keymap`] can be stored in and loaded from a TOML file.
+
+#[cfg(feature = "persistence")]
+use std::{fs, io, path::Path};
+
+/// The amount of keys on the Chip8 keypad.
+pub const KEY_COUNT: usize = 16;
+
+/// The key codes of the original COSMAC VIP hex keypad, row by row. Frontends
+/// use this to draw an on-screen keypad that can be clicked or tapped.
+pub const KEYPAD: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+/// The default QWERTY layout, indexed by Chip8 key code.
+///
+/// ```text
+/// Keypad       Keyboard
+/// 1 2 3 C      1 2 3 4
+/// 4 5 6 D      Q W E R
+/// 7 8 9 E      A S D F
+/// A 0 B F      Z X C V
+/// ```
+pub const QWERTY: [&str; KEY_COUNT] = [
+    "X", "1", "2", "3", "Q", "W", "E", "A", "S", "D", "Z", "C", "4", "R", "F", "V",
+];
+
+/// The AZERTY equivalent of [`QWERTY`], using the same physical key positions.
+pub const AZERTY: [&str; KEY_COUNT] = [
+    "X", "1", "2", "3", "A", "Z", "E", "Q", "S", "D", "W", "C", "4", "R", "F", "V",
+];
+
+/// The Dvorak equivalent of [`QWERTY`], using the same physical key positions.
+pub const DVORAK: [&str; KEY_COUNT] = [
+    "Q", "1", "2", "3", "'", ",", ".", "A", "O", "E", ";", "J", "4", "P", "U", "K",
+];
+
+/// A table of key bindings. Each of the 16 Chip8 keys is bound to exactly one
+/// host key name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Keymap {
+    /// The host key name bound to each Chip8 key, indexed by key code.
+    bindings: [String; KEY_COUNT],
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::from_layout(QWERTY)
+    }
+}
+
+impl Keymap {
+    /// Creates a new [`Keymap`] using the default [`QWERTY`] layout.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [`Keymap`] from a layout of host key names, indexed by
+    /// Chip8 key code.
+    #[must_use]
+    pub fn from_layout(layout: [&str; KEY_COUNT]) -> Self {
+        Self {
+            bindings: layout.map(String::from),
+        }
+    }
+
+    /// Returns the Chip8 key code bound to the given host key, if any. Key
+    /// names are compared case-insensitively.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The name of the host key.
+    #[must_use]
+    pub fn key_code(&self, key: &str) -> Option<u8> {
+        self.bindings
+            .iter()
+            .position(|binding| binding.eq_ignore_ascii_case(key))
+            .and_then(|index| u8::try_from(index).ok())
+    }
+
+    /// Returns the name of the host key bound to the given Chip8 key code.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key_code` is not a valid Chip8 key (`0x0..=0xF`).
+    #[must_use]
+    pub fn binding(&self, key_code: u8) -> &str {
+        &self.bindings[usize::from(key_code)]
+    }
+
+    /// Binds the given host key to a Chip8 key code. If the host key was
+    /// already bound to another Chip8 key, the two bindings are swapped so that
+    /// every Chip8 key stays reachable.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_code`: The Chip8 key code to rebind.
+    /// * `key`: The name of the host key to bind it to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key_code` is not a valid Chip8 key (`0x0..=0xF`).
+    pub fn rebind(&mut self, key_code: u8, key: &str) {
+        let index = usize::from(key_code);
+        if let Some(previous) = self.key_code(key) {
+            self.bindings.swap(index, usize::from(previous));
+        } else {
+            key.clone_into(&mut self.bindings[index]);
+        }
+    }
+
+    /// Returns an iterator over all Chip8 key codes and the host key they are
+    /// bound to.
+    pub fn iter(&self) -> impl Iterator<Item = (u8, &str)> {
+        (0..).zip(self.bindings.iter().map(String::as_str))
+    }
+
+    /// Parses a [`Keymap`] from a TOML string.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the string is not a valid keymap.
+    #[cfg(feature = "persistence")]
+    pub fn from_toml(toml: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(toml)
+    }
+
+    /// Serializes the [`Keymap`] into a TOML string.
+    ///
+    /// # Panics
+    ///
+    /// Never panics in practice, since a [`Keymap`] always serializes to TOML.
+    #[cfg(feature = "persistence")]
+    #[must_use]
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("keymap is always serializable")
+    }
+
+    /// Loads a [`Keymap`] from the TOML file at the given path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a valid keymap.
+    #[cfg(feature = "persistence")]
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Self::from_toml(&contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Saves the [`Keymap`] as TOML to the file at the given path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    #[cfg(feature = "persistence")]
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_toml())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_code() {
+        let keymap = Keymap::new();
+        assert_eq!(keymap.key_code("X"), Some(0x0));
+        assert_eq!(keymap.key_code("v"), Some(0xF));
+        assert_eq!(keymap.key_code("P"), None);
+        assert_eq!(keymap.binding(0xC), "4");
+    }
+
+    #[test]
+    fn test_rebind() {
+        let mut keymap = Keymap::new();
+
+        // Binding an unused key replaces the old binding
+        keymap.rebind(0x5, "Up");
+        assert_eq!(keymap.key_code("Up"), Some(0x5));
+        assert_eq!(keymap.key_code("W"), None);
+
+        // Binding a key that is already in use swaps the two bindings
+        keymap.rebind(0x5, "Q");
+        assert_eq!(keymap.key_code("Q"), Some(0x5));
+        assert_eq!(keymap.key_code("Up"), Some(0x4));
+    }
+
+    #[test]
+    fn test_keypad() {
+        // The keypad matches the physical layout of the default bindings
+        let keymap = Keymap::new();
+        let keys: Vec<&str> = KEYPAD
+            .iter()
+            .flatten()
+            .map(|&key_code| keymap.binding(key_code))
+            .collect();
+        assert_eq!(keys.concat(), "1234QWERASDFZXCV");
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_toml_round_trip() {
+        let mut keymap = Keymap::from_layout(AZERTY);
+        keymap.rebind(0x0, "Space");
+
+        let parsed = Keymap::from_toml(&keymap.to_toml()).unwrap();
+        assert_eq!(parsed, keymap);
+    }
+}
@@ -0,0 +1,195 @@
+//! This module provides user-assigned names and comments for memory
+//! addresses, so disassembly, traces and call stacks can show `draw_player`
+//! instead of `0x02A0`.
+//!
+//! With the `persistence` feature enabled, the [`Labels`] of a ROM can be
+//! stored in a JSON sidecar file next to it. The sidecar records the hash of
+//! the ROM, so it is ignored once the ROM changes.
+
+use alloc::collections::BTreeMap;
+use alloc::{format, string::String};
+#[cfg(feature = "persistence")]
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "persistence")]
+use crate::sidecar;
+
+/// The extension of the sidecar file holding the labels of a ROM.
+#[cfg(feature = "persistence")]
+pub const SIDECAR_EXTENSION: &str = "sym";
+
+/// A set of names and comments assigned to memory addresses.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Labels {
+    /// The name of every labeled address.
+    names: BTreeMap<usize, String>,
+
+    /// The comment of every commented address.
+    #[cfg_attr(feature = "serde", serde(default))]
+    comments: BTreeMap<usize, String>,
+}
+
+impl Labels {
+    /// Creates an empty set of [`Labels`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Assigns a name to the given address, replacing any previous name.
+    pub fn set(&mut self, address: usize, name: impl Into<String>) {
+        self.names.insert(address, name.into());
+    }
+
+    /// Removes the name of the given address. Returns the removed name, if
+    /// any.
+    pub fn remove(&mut self, address: usize) -> Option<String> {
+        self.names.remove(&address)
+    }
+
+    /// Returns the name of the given address, if any.
+    #[must_use]
+    pub fn get(&self, address: usize) -> Option<&str> {
+        self.names.get(&address).map(String::as_str)
+    }
+
+    /// Returns the address with the given name, if any.
+    #[must_use]
+    pub fn address(&self, name: &str) -> Option<usize> {
+        self.names
+            .iter()
+            .find(|(_, label)| *label == name)
+            .map(|(&address, _)| address)
+    }
+
+    /// Formats the given address as its name, or as a hexadecimal number if
+    /// it has none.
+    #[must_use]
+    pub fn format_address(&self, address: usize) -> String {
+        self.get(address)
+            .map_or_else(|| format!("{address:#06X}"), String::from)
+    }
+
+    /// Returns an iterator over all labeled addresses and their names, in
+    /// ascending order of address.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.names
+            .iter()
+            .map(|(&address, name)| (address, name.as_str()))
+    }
+
+    /// Returns the amount of labeled addresses.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns whether no address is labeled or commented.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.comments.is_empty()
+    }
+
+    /// Attaches a comment to the given address, replacing any previous
+    /// comment.
+    pub fn set_comment(&mut self, address: usize, comment: impl Into<String>) {
+        self.comments.insert(address, comment.into());
+    }
+
+    /// Removes the comment of the given address. Returns the removed comment,
+    /// if any.
+    pub fn remove_comment(&mut self, address: usize) -> Option<String> {
+        self.comments.remove(&address)
+    }
+
+    /// Returns the comment of the given address, if any.
+    #[must_use]
+    pub fn comment(&self, address: usize) -> Option<&str> {
+        self.comments.get(&address).map(String::as_str)
+    }
+
+    /// Returns an iterator over all commented addresses and their comments, in
+    /// ascending order of address.
+    pub fn comments(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.comments
+            .iter()
+            .map(|(&address, comment)| (address, comment.as_str()))
+    }
+
+    /// Returns the path of the sidecar file for the ROM at the given path,
+    /// i.e. the ROM path with its extension replaced by
+    /// [`SIDECAR_EXTENSION`].
+    #[cfg(feature = "persistence")]
+    #[must_use]
+    pub fn sidecar_path(rom_path: impl AsRef<Path>) -> PathBuf {
+        sidecar::path(rom_path, SIDECAR_EXTENSION)
+    }
+
+    /// Deserializes the [`Labels`] of the ROM with the given hash from a
+    /// sidecar JSON string. Returns [`None`] if the sidecar belongs to a
+    /// different ROM.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the string is not a valid sidecar.
+    #[cfg(feature = "persistence")]
+    pub fn from_json(json: &str, rom_hash: &str) -> Result<Option<Self>, serde_json::Error> {
+        sidecar::from_json(json, rom_hash)
+    }
+
+    /// Serializes the [`Labels`] into a sidecar JSON string for the ROM with
+    /// the given hash.
+    ///
+    /// # Panics
+    ///
+    /// Never panics in practice, since [`Labels`] always serialize to JSON.
+    #[cfg(feature = "persistence")]
+    #[must_use]
+    pub fn to_json(&self, rom_hash: &str) -> String {
+        sidecar::to_json(self, rom_hash).expect("labels are always serializable")
+    }
+
+    /// Loads the [`Labels`] of the ROM at the given path from its sidecar
+    /// file. Returns empty [`Labels`] if there is no sidecar file, or if it
+    /// was saved for a different version of the ROM.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM or an existing sidecar cannot be read, or
+    /// if the sidecar is invalid.
+    #[cfg(feature = "persistence")]
+    pub fn load_for_rom(rom_path: impl AsRef<Path>) -> io::Result<Self> {
+        sidecar::load(rom_path, SIDECAR_EXTENSION)
+    }
+
+    /// Saves the [`Labels`] to the sidecar file of the ROM at the given path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM cannot be read or the sidecar cannot be
+    /// written.
+    #[cfg(feature = "persistence")]
+    pub fn save_for_rom(&self, rom_path: impl AsRef<Path>) -> io::Result<()> {
+        sidecar::save(self, rom_path, SIDECAR_EXTENSION)
+    }
+}
+
+#[cfg(all(test, feature = "persistence"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sidecar() {
+        let mut labels = Labels::new();
+        labels.set(0x2A0, "draw_player");
+        labels.set_comment(0x2A0, "expects the position in V0 and V1");
+
+        let json = labels.to_json("abc");
+        assert_eq!(Labels::from_json(&json, "abc").unwrap(), Some(labels));
+        assert_eq!(Labels::from_json(&json, "def").unwrap(), None);
+    }
+}
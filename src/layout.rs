@@ -0,0 +1,442 @@
+//! This module arranges the debugger panels of a frontend around the
+//! display.
+//!
+//! A [`DockLayout`] docks every [`Panel`] to the left, right or bottom edge
+//! of the window, or detaches it into a window of its own. Panels docked to
+//! the same edge share its strip. [`DockLayout::arrange`] splits the window
+//! between the open panels and leaves the rest to the display, which
+//! frontends pass on to [`DisplayOptions::viewport_in`].
+//!
+//! In play mode, every debugger panel is hidden and the display fills the
+//! whole window, while the panels keep their places for developer mode.
+//! Frontends show the keypad hints and speed instead, and switch modes with
+//! [`DockLayout::set_play_mode_for`], which remembers the mode per ROM so
+//! [`DockLayout::restore_mode`] brings it back when the ROM is loaded again.
+//!
+//! [`DisplayOptions::viewport_in`]: crate::display::DisplayOptions::viewport_in
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::display::Viewport;
+
+/// A debugger panel of a frontend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Panel {
+    /// The run, pause, step and reset controls.
+    Control,
+    /// The registers, timers and stack.
+    State,
+    /// The memory viewer.
+    Memory,
+    /// The disassembly around the program counter.
+    Disassembly,
+    /// The trace of the executed instructions.
+    Trace,
+    /// The delay and sound timers as bars, and the beep indicator, see
+    /// [`crate::timer_bars`].
+    Timers,
+}
+
+impl Panel {
+    /// All panels, in the order they are stacked in a strip.
+    pub const ALL: [Self; 6] = [
+        Self::Control,
+        Self::State,
+        Self::Memory,
+        Self::Disassembly,
+        Self::Trace,
+        Self::Timers,
+    ];
+
+    /// Returns the display name of the panel.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Control => "Control",
+            Self::State => "General State",
+            Self::Memory => "Memory",
+            Self::Disassembly => "Disassembly",
+            Self::Trace => "Trace",
+            Self::Timers => "Timers",
+        }
+    }
+
+    /// Returns the panel with the given display name.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|panel| panel.name() == name)
+    }
+}
+
+/// Where a [`Panel`] is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Dock {
+    /// In a strip along the left edge of the window.
+    Left,
+    /// In a strip along the right edge of the window.
+    Right,
+    /// In a strip along the bottom edge of the window, between the left and
+    /// right strips.
+    Bottom,
+    /// In a window of its own, placed by the frontend.
+    Floating,
+}
+
+impl Dock {
+    /// All docks, in the order they should be offered to the user.
+    pub const ALL: [Self; 4] = [Self::Left, Self::Right, Self::Bottom, Self::Floating];
+
+    /// Returns the display name of the dock.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Left => "Left",
+            Self::Right => "Right",
+            Self::Bottom => "Bottom",
+            Self::Floating => "Floating",
+        }
+    }
+
+    /// Returns the dock with the given display name.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|dock| dock.name() == name)
+    }
+}
+
+/// The place of a [`Panel`] and whether it is open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PanelState {
+    /// Where the panel is shown.
+    pub dock: Dock,
+    /// Whether the panel is open in developer mode.
+    pub open: bool,
+}
+
+/// The rectangles of the display and the docked panels, in physical pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arrangement {
+    /// The area left to the display.
+    pub display: Viewport,
+    /// The area of every open docked panel.
+    pub panels: Vec<(Panel, Viewport)>,
+}
+
+impl Arrangement {
+    /// Returns the area of the given panel, or [`None`] if it is closed or
+    /// floating.
+    #[must_use]
+    pub fn panel(&self, panel: Panel) -> Option<Viewport> {
+        self.panels
+            .iter()
+            .find(|(docked, _)| *docked == panel)
+            .map(|&(_, area)| area)
+    }
+}
+
+/// The arrangement of the debugger panels of a frontend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct DockLayout {
+    /// The place of every panel. Panels missing here are closed and docked
+    /// to the right.
+    pub panels: BTreeMap<Panel, PanelState>,
+
+    /// The width of the left strip in percent of the window width.
+    pub left_width: u32,
+
+    /// The width of the right strip in percent of the window width.
+    pub right_width: u32,
+
+    /// The height of the bottom strip in percent of the window height.
+    pub bottom_height: u32,
+
+    /// Whether all panels are hidden, so the display fills the window.
+    pub play_mode: bool,
+
+    /// Whether play mode was last chosen for a ROM, by ROM hash.
+    pub rom_play_modes: BTreeMap<String, bool>,
+}
+
+impl Default for DockLayout {
+    fn default() -> Self {
+        let docks = [
+            (Panel::Control, Dock::Left),
+            (Panel::State, Dock::Left),
+            (Panel::Memory, Dock::Bottom),
+            (Panel::Disassembly, Dock::Right),
+            (Panel::Trace, Dock::Bottom),
+            (Panel::Timers, Dock::Right),
+        ];
+        Self {
+            panels: docks
+                .into_iter()
+                .map(|(panel, dock)| (panel, PanelState { dock, open: true }))
+                .collect(),
+            left_width: 25,
+            right_width: 25,
+            bottom_height: 30,
+            play_mode: false,
+            rom_play_modes: BTreeMap::new(),
+        }
+    }
+}
+
+impl DockLayout {
+    /// Creates the default developer layout, with the controls and state on
+    /// the left, the disassembly and timers on the right and the memory and
+    /// trace at the bottom.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the place of the given panel.
+    #[must_use]
+    pub fn state(&self, panel: Panel) -> PanelState {
+        self.panels.get(&panel).copied().unwrap_or(PanelState {
+            dock: Dock::Right,
+            open: false,
+        })
+    }
+
+    /// Returns whether the given panel is shown, which it never is in play
+    /// mode.
+    #[must_use]
+    pub fn is_visible(&self, panel: Panel) -> bool {
+        !self.play_mode && self.state(panel).open
+    }
+
+    /// Opens or closes the given panel.
+    pub fn set_open(&mut self, panel: Panel, open: bool) {
+        let state = PanelState {
+            open,
+            ..self.state(panel)
+        };
+        self.panels.insert(panel, state);
+    }
+
+    /// Moves the given panel to the given dock, e.g. when it is dragged to
+    /// another edge or detached from the window.
+    pub fn set_dock(&mut self, panel: Panel, dock: Dock) {
+        let state = PanelState {
+            dock,
+            ..self.state(panel)
+        };
+        self.panels.insert(panel, state);
+    }
+
+    /// Switches between play mode and developer mode, and remembers the
+    /// choice for the ROM with the given hash. Switching to developer mode
+    /// opens all panels if none are open, so there is something to debug
+    /// with.
+    pub fn set_play_mode_for(&mut self, rom_hash: &str, play_mode: bool) {
+        self.play_mode = play_mode;
+        if !rom_hash.is_empty() {
+            self.rom_play_modes.insert(rom_hash.to_string(), play_mode);
+        }
+        if !play_mode && !Panel::ALL.into_iter().any(|panel| self.state(panel).open) {
+            for panel in Panel::ALL {
+                self.set_open(panel, true);
+            }
+        }
+    }
+
+    /// Switches to the mode last chosen for the ROM with the given hash,
+    /// e.g. after loading it. Keeps the current mode for ROMs without one.
+    pub fn restore_mode(&mut self, rom_hash: &str) {
+        if let Some(&play_mode) = self.rom_play_modes.get(rom_hash) {
+            self.play_mode = play_mode;
+        }
+    }
+
+    /// Returns the visible panels in the given dock, in stacking order.
+    #[must_use]
+    pub fn docked(&self, dock: Dock) -> Vec<Panel> {
+        Panel::ALL
+            .into_iter()
+            .filter(|&panel| self.is_visible(panel) && self.state(panel).dock == dock)
+            .collect()
+    }
+
+    /// Splits a window of `width` x `height` physical pixels between the
+    /// visible docked panels and the display. Strips without panels take no
+    /// space, so in play mode the display gets the whole window.
+    #[must_use]
+    pub fn arrange(&self, width: u32, height: u32) -> Arrangement {
+        let share = |length: u32, percent: u32| {
+            u32::try_from(u64::from(length) * u64::from(percent.min(100)) / 100).unwrap_or(length)
+        };
+        let left = self.docked(Dock::Left);
+        let right = self.docked(Dock::Right);
+        let bottom = self.docked(Dock::Bottom);
+        let left_width = if left.is_empty() {
+            0
+        } else {
+            share(width, self.left_width)
+        };
+        let right_width = if right.is_empty() {
+            0
+        } else {
+            share(width, self.right_width).min(width - left_width)
+        };
+        let bottom_height = if bottom.is_empty() {
+            0
+        } else {
+            share(height, self.bottom_height)
+        };
+        let center_width = width - left_width - right_width;
+
+        let mut panels = Vec::new();
+        let mut stack = |docked: &[Panel], strip: Viewport, vertical: bool| {
+            let count = u32::try_from(docked.len()).unwrap_or(u32::MAX);
+            for (index, &panel) in (0..).zip(docked) {
+                let area = if vertical {
+                    let start = strip.height * index / count;
+                    let end = strip.height * (index + 1) / count;
+                    Viewport {
+                        y: strip.y + start,
+                        height: end - start,
+                        ..strip
+                    }
+                } else {
+                    let start = strip.width * index / count;
+                    let end = strip.width * (index + 1) / count;
+                    Viewport {
+                        x: strip.x + start,
+                        width: end - start,
+                        ..strip
+                    }
+                };
+                panels.push((panel, area));
+            }
+        };
+        stack(
+            &left,
+            Viewport {
+                x: 0,
+                y: 0,
+                width: left_width,
+                height,
+            },
+            true,
+        );
+        stack(
+            &right,
+            Viewport {
+                x: width - right_width,
+                y: 0,
+                width: right_width,
+                height,
+            },
+            true,
+        );
+        stack(
+            &bottom,
+            Viewport {
+                x: left_width,
+                y: height - bottom_height,
+                width: center_width,
+                height: bottom_height,
+            },
+            false,
+        );
+        Arrangement {
+            display: Viewport {
+                x: left_width,
+                y: 0,
+                width: center_width,
+                height: height - bottom_height,
+            },
+            panels,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arrange() {
+        let mut layout = DockLayout::new();
+        let arrangement = layout.arrange(1000, 600);
+        assert_eq!(
+            arrangement.display,
+            Viewport {
+                x: 250,
+                y: 0,
+                width: 500,
+                height: 420
+            }
+        );
+        // The controls and state share the left strip
+        assert_eq!(
+            arrangement.panel(Panel::State),
+            Some(Viewport {
+                x: 0,
+                y: 300,
+                width: 250,
+                height: 300
+            })
+        );
+        // The memory and trace share the bottom strip
+        assert_eq!(
+            arrangement.panel(Panel::Trace),
+            Some(Viewport {
+                x: 500,
+                y: 420,
+                width: 250,
+                height: 180
+            })
+        );
+
+        // A floating panel and an emptied strip take no space
+        layout.set_dock(Panel::Disassembly, Dock::Floating);
+        layout.set_dock(Panel::Timers, Dock::Floating);
+        let arrangement = layout.arrange(1000, 600);
+        assert_eq!(arrangement.panel(Panel::Disassembly), None);
+        assert_eq!(arrangement.display.width, 750);
+
+        // Play mode hides everything but keeps the places
+        layout.play_mode = true;
+        let arrangement = layout.arrange(1000, 600);
+        assert!(arrangement.panels.is_empty());
+        assert_eq!(arrangement.display.width, 1000);
+        assert_eq!(arrangement.display.height, 600);
+        layout.play_mode = false;
+        assert_eq!(layout.state(Panel::Disassembly).dock, Dock::Floating);
+        assert_eq!(layout.docked(Dock::Left), [Panel::Control, Panel::State]);
+    }
+
+    #[test]
+    fn test_mode() {
+        let mut layout = DockLayout::new();
+        layout.set_play_mode_for("game", true);
+        layout.set_play_mode_for("demo", false);
+        assert!(!layout.play_mode);
+
+        // The mode comes back with the ROM, unknown ROMs keep the current one
+        layout.restore_mode("game");
+        assert!(layout.play_mode);
+        layout.restore_mode("unknown");
+        assert!(layout.play_mode);
+
+        // Developer mode without any open panel opens them all
+        for panel in Panel::ALL {
+            layout.set_open(panel, false);
+        }
+        layout.set_play_mode_for("game", false);
+        assert!(Panel::ALL.into_iter().all(|panel| layout.is_visible(panel)));
+        layout.restore_mode("game");
+        assert!(!layout.play_mode);
+    }
+}
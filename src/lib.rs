@@ -2,28 +2,157 @@
 //! optimized code that leverages the latest Rust language features and
 //! compiler optimizations. This ensures that the emulator runs smoothly and
 //! efficiently on modern hardware, even when running demanding Chip8 games.
+//!
+//! Without the default `std` feature, the interpreter core builds with
+//! `no_std` and `alloc`, e.g. for a microcontroller handheld. The core
+//! consists of [`Chip8`], its [`processor`], [`memory`], [`graphics`] and
+//! [`input`], and the [`disassembler`]. Since there is no wall clock, the host
+//! drives the timers by calling [`Chip8::tick_timers`] at 60Hz, and creates
+//! the system with [`Chip8::new_with_rng`] or [`Chip8::builder`]. Targets
+//! without atomic compare-and-swap, like the `thumbv6m-none-eabi` target of
+//! the RP2040, also need the `critical-section` feature.
 #![warn(missing_debug_implementations, clippy::pedantic, clippy::nursery)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
-use crate::processor::Cpu;
+extern crate alloc;
 
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::{fs, io, path::Path};
+
+use crate::{
+    error::Chip8Error,
+    processor::{Cpu, StepResult},
+    rng::Rng,
+};
+
+pub mod audio;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod bench;
+pub mod builder;
+pub mod callstack;
+pub mod cheats;
 pub mod clock;
+#[cfg(feature = "std")]
+pub mod compat;
+#[cfg(feature = "persistence")]
+pub mod config;
+pub mod control;
+#[cfg(feature = "std")]
+pub mod coverage;
+pub mod decode;
+pub mod diff;
+pub mod disassembler;
+#[cfg(feature = "std")]
+pub mod display;
+pub mod error;
+#[cfg(feature = "std")]
+pub mod events;
+#[cfg(feature = "std")]
+pub mod fault;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod flags;
+#[cfg(feature = "std")]
+pub mod frontend;
+#[cfg(feature = "std")]
+pub mod gamepad;
+#[cfg(feature = "std")]
+pub mod gdb;
 pub mod graphics;
+pub mod gym;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod headless;
+pub mod history;
+#[cfg(feature = "std")]
+pub mod hotkeys;
+pub mod i18n;
+pub mod idle;
 pub mod input;
+#[cfg(feature = "std")]
+pub mod keymap;
+pub mod labels;
+#[cfg(feature = "std")]
+pub mod layout;
+#[cfg(feature = "std")]
+pub mod logging;
+pub mod megachip;
+pub mod memlog;
 pub mod memory;
+#[cfg(feature = "std")]
+pub mod netplay;
+#[cfg(feature = "std")]
+pub mod octo;
+pub mod peripheral;
 pub mod processor;
+pub mod profiler;
+pub mod quirks;
+#[cfg(feature = "std")]
+pub mod recent;
+#[cfg(feature = "std")]
+pub mod recorder;
+pub mod reference;
+pub mod replay;
+pub mod rng;
+#[cfg(feature = "std")]
+pub mod rom;
+#[cfg(feature = "persistence")]
+pub mod romdb;
+#[cfg(feature = "std")]
+pub mod roms;
+#[cfg(feature = "std")]
+pub mod runner;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod selftest;
+#[cfg(feature = "std")]
+pub mod session;
+#[cfg(feature = "persistence")]
+pub mod sidecar;
+#[cfg(feature = "std")]
+pub mod sprites;
+#[cfg(feature = "std")]
+pub mod stats;
+pub mod storage;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod supervisor;
+pub mod theme;
+pub mod timer_bars;
+#[cfg(feature = "std")]
+pub mod timing;
+#[cfg(feature = "std")]
+pub mod touch;
+#[cfg(feature = "std")]
+pub mod trace;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod triple_buffer;
+#[cfg(all(feature = "std", target_arch = "wasm32"))]
+pub mod wasm;
+#[cfg(all(feature = "watch", not(target_arch = "wasm32")))]
+pub mod watch;
+#[cfg(all(feature = "std", target_arch = "wasm32"))]
+pub mod webaudio;
+#[cfg(all(feature = "std", target_arch = "wasm32"))]
+pub mod webrom;
+#[cfg(all(feature = "persistence", target_arch = "wasm32"))]
+pub mod webstorage;
+#[cfg(all(feature = "std", target_arch = "wasm32"))]
+pub mod webtimer;
 
 /// The [`Bus`] struct contains fields for different components of a computer system
-#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bus {
     /// An instance of the [`clock::Clock`] struct, which represents the system
     /// clock of the computer. This is used to synchronize the different
     /// components of the system and ensure that they operate at the same speed.
     pub clock: clock::Clock,
 
-    /// An instance of the [`graphics::Buffer`] struct, which represents the
+    /// An instance of the [`graphics::Framebuffer`] struct, which represents the
     /// display buffer of the computer. This is used to store the contents
     /// of the screen and update it as necessary.
-    pub graphics: graphics::Buffer,
+    pub graphics: graphics::Framebuffer,
 
     /// An instance of the [`input::Input`] struct, which represents the
     /// input devices of the computer. This is used to handle user input, such
@@ -34,10 +163,30 @@ pub struct Bus {
     /// memory of the computer. This is used to store the instructions and
     /// data that the processor needs to execute.
     pub memory: memory::Memory,
+
+    /// The state of the [`megachip::MegaChip`] extensions, if enabled through
+    /// [`Chip8::set_megachip`].
+    pub megachip: Option<megachip::MegaChip>,
+
+    /// The XO-CHIP [`audio::Audio`] pattern and pitch, played by the buzzer
+    /// while the sound timer is non-zero.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub audio: audio::Audio,
+
+    /// The HP-48 [`flags::RplFlags`] written by `Fx75` and read by `Fx85`.
+    /// These are kept across [`Chip8::reset`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub flags: flags::RplFlags,
+
+    /// The [`peripheral::Peripheral`]s attached to the system, registered
+    /// through [`Chip8::register_peripheral`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub peripherals: peripheral::Peripherals,
 }
 
 /// The [`Chip8`] struct represents a computer system that uses the Chip-8 virtual machine.
-#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chip8 {
     /// An instance of the [`Cpu`] struct, which represents the CPU of
     /// the system. This is responsible for executing the instructions in
@@ -48,28 +197,344 @@ pub struct Chip8 {
     /// components of the system. This is used to connect the CPU to the other
     /// components of the system and facilitate communication between them.
     pub bus: Bus,
+
+    /// A bounded [`history::History`] of previous machine states, used to
+    /// step backwards through the program. It records nothing until its depth
+    /// is set.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub history: history::History,
+
+    /// A shared [`control::Controls`] handle used to pause and single-step the
+    /// system from another thread.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub controls: control::Controls,
+
+    /// A [`replay::Replay`] used to record the input of a run and play it
+    /// back later.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub replay: replay::Replay,
+
+    /// User-assigned [`labels::Labels`] for addresses of the loaded program,
+    /// used when disassembling and showing the call stack.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub labels: labels::Labels,
+
+    /// A [`profiler::Profiler`] counting executions and memory accesses per
+    /// address while enabled. The counts are kept across resets.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub profiler: profiler::Profiler,
+
+    /// A [`memlog::MemoryLog`] of the memory reads and writes of every
+    /// instruction while enabled. The entries are kept across resets.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub memory_log: memlog::MemoryLog,
+
+    /// An [`idle::IdleDetector`] recognizing loops the program cannot leave
+    /// without a key press, while enabled.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub idle: idle::IdleDetector,
+
+    /// The [`cheats::Cheats`] applied before every instruction. They are kept
+    /// across resets.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub cheats: cheats::Cheats,
+
+    /// A [`input::KeyQueue`] of timestamped key state changes, applied
+    /// through [`Chip8::update_key_state`] before the instruction they are
+    /// due at.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub key_queue: input::KeyQueue,
+
+    /// The per-ROM [`storage::Storage`] for save data, set through
+    /// [`Chip8::set_storage`]. It is kept across resets.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub storage: storage::Storage,
+
+    /// A copy of the loaded ROM, so [`Chip8::reset_keep_rom`] can restart the
+    /// program after [`Chip8::reset`] wiped the memory.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    rom: Vec<u8>,
+
+    /// The address ROMs are loaded at and execution starts from, set through
+    /// [`Chip8::set_load_address`].
+    #[cfg_attr(feature = "serde", serde(default = "Chip8::default_load_address"))]
+    load_address: usize,
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self {
+            processor: Cpu::default(),
+            bus: Bus::default(),
+            history: history::History::default(),
+            controls: control::Controls::default(),
+            replay: replay::Replay::default(),
+            labels: labels::Labels::default(),
+            profiler: profiler::Profiler::default(),
+            memory_log: memlog::MemoryLog::default(),
+            idle: idle::IdleDetector::default(),
+            cheats: cheats::Cheats::default(),
+            key_queue: input::KeyQueue::default(),
+            storage: storage::Storage::default(),
+            rom: Vec::new(),
+            load_address: Self::default_load_address(),
+        }
+    }
 }
 
 impl Chip8 {
     /// Creates a new instance of the [`Chip8`] struct with a new [`Cpu`] instance and
-    /// the default values for the `Bus` struct's fields.
+    /// the default values for the `Bus` struct's fields. The random number
+    /// generator is seeded from the operating system's entropy source.
     ///
     /// # Returns
     ///
     /// The newly created instance of the [`Chip8`] struct.
+    #[cfg(feature = "std")]
     #[must_use]
     pub fn new() -> Self {
+        Self::new_with_rng(Rng::from_entropy())
+    }
+
+    /// Creates a new instance of the [`Chip8`] struct like [`Chip8::new`], but
+    /// using the given [`Rng`] for the `Cxnn` instruction. Two systems created
+    /// with equally seeded generators behave identically given the same input.
+    ///
+    /// # Returns
+    ///
+    /// The newly created instance of the [`Chip8`] struct.
+    #[must_use]
+    pub fn new_with_rng(rng: Rng) -> Self {
+        let mut processor = Cpu::new();
+        processor.rng = rng;
         Self {
-            processor: Cpu::new(),
+            processor,
             ..Default::default()
         }
     }
 
-    /// Executes one instruction cycle of the Chip-8 CPU by updating the system clock and
-    /// calling the `cycle` method of the [`Cpu`] struct to execute the current instruction.
-    pub fn step(&mut self) {
+    /// Returns a [`builder::Chip8Builder`] to configure a new system and
+    /// load a ROM into it.
+    pub fn builder() -> builder::Chip8Builder {
+        builder::Chip8Builder::new()
+    }
+
+    /// Returns [`memory::PROGRAM_START`], used when deserializing a [`Chip8`]
+    /// that was stored without a load address.
+    const fn default_load_address() -> usize {
+        memory::PROGRAM_START
+    }
+
+    /// Returns the address ROMs are loaded at and execution starts from.
+    #[must_use]
+    pub const fn load_address(&self) -> usize {
+        self.load_address
+    }
+
+    /// Sets the address ROMs are loaded at and execution starts from, which
+    /// is [`memory::PROGRAM_START`] by default. It takes effect with the next
+    /// loaded ROM or [`Chip8::reset`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::InvalidLoadAddress`] if the address lies before
+    /// [`memory::PROGRAM_START`] or outside of memory.
+    pub const fn set_load_address(&mut self, addr: usize) -> Result<(), Chip8Error> {
+        if addr < memory::PROGRAM_START || addr >= self.bus.memory.len() {
+            return Err(Chip8Error::InvalidLoadAddress { addr });
+        }
+        self.load_address = addr;
+        Ok(())
+    }
+
+    /// Reseeds the random number generator used by the `Cxnn` instruction.
+    /// The seed is kept across [`Chip8::reset`], so a reset system produces
+    /// the same random numbers again.
+    pub const fn set_seed(&mut self, seed: u64) {
+        self.processor.rng = Rng::new(seed);
+    }
+
+    /// Sets the quirks of the [`Cpu`] to the profile of the given
+    /// [`quirks::Variant`].
+    pub const fn set_variant(&mut self, variant: quirks::Variant) {
+        self.processor.quirks = variant.quirks();
+    }
+
+    /// Enables or disables the Mega-Chip extensions. While enabled, ROMs up to
+    /// [`memory::MEGACHIP_MEMORY_SIZE`] bytes can be loaded and the Mega-Chip
+    /// opcodes are decoded. Since every step of a large ROM records a sizeable
+    /// snapshot, the [`Chip8::history`] depth should be kept low if it is
+    /// enabled.
+    pub fn set_megachip(&mut self, enabled: bool) {
+        self.bus.megachip = enabled.then(megachip::MegaChip::new);
+    }
+
+    /// Executes one instruction cycle of the Chip-8 CPU by calling the `cycle`
+    /// method of the [`Cpu`] struct to execute the current instruction. The
+    /// timers are left alone, unless a [`clock::TimeSource`] ticking them was
+    /// selected, see [`Chip8::tick_timers`].
+    ///
+    /// # Returns
+    ///
+    /// The [`StepResult`] describing the outcome of the cycle.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`Chip8Error`] if the current instruction cannot be executed.
+    pub fn step(&mut self) -> Result<StepResult, Chip8Error> {
+        if let Some(event) = self.key_queue.pop_due() {
+            self.update_key_state(event.key_code, event.pressed);
+        }
+        self.key_queue.advance();
+        self.replay.advance(&mut self.bus.input);
         self.bus.clock.update();
-        self.processor.cycle(&mut self.bus);
+        self.cheats.apply(&mut self.processor, &mut self.bus.memory);
+        let snapshot = self.history.capture(&self.processor, &self.bus);
+        let traced =
+            self.profiler.is_enabled() || self.memory_log.is_enabled() || self.idle.is_enabled();
+        let (pc, i) = (self.processor.pc, self.processor.i);
+        let opcode = if traced { self.current_opcode() } else { None };
+
+        let result = self.processor.cycle(&mut self.bus);
+        if !matches!(result, Ok(StepResult::Continue | StepResult::Loop)) {
+            return result;
+        }
+        // only executed instructions can be undone
+        if let Some(snapshot) = snapshot {
+            self.history.push(snapshot);
+        }
+        if let Some(opcode) = opcode {
+            if self.profiler.is_enabled() {
+                self.profiler.record(pc, opcode, i, self.bus.memory.len());
+            }
+            if self.memory_log.is_enabled() {
+                self.memory_log.record(pc, opcode, i, &self.bus.memory);
+            }
+            if self.idle.is_enabled() {
+                self.idle.record(pc, opcode, &self.processor, &self.bus);
+            }
+        }
+        result
+    }
+
+    /// Executes up to `n` instructions in a row, stopping early once the
+    /// display changed or the program cannot continue. Unlike calling
+    /// [`Chip8::try_step`] in a loop, the pause state of [`Chip8::controls`] is
+    /// not checked, which suits fast-forwarding and headless runs at high
+    /// instruction rates.
+    pub fn step_n(&mut self, n: u32) -> processor::BatchResult {
+        self.step_until(n, |_| false)
+    }
+
+    /// Executes up to `n` instructions like [`Chip8::step_n`], and also stops
+    /// once the program counter reaches an address `is_breakpoint` returns
+    /// `true` for. The address the batch starts at is not checked, so a batch
+    /// can resume from a breakpoint.
+    pub fn step_until(
+        &mut self,
+        n: u32,
+        is_breakpoint: impl Fn(usize) -> bool,
+    ) -> processor::BatchResult {
+        use processor::{BatchResult, BatchStop};
+
+        let mut executed = 0;
+        let stop = loop {
+            if executed == n {
+                break BatchStop::Completed;
+            }
+            let opcode = self.current_opcode().unwrap_or_default();
+            match self.step() {
+                Ok(StepResult::Continue) => executed += 1,
+                Ok(StepResult::Loop) => {
+                    executed += 1;
+                    break BatchStop::Loop;
+                }
+                Ok(StepResult::WaitingForKey) => break BatchStop::WaitingForKey,
+                Ok(StepResult::WaitingForVblank) => break BatchStop::WaitingForVblank,
+                Ok(StepResult::End) => break BatchStop::End,
+                Err(err) => break BatchStop::Error(err),
+            }
+            if processor::updates_display(opcode) {
+                break BatchStop::DisplayUpdated;
+            }
+            if is_breakpoint(self.processor.pc) {
+                break BatchStop::Breakpoint;
+            }
+        };
+        BatchResult { executed, stop }
+    }
+
+    /// Executes one instruction cycle, unless execution was paused through
+    /// [`Chip8::controls`]. While paused, an instruction is only executed if a
+    /// step was requested.
+    ///
+    /// # Returns
+    ///
+    /// The result of [`Chip8::step`], or [`None`] if execution is paused.
+    pub fn try_step(&mut self) -> Option<Result<StepResult, Chip8Error>> {
+        self.controls.should_step().then(|| self.step())
+    }
+
+    /// Attaches the given [`peripheral::Peripheral`] to the system, after all
+    /// previously registered ones.
+    pub fn register_peripheral(&mut self, peripheral: impl peripheral::Peripheral + 'static) {
+        self.bus.peripherals.register(peripheral);
+    }
+
+    /// Returns the opcode at the program counter, or [`None`] if the program
+    /// counter points outside of memory.
+    #[must_use]
+    pub fn current_opcode(&self) -> Option<usize> {
+        let pc = self.processor.pc;
+        let hi = self.bus.memory.read(pc).ok()?;
+        let lo = self.bus.memory.read(pc + 1).ok()?;
+        Some((usize::from(hi) << 8) | usize::from(lo))
+    }
+
+    /// Returns the pending subroutine calls, with the most recent call first.
+    #[must_use]
+    pub fn call_stack(&self) -> Vec<callstack::CallFrame> {
+        callstack::call_stack(&self.processor, &self.bus.memory)
+    }
+
+    /// Moves the program counter past the current instruction without
+    /// executing it. This is used to ignore an instruction that raised a
+    /// [`Chip8Error`] and continue with the rest of the program.
+    pub const fn skip_instruction(&mut self) {
+        self.processor.pc += 2;
+    }
+
+    /// Undoes the most recently executed instruction by restoring the last
+    /// snapshot recorded in [`Chip8::history`].
+    ///
+    /// # Returns
+    ///
+    /// [`true`] if an instruction was undone, or [`false`] if the history is empty.
+    pub fn step_back(&mut self) -> bool {
+        self.history.rewind(&mut self.processor, &mut self.bus)
+    }
+
+    /// Captures the current machine state, so it can be loaded again through
+    /// [`Chip8::load_state`].
+    #[must_use]
+    pub fn save_state(&self) -> history::SaveState {
+        history::SaveState::capture(&self.processor, &self.bus)
+    }
+
+    /// Loads a state taken through [`Chip8::save_state`]. The rewind history
+    /// is discarded, since it belongs to a different timeline.
+    pub fn load_state(&mut self, state: &history::SaveState) {
+        state.load(&mut self.processor, &mut self.bus);
+        self.history.clear();
+        self.idle.reset();
+    }
+
+    /// Returns the changes made by the most recent instruction, e.g. to
+    /// highlight them while paused, or [`None`] if [`Chip8::history`] holds
+    /// no snapshot to compare with.
+    #[must_use]
+    pub fn diff(&self) -> Option<diff::StateDiff> {
+        self.history.diff(&self.processor, &self.bus)
     }
 
     /// Loads the given [`Vec<u8>`] of ROM data into the memory of the [`Bus`] struct. This
@@ -78,38 +543,173 @@ impl Chip8 {
     /// # Arguments
     ///
     /// * `data`: A [`Vec<u8>`] of ROM data to load into the memory.
-    pub fn load_rom_data(&mut self, data: Vec<u8>) {
-        self.bus.memory.load_rom(data);
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::RomTooLarge`] if the ROM does not fit into memory.
+    /// With the Mega-Chip extensions enabled, the memory grows to fit the ROM.
+    /// The memory region of [`Chip8::storage`] is written over the ROM.
+    pub fn load_rom_data(&mut self, data: Vec<u8>) -> Result<(), Chip8Error> {
+        if self.bus.megachip.is_some() {
+            self.bus
+                .memory
+                .grow_for_rom(data.len() + self.load_address - memory::PROGRAM_START);
+        }
+        self.bus
+            .memory
+            .load_rom_at(data.clone(), self.load_address)?;
+        self.storage.restore(&mut self.bus.memory);
+        self.rom = data;
+        Ok(())
+    }
+
+    /// Replaces the [`storage::Storage`] of the loaded ROM, e.g. with the one
+    /// loaded from its sidecar file, and writes its memory region into
+    /// memory.
+    pub fn set_storage(&mut self, storage: storage::Storage) {
+        self.storage = storage;
+        self.storage.restore(&mut self.bus.memory);
+    }
+
+    /// Stores the contents of the memory region reserved through
+    /// [`storage::Storage::set_region`] in [`Chip8::storage`]. Frontends call
+    /// this regularly and save the storage once
+    /// [`storage::Storage::take_changed`] reports a change.
+    pub fn sync_storage(&mut self) {
+        self.storage.capture(&self.bus.memory);
+    }
+
+    /// Returns the most recently loaded ROM, which is kept across
+    /// [`Chip8::reset`].
+    #[must_use]
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
     }
 
     /// Updates the state of a key on the input device. Takes in a [`u8`] representing the
     /// key code and a boolean `pressed` indicating whether the key is pressed or released.
-    /// This method is called to handle keyboard input events.
+    /// This method is called to handle keyboard input events. While a movie is
+    /// played back through [`Chip8::replay`], live input is ignored.
     ///
     /// # Arguments
     ///
     /// * `key_code`: A [`u8`] representing the key code of the pressed or released key.
     /// * `pressed`: A boolean indicating whether the key is pressed ([`true`]) or released ([`false`]).
     pub fn update_key_state(&mut self, key_code: u8, pressed: bool) {
-        self.bus.input.update(key_code, pressed);
+        if self.replay.key(key_code, pressed) {
+            self.bus.input.update(key_code, pressed);
+        }
+    }
+
+    /// Presses the given key, see [`Chip8::update_key_state`].
+    pub fn press(&mut self, key_code: u8) {
+        self.update_key_state(key_code, true);
+    }
+
+    /// Releases the given key, see [`Chip8::update_key_state`].
+    pub fn release(&mut self, key_code: u8) {
+        self.update_key_state(key_code, false);
+    }
+
+    /// Sets the state of all 16 keys from a bitmask, where bit `n` is set if
+    /// key `n` is pressed. Only keys whose state changed are updated, so a
+    /// frontend can pass its whole keypad every frame without `Fx0A` seeing
+    /// held keys as new presses.
+    pub fn set_keys(&mut self, keys: u16) {
+        for key_code in 0..16 {
+            let pressed = keys & 1 << key_code != 0;
+            if self.bus.input.is_key_pressed(key_code) != pressed {
+                self.update_key_state(key_code, pressed);
+            }
+        }
+    }
+
+    /// Schedules a key state change `delay` instructions from now through
+    /// [`Chip8::key_queue`].
+    pub fn queue_key(&mut self, delay: u64, key_code: u8, pressed: bool) {
+        let at = self.key_queue.now() + delay;
+        self.key_queue.push(input::TimedKey {
+            at,
+            key_code,
+            pressed,
+        });
+    }
+
+    /// Returns the current values of the delay and sound timers.
+    #[must_use]
+    pub fn timers(&self) -> (u8, u8) {
+        let clock = &self.bus.clock;
+        (
+            clock.delay_timer,
+            clock.sound_timer.load(core::sync::atomic::Ordering::SeqCst),
+        )
+    }
+
+    /// Decrements the delay and sound timers once and raises the vblank
+    /// interrupt. [`Chip8::step`] does not tick the timers, so the host calls
+    /// this at 60Hz, e.g. from `requestAnimationFrame` or a hardware timer;
+    /// [`runner::Chip8Runner`] does so in step with the emulated time.
+    ///
+    /// The timers keep ticking while the program waits for a key press in
+    /// `Fx0A`. With [`quirks::Quirks::vblank_wait`], a sprite is only drawn
+    /// once a tick raised the vblank interrupt, so a program drawing several
+    /// sprites waits for a tick before each of them.
+    pub fn tick_timers(&mut self) {
+        self.bus.clock.tick();
     }
 
-    /// Resets the state of the Chip8 system by clearing the display buffer of the [`Bus`]
-    /// struct and creating a new [`Bus`] instance with the same graphics buffer as the
-    /// previous [`Bus`] instance. It also creates a new [`Cpu`] instance with the same
-    /// shift quirk and vblank wait settings as the previous [`Cpu`] instance.
+    /// Ticks the timers once, like [`Chip8::tick_timers`], which replaced
+    /// this method.
+    #[deprecated(note = "use `Chip8::tick_timers` instead")]
+    pub fn tick_60hz(&mut self) {
+        self.tick_timers();
+    }
+
+    /// Replaces the [`clock::TimeSource`] deciding whether the timers tick
+    /// during [`Chip8::step`], which is [`clock::Manual`] by default. The
+    /// source is kept across resets.
+    pub fn set_time_source(&mut self, source: impl clock::TimeSource + 'static) {
+        self.bus.clock.set_source(source);
+    }
+
+    /// Resets the state of the Chip8 system by clearing all planes of the display buffer
+    /// of the [`Bus`] struct and creating a new [`Bus`] instance with the same graphics
+    /// buffer and time source as the previous [`Bus`] instance, with the Mega-Chip
+    /// extensions enabled if they were before. It also creates a new [`Cpu`] instance
+    /// with the same [`quirks::Quirks`] as the previous [`Cpu`] instance. The random
+    /// number generator is reseeded with its original seed. The rewind history is
+    /// cleared, but its depth is kept, any recording or playback is stopped,
+    /// and pending queued key changes are discarded. The memory is wiped, but
+    /// a copy of the loaded ROM is kept for [`Chip8::reset_keep_rom`].
     pub fn reset(&mut self) {
-        self.bus.graphics.clear();
+        self.bus.graphics.set_hires(false);
+        self.bus.graphics.select_planes(1);
+        let time_source = self.bus.clock.take_source();
         self.bus = Bus {
             graphics: self.bus.graphics,
+            megachip: self
+                .bus
+                .megachip
+                .as_ref()
+                .map(|_| megachip::MegaChip::new()),
+            peripherals: core::mem::take(&mut self.bus.peripherals),
+            flags: self.bus.flags,
             ..Default::default()
         };
+        self.bus.clock.restore_source(time_source);
 
-        let shift_quirk_enabled = self.processor.shift_quirk_enabled;
-        let vblank_wait = self.processor.vblank_wait;
+        let quirks = self.processor.quirks;
+        let seed = self.processor.rng.seed();
+        let decode_cache = self.processor.decode_cache();
         self.processor = Cpu::new();
-        self.processor.shift_quirk_enabled = shift_quirk_enabled;
-        self.processor.vblank_wait = vblank_wait;
+        self.processor.pc = self.load_address;
+        self.processor.rng = Rng::new(seed);
+        self.processor.quirks = quirks;
+        self.processor.set_decode_cache(decode_cache);
+        self.history.clear();
+        self.idle.reset();
+        self.replay.stop();
+        self.key_queue.clear();
     }
 
     /// The `reset_and_load` method is a convenience method that resets the
@@ -120,8 +720,112 @@ impl Chip8 {
     /// # Arguments
     ///
     /// * `data` - A [`Vec<u8>`] representing the ROM data to load into the memory.
-    pub fn reset_and_load(&mut self, data: Vec<u8>) {
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::RomTooLarge`] if the ROM does not fit into memory.
+    /// The system is left untouched in that case.
+    pub fn reset_and_load(&mut self, data: Vec<u8>) -> Result<(), Chip8Error> {
+        let mut memory = memory::Memory::new();
+        if self.bus.megachip.is_some() {
+            memory.grow_for_rom(data.len() + self.load_address - memory::PROGRAM_START);
+        }
+        memory.load_rom_at(data.clone(), self.load_address)?;
+        self.sync_storage();
+        self.storage.restore(&mut memory);
         self.reset();
-        self.load_rom_data(data);
+        self.bus.memory = memory;
+        self.rom = data;
+        Ok(())
+    }
+
+    /// Resets the Chip8 system through [`Chip8::reset`] and loads the most
+    /// recently loaded ROM again, restarting the program. The random number
+    /// generator starts over with the same seed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::RomTooLarge`] if the ROM no longer fits into
+    /// memory, e.g. because the load address moved. The system is left
+    /// untouched in that case.
+    pub fn reset_keep_rom(&mut self) -> Result<(), Chip8Error> {
+        self.reset_and_load(self.rom.clone())
+    }
+
+    /// Power-cycles the Chip8 system: like [`Chip8::reset_keep_rom`], but the
+    /// random number generator is reseeded with the given seed, so the
+    /// program does not replay the random numbers of the previous run.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::RomTooLarge`] if the ROM no longer fits into
+    /// memory. The system is left untouched in that case.
+    pub fn power_cycle(&mut self, seed: u64) -> Result<(), Chip8Error> {
+        let rng = self.processor.rng;
+        self.set_seed(seed);
+        let result = self.reset_keep_rom();
+        if result.is_err() {
+            self.processor.rng = rng;
+        }
+        result
+    }
+
+    /// Resets the Chip8 system, loads the given ROM data and starts recording
+    /// its input through [`Chip8::replay`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::RomTooLarge`] if the ROM does not fit into memory.
+    pub fn record(&mut self, data: Vec<u8>) -> Result<(), Chip8Error> {
+        self.reset_and_load(data)?;
+        self.replay.record(self.processor.rng.seed());
+        Ok(())
+    }
+
+    /// Resets the Chip8 system with the seed of the given [`replay::Movie`],
+    /// loads the given ROM data and starts playing back the movie's input.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::RomTooLarge`] if the ROM does not fit into memory.
+    pub fn play(&mut self, data: Vec<u8>, movie: replay::Movie) -> Result<(), Chip8Error> {
+        let seed = self.processor.rng.seed();
+        self.set_seed(movie.seed);
+        if let Err(err) = self.reset_and_load(data) {
+            self.set_seed(seed);
+            return Err(err);
+        }
+        self.replay.play(movie);
+        Ok(())
+    }
+
+    /// Resets the Chip8 system and loads the ROM file at the given path. Since
+    /// the file is read from disk every time, calling this again with the same
+    /// path reloads the ROM after it was rebuilt by an external assembler.
+    /// Files with the `.8o` extension are Octo sources and are assembled with
+    /// [`octo::assemble`] first.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the ROM file to load.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, or an error of kind
+    /// [`io::ErrorKind::InvalidData`] if an Octo source fails to assemble or
+    /// the ROM does not fit into memory. The system is left untouched in all
+    /// cases.
+    #[cfg(feature = "std")]
+    pub fn load_rom_file(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let data = if path.extension().is_some_and(|ext| ext == "8o") {
+            let source = fs::read_to_string(path)?;
+            octo::assemble(&source)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?
+        } else {
+            fs::read(path)?
+        };
+        self.reset_and_load(data)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
     }
 }
@@ -0,0 +1,382 @@
+//! This module provides the logger of the frontends, which filters log
+//! records by module, keeps the most recent ones for an in-app log console
+//! and writes them to stderr or a log file.
+//!
+//! The emulator logs through the `log` facade, under the paths of its
+//! modules: the processor traces every executed instruction, the runner
+//! reports loaded ROMs, pauses, save states and the events that stop it, and
+//! Web Audio reports its context and underruns. The frontends log under
+//! their own names, e.g. `chip8_pixels`.
+//!
+//! A [`LogFilter`] is written like `warn,runner=debug,processor=trace`: a
+//! default level followed by the levels of single modules, which are named
+//! without the `chip8::` prefix and include their submodules. [`init`]
+//! installs the global [`Logger`], whose [`LogConsole`] a frontend shows
+//! with level filters of its own.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::{self, Write},
+    sync::{Mutex, MutexGuard, OnceLock, PoisonError, RwLock},
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+/// The default amount of records kept by a [`LogConsole`].
+pub const DEFAULT_CONSOLE_CAPACITY: usize = 1000;
+
+/// The prefix of the targets of the emulator's own modules.
+const CRATE_PREFIX: &str = "chip8::";
+
+/// The levels of log records, by module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter {
+    /// The level of modules without a level of their own.
+    default: LevelFilter,
+    /// The modules with a level of their own.
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self::new(LevelFilter::Warn)
+    }
+}
+
+impl LogFilter {
+    /// Creates a [`LogFilter`] letting records up to the given level through
+    /// for every module.
+    #[must_use]
+    pub const fn new(default: LevelFilter) -> Self {
+        Self {
+            default,
+            modules: Vec::new(),
+        }
+    }
+
+    /// Parses a filter like `warn,runner=debug,processor=trace`. The default
+    /// level may be left out, in which case it is `warn`. Returns [`None`] if
+    /// a level is unknown.
+    #[must_use]
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut filter = Self::default();
+        for directive in text.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((module, level)) => {
+                    filter.set_module(module.trim(), level.trim().parse().ok()?);
+                }
+                None => filter.default = directive.parse().ok()?,
+            }
+        }
+        Some(filter)
+    }
+
+    /// Sets the level of the given module and its submodules.
+    pub fn set_module(&mut self, module: &str, level: LevelFilter) {
+        let module = module.strip_prefix(CRATE_PREFIX).unwrap_or(module);
+        match self.modules.iter_mut().find(|(name, _)| name == module) {
+            Some((_, existing)) => *existing = level,
+            None => self.modules.push((module.to_string(), level)),
+        }
+    }
+
+    /// Returns the level of records with the given target, which is the
+    /// level of the most specific module containing it.
+    #[must_use]
+    pub fn level(&self, target: &str) -> LevelFilter {
+        let target = target.strip_prefix(CRATE_PREFIX).unwrap_or(target);
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                target
+                    .strip_prefix(module.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    /// Returns the most verbose level of any module.
+    #[must_use]
+    pub fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+impl fmt::Display for LogFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.default.as_str().to_lowercase())?;
+        for (module, level) in &self.modules {
+            write!(f, ",{module}={}", level.as_str().to_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+/// A log record kept by a [`LogConsole`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    /// The level of the record.
+    pub level: Level,
+    /// The module the record comes from.
+    pub target: String,
+    /// The formatted message.
+    pub message: String,
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let target = self
+            .target
+            .strip_prefix(CRATE_PREFIX)
+            .unwrap_or(&self.target);
+        write!(f, "{:<5} {target}: {}", self.level, self.message)
+    }
+}
+
+/// A ring buffer of the most recent log records, which a log console shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogConsole {
+    /// The records, with the oldest one at the front.
+    entries: VecDeque<LogEntry>,
+    /// The maximum amount of records to keep.
+    capacity: usize,
+}
+
+impl Default for LogConsole {
+    fn default() -> Self {
+        Self::new(DEFAULT_CONSOLE_CAPACITY)
+    }
+}
+
+impl LogConsole {
+    /// Creates an empty [`LogConsole`] keeping at most `capacity` records.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Appends a record, discarding the oldest one once the console is full.
+    pub fn push(&mut self, entry: LogEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// Returns the records at or above the given level, from oldest to
+    /// newest.
+    pub fn entries(&self, level: LevelFilter) -> impl Iterator<Item = &LogEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.level <= level)
+    }
+
+    /// Returns the amount of records kept.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether no records are kept.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Discards all records.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// A logger filtering records with a [`LogFilter`], keeping them in a
+/// [`LogConsole`] and writing them to an output.
+pub struct Logger {
+    /// The levels of the records to keep.
+    filter: RwLock<LogFilter>,
+    /// The most recent records.
+    console: Mutex<LogConsole>,
+    /// Where records are written to, if anywhere.
+    output: Mutex<Option<Box<dyn Write + Send>>>,
+}
+
+impl fmt::Debug for Logger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Logger")
+            .field("filter", &self.filter)
+            .field("console", &self.console)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Self {
+            filter: RwLock::new(LogFilter::default()),
+            console: Mutex::new(LogConsole::default()),
+            output: Mutex::new(Some(Box::new(io::stderr()))),
+        }
+    }
+}
+
+impl Logger {
+    /// Returns the levels of the records the logger keeps.
+    #[must_use]
+    pub fn filter(&self) -> LogFilter {
+        self.filter
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Changes the levels of the records the logger keeps.
+    pub fn set_filter(&self, filter: LogFilter) {
+        log::set_max_level(filter.max_level());
+        *self.filter.write().unwrap_or_else(PoisonError::into_inner) = filter;
+    }
+
+    /// Writes records to the given output, e.g. a log file, or to nowhere
+    /// if [`None`] is given. Records are written to stderr by default.
+    pub fn set_output(&self, output: Option<Box<dyn Write + Send>>) {
+        *self.output.lock().unwrap_or_else(PoisonError::into_inner) = output;
+    }
+
+    /// Locks and returns the log console.
+    pub fn console(&self) -> MutexGuard<'_, LogConsole> {
+        self.console.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level()
+            <= self
+                .filter
+                .read()
+                .map_or(LevelFilter::Off, |filter| filter.level(metadata.target()))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let entry = LogEntry {
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        };
+        if let Some(output) = self
+            .output
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_mut()
+        {
+            // there is nowhere to report a failing log output
+            let _ = writeln!(output, "{entry}");
+        }
+        self.console().push(entry);
+    }
+
+    fn flush(&self) {
+        if let Some(output) = self
+            .output
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_mut()
+        {
+            let _ = output.flush();
+        }
+    }
+}
+
+/// The global logger, created on first use.
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Returns the global [`Logger`]. It only receives records once it was
+/// installed with [`init`].
+pub fn logger() -> &'static Logger {
+    LOGGER.get_or_init(Logger::default)
+}
+
+/// Installs the global [`Logger`] with the given filter as the logger of the
+/// `log` facade, and returns it.
+///
+/// # Errors
+///
+/// Returns an error if a logger was already installed, in which case the
+/// filter is left unchanged.
+pub fn init(filter: LogFilter) -> Result<&'static Logger, SetLoggerError> {
+    let logger = logger();
+    log::set_logger(logger)?;
+    logger.set_filter(filter);
+    Ok(logger)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logger() {
+        let filter = LogFilter::parse("info, runner=debug, chip8::processor=trace").unwrap();
+        assert_eq!(filter.to_string(), "info,runner=debug,processor=trace");
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+        assert_eq!(filter.level("chip8::runner"), LevelFilter::Debug);
+        assert_eq!(filter.level("chip8::processor::tests"), LevelFilter::Trace);
+        assert_eq!(filter.level("chip8::runners"), LevelFilter::Info);
+        assert_eq!(filter.level("chip8_pixels"), LevelFilter::Info);
+        assert_eq!(LogFilter::parse("runner=loud"), None);
+        assert_eq!(LogFilter::parse("").unwrap(), LogFilter::default());
+
+        let logger = Logger::default();
+        logger.set_output(None);
+        logger.set_filter(LogFilter::parse("warn,runner=debug").unwrap());
+        for (level, target) in [
+            (Level::Debug, "chip8::runner"),
+            (Level::Debug, "chip8::processor"),
+            (Level::Error, "chip8::processor"),
+        ] {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target(target)
+                    .args(format_args!("message"))
+                    .build(),
+            );
+        }
+        let console = logger.console().clone();
+        assert_eq!(console.len(), 2);
+        let lines: Vec<_> = console
+            .entries(LevelFilter::Trace)
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(lines, ["DEBUG runner: message", "ERROR processor: message"]);
+        assert_eq!(console.entries(LevelFilter::Warn).count(), 1);
+
+        // The console is a ring buffer
+        let mut console = LogConsole::new(2);
+        for message in ["a", "b", "c"] {
+            console.push(LogEntry {
+                level: Level::Info,
+                target: "app".to_string(),
+                message: message.to_string(),
+            });
+        }
+        let messages: Vec<_> = console
+            .entries(LevelFilter::Info)
+            .map(|entry| entry.message.as_str())
+            .collect();
+        assert_eq!(messages, ["b", "c"]);
+    }
+}
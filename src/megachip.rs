@@ -0,0 +1,405 @@
+//! This module provides the Mega-Chip extensions, which add a 256x192 color
+//! display, palette based sprites with blending and digitized sound to the
+//! Chip8.
+//!
+//! The extensions have to be enabled through [`super::Chip8::set_megachip`].
+//! Programs then switch into Mega-Chip mode with `0011` and back with `0010`.
+//! While the mode is active, sprites are drawn into a back buffer that is shown
+//! by `00E0`, so frontends should render [`MegaChip::frame`] instead of
+//! [`super::graphics::Framebuffer`].
+
+use alloc::{vec, vec::Vec};
+
+/// The width of the Mega-Chip display in pixels.
+pub const WIDTH: usize = 256;
+/// The height of the Mega-Chip display in pixels.
+pub const HEIGHT: usize = 192;
+/// The total number of pixels of the Mega-Chip display.
+pub const PIXEL_COUNT: usize = WIDTH * HEIGHT;
+
+/// The color of a pixel that was never drawn to.
+const BLACK: [u8; 4] = [0, 0, 0, 0xFF];
+
+/// How sprite pixels are combined with the pixels they are drawn over.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlendMode {
+    /// Sprite pixels replace the pixels they are drawn over.
+    #[default]
+    Normal,
+
+    /// Sprite pixels are drawn with 25% opacity.
+    Percent25,
+
+    /// Sprite pixels are drawn with 50% opacity.
+    Percent50,
+
+    /// Sprite pixels are drawn with 75% opacity.
+    Percent75,
+
+    /// Sprite pixels are added to the pixels they are drawn over.
+    Additive,
+
+    /// Sprite pixels are multiplied with the pixels they are drawn over.
+    Multiply,
+}
+
+impl BlendMode {
+    /// Returns the blend mode selected by `080n`, if `n` is valid.
+    #[must_use]
+    pub const fn from_index(n: u8) -> Option<Self> {
+        match n {
+            0 => Some(Self::Normal),
+            1 => Some(Self::Percent25),
+            2 => Some(Self::Percent50),
+            3 => Some(Self::Percent75),
+            4 => Some(Self::Additive),
+            5 => Some(Self::Multiply),
+            _ => None,
+        }
+    }
+
+    /// Combines the sprite color `src` with the color `dst` it is drawn over.
+    #[allow(clippy::cast_possible_truncation)]
+    fn blend(self, src: [u8; 4], dst: [u8; 4]) -> [u8; 4] {
+        let mix = |weight: u16| {
+            let mut color = BLACK;
+            for c in 0..3 {
+                let mixed = u16::from(src[c]) * weight + u16::from(dst[c]) * (4 - weight);
+                color[c] = (mixed / 4) as u8;
+            }
+            color
+        };
+        match self {
+            Self::Normal => [src[0], src[1], src[2], 0xFF],
+            Self::Percent25 => mix(1),
+            Self::Percent50 => mix(2),
+            Self::Percent75 => mix(3),
+            Self::Additive => {
+                let mut color = BLACK;
+                for c in 0..3 {
+                    color[c] = src[c].saturating_add(dst[c]);
+                }
+                color
+            }
+            Self::Multiply => {
+                let mut color = BLACK;
+                for c in 0..3 {
+                    color[c] = (u16::from(src[c]) * u16::from(dst[c]) / 0xFF) as u8;
+                }
+                color
+            }
+        }
+    }
+}
+
+/// A digitized sound started by `060n`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sound {
+    /// The amount of samples played per second.
+    pub sample_rate: u16,
+
+    /// The unsigned 8-bit samples.
+    pub samples: Vec<u8>,
+
+    /// Whether the sound restarts after the last sample.
+    pub looping: bool,
+}
+
+impl Sound {
+    /// The size of the header preceding the samples in memory: a 16-bit sample
+    /// rate, a 24-bit sample count and a reserved byte.
+    pub const HEADER_SIZE: usize = 6;
+
+    /// Parses the header of a digitized sound, returning the sample rate and
+    /// the amount of samples.
+    #[must_use]
+    pub fn parse_header(header: &[u8; Self::HEADER_SIZE]) -> (u16, usize) {
+        let sample_rate = u16::from_be_bytes([header[0], header[1]]);
+        let len =
+            usize::from(header[2]) << 16 | usize::from(header[3]) << 8 | usize::from(header[4]);
+        (sample_rate, len)
+    }
+}
+
+/// The state of the Mega-Chip extensions.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MegaChip {
+    /// Whether Mega-Chip mode is active.
+    active: bool,
+    /// The palette used by sprites, indexed by the bytes of the sprite data.
+    /// Index `0` is transparent.
+    palette: Vec<[u8; 4]>,
+    /// The width of sprites drawn by `Dxyn`.
+    sprite_width: usize,
+    /// The height of sprites drawn by `Dxyn`.
+    sprite_height: usize,
+    /// The opacity of the whole display.
+    alpha: u8,
+    /// How sprites are combined with the pixels they are drawn over.
+    blend_mode: BlendMode,
+    /// The palette index that raises a collision when drawn over.
+    collision_color: u8,
+    /// The palette index of every pixel in the back buffer.
+    indices: Vec<u8>,
+    /// The buffer sprites are drawn into.
+    back: Vec<[u8; 4]>,
+    /// The buffer shown on the display.
+    front: Vec<[u8; 4]>,
+    /// The digitized sound being played, if any.
+    sound: Option<Sound>,
+}
+
+impl Default for MegaChip {
+    fn default() -> Self {
+        Self {
+            active: false,
+            palette: vec![BLACK; 256],
+            sprite_width: 8,
+            sprite_height: 1,
+            alpha: 0xFF,
+            blend_mode: BlendMode::Normal,
+            collision_color: 0,
+            indices: vec![0; PIXEL_COUNT],
+            back: vec![BLACK; PIXEL_COUNT],
+            front: vec![BLACK; PIXEL_COUNT],
+            sound: None,
+        }
+    }
+}
+
+impl MegaChip {
+    /// Creates a new [`MegaChip`] with Mega-Chip mode inactive.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether Mega-Chip mode is active.
+    #[must_use]
+    pub const fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Activates or deactivates Mega-Chip mode.
+    pub const fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    /// Returns the color of the given palette index as RGBA.
+    #[must_use]
+    pub fn color(&self, index: u8) -> [u8; 4] {
+        self.palette[usize::from(index)]
+    }
+
+    /// Loads palette colors, stored as ARGB, into the palette indices
+    /// starting at `1`.
+    pub fn load_palette(&mut self, colors: &[u8]) {
+        for (index, argb) in colors.chunks_exact(4).take(255).enumerate() {
+            self.palette[index + 1] = [argb[1], argb[2], argb[3], argb[0]];
+        }
+    }
+
+    /// Returns the width and height of sprites drawn by `Dxyn`.
+    #[must_use]
+    pub const fn sprite_size(&self) -> (usize, usize) {
+        (self.sprite_width, self.sprite_height)
+    }
+
+    /// Sets the width of sprites drawn by `Dxyn`. A width of `0` means `256`.
+    pub fn set_sprite_width(&mut self, width: u8) {
+        self.sprite_width = if width == 0 { 256 } else { usize::from(width) };
+    }
+
+    /// Sets the height of sprites drawn by `Dxyn`. A height of `0` means
+    /// `256`.
+    pub fn set_sprite_height(&mut self, height: u8) {
+        self.sprite_height = if height == 0 {
+            256
+        } else {
+            usize::from(height)
+        };
+    }
+
+    /// Returns the opacity of the whole display.
+    #[must_use]
+    pub const fn alpha(&self) -> u8 {
+        self.alpha
+    }
+
+    /// Sets the opacity of the whole display.
+    pub const fn set_alpha(&mut self, alpha: u8) {
+        self.alpha = alpha;
+    }
+
+    /// Returns how sprites are combined with the pixels they are drawn over.
+    #[must_use]
+    pub const fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    /// Sets how sprites are combined with the pixels they are drawn over.
+    pub const fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+
+    /// Returns the palette index that raises a collision when drawn over.
+    #[must_use]
+    pub const fn collision_color(&self) -> u8 {
+        self.collision_color
+    }
+
+    /// Sets the palette index that raises a collision when drawn over.
+    pub const fn set_collision_color(&mut self, index: u8) {
+        self.collision_color = index;
+    }
+
+    /// Draws a sprite of palette indices at the given position, using the
+    /// current sprite size and blend mode. Pixels with index `0` are
+    /// transparent, and pixels outside of the display are clipped.
+    ///
+    /// # Returns
+    ///
+    /// [`true`] if a pixel was drawn over a pixel of the collision color. Since
+    /// index `0` is transparent, it never raises a collision.
+    pub fn draw_sprite(&mut self, x: usize, y: usize, data: &[u8]) -> bool {
+        let mut collision = false;
+        for (row, line) in data.chunks(self.sprite_width).enumerate() {
+            let py = y + row;
+            if py >= HEIGHT {
+                break;
+            }
+            for (column, &index) in line.iter().enumerate() {
+                let px = x + column;
+                if index == 0 || px >= WIDTH {
+                    continue;
+                }
+                let pos = py * WIDTH + px;
+                collision |= self.indices[pos] != 0 && self.indices[pos] == self.collision_color;
+                self.indices[pos] = index;
+                self.back[pos] = self.blend_mode.blend(self.color(index), self.back[pos]);
+            }
+        }
+        collision
+    }
+
+    /// Shows the back buffer on the display and clears the back buffer for the
+    /// next frame.
+    pub fn present(&mut self) {
+        self.front.copy_from_slice(&self.back);
+        self.back.fill(BLACK);
+        self.indices.fill(0);
+    }
+
+    /// Scrolls the back buffer up by `n` lines.
+    pub fn scroll_up(&mut self, n: usize) {
+        let n = n.min(HEIGHT);
+        self.back.copy_within(n * WIDTH.., 0);
+        self.back[(HEIGHT - n) * WIDTH..].fill(BLACK);
+        self.indices.copy_within(n * WIDTH.., 0);
+        self.indices[(HEIGHT - n) * WIDTH..].fill(0);
+    }
+
+    /// Returns the frame shown on the display as RGBA pixels, row by row.
+    #[must_use]
+    pub fn frame(&self) -> &[[u8; 4]] {
+        &self.front
+    }
+
+    /// Returns the frame shown on the display as a flat array of RGB values.
+    #[must_use]
+    pub fn as_rgb8(&self) -> Vec<u8> {
+        self.front
+            .iter()
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+            .collect()
+    }
+
+    /// Returns the digitized sound being played, if any. Frontends are
+    /// responsible for the actual playback.
+    #[must_use]
+    pub const fn sound(&self) -> Option<&Sound> {
+        self.sound.as_ref()
+    }
+
+    /// Starts playing the given digitized sound, replacing the current one.
+    pub fn play_sound(&mut self, sound: Sound) {
+        self.sound = Some(sound);
+    }
+
+    /// Stops the digitized sound.
+    pub fn stop_sound(&mut self) {
+        self.sound = None;
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_sprite() {
+        let mut megachip = MegaChip::new();
+        megachip.load_palette(&[0xFF, 0xFF, 0x00, 0x00, 0xFF, 0x00, 0x00, 0xFF]);
+        megachip.set_sprite_width(2);
+        megachip.set_sprite_height(2);
+        megachip.set_collision_color(1);
+
+        // Index 0 is transparent
+        assert!(!megachip.draw_sprite(0, 0, &[1, 0, 0, 2]));
+        megachip.present();
+        assert_eq!(megachip.frame()[0], [0xFF, 0x00, 0x00, 0xFF]);
+        assert_eq!(megachip.frame()[1], BLACK);
+        assert_eq!(megachip.frame()[WIDTH + 1], [0x00, 0x00, 0xFF, 0xFF]);
+
+        // Drawing over the collision color raises a collision
+        megachip.draw_sprite(0, 0, &[1, 0, 0, 0]);
+        megachip.set_blend_mode(BlendMode::Percent50);
+        assert!(megachip.draw_sprite(0, 0, &[2, 0, 0, 0]));
+        megachip.present();
+        assert_eq!(megachip.frame()[0], [0x7F, 0x00, 0x7F, 0xFF]);
+    }
+
+    #[test]
+    fn test_scroll_up() {
+        let mut megachip = MegaChip::new();
+        megachip.load_palette(&[0xFF, 0xFF, 0xFF, 0xFF]);
+        megachip.set_sprite_width(1);
+        megachip.draw_sprite(0, 5, &[1]);
+        megachip.scroll_up(5);
+        megachip.present();
+        assert_eq!(megachip.frame()[0], [0xFF; 4]);
+        assert_eq!(megachip.frame()[5 * WIDTH], BLACK);
+    }
+
+    #[test]
+    fn test_megachip_program() {
+        let mut rom = vec![
+            0x00, 0x11, // enable Mega-Chip mode
+            0x01, 0x00, 0x03, 0x00, // I = 0x300
+            0x02, 0x01, // load 1 palette color
+            0x03, 0x01, // sprite width 1
+            0x04, 0x01, // sprite height 1
+            0x01, 0x00, 0x03, 0x04, // I = 0x304
+            0xD0, 0x00, // draw at (V0, V0)
+            0x00, 0xE0, // update the screen
+        ];
+        rom.resize(0x100, 0);
+        rom.extend_from_slice(&[0xFF, 0x12, 0x34, 0x56, 0x01]);
+        // Mega-Chip ROMs may exceed the regular memory
+        rom.resize(0x2000, 0);
+
+        let mut chip8 = crate::Chip8::new();
+        assert!(chip8.load_rom_data(rom.clone()).is_err());
+        chip8.set_megachip(true);
+        chip8.reset_and_load(rom).unwrap();
+        for _ in 0..8 {
+            chip8.step().unwrap();
+        }
+        let megachip = chip8.bus.megachip.as_ref().unwrap();
+        assert!(megachip.is_active());
+        assert_eq!(megachip.frame()[0], [0x12, 0x34, 0x56, 0xFF]);
+    }
+}
@@ -0,0 +1,232 @@
+//! This module provides a bounded log of every memory read and write, e.g. to
+//! find out which instruction corrupts a sprite table.
+//!
+//! While enabled, the [`MemoryLog`] records the address, value and program
+//! counter of every byte an instruction reads or writes through the index
+//! register. A filter limits recording to an address range, so the log is
+//! not flooded by unrelated accesses. Once the capacity is reached the oldest
+//! entries are discarded.
+
+use alloc::collections::VecDeque;
+use core::{fmt, ops::Range};
+
+use crate::{diff::Accesses, memory::Memory};
+
+/// The default amount of entries kept by a [`MemoryLog`].
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// Whether a memory access read or wrote a byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    /// The byte was read.
+    Read,
+    /// The byte was written.
+    Write,
+}
+
+/// A single logged memory access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    /// The address of the instruction that accessed memory.
+    pub pc: usize,
+    /// The accessed address.
+    pub address: usize,
+    /// The value read, or the value written.
+    pub value: u8,
+    /// Whether the byte was read or written.
+    pub kind: AccessKind,
+}
+
+impl fmt::Display for MemoryAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            AccessKind::Read => "read ",
+            AccessKind::Write => "write",
+        };
+        write!(
+            f,
+            "{:#06X}: {kind} {:#06X} = {:#04X}",
+            self.pc, self.address, self.value
+        )
+    }
+}
+
+/// A bounded log of memory accesses.
+#[derive(Debug)]
+pub struct MemoryLog {
+    /// Whether accesses are recorded.
+    enabled: bool,
+    /// The maximum amount of entries to keep.
+    capacity: usize,
+    /// The addresses to record accesses to, or [`None`] for all of them.
+    filter: Option<Range<usize>>,
+    /// The recorded entries, with the oldest one at the front.
+    entries: VecDeque<MemoryAccess>,
+}
+
+impl Default for MemoryLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl MemoryLog {
+    /// Creates a new, disabled [`MemoryLog`] that keeps at most `capacity`
+    /// entries.
+    #[must_use]
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            enabled: false,
+            capacity,
+            filter: None,
+            entries: VecDeque::new(),
+        }
+    }
+
+    /// Returns whether accesses are recorded.
+    #[must_use]
+    pub const fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Starts or stops recording accesses. Recorded entries are kept.
+    pub const fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Returns the maximum amount of entries that are kept.
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Sets the maximum amount of entries that are kept, discarding the
+    /// oldest ones if there are more.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        let excess = self.entries.len().saturating_sub(capacity);
+        self.entries.drain(..excess);
+    }
+
+    /// Returns the addresses accesses are recorded to, if limited.
+    #[must_use]
+    pub const fn filter(&self) -> Option<&Range<usize>> {
+        self.filter.as_ref()
+    }
+
+    /// Only records accesses to the given addresses from now on, or to all
+    /// addresses if [`None`].
+    pub const fn set_filter(&mut self, filter: Option<Range<usize>>) {
+        self.filter = filter;
+    }
+
+    /// Returns the amount of recorded entries.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether no access was recorded.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Discards all recorded entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Returns an iterator over all recorded entries, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &MemoryAccess> {
+        self.entries.iter()
+    }
+
+    /// Returns an iterator over the recorded accesses to the given addresses,
+    /// oldest first.
+    pub fn in_range(&self, range: Range<usize>) -> impl Iterator<Item = &MemoryAccess> {
+        self.entries
+            .iter()
+            .filter(move |entry| range.contains(&entry.address))
+    }
+
+    /// Records the accesses of the given opcode, executed at `pc` with the
+    /// index register set to `i`. Values are taken from `memory` after the
+    /// instruction was executed, and addresses wrap around at its size.
+    pub fn record(&mut self, pc: usize, opcode: usize, i: usize, memory: &Memory) {
+        if self.capacity == 0 {
+            return;
+        }
+        let accesses = Accesses::of(opcode, i);
+        let reads = accesses
+            .memory_reads
+            .map(|address| (address, AccessKind::Read));
+        let writes = accesses
+            .memory_writes
+            .map(|address| (address, AccessKind::Write));
+        for (address, kind) in reads.chain(writes) {
+            let address = address % memory.len();
+            if self
+                .filter
+                .as_ref()
+                .is_some_and(|filter| !filter.contains(&address))
+            {
+                continue;
+            }
+            if self.entries.len() == self.capacity {
+                self.entries.pop_front();
+            }
+            self.entries.push_back(MemoryAccess {
+                pc,
+                address,
+                value: memory[address],
+                kind,
+            });
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    #[test]
+    fn test_memory_log() {
+        let mut chip8 = Chip8::new();
+        // 6007: V0 = 7, then with I = 0x300 each: F055: store V0,
+        // F065: load V0, F033: BCD of V0
+        let rom = vec![
+            0x60, 0x07, 0xA3, 0x00, 0xF0, 0x55, 0xA3, 0x00, 0xF0, 0x65, 0xA3, 0x00, 0xF0, 0x33,
+        ];
+        chip8.load_rom_data(rom).unwrap();
+        chip8.memory_log.set_enabled(true);
+        chip8.memory_log.set_filter(Some(0x300..0x302));
+        for _ in 0..7 {
+            chip8.step().unwrap();
+        }
+
+        let entries: Vec<_> = chip8.memory_log.iter().copied().collect();
+        let access = |pc, value, kind| MemoryAccess {
+            pc,
+            address: 0x300,
+            value,
+            kind,
+        };
+        assert_eq!(
+            entries[..3],
+            [
+                access(0x204, 7, AccessKind::Write),
+                access(0x208, 7, AccessKind::Read),
+                access(0x20C, 0, AccessKind::Write),
+            ]
+        );
+        // the BCD also wrote 0x301, but not 0x302
+        assert_eq!(entries.len(), 4);
+        assert_eq!(chip8.memory_log.in_range(0x301..0x302).count(), 1);
+        assert_eq!(entries[0].to_string(), "0x0204: write 0x0300 = 0x07");
+
+        chip8.memory_log.set_capacity(1);
+        assert_eq!(chip8.memory_log.len(), 1);
+    }
+}
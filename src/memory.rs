@@ -1,15 +1,39 @@
 //! The `memory` module provides a struct and some associated functions to
-//! represent the memory of a Chip8 system. The memory is represented as an
-//! array of 8-bit unsigned integers ([`u8`]), with a size of 4096 bytes.
+//! represent the memory of a Chip8 system.
+//!
+//! The memory is represented as an array of 8-bit unsigned integers ([`u8`]),
+//! with a size of 4096 bytes. With the Mega-Chip extensions enabled, the memory
+//! grows to fit larger ROMs, up to [`MEGACHIP_MEMORY_SIZE`] bytes.
 
-use std::ops::{Index, IndexMut};
+use alloc::{vec, vec::Vec};
+use core::ops::{Index, IndexMut};
+
+use crate::error::Chip8Error;
 
 /// The total size of the Chip8 memory.
-const MEMORY_SIZE: usize = 4096;
+pub const MEMORY_SIZE: usize = 4096;
+
+/// The largest memory size addressable by Mega-Chip programs, which use 24-bit
+/// addresses.
+pub const MEGACHIP_MEMORY_SIZE: usize = 1 << 24;
 
 /// The size of the interpreter. This is used to determine where the program memory should start.
 const INTERPRETER_SIZE: usize = 512;
 
+/// The address ROMs are loaded at by default, right after the interpreter.
+pub const PROGRAM_START: usize = INTERPRETER_SIZE;
+
+/// The address ROMs for the ETI-660 are loaded at.
+pub const ETI_660_START: usize = 0x600;
+
+/// The address the program of a two-page hires ROM starts at. Such ROMs begin
+/// with a `1260` jump into a patch for the interpreter, which is emulated
+/// instead.
+pub const HIRES_START: usize = 0x2C0;
+
+/// The maximum size of a ROM, which is the memory left after the interpreter.
+pub const MAX_ROM_SIZE: usize = MEMORY_SIZE - INTERPRETER_SIZE;
+
 /// Built-in Chip8 font data. This is stored in the interpreter's memory.
 const FONT: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -31,17 +55,17 @@ const FONT: [u8; 80] = [
 ];
 
 /// The [`Memory`] struct represents the memory of a Chip8 system. It contains
-/// a fixed-size array of [`u8`] values that can be accessed using the [`Index`]
-/// and [`IndexMut`] traits.
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+/// an array of [`u8`] values that can be accessed using the [`Index`] and
+/// [`IndexMut`] traits.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Memory {
-    #[serde(with = "serde_big_array::BigArray")]
-    memory: [u8; MEMORY_SIZE],
+    memory: Vec<u8>,
 }
 
 impl Default for Memory {
     fn default() -> Self {
-        let mut memory = [0; MEMORY_SIZE];
+        let mut memory = vec![0; MEMORY_SIZE];
         memory[..80].clone_from_slice(&FONT);
         Self { memory }
     }
@@ -68,11 +92,186 @@ impl Memory {
         Self::default()
     }
 
+    /// Returns the size of the memory in bytes.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.memory.len()
+    }
+
+    /// Returns whether the memory has a size of zero bytes, which is never
+    /// the case.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.memory.is_empty()
+    }
+
+    /// Returns the size of the largest ROM that fits into the memory.
+    #[must_use]
+    pub const fn max_rom_size(&self) -> usize {
+        self.len() - INTERPRETER_SIZE
+    }
+
+    /// Grows the memory so that a ROM of `rom_size` bytes fits into it, up to
+    /// [`MEGACHIP_MEMORY_SIZE`] bytes. The memory never shrinks.
+    pub fn grow_for_rom(&mut self, rom_size: usize) {
+        let size = (INTERPRETER_SIZE + rom_size).min(MEGACHIP_MEMORY_SIZE);
+        if size > self.len() {
+            self.memory.resize(size, 0);
+        }
+    }
+
     /// Loads the ROM bytes from `data`. If this is smaller than the program
-    /// size (`MEMORY_SIZE - INTERPRETER_SIZE`), then the remaining memory will
-    /// be filled with zeroes.
-    pub fn load_rom(&mut self, mut data: Vec<u8>) {
-        data.resize(MEMORY_SIZE - INTERPRETER_SIZE, 0);
-        self.memory[INTERPRETER_SIZE..=0xFFF].clone_from_slice(&data);
+    /// size ([`Memory::max_rom_size`]), then the remaining memory will be
+    /// filled with zeroes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::RomTooLarge`] if `data` is larger than
+    /// [`Memory::max_rom_size`]. The memory is left untouched in that case.
+    pub fn load_rom(&mut self, data: Vec<u8>) -> Result<(), Chip8Error> {
+        self.load_rom_at(data, PROGRAM_START)
+    }
+
+    /// Loads the ROM bytes from `data` at the given address, filling the
+    /// remaining memory after it with zeroes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::InvalidLoadAddress`] if `addr` lies before
+    /// [`PROGRAM_START`] or outside of memory, and
+    /// [`Chip8Error::RomTooLarge`] if `data` does not fit between `addr` and
+    /// the end of memory. The memory is left untouched in both cases.
+    pub fn load_rom_at(&mut self, mut data: Vec<u8>, addr: usize) -> Result<(), Chip8Error> {
+        if addr < PROGRAM_START || addr >= self.len() {
+            return Err(Chip8Error::InvalidLoadAddress { addr });
+        }
+        let max = self.len() - addr;
+        if data.len() > max {
+            return Err(Chip8Error::RomTooLarge {
+                size: data.len(),
+                max,
+            });
+        }
+        data.resize(max, 0);
+        self.memory[addr..].clone_from_slice(&data);
+        Ok(())
+    }
+
+    /// Returns the `len` bytes starting at the given address.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::MemoryOutOfBounds`] if any of the bytes is
+    /// outside of the address space.
+    pub fn read_slice(&self, addr: usize, len: usize) -> Result<&[u8], Chip8Error> {
+        self.memory
+            .get(addr..addr + len)
+            .ok_or_else(|| Chip8Error::MemoryOutOfBounds {
+                addr: (addr + len).saturating_sub(1),
+            })
+    }
+
+    /// Returns the byte at the given address.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::MemoryOutOfBounds`] if `addr` is outside of the
+    /// address space.
+    pub fn read(&self, addr: usize) -> Result<u8, Chip8Error> {
+        self.memory
+            .get(addr)
+            .copied()
+            .ok_or(Chip8Error::MemoryOutOfBounds { addr })
+    }
+
+    /// Returns the big-endian opcode stored in the two bytes starting at the
+    /// given address.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::MemoryOutOfBounds`] if either byte is outside of
+    /// the address space.
+    pub fn read_opcode(&self, addr: usize) -> Result<u16, Chip8Error> {
+        let bytes = self.read_slice(addr, 2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Writes a byte to the given address.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::MemoryOutOfBounds`] if `addr` is outside of the
+    /// address space.
+    pub fn write(&mut self, addr: usize, value: u8) -> Result<(), Chip8Error> {
+        let byte = self
+            .memory
+            .get_mut(addr)
+            .ok_or(Chip8Error::MemoryOutOfBounds { addr })?;
+        *byte = value;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_rom() {
+        let mut memory = Memory::new();
+        assert_eq!(memory.load_rom(vec![0xAB; MAX_ROM_SIZE]), Ok(()));
+        assert_eq!(memory[0xFFF], 0xAB);
+
+        assert_eq!(
+            memory.load_rom(vec![0; MAX_ROM_SIZE + 1]),
+            Err(Chip8Error::RomTooLarge {
+                size: MAX_ROM_SIZE + 1,
+                max: MAX_ROM_SIZE
+            })
+        );
+
+        assert_eq!(memory.load_rom_at(vec![0xCD; 2], 0x600), Ok(()));
+        assert_eq!(
+            (memory[0x5FF], memory[0x601], memory[0x602]),
+            (0xAB, 0xCD, 0)
+        );
+        assert_eq!(
+            memory.load_rom_at(vec![0xCD], 0x100),
+            Err(Chip8Error::InvalidLoadAddress { addr: 0x100 })
+        );
+    }
+
+    #[test]
+    fn test_grow_for_rom() {
+        let mut memory = Memory::new();
+        memory.grow_for_rom(0x10000);
+        assert_eq!(memory.len(), 0x10200);
+        assert_eq!(memory.load_rom(vec![0xAB; 0x10000]), Ok(()));
+
+        // The memory never shrinks, and never exceeds the Mega-Chip limit
+        memory.grow_for_rom(0x100);
+        assert_eq!(memory.len(), 0x10200);
+        memory.grow_for_rom(usize::MAX / 2);
+        assert_eq!(memory.len(), MEGACHIP_MEMORY_SIZE);
+    }
+
+    #[test]
+    fn test_checked_access() {
+        let mut memory = Memory::new();
+        assert_eq!(memory.write(0xFFF, 7), Ok(()));
+        assert_eq!(memory.read(0xFFF), Ok(7));
+        assert_eq!(
+            memory.read(0x1000),
+            Err(Chip8Error::MemoryOutOfBounds { addr: 0x1000 })
+        );
+        assert_eq!(
+            memory.write(0x1000, 7),
+            Err(Chip8Error::MemoryOutOfBounds { addr: 0x1000 })
+        );
+        assert_eq!(memory.read_slice(0xFFE, 2), Ok(&[0, 7][..]));
+        assert_eq!(
+            memory.read_slice(0xFFE, 3),
+            Err(Chip8Error::MemoryOutOfBounds { addr: 0x1000 })
+        );
     }
 }
@@ -0,0 +1,397 @@
+//! This module provides netplay: two emulators on different machines run the
+//! same ROM in lockstep and share their keypads, so two-player games like
+//! Pong can be played across the network.
+//!
+//! Both peers start from the same seed and execute a fixed amount of
+//! instructions per frame, with the timers ticked once per frame instead of
+//! following the wall clock. The keypad state of every frame is the
+//! combination of the keys held by both players, and each peer sends its own
+//! keys ahead of time, tagged with the frame they apply to. A frame only runs
+//! once the keys of the other player arrived, so both emulators stay in sync.
+//! The input delay, in frames, hides the network latency.
+//!
+//! [`Lockstep`] implements the synchronization independent of the transport.
+//! Its [`Message`]s are single lines of text, which [`TcpPeer`] sends over a
+//! TCP connection. A browser frontend can forward the same lines over a
+//! WebSocket.
+
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    fmt,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+};
+
+use crate::{error::Chip8Error, keymap::KEY_COUNT, Chip8};
+
+/// The version of the netplay protocol. Peers with different versions refuse
+/// to play together.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The default input delay in frames.
+pub const DEFAULT_INPUT_DELAY: u64 = 2;
+
+/// A message exchanged between two netplay peers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// Sent by the host when the connection is established.
+    Hello {
+        /// The [`PROTOCOL_VERSION`] of the host.
+        version: u32,
+        /// The seed both peers start the ROM with.
+        seed: u64,
+        /// The [`crate::roms::hash`] of the ROM to play.
+        rom_hash: String,
+    },
+
+    /// The keys a player holds during a frame.
+    Input {
+        /// The frame the keys apply to.
+        frame: u64,
+        /// The held keys, with bit `n` set if key `n` is held.
+        keys: u16,
+    },
+}
+
+impl Message {
+    /// Parses a message from a line of text, or returns [`None`] if the line
+    /// is not a valid message.
+    #[must_use]
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        let message = match words.next()? {
+            "HELLO" => Self::Hello {
+                version: words.next()?.parse().ok()?,
+                seed: words.next()?.parse().ok()?,
+                rom_hash: words.next()?.to_string(),
+            },
+            "INPUT" => Self::Input {
+                frame: words.next()?.parse().ok()?,
+                keys: u16::from_str_radix(words.next()?, 16).ok()?,
+            },
+            _ => return None,
+        };
+        words.next().is_none().then_some(message)
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Hello {
+                version,
+                seed,
+                rom_hash,
+            } => write!(f, "HELLO {version} {seed} {rom_hash}"),
+            Self::Input { frame, keys } => write!(f, "INPUT {frame} {keys:04X}"),
+        }
+    }
+}
+
+/// Keeps a [`Chip8`] in lockstep with a remote peer.
+#[derive(Debug)]
+pub struct Lockstep {
+    /// The next frame to execute.
+    frame: u64,
+    /// The amount of frames between a key press and the frame it applies to.
+    delay: u64,
+    /// The amount of instructions executed per frame.
+    instructions_per_frame: u32,
+    /// The keys currently held by the local player.
+    held: u16,
+    /// The keys of the local player, by frame.
+    local: BTreeMap<u64, u16>,
+    /// The keys of the remote player, by frame.
+    remote: BTreeMap<u64, u16>,
+    /// The messages waiting to be sent to the remote peer.
+    outgoing: Vec<Message>,
+}
+
+impl Lockstep {
+    /// Creates a new [`Lockstep`] executing the given amount of instructions
+    /// per frame, with keys applying `delay` frames after they were pressed.
+    /// Both peers must use the same settings.
+    #[must_use]
+    pub fn new(instructions_per_frame: u32, delay: u64) -> Self {
+        // nobody holds a key during the first frames
+        let idle: BTreeMap<u64, u16> = (0..delay).map(|frame| (frame, 0)).collect();
+        Self {
+            frame: 0,
+            delay,
+            instructions_per_frame,
+            held: 0,
+            local: idle.clone(),
+            remote: idle,
+            outgoing: Vec::new(),
+        }
+    }
+
+    /// Returns the next frame to execute.
+    #[must_use]
+    pub const fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Handles a key of the local player changing state. The change applies
+    /// to the first frame that was not sent to the remote peer yet. Invalid
+    /// key codes are ignored.
+    pub fn update_key_state(&mut self, key_code: u8, pressed: bool) {
+        let Some(bit) = 1u16.checked_shl(u32::from(key_code)) else {
+            return;
+        };
+        if pressed {
+            self.held |= bit;
+        } else {
+            self.held &= !bit;
+        }
+    }
+
+    /// Handles a message received from the remote peer. Returns whether the
+    /// message was expected during play.
+    pub fn receive(&mut self, message: &Message) -> bool {
+        match *message {
+            Message::Input { frame, keys } if frame >= self.frame => {
+                self.remote.insert(frame, keys);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the messages that should be sent to the remote peer, and
+    /// forgets them.
+    pub fn take_outgoing(&mut self) -> Vec<Message> {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    /// Executes the next frame on the given [`Chip8`], if the keys of the
+    /// remote player for it arrived. The timers of the [`Chip8`] are stopped
+    /// and ticked once per frame instead, so both peers see the same timer
+    /// values.
+    ///
+    /// # Returns
+    ///
+    /// Whether the frame was executed, or [`false`] if the keys of the remote
+    /// player are still missing.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`Chip8Error`] if an instruction cannot be executed.
+    pub fn step_frame(&mut self, chip8: &mut Chip8) -> Result<bool, Chip8Error> {
+        let scheduled = self.frame + self.delay;
+        if let Entry::Vacant(entry) = self.local.entry(scheduled) {
+            entry.insert(self.held);
+            self.outgoing.push(Message::Input {
+                frame: scheduled,
+                keys: self.held,
+            });
+        }
+        let Some(remote) = self.remote.get(&self.frame).copied() else {
+            return Ok(false);
+        };
+        let keys = self.local[&self.frame] | remote;
+        #[allow(clippy::cast_possible_truncation)] // there are 16 keys
+        for key_code in 0..KEY_COUNT as u8 {
+            let pressed = keys & (1 << key_code) != 0;
+            if chip8.bus.input.is_key_pressed(key_code) != pressed {
+                chip8.update_key_state(key_code, pressed);
+            }
+        }
+
+        for _ in 0..self.instructions_per_frame {
+            chip8.step()?;
+        }
+        chip8.tick_timers();
+
+        self.local.remove(&self.frame);
+        self.remote.remove(&self.frame);
+        self.frame += 1;
+        Ok(true)
+    }
+}
+
+/// A netplay connection to a remote peer over TCP.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug)]
+pub struct TcpPeer {
+    /// The buffered read half of the connection.
+    reader: BufReader<TcpStream>,
+    /// The write half of the connection.
+    writer: TcpStream,
+    /// The bytes of a partially received line.
+    line: Vec<u8>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl TcpPeer {
+    /// Waits for a peer to connect to the given address, and sends it the
+    /// seed and hash of the ROM to play.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the address cannot be bound or the connection
+    /// fails.
+    pub fn host(addr: impl ToSocketAddrs, seed: u64, rom_hash: &str) -> io::Result<Self> {
+        let (stream, _) = TcpListener::bind(addr)?.accept()?;
+        let mut peer = Self::new(stream)?;
+        peer.send(&Message::Hello {
+            version: PROTOCOL_VERSION,
+            seed,
+            rom_hash: rom_hash.to_string(),
+        })?;
+        Ok(peer)
+    }
+
+    /// Connects to a host at the given address and waits for its greeting.
+    ///
+    /// # Returns
+    ///
+    /// The connection and the seed to start the ROM with.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection fails, or an error of kind
+    /// [`io::ErrorKind::InvalidData`] if the host speaks a different protocol
+    /// version or plays a different ROM.
+    pub fn connect(addr: impl ToSocketAddrs, rom_hash: &str) -> io::Result<(Self, u64)> {
+        let mut peer = Self::new(TcpStream::connect(addr)?)?;
+        let mut line = String::new();
+        peer.reader.read_line(&mut line)?;
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+        match Message::parse(&line) {
+            Some(Message::Hello {
+                version,
+                seed,
+                rom_hash: host_hash,
+            }) => {
+                if version != PROTOCOL_VERSION {
+                    return Err(invalid("host uses a different protocol version"));
+                }
+                if host_hash != rom_hash {
+                    return Err(invalid("host plays a different ROM"));
+                }
+                Ok((peer, seed))
+            }
+            _ => Err(invalid("host sent no greeting")),
+        }
+    }
+
+    /// Wraps an established connection.
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            line: Vec::new(),
+        })
+    }
+
+    /// Sends a message to the remote peer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection fails.
+    pub fn send(&mut self, message: &Message) -> io::Result<()> {
+        writeln!(self.writer, "{message}")
+    }
+
+    /// Returns the messages that arrived since the previous call, without
+    /// waiting for more.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection fails or was closed by the peer.
+    pub fn receive(&mut self) -> io::Result<Vec<Message>> {
+        self.reader.get_ref().set_nonblocking(true)?;
+        let mut messages = Vec::new();
+        let result = loop {
+            match self.reader.read_until(b'\n', &mut self.line) {
+                Ok(0) => break Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(_) if self.line.ends_with(b"\n") => {
+                    let line = String::from_utf8_lossy(&self.line);
+                    messages.extend(Message::parse(&line));
+                    self.line.clear();
+                }
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break Ok(messages),
+                Err(err) => break Err(err),
+            }
+        };
+        self.reader.get_ref().set_nonblocking(false)?;
+        result
+    }
+
+    /// Sends the outgoing messages of the given [`Lockstep`] and hands it the
+    /// messages that arrived. Call this before every
+    /// [`Lockstep::step_frame`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection fails or was closed by the peer.
+    pub fn exchange(&mut self, lockstep: &mut Lockstep) -> io::Result<()> {
+        for message in lockstep.take_outgoing() {
+            self.send(&message)?;
+        }
+        for message in self.receive()? {
+            lockstep.receive(&message);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockstep() {
+        // C0FF: V0 = random, 6105: V1 = 5, E1A1: skip unless key V1 is held,
+        // 7301: V3 += 1, 610C: V1 = C, E1A1, 7401: V4 += 1, 1200: loop
+        let rom = vec![
+            0xC0, 0xFF, 0x61, 0x05, 0xE1, 0xA1, 0x73, 0x01, 0x61, 0x0C, 0xE1, 0xA1, 0x74, 0x01,
+            0x12, 0x00,
+        ];
+        let mut chips: Vec<Chip8> = (0..2)
+            .map(|_| {
+                let mut chip8 = Chip8::new();
+                chip8.set_seed(42);
+                chip8.reset_and_load(rom.clone()).unwrap();
+                chip8
+            })
+            .collect();
+        let mut peers = [Lockstep::new(16, 2), Lockstep::new(16, 2)];
+
+        // Each player holds their own paddle key
+        peers[0].update_key_state(0x5, true);
+        peers[1].update_key_state(0xC, true);
+        for _ in 0..5 {
+            for (peer, chip8) in peers.iter_mut().zip(&mut chips) {
+                assert!(peer.step_frame(chip8).unwrap());
+            }
+            let messages: Vec<Vec<Message>> =
+                peers.iter_mut().map(Lockstep::take_outgoing).collect();
+            for message in &messages[0] {
+                assert_eq!(Message::parse(&message.to_string()).as_ref(), Some(message));
+                assert!(peers[1].receive(message));
+            }
+            for message in &messages[1] {
+                assert!(peers[0].receive(message));
+            }
+        }
+
+        assert_eq!(chips[0].processor.v[..5], chips[1].processor.v[..5]);
+        assert!(chips[1].processor.v[3] > 0);
+        assert!(chips[1].processor.v[4] > 0);
+
+        // Without the remote keys, the next frame has to wait
+        let mut lonely = Lockstep::new(16, 2);
+        let mut solo = Chip8::new();
+        solo.load_rom_data(rom).unwrap();
+        assert!(lonely.step_frame(&mut solo).unwrap());
+        assert!(lonely.step_frame(&mut solo).unwrap());
+        assert!(!lonely.step_frame(&mut solo).unwrap());
+    }
+}
@@ -0,0 +1,756 @@
+//! This module assembles programs written in the [Octo] assembly language,
+//! so the large Octo ROM ecosystem can be loaded without a separate
+//! toolchain.
+//!
+//! The supported subset covers the everyday language:
+//!
+//! - Labels (`: name`), `:alias`, `:const`, `:org`, `:call` and `:byte`, as
+//!   well as bare numbers for sprite data and bare label names for
+//!   subroutine calls.
+//! - The Chip8, SUPER-CHIP and XO-CHIP statements the core executes, such as
+//!   `v0 := random 7`, `i := hex v1`, `sprite v0 v1 5`, `save v2 - v4` or
+//!   `i := long label`.
+//! - Control flow with `if ... then`, `if ... begin ... else ... end` and
+//!   `loop ... while ... again`, for the conditions `==`, `!=`, `key` and
+//!   `-key`.
+//!
+//! Macros, `:calc`, `:unpack`, `:stringmode`, the comparisons `<`, `>`, `<=`
+//! and `>=`, and the SUPER-CHIP statements `lores`, `hires` and
+//! `i := bighex`, which need a 128x64 display the core does not have, are not
+//! supported and raise an [`OctoError`]. Like Octo, the program starts with a
+//! jump to the `main` label.
+//!
+//! [Octo]: https://github.com/JohnEarnest/Octo
+
+use std::{collections::HashMap, fmt};
+
+/// The address programs are assembled for.
+const START_ADDRESS: usize = 0x200;
+
+/// An error raised while assembling, with the line it was raised on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OctoError {
+    /// The line of the source the error was raised on, starting at 1.
+    pub line: usize,
+
+    /// A description of the error.
+    pub message: String,
+}
+
+impl fmt::Display for OctoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for OctoError {}
+
+/// Assembles the given Octo source into a ROM starting at address `0x200`.
+///
+/// # Errors
+///
+/// Returns an [`OctoError`] if the source is invalid or uses an unsupported
+/// feature.
+pub fn assemble(source: &str) -> Result<Vec<u8>, OctoError> {
+    let tokens = source
+        .lines()
+        .enumerate()
+        .flat_map(|(index, line)| {
+            let code = line.split('#').next().unwrap_or_default();
+            code.split_whitespace().map(move |text| Token {
+                text,
+                line: index + 1,
+            })
+        })
+        .collect();
+    Assembler::new(tokens).run()
+}
+
+/// A word of the source.
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+/// A reference to a label that is resolved once all labels are known.
+#[derive(Debug)]
+struct Fixup {
+    /// The offset of the referencing instruction in the ROM.
+    offset: usize,
+    /// The name of the label.
+    label: String,
+    /// The line the label was referenced on.
+    line: usize,
+    /// Whether the reference is a full 16-bit address rather than the lower
+    /// 12 bits of an opcode.
+    long: bool,
+}
+
+/// An open control flow block.
+#[derive(Debug)]
+enum Block {
+    /// An `if ... begin` block, with the offset of the jump past the block.
+    If { jump: usize },
+    /// A `loop` block, with its start address and the offsets of the jumps
+    /// of its `while`s.
+    Loop { start: usize, exits: Vec<usize> },
+}
+
+/// A condition of an `if` or `while`.
+#[derive(Debug, Clone, Copy)]
+enum Condition {
+    Equal(u16, Operand),
+    NotEqual(u16, Operand),
+    Key(u16),
+    NotKey(u16),
+}
+
+impl Condition {
+    /// Returns the negated condition.
+    const fn negate(self) -> Self {
+        match self {
+            Self::Equal(x, operand) => Self::NotEqual(x, operand),
+            Self::NotEqual(x, operand) => Self::Equal(x, operand),
+            Self::Key(x) => Self::NotKey(x),
+            Self::NotKey(x) => Self::Key(x),
+        }
+    }
+
+    /// Returns the instruction that skips the next instruction if the
+    /// condition holds.
+    const fn skip_opcode(self) -> u16 {
+        match self {
+            Self::Equal(x, Operand::Byte(nn)) => 0x3000 | x << 8 | nn,
+            Self::NotEqual(x, Operand::Byte(nn)) => 0x4000 | x << 8 | nn,
+            Self::Equal(x, Operand::Register(y)) => 0x5000 | x << 8 | y << 4,
+            Self::NotEqual(x, Operand::Register(y)) => 0x9000 | x << 8 | y << 4,
+            Self::Key(x) => 0xE09E | x << 8,
+            Self::NotKey(x) => 0xE0A1 | x << 8,
+        }
+    }
+}
+
+/// The right-hand side of a register operation.
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+    Register(u16),
+    Byte(u16),
+}
+
+/// The state of the assembler.
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    /// The index of the next token.
+    pos: usize,
+    /// The assembled bytes, starting at [`START_ADDRESS`].
+    rom: Vec<u8>,
+    labels: HashMap<&'a str, usize>,
+    constants: HashMap<&'a str, u16>,
+    aliases: HashMap<&'a str, u16>,
+    fixups: Vec<Fixup>,
+    blocks: Vec<Block>,
+}
+
+impl<'a> Assembler<'a> {
+    fn new(tokens: Vec<Token<'a>>) -> Self {
+        Self {
+            tokens,
+            pos: 0,
+            rom: Vec::new(),
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            fixups: Vec::new(),
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Assembles all tokens.
+    fn run(mut self) -> Result<Vec<u8>, OctoError> {
+        self.jump_to_label(0x1000, "main", 0);
+        while self.pos < self.tokens.len() {
+            self.statement()?;
+        }
+        if let Some(block) = self.blocks.last() {
+            let open = if matches!(block, Block::If { .. }) {
+                "begin"
+            } else {
+                "loop"
+            };
+            return Err(self.error(format!("`{open}` is never closed")));
+        }
+        for fixup in std::mem::take(&mut self.fixups) {
+            let Some(&address) = self.labels.get(fixup.label.as_str()) else {
+                return Err(OctoError {
+                    line: fixup.line,
+                    message: format!("undefined label `{}`", fixup.label),
+                });
+            };
+            if fixup.long {
+                self.patch_long(fixup.offset, address, fixup.line)?;
+            } else {
+                self.patch(fixup.offset, address, fixup.line)?;
+            }
+        }
+        Ok(self.rom)
+    }
+
+    /// Assembles a single statement.
+    fn statement(&mut self) -> Result<(), OctoError> {
+        let token = self.next()?;
+        match token.text {
+            "clear" => self.emit(0x00E0),
+            "return" | ";" => self.emit(0x00EE),
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit(0x00C0 | n);
+            }
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.emit(0x00D0 | n);
+            }
+            "scroll-right" => self.emit(0x00FB),
+            "scroll-left" => self.emit(0x00FC),
+            "exit" => self.emit(0x00FD),
+            // The core has no 128x64 SUPER-CHIP display
+            "lores" | "hires" => {
+                return Err(self.error(format!("unsupported statement `{}`", token.text)));
+            }
+            "jump" => self.address_operand(0x1000)?,
+            "jump0" => self.address_operand(0xB000)?,
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.emit(0xD000 | x << 8 | y << 4 | n);
+            }
+            "bcd" => self.register_op(0xF033)?,
+            "save" => self.save_load(0xF055, 0x5002)?,
+            "load" => self.save_load(0xF065, 0x5003)?,
+            "saveflags" => self.register_op(0xF075)?,
+            "loadflags" => self.register_op(0xF085)?,
+            "plane" => {
+                let n = self.nibble()?;
+                self.emit(0xF001 | n << 8);
+            }
+            "audio" => self.emit(0xF002),
+            "delay" => self.timer_assignment(0xF015)?,
+            "buzzer" => self.timer_assignment(0xF018)?,
+            "pitch" => self.timer_assignment(0xF03A)?,
+            "i" => self.index_statement()?,
+            "if" | "else" | "end" | "loop" | "while" | "again" => self.control_flow(token)?,
+            text if text.starts_with(':') => self.directive(text)?,
+            text => {
+                if let Some(x) = self.lookup_register(text) {
+                    return self.register_statement(x);
+                }
+                if let Some(value) = self.lookup_value(text) {
+                    let value = Self::to_byte(value)
+                        .ok_or_else(|| self.error(format!("`{text}` does not fit into a byte")))?;
+                    self.rom.push(value);
+                } else {
+                    // A bare label name calls the subroutine
+                    self.jump_to_label(0x2000, text, token.line);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Assembles a directive, e.g. `: main` or `:const SPEED 2`.
+    fn directive(&mut self, text: &str) -> Result<(), OctoError> {
+        match text {
+            ":" => {
+                let name = self.next()?.text;
+                if self.labels.insert(name, self.address()).is_some() {
+                    return Err(self.error(format!("label `{name}` is defined twice")));
+                }
+            }
+            ":alias" => {
+                let name = self.next()?.text;
+                let register = self.register()?;
+                self.aliases.insert(name, register);
+            }
+            ":const" => {
+                let name = self.next()?.text;
+                let value = self.value()?;
+                self.constants.insert(name, value);
+            }
+            ":org" => {
+                let address = usize::from(self.value()?);
+                let Some(offset) = address.checked_sub(START_ADDRESS) else {
+                    return Err(self.error(format!("cannot assemble at {address:#05X}")));
+                };
+                if offset < self.rom.len() {
+                    return Err(self.error(format!("{address:#05X} is already assembled")));
+                }
+                self.rom.resize(offset, 0);
+            }
+            ":call" => self.address_operand(0x2000)?,
+            ":byte" => {
+                let value = self.byte()?;
+                self.rom.push(value);
+            }
+            _ => return Err(self.error(format!("unsupported directive `{text}`"))),
+        }
+        Ok(())
+    }
+
+    /// Assembles a control flow statement: `if`, `else`, `end`, `loop`,
+    /// `while` or `again`.
+    fn control_flow(&mut self, token: Token<'a>) -> Result<(), OctoError> {
+        match token.text {
+            "if" => self.if_statement()?,
+            "else" => {
+                let Some(Block::If { jump }) = self.blocks.pop() else {
+                    return Err(self.error("`else` without `if ... begin`"));
+                };
+                let end = self.placeholder_jump();
+                self.patch(jump, self.address(), token.line)?;
+                self.blocks.push(Block::If { jump: end });
+            }
+            "end" => {
+                let Some(Block::If { jump }) = self.blocks.pop() else {
+                    return Err(self.error("`end` without `if ... begin`"));
+                };
+                self.patch(jump, self.address(), token.line)?;
+            }
+            "loop" => self.blocks.push(Block::Loop {
+                start: self.address(),
+                exits: Vec::new(),
+            }),
+            "while" => {
+                let condition = self.condition()?;
+                self.emit(condition.skip_opcode());
+                let exit = self.placeholder_jump();
+                let Some(Block::Loop { exits, .. }) = self
+                    .blocks
+                    .iter_mut()
+                    .rev()
+                    .find(|block| matches!(block, Block::Loop { .. }))
+                else {
+                    return Err(self.error("`while` outside of a loop"));
+                };
+                exits.push(exit);
+            }
+            _ => {
+                let Some(Block::Loop { start, exits }) = self.blocks.pop() else {
+                    return Err(self.error("`again` without `loop`"));
+                };
+                self.emit_address(0x1000, start, token.line)?;
+                for exit in exits {
+                    self.patch(exit, self.address(), token.line)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Assembles a statement starting with a register, e.g. `v0 += 1`.
+    fn register_statement(&mut self, x: u16) -> Result<(), OctoError> {
+        let op = self.next()?.text;
+        if op == ":=" {
+            match self.peek() {
+                Some("random") => {
+                    self.pos += 1;
+                    let nn = u16::from(self.byte()?);
+                    self.emit(0xC000 | x << 8 | nn);
+                }
+                Some("delay") => {
+                    self.pos += 1;
+                    self.emit(0xF007 | x << 8);
+                }
+                Some("key") => {
+                    self.pos += 1;
+                    self.emit(0xF00A | x << 8);
+                }
+                _ => match self.operand()? {
+                    Operand::Register(y) => self.emit(0x8000 | x << 8 | y << 4),
+                    Operand::Byte(nn) => self.emit(0x6000 | x << 8 | nn),
+                },
+            }
+            return Ok(());
+        }
+        let alu = match op {
+            "|=" => 0x1,
+            "&=" => 0x2,
+            "^=" => 0x3,
+            "+=" => 0x4,
+            "-=" => 0x5,
+            ">>=" => 0x6,
+            "=-" => 0x7,
+            "<<=" => 0xE,
+            _ => return Err(self.error(format!("unknown operator `{op}`"))),
+        };
+        match (self.operand()?, alu) {
+            (Operand::Register(y), _) => self.emit(0x8000 | x << 8 | y << 4 | alu),
+            (Operand::Byte(nn), 0x4) => self.emit(0x7000 | x << 8 | nn),
+            (Operand::Byte(nn), 0x5) => self.emit(0x7000 | x << 8 | (0x100 - nn) & 0xFF),
+            (Operand::Byte(_), _) => {
+                return Err(self.error(format!("`{op}` needs a register operand")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Assembles a statement starting with `i`.
+    fn index_statement(&mut self) -> Result<(), OctoError> {
+        let op = self.next()?.text;
+        match op {
+            "+=" => self.register_op(0xF01E),
+            ":=" => match self.peek() {
+                Some("hex") => {
+                    self.pos += 1;
+                    self.register_op(0xF029)
+                }
+                Some("bighex") => Err(self.error("unsupported statement `i := bighex`")),
+                Some("long") => {
+                    self.pos += 1;
+                    self.emit(0xF000);
+                    let token = self.next()?;
+                    let offset = self.rom.len();
+                    self.rom.extend([0, 0]);
+                    if let Some(address) = self.lookup_address(token.text) {
+                        self.patch_long(offset, address, token.line)?;
+                    } else {
+                        self.fixups.push(Fixup {
+                            offset,
+                            label: token.text.to_string(),
+                            line: token.line,
+                            long: true,
+                        });
+                    }
+                    Ok(())
+                }
+                _ => self.address_operand(0xA000),
+            },
+            _ => Err(self.error(format!("unknown operator `{op}` for `i`"))),
+        }
+    }
+
+    /// Assembles an `if` statement, either `if ... then` or `if ... begin`.
+    fn if_statement(&mut self) -> Result<(), OctoError> {
+        let condition = self.condition()?;
+        match self.next()?.text {
+            // Skip the next statement unless the condition holds
+            "then" => self.emit(condition.negate().skip_opcode()),
+            // Skip the jump past the block if the condition holds
+            "begin" => {
+                self.emit(condition.skip_opcode());
+                let jump = self.placeholder_jump();
+                self.blocks.push(Block::If { jump });
+            }
+            text => return Err(self.error(format!("expected `then` or `begin`, found `{text}`"))),
+        }
+        Ok(())
+    }
+
+    /// Parses a condition, e.g. `v0 == 3` or `v1 -key`.
+    fn condition(&mut self) -> Result<Condition, OctoError> {
+        let x = self.register()?;
+        let op = self.next()?.text;
+        Ok(match op {
+            "==" => Condition::Equal(x, self.operand()?),
+            "!=" => Condition::NotEqual(x, self.operand()?),
+            "key" => Condition::Key(x),
+            "-key" => Condition::NotKey(x),
+            "<" | ">" | "<=" | ">=" => {
+                return Err(self.error(format!("unsupported comparison `{op}`")));
+            }
+            _ => return Err(self.error(format!("unknown comparison `{op}`"))),
+        })
+    }
+
+    /// Assembles `save vx` or `save vx - vy`, and the same for `load`.
+    fn save_load(&mut self, single: u16, range: u16) -> Result<(), OctoError> {
+        let x = self.register()?;
+        if self.peek() == Some("-") {
+            self.pos += 1;
+            let y = self.register()?;
+            self.emit(range | x << 8 | y << 4);
+        } else {
+            self.emit(single | x << 8);
+        }
+        Ok(())
+    }
+
+    /// Assembles `delay := vx` and similar assignments from a register.
+    fn timer_assignment(&mut self, opcode: u16) -> Result<(), OctoError> {
+        let op = self.next()?.text;
+        if op != ":=" {
+            return Err(self.error(format!("expected `:=`, found `{op}`")));
+        }
+        self.register_op(opcode)
+    }
+
+    /// Assembles an instruction with a register operand in its `x` nibble.
+    fn register_op(&mut self, opcode: u16) -> Result<(), OctoError> {
+        let x = self.register()?;
+        self.emit(opcode | x << 8);
+        Ok(())
+    }
+
+    /// Assembles an instruction with an address operand.
+    fn address_operand(&mut self, opcode: u16) -> Result<(), OctoError> {
+        let token = self.next()?;
+        match self.lookup_address(token.text) {
+            Some(address) => self.emit_address(opcode, address, token.line)?,
+            None => self.jump_to_label(opcode, token.text, token.line),
+        }
+        Ok(())
+    }
+
+    /// Emits an instruction referencing the given label, which may not be
+    /// defined yet.
+    fn jump_to_label(&mut self, opcode: u16, label: &str, line: usize) {
+        self.fixups.push(Fixup {
+            offset: self.rom.len(),
+            label: label.to_string(),
+            line,
+            long: false,
+        });
+        self.emit(opcode);
+    }
+
+    /// Emits a jump whose target is patched in later, and returns its offset.
+    fn placeholder_jump(&mut self) -> usize {
+        let offset = self.rom.len();
+        self.emit(0x1000);
+        offset
+    }
+
+    /// Emits an instruction with the given address in its lower 12 bits.
+    fn emit_address(&mut self, opcode: u16, address: usize, line: usize) -> Result<(), OctoError> {
+        let offset = self.rom.len();
+        self.emit(opcode);
+        self.patch(offset, address, line)
+    }
+
+    /// Writes the given address into the lower 12 bits of the instruction at
+    /// the given offset.
+    fn patch(&mut self, offset: usize, address: usize, line: usize) -> Result<(), OctoError> {
+        if address > 0xFFF {
+            return Err(OctoError {
+                line,
+                message: format!("address {address:#X} does not fit into 12 bits"),
+            });
+        }
+        let [hi, lo] = u16::try_from(address).unwrap_or_default().to_be_bytes();
+        self.rom[offset] = (self.rom[offset] & 0xF0) | hi;
+        self.rom[offset + 1] = lo;
+        Ok(())
+    }
+
+    /// Writes the given 16-bit address at the given offset.
+    fn patch_long(&mut self, offset: usize, address: usize, line: usize) -> Result<(), OctoError> {
+        let Ok(address) = u16::try_from(address) else {
+            return Err(OctoError {
+                line,
+                message: format!("address {address:#X} does not fit into 16 bits"),
+            });
+        };
+        self.rom[offset..offset + 2].copy_from_slice(&address.to_be_bytes());
+        Ok(())
+    }
+
+    fn emit(&mut self, opcode: u16) {
+        self.rom.extend(opcode.to_be_bytes());
+    }
+
+    /// Returns the address the next byte is assembled at.
+    const fn address(&self) -> usize {
+        START_ADDRESS + self.rom.len()
+    }
+
+    /// Parses a register or byte operand.
+    fn operand(&mut self) -> Result<Operand, OctoError> {
+        let token = self.next()?;
+        if let Some(y) = self.lookup_register(token.text) {
+            return Ok(Operand::Register(y));
+        }
+        self.pos -= 1;
+        Ok(Operand::Byte(u16::from(self.byte()?)))
+    }
+
+    /// Parses a register.
+    fn register(&mut self) -> Result<u16, OctoError> {
+        let token = self.next()?;
+        self.lookup_register(token.text)
+            .ok_or_else(|| self.error(format!("expected a register, found `{}`", token.text)))
+    }
+
+    /// Parses a value that fits into a byte.
+    fn byte(&mut self) -> Result<u8, OctoError> {
+        let value = self.value()?;
+        Self::to_byte(value).ok_or_else(|| self.error(format!("{value} does not fit into a byte")))
+    }
+
+    /// Parses a value that fits into a nibble.
+    fn nibble(&mut self) -> Result<u16, OctoError> {
+        let value = self.value()?;
+        if value > 0xF {
+            return Err(self.error(format!("{value} does not fit into a nibble")));
+        }
+        Ok(value)
+    }
+
+    /// Parses a number, constant or defined label.
+    fn value(&mut self) -> Result<u16, OctoError> {
+        let token = self.next()?;
+        self.lookup_value(token.text)
+            .or_else(|| {
+                let address = self.labels.get(token.text)?;
+                u16::try_from(*address).ok()
+            })
+            .ok_or_else(|| self.error(format!("expected a value, found `{}`", token.text)))
+    }
+
+    /// Converts a value into a byte, accepting negative bytes as two's
+    /// complement.
+    fn to_byte(value: u16) -> Option<u8> {
+        u8::try_from(value)
+            .ok()
+            .or_else(|| (value >= 0xFF80).then(|| value.to_be_bytes()[1]))
+    }
+
+    /// Returns the register with the given name or alias.
+    fn lookup_register(&self, text: &str) -> Option<u16> {
+        if let Some(&register) = self.aliases.get(text) {
+            return Some(register);
+        }
+        let digit = text.strip_prefix(['v', 'V'])?;
+        if digit.len() != 1 {
+            return None;
+        }
+        u16::from_str_radix(digit, 16).ok()
+    }
+
+    /// Returns the value of a number or constant. Negative numbers are
+    /// returned as 16-bit two's complement.
+    fn lookup_value(&self, text: &str) -> Option<u16> {
+        if let Some(&value) = self.constants.get(text) {
+            return Some(value);
+        }
+        let (negative, digits) = text
+            .strip_prefix('-')
+            .map_or((false, text), |digits| (true, digits));
+        let value = if let Some(hex) = digits.strip_prefix("0x") {
+            u16::from_str_radix(hex, 16).ok()?
+        } else if let Some(binary) = digits.strip_prefix("0b") {
+            u16::from_str_radix(binary, 2).ok()?
+        } else {
+            digits.parse().ok()?
+        };
+        Some(if negative {
+            value.wrapping_neg()
+        } else {
+            value
+        })
+    }
+
+    /// Returns the address of a number, constant or defined label.
+    fn lookup_address(&self, text: &str) -> Option<usize> {
+        self.lookup_value(text)
+            .map(usize::from)
+            .or_else(|| self.labels.get(text).copied())
+    }
+
+    /// Returns the next token.
+    fn next(&mut self) -> Result<Token<'a>, OctoError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .copied()
+            .ok_or_else(|| OctoError {
+                line: self.tokens.last().map_or(1, |token| token.line),
+                message: "unexpected end of the source".into(),
+            })?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    /// Returns the text of the next token without consuming it.
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(|token| token.text)
+    }
+
+    /// Returns an error on the line of the previous token.
+    fn error(&self, message: impl Into<String>) -> OctoError {
+        let index = self.pos.saturating_sub(1);
+        OctoError {
+            line: self.tokens.get(index).map_or(1, |token| token.line),
+            message: message.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    #[test]
+    fn test_assemble() {
+        let source = "
+            :alias counter v3
+            :const SPEED 2
+            : main
+                clear
+                counter := 0
+                loop
+                    counter += SPEED
+                    if counter == 10 then v0 := key
+                    while counter != 10
+                again
+                i := digits
+                if v0 -key begin
+                    sprite v1 v2 5
+                else
+                    save v2 - v4
+                end
+                draw
+            : draw
+                return
+            : digits 0xF0 -112 0b1111
+        ";
+        let rom = assemble(source).unwrap();
+        #[rustfmt::skip]
+        let expected = [
+            0x12, 0x02, // jump main
+            0x00, 0xE0, // clear
+            0x63, 0x00, // counter := 0
+            0x73, 0x02, // counter += SPEED
+            0x43, 0x0A, 0xF0, 0x0A, // if counter == 10 then v0 := key
+            0x43, 0x0A, 0x12, 0x12, // while counter != 10
+            0x12, 0x06, // again
+            0xA2, 0x22, // i := digits
+            0xE0, 0xA1, 0x12, 0x1C, // if v0 -key begin
+            0xD1, 0x25, 0x12, 0x1E, // sprite, else
+            0x52, 0x42, // save v2 - v4
+            0x22, 0x20, // draw
+            0x00, 0xEE, // return
+            0xF0, 0x90, 0x0F, // digits
+        ];
+        assert_eq!(rom, expected);
+
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_data(rom).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = assemble(": main\n  v0 := 300").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: 300 does not fit into a byte");
+        assert_eq!(assemble(": main\n jump nowhere").unwrap_err().line, 2);
+        assert!(assemble("clear").is_err());
+        assert!(assemble(": main\n :macro foo").is_err());
+        assert!(assemble(": main\n hires").is_err());
+        assert!(assemble(": main\n i := bighex v0").is_err());
+        let error = assemble(": main\n i := long far\n :org 0xFFFF 0 0 : far").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 2: address 0x10001 does not fit into 16 bits"
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! This module lets library users attach custom peripherals to the emulated
+//! machine, for experiments like serial output, extra storage or homebrew
+//! extensions.
+//!
+//! A [`Peripheral`] is registered through [`Chip8::register_peripheral`] and
+//! can intercept two kinds of accesses:
+//!
+//! - `0nnn` (SYS) instructions that are not handled by the interpreter,
+//!   which would otherwise raise [`Chip8Error::InvalidOpcode`].
+//! - Memory accesses of the `Fx33`, `Fx55` and `Fx65` instructions to the
+//!   addresses the peripheral maps, which are redirected to the peripheral
+//!   instead of RAM.
+//!
+//! Peripherals are asked in the order they were registered, and the first
+//! one that handles an access wins. They are kept across resets, but are not
+//! part of saved states or the rewind history.
+//!
+//! [`Chip8`]: crate::Chip8
+//! [`Chip8::register_peripheral`]: crate::Chip8::register_peripheral
+//! [`Chip8Error::InvalidOpcode`]: crate::error::Chip8Error::InvalidOpcode
+
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::{processor::Cpu, Bus};
+
+/// A device attached to the emulated machine.
+pub trait Peripheral: fmt::Debug + Send {
+    /// Returns the name of the peripheral, shown in the disassembly of the
+    /// instructions it handles.
+    fn name(&self) -> &str;
+
+    /// Handles the `0nnn` (SYS) instruction with the given address. Returns
+    /// whether the instruction was handled, in which case the program
+    /// continues with the next instruction.
+    fn sys(&mut self, nnn: usize, cpu: &mut Cpu, bus: &mut Bus) -> bool {
+        let _ = (nnn, cpu, bus);
+        false
+    }
+
+    /// Returns whether memory accesses to the given address are redirected
+    /// to this peripheral.
+    fn maps(&self, addr: usize) -> bool {
+        let _ = addr;
+        false
+    }
+
+    /// Reads the byte at the given mapped address.
+    fn read(&mut self, addr: usize) -> u8 {
+        let _ = addr;
+        0
+    }
+
+    /// Writes a byte to the given mapped address.
+    fn write(&mut self, addr: usize, value: u8) {
+        let _ = (addr, value);
+    }
+}
+
+/// The [`Peripheral`]s registered with a [`Bus`].
+#[derive(Debug, Default)]
+pub struct Peripherals {
+    devices: Vec<Box<dyn Peripheral>>,
+}
+
+impl Peripherals {
+    /// Registers the given peripheral after all others.
+    pub fn register(&mut self, peripheral: impl Peripheral + 'static) {
+        self.devices.push(Box::new(peripheral));
+    }
+
+    /// Removes all peripherals.
+    pub fn clear(&mut self) {
+        self.devices.clear();
+    }
+
+    /// Returns an iterator over the registered peripherals.
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Peripheral> {
+        self.devices.iter().map(AsRef::as_ref)
+    }
+
+    /// Returns the amount of registered peripherals.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Returns whether no peripheral is registered.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// Returns the peripheral that maps the given address.
+    fn mapping(&mut self, addr: usize) -> Option<&mut Box<dyn Peripheral>> {
+        self.devices.iter_mut().find(|device| device.maps(addr))
+    }
+}
+
+impl Bus {
+    /// Reads the byte at the given address, from the [`Peripheral`] that maps
+    /// it or from memory.
+    pub(crate) fn read_byte(&mut self, addr: usize) -> u8 {
+        self.peripherals
+            .mapping(addr)
+            .map_or_else(|| self.memory[addr], |device| device.read(addr))
+    }
+
+    /// Writes a byte to the given address, to the [`Peripheral`] that maps it
+    /// or to memory.
+    pub(crate) fn write_byte(&mut self, addr: usize, value: u8) {
+        match self.peripherals.mapping(addr) {
+            Some(device) => device.write(addr, value),
+            None => self.memory[addr] = value,
+        }
+    }
+
+    /// Lets the registered [`Peripheral`]s handle the given SYS instruction.
+    ///
+    /// # Returns
+    ///
+    /// The name of the peripheral that handled the instruction, or [`None`]
+    /// if none did.
+    pub(crate) fn sys(&mut self, nnn: usize, cpu: &mut Cpu) -> Option<String> {
+        // Take the peripherals out so they can access the rest of the bus
+        let mut peripherals = core::mem::take(&mut self.peripherals);
+        let name = peripherals.devices.iter_mut().find_map(|device| {
+            device
+                .sys(nnn, cpu, self)
+                .then(|| device.name().to_string())
+        });
+        peripherals.devices.append(&mut self.peripherals.devices);
+        self.peripherals = peripherals;
+        name
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{processor::StepResult, Chip8};
+
+    /// A serial port that collects the bytes written to 0xF00 and sends V0
+    /// through `SYS 0x100`.
+    #[derive(Debug, Default)]
+    struct Serial {
+        output: std::sync::Arc<std::sync::Mutex<Vec<u8>>>,
+    }
+
+    impl Peripheral for Serial {
+        fn name(&self) -> &'static str {
+            "serial"
+        }
+
+        fn sys(&mut self, nnn: usize, cpu: &mut Cpu, _bus: &mut Bus) -> bool {
+            if nnn != 0x100 {
+                return false;
+            }
+            self.output.lock().unwrap().push(cpu.v[0]);
+            true
+        }
+
+        fn maps(&self, addr: usize) -> bool {
+            addr == 0xF00
+        }
+
+        fn read(&mut self, _addr: usize) -> u8 {
+            0x42
+        }
+
+        fn write(&mut self, _addr: usize, value: u8) {
+            self.output.lock().unwrap().push(value);
+        }
+    }
+
+    #[test]
+    fn test_peripheral() {
+        let serial = Serial::default();
+        let output = serial.output.clone();
+        let mut chip8 = Chip8::new();
+        chip8.register_peripheral(serial);
+        // 6007: V0 = 7, 0100: SYS 0x100, AF00: I = 0xF00, F055: store V0,
+        // F065: load V0, 0123: SYS 0x123
+        chip8
+            .load_rom_data(vec![
+                0x60, 0x07, 0x01, 0x00, 0xAF, 0x00, 0xF0, 0x55, 0xAF, 0x00, 0xF0, 0x65, 0x01, 0x23,
+            ])
+            .unwrap();
+        for _ in 0..6 {
+            assert_eq!(chip8.step(), Ok(StepResult::Continue));
+        }
+        assert_eq!(*output.lock().unwrap(), [7, 7]);
+        assert_eq!(chip8.bus.memory[0xF00], 0);
+        assert_eq!(chip8.processor.v[0], 0x42);
+
+        // Unhandled SYS instructions are still invalid
+        assert!(chip8.step().is_err());
+        assert_eq!(chip8.bus.peripherals.len(), 1);
+    }
+}
@@ -2,9 +2,26 @@
 //! unit (CPU). The CPU executes the instructions stored in the memory of the
 //! Chip8 computer.
 
-use std::collections::VecDeque;
-
-use crate::graphics;
+use alloc::{
+    collections::VecDeque,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::{
+    audio,
+    decode::DecodeCache,
+    disassembler::{self, Syntax},
+    error::Chip8Error,
+    graphics,
+    labels::Labels,
+    megachip::{BlendMode, MegaChip, Sound},
+    memory,
+    quirks::{MemoryIncrement, Quirks},
+    rng::Rng,
+};
 
 use super::Bus;
 
@@ -19,19 +36,103 @@ const STARTING_PC: usize = 0x200;
 /// Describes how the program counter should be updated after
 /// executing an instruction.
 #[derive(Debug)]
-enum ProgramCounterUpdate {
+pub(crate) enum ProgramCounterUpdate {
     /// Go directly to the next instruction (pc + 2)
     Next,
 
     /// Skip the next instruction (pc + 4).
     SkipNext,
 
+    /// Go past an instruction with a 16-bit operand (pc + 4).
+    Long,
+
     /// Jump to the given address.
     Jump(usize),
 }
 
+/// Describes the outcome of a single [`Cpu::cycle`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StepResult {
+    /// An instruction was executed and the program continues normally.
+    Continue,
+
+    /// No instruction was executed because the processor is waiting for a
+    /// key press.
+    WaitingForKey,
+
+    /// No instruction was executed because the processor waits for the
+    /// vertical blank interrupt before drawing a sprite, see
+    /// [`crate::quirks::Quirks::vblank_wait`]. The sprite is drawn once the
+    /// next [`crate::Chip8::tick_timers`] raised the interrupt.
+    WaitingForVblank,
+
+    /// An instruction was executed that jumped to its own address. Most
+    /// programs use this to halt, so execution will not make any progress.
+    Loop,
+
+    /// The program counter ran past the end of memory, so there are no more
+    /// instructions to execute.
+    End,
+}
+
+/// Why [`crate::Chip8::step_n`] stopped executing instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchStop {
+    /// All requested instructions were executed.
+    Completed,
+
+    /// An instruction cleared, scrolled or drew onto the display, or switched
+    /// its resolution.
+    DisplayUpdated,
+
+    /// The program counter reached a breakpoint.
+    Breakpoint,
+
+    /// The processor waits for a key press.
+    WaitingForKey,
+
+    /// The processor waits for the vertical blank interrupt.
+    WaitingForVblank,
+
+    /// An instruction jumped to its own address.
+    Loop,
+
+    /// The program counter ran past the end of memory.
+    End,
+
+    /// An instruction could not be executed.
+    Error(Chip8Error),
+}
+
+/// Describes the outcome of a batch of instructions executed by
+/// [`crate::Chip8::step_n`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchResult {
+    /// The amount of instructions executed.
+    pub executed: u32,
+
+    /// Why the batch stopped.
+    pub stop: BatchStop,
+}
+
+/// Returns whether the given opcode changes the display, i.e. clears,
+/// scrolls or draws onto it.
+///
+/// These are the opcodes [`Cpu::cycle`] decodes to `00Cn`, `00Dn`, `00FB`,
+/// `00FC`, `Dxyn` and `00E0`, which every other `0nn0` executes as well,
+/// e.g. `0230` of the hires patch.
+#[must_use]
+pub const fn updates_display(opcode: usize) -> bool {
+    matches!(opcode, 0x00FB | 0x00FC)
+        || opcode & 0xFFE0 == 0x00C0
+        || opcode & 0xF00F == 0x0000
+        || opcode & 0xF000 == 0xD000
+}
+
 /// This structs contains information about an instruction in a computer program.
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Instruction {
     /// An unsigned integer representing the memory address where the instruction is located.
     pub address: usize,
@@ -43,8 +144,42 @@ pub struct Instruction {
     pub display: String,
 }
 
+impl Instruction {
+    /// Returns the instruction written in the given [`Syntax`], with address
+    /// operands written as their label if one is assigned. Falls back to the
+    /// explanation if the opcode cannot be disassembled, and to
+    /// [`Syntax::Cowgod`] for instructions executed without an explanation,
+    /// see [`Cpu::set_decode_cache`].
+    #[must_use]
+    pub fn text(&self, syntax: Syntax, labels: &Labels) -> String {
+        let syntax = match syntax {
+            Syntax::Description if self.display.is_empty() => Syntax::Cowgod,
+            syntax => syntax,
+        };
+        u16::try_from(self.opcode)
+            .ok()
+            .filter(|_| syntax != Syntax::Description)
+            .and_then(|opcode| disassembler::disassemble_as(opcode, syntax, labels))
+            .unwrap_or_else(|| self.display.clone())
+    }
+}
+
+impl fmt::Display for Instruction {
+    /// Writes the explanation of the instruction, or its Octo statement with
+    /// the alternate flag (`{:#}`).
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let syntax = if f.alternate() {
+            Syntax::Octo
+        } else {
+            Syntax::Description
+        };
+        f.write_str(&self.text(syntax, &Labels::default()))
+    }
+}
+
 /// This struct represents the central processing unit of a computer.
-#[derive(Debug, serde::Serialize, serde::Deserialize, Default)]
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Cpu {
     /// An array of 16 unsigned 8-bit integers representing the Vx registers.
     pub v: [u8; 16],
@@ -58,16 +193,18 @@ pub struct Cpu {
     /// An unsigned integer representing the stack pointer.
     pub sp: usize,
 
-    /// An array of 16 unsigned integers representing the stack memory.
-    pub stack: [usize; 16],
+    /// The stack memory, holding the return addresses of the nested calls
+    /// below the stack pointer. It grows as deep as the
+    /// [`Quirks::stack_depth`] allows.
+    pub stack: Vec<usize>,
 
-    /// A boolean indicating whether the shift quirk is enabled. This affects
-    /// the behavior of certain instructions.
-    pub shift_quirk_enabled: bool,
+    /// The [`Quirks`] that affect the behavior of certain instructions.
+    pub quirks: Quirks,
 
-    /// A boolean indicating whether the processor should wait for the vertical
-    /// blank interrupt before drawing a sprite.
-    pub vblank_wait: bool,
+    /// The [`Rng`] used by the `Cxnn` instruction. Its state is part of the
+    /// serialized [`Cpu`], so restored systems continue with the same random
+    /// numbers.
+    pub rng: Rng,
 
     /// A string representing a display-friendly explanation of what the
     /// current opcode is doing.
@@ -77,6 +214,11 @@ pub struct Cpu {
     /// `INSTRUCTION_BUFFER_LENGTH` instructions that the [`Cpu`] has
     /// executed.
     pub instructions: VecDeque<Instruction>,
+
+    /// The [`DecodeCache`] used while enabled through
+    /// [`Cpu::set_decode_cache`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    decode_cache: Option<DecodeCache>,
 }
 
 impl Cpu {
@@ -89,31 +231,67 @@ impl Cpu {
             sp: 0,
             v: [0; 16],
             i: 0,
-            stack: [0; 16],
-            shift_quirk_enabled: false,
-            vblank_wait: false,
+            stack: Vec::new(),
+            quirks: Quirks::new(),
+            rng: Rng::new(0),
             display: String::new(),
             instructions: VecDeque::new(),
+            decode_cache: None,
         }
     }
 
+    /// Enables or disables the [`DecodeCache`], which executes the simple
+    /// instructions of inner loops without decoding them again. Their
+    /// entries in [`Cpu::instructions`] have no explanation while enabled.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.decode_cache = enabled.then(DecodeCache::new);
+    }
+
+    /// Returns whether the [`DecodeCache`] is enabled.
+    #[must_use]
+    pub const fn decode_cache(&self) -> bool {
+        self.decode_cache.is_some()
+    }
+
     /// Execute one processor cycle. This will fetch, decode, and execute the next
     /// opcode from memory. Note that if the processor is currently waiting on
-    /// input from the user, no instructions will be executed.
-    pub fn cycle(&mut self, bus: &mut Bus) {
+    /// input from the user, or on the vblank interrupt before drawing a
+    /// sprite, no instructions will be executed.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`Chip8Error`] if the current opcode cannot be executed. The
+    /// program counter is left pointing at the faulting instruction.
+    pub fn cycle(&mut self, bus: &mut Bus) -> Result<StepResult, Chip8Error> {
         if bus.input.waiting() {
-            return;
+            return Ok(StepResult::WaitingForKey);
         } else if let Some(request) = bus.input.request_response() {
             self.v[request.register] = request.key_code;
         }
 
-        if self.pc >= 4096 {
-            return;
+        if self.pc + 1 >= bus.memory.len() {
+            return Ok(StepResult::End);
         }
         // get the next two bytes and combine into one two-byte instruction
         let opcode = (usize::from(bus.memory[self.pc]) << 8) | usize::from(bus.memory[self.pc + 1]);
+        log::trace!("{:#05X}  {opcode:04X}", self.pc);
 
-        let (pc_update, display) = self.process_opcode(opcode, bus);
+        if self.quirks.vblank_wait && opcode & 0xF000 == 0xD000 {
+            // at most one sprite is drawn per vblank interrupt
+            if !bus.clock.vblank_interrupt {
+                return Ok(StepResult::WaitingForVblank);
+            }
+            bus.clock.vblank_interrupt = false;
+        }
+
+        let cached = u16::try_from(opcode)
+            .ok()
+            .zip(self.decode_cache.as_mut())
+            .and_then(|(opcode, cache)| cache.get(self.pc, opcode));
+        let (pc_update, display) = match cached {
+            Some(op) => (op.execute(self), String::new()),
+            None => self.process_opcode(opcode, bus)?,
+        };
 
         // push new instruction
         let instruction = Instruction {
@@ -125,9 +303,13 @@ impl Cpu {
 
         match pc_update {
             ProgramCounterUpdate::Next => self.pc += 2,
-            ProgramCounterUpdate::SkipNext => self.pc += 4,
+            // skipping `F000 nnnn` skips its address as well
+            ProgramCounterUpdate::SkipNext if Self::long_index_at(bus, self.pc + 2) => self.pc += 6,
+            ProgramCounterUpdate::SkipNext | ProgramCounterUpdate::Long => self.pc += 4,
+            ProgramCounterUpdate::Jump(addr) if addr == self.pc => return Ok(StepResult::Loop),
             ProgramCounterUpdate::Jump(addr) => self.pc = addr,
         }
+        Ok(StepResult::Continue)
     }
 
     /// Push an instruction to the instruction buffer. This will
@@ -140,37 +322,112 @@ impl Cpu {
         }
     }
 
+    /// Returns whether the `F000 nnnn` instruction, which is twice as long
+    /// as the others, starts at the given address.
+    fn long_index_at(bus: &Bus, addr: usize) -> bool {
+        addr + 1 < bus.memory.len() && bus.memory[addr] == 0xF0 && bus.memory[addr + 1] == 0x00
+    }
+
+    /// Resolves a memory address computed by an instruction, for a memory of
+    /// `size` bytes. Depending on the memory wrap quirk, out-of-range
+    /// addresses either wrap around or raise an error.
+    const fn address(&self, addr: usize, size: usize) -> Result<usize, Chip8Error> {
+        if self.quirks.memory_wrap {
+            Ok(addr % size)
+        } else if addr < size {
+            Ok(addr)
+        } else {
+            Err(Chip8Error::MemoryOutOfBounds { addr })
+        }
+    }
+
+    /// Increments the index register after `Fx55` or `Fx65` accessed the
+    /// registers `V0` to `Vx`, according to the memory increment quirk.
+    const fn increment_index(&mut self, x: usize) {
+        match self.quirks.memory_increment {
+            MemoryIncrement::XPlusOne => self.i += x + 1,
+            MemoryIncrement::X => self.i += x,
+            MemoryIncrement::Unchanged => {}
+        }
+    }
+
     /// Process a single opcode. This will apply any state changing effects of the
     /// instructions onto the given [`Bus`].
-    fn process_opcode(&mut self, opcode: usize, bus: &mut Bus) -> (ProgramCounterUpdate, String) {
+    fn process_opcode(
+        &mut self,
+        opcode: usize,
+        bus: &mut Bus,
+    ) -> Result<(ProgramCounterUpdate, String), Chip8Error> {
         // define some commonly used variables
         let x = (opcode & 0x0F00) >> 8;
         let y = (opcode & 0x00F0) >> 4;
         let nn = u8::try_from(opcode & 0x00FF).unwrap();
         let nnn = opcode & 0x0FFF;
+        let invalid = Chip8Error::InvalidOpcode {
+            pc: self.pc,
+            opcode,
+        };
 
-        match (opcode & 0xF000) >> 12 {
+        let result = match (opcode & 0xF000) >> 12 {
             // 0___
-            0x0 => match opcode & 0x000F {
-                // 00E0
-                0x0000 => Self::op_00e0(bus),
+            0x0 => {
+                // Mega-Chip extensions
+                if let Some(result) = self.op_megachip(opcode, bus)? {
+                    return Ok(result);
+                }
+
+                // SYS calls handled by peripherals
+                if !matches!(opcode, 0x00E0 | 0x00EE) {
+                    if let Some(name) = bus.sys(nnn, self) {
+                        return Ok((
+                            ProgramCounterUpdate::Next,
+                            format!("SYS {nnn:03X} ({name})"),
+                        ));
+                    }
+                }
 
-                // 00EE
-                0x000E => self.op_00ee(),
+                match opcode {
+                    // 00Cn
+                    0x00C0..=0x00CF => Self::op_00cn(bus, opcode & 0x000F),
 
-                // invalid
-                _ => {
-                    log::error!("Invalid 0x0___ instruction: {opcode:X}");
-                    let display = "Invalid instruction".into();
-                    (ProgramCounterUpdate::Next, display)
+                    // 00Dn
+                    0x00D0..=0x00DF => Self::op_00dn(bus, opcode & 0x000F),
+
+                    // 00FB
+                    0x00FB => Self::op_00fb(bus),
+
+                    // 00FC
+                    0x00FC => Self::op_00fc(bus),
+
+                    // 0230
+                    0x0230 if bus.graphics.is_hires() => Self::op_00e0(bus),
+
+                    // 00FD
+                    0x00FD => self.op_00fd(),
+
+                    _ => match opcode & 0x000F {
+                        // 00E0
+                        0x0000 => Self::op_00e0(bus),
+
+                        // 00EE
+                        0x000E => self.op_00ee()?,
+
+                        // invalid
+                        _ => return Err(invalid),
+                    },
                 }
-            },
+            }
+
+            // 1260 at the start of a hires program
+            0x1 if opcode == 0x1260 && self.quirks.hires && self.pc == memory::PROGRAM_START => {
+                Self::op_1260(bus)
+            }
 
             // 1nnn
             0x1 => Self::op_1nnn(nnn),
 
             // 2nnn
-            0x2 => self.op_2nnn(nnn),
+            0x2 => self.op_2nnn(nnn)?,
 
             // 3xnn
             0x3 => self.op_3xnn(x, nn),
@@ -178,8 +435,20 @@ impl Cpu {
             // 4Xnn
             0x4 => self.op_4xnn(x, nn),
 
-            // 5xy0
-            0x5 => self.op_5xy0(x, y),
+            // 5___
+            0x5 => match opcode & 0x000F {
+                // 5xy0
+                0x0 => self.op_5xy0(x, y),
+
+                // 5xy2
+                0x2 => self.op_5xy2(bus, x, y)?,
+
+                // 5xy3
+                0x3 => self.op_5xy3(bus, x, y)?,
+
+                // invalid
+                _ => return Err(invalid),
+            },
 
             // 6xnn
             0x6 => self.op_6xnn(x, nn),
@@ -217,11 +486,7 @@ impl Cpu {
                 0xE => self.op_8xye(x, y),
 
                 // invalid
-                _ => {
-                    let display = "Invalid instruction".into();
-                    log::error!("Invalid 8XY_ instruction: {opcode:X}");
-                    (ProgramCounterUpdate::Next, display)
-                }
+                _ => return Err(invalid),
             },
 
             // 9xy0
@@ -231,13 +496,16 @@ impl Cpu {
             0xA => self.op_annn(nnn),
 
             // Bnnn
-            0xB => self.op_bnnn(nnn),
+            0xB => self.op_bnnn(x, nnn),
 
             // Cxnn
             0xC => self.op_cxnn(x, nn),
 
             // Dxyn
-            0xD => self.op_dxyn(bus, opcode, x, y),
+            0xD if bus.megachip.as_ref().is_some_and(MegaChip::is_active) => {
+                self.op_dxyn_megachip(bus, opcode, x, y)?
+            }
+            0xD => self.op_dxyn(bus, opcode, x, y)?,
 
             // E___
             0xE => match opcode & 0x000F {
@@ -248,15 +516,20 @@ impl Cpu {
                 0x0001 => self.op_exa1(bus, x),
 
                 // invalid
-                _ => {
-                    let display = "Invalid instruction".into();
-                    log::error!("Invalid EX__ instruction: {opcode:X}");
-                    (ProgramCounterUpdate::Next, display)
-                }
+                _ => return Err(invalid),
             },
 
             // F___
             0xF => match opcode & 0x00FF {
+                // F000 nnnn
+                0x0000 if x == 0 => self.op_f000(bus)?,
+
+                // Fx01
+                0x0001 => Self::op_fx01(bus, x),
+
+                // F002
+                0x0002 if x == 0 => self.op_f002(bus)?,
+
                 // Fx07
                 0x0007 => self.op_fx07(bus, x),
 
@@ -270,61 +543,94 @@ impl Cpu {
                 0x0018 => self.op_fx18(bus, x),
 
                 // Fx1E
-                0x001E => self.op_fx1e(x),
+                0x001E => self.op_fx1e(x, bus),
 
                 // Fx29
                 0x0029 => self.op_fx29(x),
 
                 // Fx33
-                0x0033 => self.op_fx33(bus, x),
+                0x0033 => self.op_fx33(bus, x)?,
+
+                // Fx3A
+                0x003A => self.op_fx3a(bus, x),
 
                 // Fx55
-                0x0055 => self.op_fx55(x, bus),
+                0x0055 => self.op_fx55(x, bus)?,
 
                 // Fx65
-                0x0065 => self.op_fx65(x, bus),
+                0x0065 => self.op_fx65(x, bus)?,
+
+                // Fx75
+                0x0075 => self.op_fx75(x, bus),
+
+                // Fx85
+                0x0085 => self.op_fx85(x, bus),
 
                 // invalid
-                _ => {
-                    let display = "Invalid instruction".into();
-                    log::error!("Invalid FX__ instruction: {opcode:X}");
-                    (ProgramCounterUpdate::Next, display)
-                }
+                _ => return Err(invalid),
             },
 
             // invalid
-            _ => {
-                let display = "Invalid instruction".into();
-                log::error!("Unknown opcode: {opcode:X}");
-                (ProgramCounterUpdate::Next, display)
-            }
-        }
+            _ => return Err(invalid),
+        };
+        Ok(result)
+    }
+
+    fn op_fx85(&mut self, x: usize, bus: &Bus) -> (ProgramCounterUpdate, String) {
+        let display = format!("Read RPL flags into V0 to V{x:X}");
+        self.v[..=x].copy_from_slice(&bus.flags.values()[..=x]);
+        (ProgramCounterUpdate::Next, display)
+    }
+
+    fn op_fx75(&self, x: usize, bus: &mut Bus) -> (ProgramCounterUpdate, String) {
+        let display = format!("Store V0 to V{x:X} in RPL flags");
+        bus.flags.store(&self.v[..=x]);
+        (ProgramCounterUpdate::Next, display)
     }
 
-    fn op_fx65(&mut self, x: usize, bus: &Bus) -> (ProgramCounterUpdate, String) {
+    fn op_fx65(
+        &mut self,
+        x: usize,
+        bus: &mut Bus,
+    ) -> Result<(ProgramCounterUpdate, String), Chip8Error> {
         let display = format!("Read memory at I into V0 to V{x:X}");
+        let size = bus.memory.len();
+        self.address(self.i + x, size)?;
         for i in 0..=x {
-            self.v[i] = bus.memory[self.i];
-            self.i += 1;
+            self.v[i] = bus.read_byte(self.address(self.i + i, size)?);
         }
-        (ProgramCounterUpdate::Next, display)
+        self.increment_index(x);
+        Ok((ProgramCounterUpdate::Next, display))
     }
 
-    fn op_fx55(&mut self, x: usize, bus: &mut Bus) -> (ProgramCounterUpdate, String) {
+    fn op_fx55(
+        &mut self,
+        x: usize,
+        bus: &mut Bus,
+    ) -> Result<(ProgramCounterUpdate, String), Chip8Error> {
         let display = format!("Store V0 to V{x:X} starting at I");
+        let size = bus.memory.len();
+        self.address(self.i + x, size)?;
         for i in 0..=x {
-            bus.memory[self.i] = self.v[i];
-            self.i += 1;
+            bus.write_byte(self.address(self.i + i, size)?, self.v[i]);
         }
-        (ProgramCounterUpdate::Next, display)
+        self.increment_index(x);
+        Ok((ProgramCounterUpdate::Next, display))
     }
 
-    fn op_fx33(&mut self, bus: &mut Bus, x: usize) -> (ProgramCounterUpdate, String) {
+    fn op_fx33(
+        &self,
+        bus: &mut Bus,
+        x: usize,
+    ) -> Result<(ProgramCounterUpdate, String), Chip8Error> {
         let display = format!("Store BCD of {} starting at I", self.v[x]);
-        bus.memory[self.i] = (self.v[x] / 100) % 10;
-        bus.memory[self.i + 1] = (self.v[x] / 10) % 10;
-        bus.memory[self.i + 2] = self.v[x] % 10;
-        (ProgramCounterUpdate::Next, display)
+        let digits = [self.v[x] / 100, (self.v[x] / 10) % 10, self.v[x] % 10];
+        let size = bus.memory.len();
+        self.address(self.i + 2, size)?;
+        for (offset, digit) in digits.into_iter().enumerate() {
+            bus.write_byte(self.address(self.i + offset, size)?, digit);
+        }
+        Ok((ProgramCounterUpdate::Next, display))
     }
 
     fn op_fx29(&mut self, x: usize) -> (ProgramCounterUpdate, String) {
@@ -333,21 +639,63 @@ impl Cpu {
         (ProgramCounterUpdate::Next, display)
     }
 
-    fn op_fx1e(&mut self, x: usize) -> (ProgramCounterUpdate, String) {
+    fn op_fx1e(&mut self, x: usize, bus: &Bus) -> (ProgramCounterUpdate, String) {
         let display = format!("Set I to I + V{x:X}");
-        self.i += usize::from(self.v[x]);
+        let size = bus.memory.len();
+        let sum = self.i + usize::from(self.v[x]);
+        // I wraps around within memory, i.e. within 12 bits unless Mega-Chip
+        // grew the memory. Addresses already beyond memory are left alone.
+        let overflow = self.i < size && sum >= size;
+        self.i = if overflow { sum - size } else { sum };
+        if self.quirks.index_overflow {
+            self.v[0xF] = overflow.into();
+        }
+        (ProgramCounterUpdate::Next, display)
+    }
+
+    fn op_f000(&mut self, bus: &Bus) -> Result<(ProgramCounterUpdate, String), Chip8Error> {
+        let size = bus.memory.len();
+        let hi = bus.memory[self.address(self.pc + 2, size)?];
+        let lo = bus.memory[self.address(self.pc + 3, size)?];
+        self.i = usize::from(u16::from_be_bytes([hi, lo]));
+        let display = format!("Set I to addr {:#06X}", self.i);
+        Ok((ProgramCounterUpdate::Long, display))
+    }
+
+    fn op_fx01(bus: &mut Bus, x: usize) -> (ProgramCounterUpdate, String) {
+        // x is a nibble, the graphics keep the planes it has
+        bus.graphics.select_planes(u8::try_from(x).unwrap());
+        (ProgramCounterUpdate::Next, format!("Select planes {x}"))
+    }
+
+    fn op_f002(&self, bus: &mut Bus) -> Result<(ProgramCounterUpdate, String), Chip8Error> {
+        let display = "Load audio pattern from I".to_string();
+        let size = bus.memory.len();
+        let mut pattern = [0; audio::PATTERN_SIZE];
+        for (offset, byte) in pattern.iter_mut().enumerate() {
+            *byte = bus.memory[self.address(self.i + offset, size)?];
+        }
+        bus.audio.pattern = Some(pattern);
+        Ok((ProgramCounterUpdate::Next, display))
+    }
+
+    fn op_fx3a(&self, bus: &mut Bus, x: usize) -> (ProgramCounterUpdate, String) {
+        let display = format!("Set audio pitch to V{x:X} ({})", self.v[x]);
+        bus.audio.pitch = self.v[x];
         (ProgramCounterUpdate::Next, display)
     }
 
-    fn op_fx18(&mut self, bus: &Bus, x: usize) -> (ProgramCounterUpdate, String) {
+    fn op_fx18(&self, bus: &mut Bus, x: usize) -> (ProgramCounterUpdate, String) {
         let display = format!("Set sound timer to V{x:X} ({})", self.v[x]);
-        (*bus.clock.sound_timer).store(self.v[x], std::sync::atomic::Ordering::SeqCst);
+        (*bus.clock.sound_timer).store(self.v[x], core::sync::atomic::Ordering::SeqCst);
+        bus.clock.sound_load.record(self.v[x]);
         (ProgramCounterUpdate::Next, display)
     }
 
-    fn op_fx15(&mut self, bus: &mut Bus, x: usize) -> (ProgramCounterUpdate, String) {
+    fn op_fx15(&self, bus: &mut Bus, x: usize) -> (ProgramCounterUpdate, String) {
         let display = format!("Set delay timer to V{x:X} ({})", self.v[x]);
         bus.clock.delay_timer = self.v[x];
+        bus.clock.delay_load.record(self.v[x]);
         (ProgramCounterUpdate::Next, display)
     }
 
@@ -357,7 +705,7 @@ impl Cpu {
         (ProgramCounterUpdate::Next, display)
     }
 
-    fn op_exa1(&mut self, bus: &Bus, x: usize) -> (ProgramCounterUpdate, String) {
+    fn op_exa1(&self, bus: &Bus, x: usize) -> (ProgramCounterUpdate, String) {
         let not_pressed = !bus.input.is_key_pressed(self.v[x]);
         let display = format!(
             "Skip next instr if key code {:#X} not pressed ({not_pressed})",
@@ -370,7 +718,7 @@ impl Cpu {
         }
     }
 
-    fn op_ex9e(&mut self, bus: &Bus, x: usize) -> (ProgramCounterUpdate, String) {
+    fn op_ex9e(&self, bus: &Bus, x: usize) -> (ProgramCounterUpdate, String) {
         let pressed = bus.input.is_key_pressed(self.v[x]);
         let display = format!("Skip instr if key {:#X} pressed ({pressed})", self.v[x]);
         if pressed {
@@ -386,44 +734,71 @@ impl Cpu {
         opcode: usize,
         x: usize,
         y: usize,
-    ) -> (ProgramCounterUpdate, String) {
-        if self.vblank_wait {
-            // spin wait for vblank
-            loop {
-                bus.clock.update();
-                if bus.clock.vblank_interrupt {
-                    break;
-                }
-            }
-        }
+    ) -> Result<(ProgramCounterUpdate, String), Chip8Error> {
         let n = opcode & 0xF;
         let x = usize::from(self.v[x]) % graphics::WIDTH;
-        let y = usize::from(self.v[y]) % graphics::HEIGHT;
+        let y = usize::from(self.v[y]) % bus.graphics.height();
         let display = format!(
             "Draw {n} byte sprite from addr {:#06X} at point ({x}, {y})",
             self.i
         );
+        let size = bus.memory.len();
+        if n > 0 {
+            self.address(self.i + n - 1, size)?;
+        }
         let mut collision = false;
         for i in 0..n {
-            let data = bus.memory[self.i + i];
-            collision |= bus.graphics.draw_byte(x, y + i, data);
+            let data = bus.memory[self.address(self.i + i, size)?];
+            collision |= if self.quirks.clip_sprites {
+                bus.graphics.draw_byte(x, y + i, data)
+            } else {
+                bus.graphics.draw_byte_wrapping(x, y + i, data)
+            };
         }
         self.v[0xF] = collision.into();
-        (ProgramCounterUpdate::Next, display)
+        Ok((ProgramCounterUpdate::Next, display))
+    }
+
+    fn op_dxyn_megachip(
+        &mut self,
+        bus: &mut Bus,
+        opcode: usize,
+        x: usize,
+        y: usize,
+    ) -> Result<(ProgramCounterUpdate, String), Chip8Error> {
+        let Some(megachip) = bus.megachip.as_mut() else {
+            return Err(Chip8Error::InvalidOpcode {
+                pc: self.pc,
+                opcode,
+            });
+        };
+        let (width, height) = megachip.sprite_size();
+        let x = usize::from(self.v[x]);
+        let y = usize::from(self.v[y]);
+        let display = format!(
+            "Draw {width}x{height} color sprite from addr {:#06X} at point ({x}, {y})",
+            self.i
+        );
+        let data = bus.memory.read_slice(self.i, width * height)?;
+        self.v[0xF] = megachip.draw_sprite(x, y, data).into();
+        Ok((ProgramCounterUpdate::Next, display))
     }
 
     fn op_cxnn(&mut self, x: usize, nn: u8) -> (ProgramCounterUpdate, String) {
-        let mut buf = [0u8; 1];
-        getrandom::getrandom(&mut buf).unwrap();
-        let display = format!("Set V{x:X} to {} [rand] AND {nn:#X}", buf[0]);
-        self.v[x] = buf[0] & nn;
+        let random = self.rng.next_u8();
+        let display = format!("Set V{x:X} to {random} [rand] AND {nn:#X}");
+        self.v[x] = random & nn;
         (ProgramCounterUpdate::Next, display)
     }
 
-    fn op_bnnn(&mut self, nnn: usize) -> (ProgramCounterUpdate, String) {
-        let display = format!("Jump to {nnn:#06X} + {:#06X}", self.v[0]);
+    fn op_bnnn(&self, x: usize, nnn: usize) -> (ProgramCounterUpdate, String) {
+        let register = if self.quirks.jump { x } else { 0 };
+        let display = format!(
+            "Jump to {nnn:#06X} + V{register:X} ({:#06X})",
+            self.v[register]
+        );
         (
-            ProgramCounterUpdate::Jump(nnn + usize::from(self.v[0])),
+            ProgramCounterUpdate::Jump(nnn + usize::from(self.v[register])),
             display,
         )
     }
@@ -434,7 +809,7 @@ impl Cpu {
         (ProgramCounterUpdate::Next, display)
     }
 
-    fn op_9xy0(&mut self, x: usize, y: usize) -> (ProgramCounterUpdate, String) {
+    fn op_9xy0(&self, x: usize, y: usize) -> (ProgramCounterUpdate, String) {
         let display = format!(
             "If V{x:X} ({}) != V{y:X} ({}), skip next instr",
             self.v[x], self.v[y]
@@ -447,7 +822,7 @@ impl Cpu {
     }
 
     fn op_8xye(&mut self, x: usize, y: usize) -> (ProgramCounterUpdate, String) {
-        if self.shift_quirk_enabled {
+        if self.quirks.shift {
             self.v[x] = self.v[y];
         }
         let overflow = (self.v[x] & 0x80) >> 7;
@@ -471,7 +846,7 @@ impl Cpu {
     }
 
     fn op_8xy6(&mut self, x: usize, y: usize) -> (ProgramCounterUpdate, String) {
-        if self.shift_quirk_enabled {
+        if self.quirks.shift {
             self.v[x] = self.v[y];
         }
         let overflow = self.v[x] & 1;
@@ -513,7 +888,9 @@ impl Cpu {
             self.v[x], self.v[y]
         );
         self.v[x] ^= self.v[y];
-        self.v[0xF] = 0;
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
         (ProgramCounterUpdate::Next, display)
     }
 
@@ -523,7 +900,9 @@ impl Cpu {
             self.v[x], self.v[y]
         );
         self.v[x] &= self.v[y];
-        self.v[0xF] = 0;
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
         (ProgramCounterUpdate::Next, display)
     }
 
@@ -533,7 +912,9 @@ impl Cpu {
             self.v[x], self.v[y]
         );
         self.v[x] |= self.v[y];
-        self.v[0xF] = 0;
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
         (ProgramCounterUpdate::Next, display)
     }
 
@@ -555,7 +936,7 @@ impl Cpu {
         (ProgramCounterUpdate::Next, display)
     }
 
-    fn op_5xy0(&mut self, x: usize, y: usize) -> (ProgramCounterUpdate, String) {
+    fn op_5xy0(&self, x: usize, y: usize) -> (ProgramCounterUpdate, String) {
         let display = format!(
             "If V{x:X} ({}) == V{y:X} ({}), skip next instr",
             self.v[x], self.v[y]
@@ -567,7 +948,43 @@ impl Cpu {
         }
     }
 
-    fn op_4xnn(&mut self, x: usize, nn: u8) -> (ProgramCounterUpdate, String) {
+    /// Returns the registers from VX to VY, which `5xy2` and `5xy3` walk in
+    /// descending order if X is greater than Y.
+    fn register_range(x: usize, y: usize) -> impl Iterator<Item = usize> {
+        (0..=x.abs_diff(y)).map(move |offset| if x <= y { x + offset } else { x - offset })
+    }
+
+    fn op_5xy2(
+        &self,
+        bus: &mut Bus,
+        x: usize,
+        y: usize,
+    ) -> Result<(ProgramCounterUpdate, String), Chip8Error> {
+        let display = format!("Store V{x:X} to V{y:X} starting at I");
+        let size = bus.memory.len();
+        self.address(self.i + x.abs_diff(y), size)?;
+        for (offset, r) in Self::register_range(x, y).enumerate() {
+            bus.write_byte(self.address(self.i + offset, size)?, self.v[r]);
+        }
+        Ok((ProgramCounterUpdate::Next, display))
+    }
+
+    fn op_5xy3(
+        &mut self,
+        bus: &mut Bus,
+        x: usize,
+        y: usize,
+    ) -> Result<(ProgramCounterUpdate, String), Chip8Error> {
+        let display = format!("Read memory at I into V{x:X} to V{y:X}");
+        let size = bus.memory.len();
+        self.address(self.i + x.abs_diff(y), size)?;
+        for (offset, r) in Self::register_range(x, y).enumerate() {
+            self.v[r] = bus.read_byte(self.address(self.i + offset, size)?);
+        }
+        Ok((ProgramCounterUpdate::Next, display))
+    }
+
+    fn op_4xnn(&self, x: usize, nn: u8) -> (ProgramCounterUpdate, String) {
         let display = format!("If V{x:X} ({}) != {nn}, skip next instr", self.v[x]);
         if self.v[x] == nn {
             (ProgramCounterUpdate::Next, display)
@@ -576,7 +993,7 @@ impl Cpu {
         }
     }
 
-    fn op_3xnn(&mut self, x: usize, nn: u8) -> (ProgramCounterUpdate, String) {
+    fn op_3xnn(&self, x: usize, nn: u8) -> (ProgramCounterUpdate, String) {
         let display = format!("If V{x:X} ({}) == {nn}, skip next instr", self.v[x]);
         if self.v[x] == nn {
             (ProgramCounterUpdate::SkipNext, display)
@@ -585,11 +1002,47 @@ impl Cpu {
         }
     }
 
-    fn op_2nnn(&mut self, nnn: usize) -> (ProgramCounterUpdate, String) {
-        self.stack[self.sp] = self.pc + 2;
+    fn op_2nnn(&mut self, nnn: usize) -> Result<(ProgramCounterUpdate, String), Chip8Error> {
+        if !self.quirks.stack_depth.allows(self.sp) {
+            return Err(Chip8Error::StackOverflow { pc: self.pc });
+        }
+        if self.sp < self.stack.len() {
+            self.stack[self.sp] = self.pc + 2;
+        } else {
+            self.stack.resize(self.sp, 0);
+            self.stack.push(self.pc + 2);
+        }
         self.sp += 1;
         let display = format!("Call subroutine at {nnn:#06X}");
-        (ProgramCounterUpdate::Jump(nnn), display)
+        Ok((ProgramCounterUpdate::Jump(nnn), display))
+    }
+
+    fn op_00cn(bus: &mut Bus, n: usize) -> (ProgramCounterUpdate, String) {
+        bus.graphics.scroll_down(n);
+        (ProgramCounterUpdate::Next, format!("Scroll down {n} lines"))
+    }
+
+    fn op_00dn(bus: &mut Bus, n: usize) -> (ProgramCounterUpdate, String) {
+        bus.graphics.scroll_up(n);
+        (ProgramCounterUpdate::Next, format!("Scroll up {n} lines"))
+    }
+
+    fn op_00fb(bus: &mut Bus) -> (ProgramCounterUpdate, String) {
+        bus.graphics.scroll_right(4);
+        (ProgramCounterUpdate::Next, "Scroll right 4 pixels".into())
+    }
+
+    fn op_00fc(bus: &mut Bus) -> (ProgramCounterUpdate, String) {
+        bus.graphics.scroll_left(4);
+        (ProgramCounterUpdate::Next, "Scroll left 4 pixels".into())
+    }
+
+    fn op_00fd(&self) -> (ProgramCounterUpdate, String) {
+        // jumping to itself halts the program, see `StepResult::Loop`
+        (
+            ProgramCounterUpdate::Jump(self.pc),
+            "Exit the interpreter".into(),
+        )
     }
 
     fn op_00e0(bus: &mut Bus) -> (ProgramCounterUpdate, String) {
@@ -598,10 +1051,25 @@ impl Cpu {
         (ProgramCounterUpdate::Next, display)
     }
 
-    fn op_00ee(&mut self) -> (ProgramCounterUpdate, String) {
+    fn op_00ee(&mut self) -> Result<(ProgramCounterUpdate, String), Chip8Error> {
+        if self.sp == 0 {
+            return Err(Chip8Error::StackUnderflow { pc: self.pc });
+        }
         self.sp -= 1;
-        let display = format!("Return to addr {:#06X}", self.stack[self.sp]);
-        (ProgramCounterUpdate::Jump(self.stack[self.sp]), display)
+        let address = self.stack.get(self.sp).copied().unwrap_or_default();
+        let display = format!("Return to addr {address:#06X}");
+        Ok((ProgramCounterUpdate::Jump(address), display))
+    }
+
+    /// Enters the two-page hires mode and skips the interpreter patch that
+    /// hires ROMs carry in front of the program.
+    fn op_1260(bus: &mut Bus) -> (ProgramCounterUpdate, String) {
+        bus.graphics.set_hires(true);
+        let display = format!(
+            "Enter hires mode and jump to addr {:#06X}",
+            memory::HIRES_START
+        );
+        (ProgramCounterUpdate::Jump(memory::HIRES_START), display)
     }
 
     fn op_1nnn(nnn: usize) -> (ProgramCounterUpdate, String) {
@@ -609,9 +1077,662 @@ impl Cpu {
         (ProgramCounterUpdate::Jump(nnn), display)
     }
 
+    /// Executes the Mega-Chip extensions of the `0___` opcodes. Returns
+    /// [`None`] if the extensions are disabled or the opcode is not one of
+    /// them.
+    fn op_megachip(
+        &mut self,
+        opcode: usize,
+        bus: &mut Bus,
+    ) -> Result<Option<(ProgramCounterUpdate, String)>, Chip8Error> {
+        let Some(megachip) = bus.megachip.as_mut() else {
+            return Ok(None);
+        };
+        let nn = u8::try_from(opcode & 0x00FF).unwrap();
+        let result = match opcode & 0xFF00 {
+            0x0000 => match opcode {
+                // 0010
+                0x0010 => {
+                    megachip.set_active(false);
+                    (ProgramCounterUpdate::Next, "Disable Mega-Chip mode".into())
+                }
+
+                // 0011
+                0x0011 => {
+                    megachip.set_active(true);
+                    (ProgramCounterUpdate::Next, "Enable Mega-Chip mode".into())
+                }
+
+                // 00Bn
+                0x00B0..=0x00BF if megachip.is_active() => {
+                    let n = opcode & 0x000F;
+                    megachip.scroll_up(n);
+                    (ProgramCounterUpdate::Next, format!("Scroll up {n} lines"))
+                }
+
+                // 00E0
+                0x00E0 if megachip.is_active() => {
+                    megachip.present();
+                    (ProgramCounterUpdate::Next, "Update the screen".into())
+                }
+
+                _ => return Ok(None),
+            },
+
+            // 01nn nnnn
+            0x0100 => {
+                let low = bus.memory.read_slice(self.pc + 2, 2)?;
+                self.i = usize::from(nn) << 16 | usize::from(low[0]) << 8 | usize::from(low[1]);
+                let display = format!("Set I register to {:#08X}", self.i);
+                (ProgramCounterUpdate::Long, display)
+            }
+
+            // 02nn
+            0x0200 => {
+                let colors = bus.memory.read_slice(self.i, 4 * usize::from(nn))?;
+                megachip.load_palette(colors);
+                let display = format!("Load {nn} palette colors from addr {:#06X}", self.i);
+                (ProgramCounterUpdate::Next, display)
+            }
+
+            // 03nn
+            0x0300 => {
+                megachip.set_sprite_width(nn);
+                (
+                    ProgramCounterUpdate::Next,
+                    format!("Set sprite width to {nn}"),
+                )
+            }
+
+            // 04nn
+            0x0400 => {
+                megachip.set_sprite_height(nn);
+                (
+                    ProgramCounterUpdate::Next,
+                    format!("Set sprite height to {nn}"),
+                )
+            }
+
+            // 05nn
+            0x0500 => {
+                megachip.set_alpha(nn);
+                (
+                    ProgramCounterUpdate::Next,
+                    format!("Set screen alpha to {nn}"),
+                )
+            }
+
+            // 060n
+            0x0600 if nn <= 1 => {
+                let header = bus.memory.read_slice(self.i, Sound::HEADER_SIZE)?;
+                let (sample_rate, len) = Sound::parse_header(header.try_into().unwrap());
+                let samples = bus
+                    .memory
+                    .read_slice(self.i + Sound::HEADER_SIZE, len)?
+                    .to_vec();
+                megachip.play_sound(Sound {
+                    sample_rate,
+                    samples,
+                    looping: nn == 0,
+                });
+                let display = format!("Play sound at addr {:#06X}", self.i);
+                (ProgramCounterUpdate::Next, display)
+            }
+
+            // 0700
+            0x0700 if nn == 0 => {
+                megachip.stop_sound();
+                (ProgramCounterUpdate::Next, "Stop the sound".into())
+            }
+
+            // 080n
+            0x0800 => {
+                let Some(blend_mode) = BlendMode::from_index(nn) else {
+                    return Ok(None);
+                };
+                megachip.set_blend_mode(blend_mode);
+                let display = format!("Set blend mode to {blend_mode:?}");
+                (ProgramCounterUpdate::Next, display)
+            }
+
+            // 09nn
+            0x0900 => {
+                megachip.set_collision_color(nn);
+                (
+                    ProgramCounterUpdate::Next,
+                    format!("Set collision color to {nn}"),
+                )
+            }
+
+            _ => return Ok(None),
+        };
+        Ok(Some(result))
+    }
+
     fn op_fx0a(bus: &mut Bus, x: usize) -> (ProgramCounterUpdate, String) {
         let display = format!("Store next key press in V{x:X}");
         bus.input.request_key_press(x);
         (ProgramCounterUpdate::Next, display)
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{
+        quirks::{StackDepth, Variant},
+        Chip8,
+    };
+
+    #[test]
+    fn test_invalid_opcode() {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_data(vec![0x60, 0x05, 0x80, 0x08]).unwrap();
+
+        assert_eq!(chip8.step(), Ok(StepResult::Continue));
+        assert_eq!(
+            chip8.step(),
+            Err(Chip8Error::InvalidOpcode {
+                pc: 0x202,
+                opcode: 0x8008
+            })
+        );
+        // The program counter stays on the faulting instruction
+        assert_eq!(chip8.processor.pc, 0x202);
+    }
+
+    #[test]
+    fn test_step_n() {
+        let mut chip8 = Chip8::new();
+        // 6001: V0 = 1, 7001: V0 += 1, D005: draw, 7001: V0 += 1, 1208: jump
+        // to self
+        let rom = vec![0x60, 0x01, 0x70, 0x01, 0xD0, 0x05, 0x70, 0x01, 0x12, 0x08];
+        chip8.load_rom_data(rom).unwrap();
+
+        let stop = |executed, stop| BatchResult { executed, stop };
+        assert_eq!(chip8.step_n(1), stop(1, BatchStop::Completed));
+        assert_eq!(chip8.step_n(10), stop(2, BatchStop::DisplayUpdated));
+        assert_eq!(
+            chip8.step_until(10, |pc| pc == 0x208),
+            stop(1, BatchStop::Breakpoint)
+        );
+        assert_eq!(chip8.step_n(10), stop(1, BatchStop::Loop));
+        assert_eq!(chip8.processor.v[0], 3);
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_serde_round_trip() {
+        let mut chip8 = Chip8::new();
+        // 6005: V0 = 5, A123: I = 0x123
+        chip8.load_rom_data(vec![0x60, 0x05, 0xA1, 0x23]).unwrap();
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+
+        let json = serde_json::to_string(&chip8).unwrap();
+        let restored: Chip8 = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.processor.v, chip8.processor.v);
+        assert_eq!(
+            (restored.processor.pc, restored.processor.i),
+            (0x204, 0x123)
+        );
+        assert_eq!(restored.processor.instructions[0].opcode, 0xA123);
+        assert_eq!(restored.processor.quirks, chip8.processor.quirks);
+        assert_eq!(restored.bus.memory[0x201], 0x05);
+    }
+
+    #[test]
+    fn test_stack_errors() {
+        let mut chip8 = Chip8::new();
+        // 00EE: return with an empty stack
+        chip8.load_rom_data(vec![0x00, 0xEE]).unwrap();
+        assert_eq!(chip8.step(), Err(Chip8Error::StackUnderflow { pc: 0x200 }));
+
+        // 2200: call itself until the stack is full
+        chip8.reset_and_load(vec![0x22, 0x00]).unwrap();
+        for _ in 0..16 {
+            assert_eq!(chip8.step(), Ok(StepResult::Loop));
+            chip8.processor.pc = 0x200;
+        }
+        assert_eq!(chip8.step(), Err(Chip8Error::StackOverflow { pc: 0x200 }));
+
+        // The depth is configurable, or unlimited
+        chip8.processor.quirks.stack_depth = StackDepth::Limited(4);
+        chip8.reset_and_load(vec![0x22, 0x00]).unwrap();
+        for _ in 0..4 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.step(), Err(Chip8Error::StackOverflow { pc: 0x200 }));
+
+        chip8.processor.quirks.stack_depth = StackDepth::Unlimited;
+        for _ in 0..100 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.processor.sp, 104);
+    }
+
+    #[test]
+    fn test_memory_out_of_bounds() {
+        let mut chip8 = Chip8::new();
+        // AFFE: I = 0xFFE, D005: draw 5 bytes, F255: store V0 to V2
+        chip8
+            .load_rom_data(vec![0xAF, 0xFE, 0xD0, 0x05, 0xF2, 0x55])
+            .unwrap();
+        chip8.step().unwrap();
+        assert_eq!(
+            chip8.step(),
+            Err(Chip8Error::MemoryOutOfBounds { addr: 0x1002 })
+        );
+
+        // With the memory wrap quirk, addresses wrap around to the font data
+        chip8.processor.quirks.memory_wrap = true;
+        assert_eq!(chip8.step(), Ok(StepResult::Continue));
+        chip8.processor.v[2] = 0xAB;
+        assert_eq!(chip8.step(), Ok(StepResult::Continue));
+        assert_eq!(chip8.bus.memory[0x000], 0xAB);
+        assert_eq!(chip8.processor.i, 0x1001);
+    }
+
+    #[test]
+    fn test_seeded_random() {
+        // C0FF: V0 = random, C1FF: V1 = random
+        let rom = vec![0xC0, 0xFF, 0xC1, 0xFF];
+        let run = |seed| {
+            let mut chip8 = Chip8::new_with_rng(crate::rng::Rng::new(seed));
+            chip8.load_rom_data(rom.clone()).unwrap();
+            chip8.step().unwrap();
+            chip8.step().unwrap();
+            (chip8.processor.v[0], chip8.processor.v[1])
+        };
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7), run(8));
+
+        // A reset system produces the same numbers again
+        let mut chip8 = Chip8::new_with_rng(crate::rng::Rng::new(7));
+        chip8.load_rom_data(rom.clone()).unwrap();
+        chip8.step().unwrap();
+        chip8.reset_and_load(rom.clone()).unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.processor.v[0], run(7).0);
+    }
+
+    /// Loads the given program and executes `steps` instructions.
+    fn run(rom: &[u8], steps: usize) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_data(rom.to_vec()).unwrap();
+        for _ in 0..steps {
+            chip8.step().unwrap();
+        }
+        chip8
+    }
+
+    #[test]
+    fn test_skip_instructions() {
+        // 6005: V0 = 5, 6105: V1 = 5, then a skip followed by 6201: V2 = 1
+        let cases: [([u8; 2], bool); 8] = [
+            ([0x30, 0x05], true),  // 3xnn: skip if V0 == 5
+            ([0x30, 0x06], false), // 3xnn: skip if V0 == 6
+            ([0x40, 0x06], true),  // 4xnn: skip if V0 != 6
+            ([0x40, 0x05], false), // 4xnn: skip if V0 != 5
+            ([0x50, 0x10], true),  // 5xy0: skip if V0 == V1
+            ([0x50, 0x20], false), // 5xy0: skip if V0 == V2
+            ([0x90, 0x20], true),  // 9xy0: skip if V0 != V2
+            ([0x90, 0x10], false), // 9xy0: skip if V0 != V1
+        ];
+        for (skip, skipped) in cases {
+            let rom = [0x60, 0x05, 0x61, 0x05, skip[0], skip[1], 0x62, 0x01];
+            let chip8 = run(&rom, 3);
+            let expected_pc = if skipped { 0x208 } else { 0x206 };
+            assert_eq!(chip8.processor.pc, expected_pc, "{skip:02X?}");
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        // Executes 60xx: V0 = a, 61xx: V1 = b followed by the given opcode
+        let exec = |a: u8, b: u8, op: [u8; 2]| {
+            let chip8 = run(&[0x60, a, 0x61, b, op[0], op[1]], 3);
+            (chip8.processor.v[0], chip8.processor.v[0xF])
+        };
+
+        assert_eq!(exec(0x12, 0x34, [0x70, 0xFF]).0, 0x11); // 7xnn wraps
+        assert_eq!(exec(0x12, 0x34, [0x80, 0x10]).0, 0x34); // 8xy0
+        assert_eq!(exec(0x0F, 0xF0, [0x80, 0x11]), (0xFF, 0)); // 8xy1
+        assert_eq!(exec(0x3C, 0x0F, [0x80, 0x12]), (0x0C, 0)); // 8xy2
+        assert_eq!(exec(0x3C, 0x0F, [0x80, 0x13]), (0x33, 0)); // 8xy3
+        assert_eq!(exec(0xF0, 0x20, [0x80, 0x14]), (0x10, 1)); // 8xy4 carry
+        assert_eq!(exec(0x10, 0x20, [0x80, 0x14]), (0x30, 0)); // 8xy4
+        assert_eq!(exec(0x30, 0x10, [0x80, 0x15]), (0x20, 1)); // 8xy5
+        assert_eq!(exec(0x10, 0x30, [0x80, 0x15]), (0xE0, 0)); // 8xy5 borrow
+        assert_eq!(exec(0x05, 0x00, [0x80, 0x16]), (0x02, 1)); // 8xy6
+        assert_eq!(exec(0x10, 0x30, [0x80, 0x17]), (0x20, 1)); // 8xy7
+        assert_eq!(exec(0x30, 0x10, [0x80, 0x17]), (0xE0, 0)); // 8xy7 borrow
+        assert_eq!(exec(0x81, 0x00, [0x80, 0x1E]), (0x02, 1)); // 8xyE
+    }
+
+    #[test]
+    fn test_shift_quirk() {
+        // 6001: V0 = 1, 6106: V1 = 6, 8016: V0 = V1 >> 1
+        let rom = [0x60, 0x01, 0x61, 0x06, 0x80, 0x16];
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_data(rom.to_vec()).unwrap();
+        chip8.processor.quirks.shift = true;
+        for _ in 0..3 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.processor.v[0], 3);
+        assert_eq!(chip8.processor.v[0xF], 0);
+
+        // Without the quirk, V0 itself is shifted
+        assert_eq!(run(&rom, 3).processor.v[0], 0);
+    }
+
+    #[test]
+    fn test_jumps_and_calls() {
+        // 1208: jump over 6001 and 6002 to 6003
+        let chip8 = run(&[0x12, 0x06, 0x60, 0x01, 0x60, 0x02, 0x60, 0x03], 2);
+        assert_eq!(chip8.processor.v[0], 3);
+
+        // 2206: call 0x206, which returns with 00EE to 6001
+        let chip8 = run(&[0x22, 0x06, 0x60, 0x01, 0x00, 0x00, 0x00, 0xEE], 1);
+        assert_eq!(chip8.processor.pc, 0x206);
+        assert_eq!(chip8.processor.sp, 1);
+        let mut chip8 = chip8;
+        chip8.step().unwrap();
+        assert_eq!(chip8.processor.pc, 0x202);
+        assert_eq!(chip8.processor.sp, 0);
+
+        // 6004: V0 = 4, B202: jump to 0x202 + V0
+        let chip8 = run(&[0x60, 0x04, 0xB2, 0x02], 2);
+        assert_eq!(chip8.processor.pc, 0x206);
+    }
+
+    #[test]
+    fn test_index_and_memory() {
+        // A300: I = 0x300, 60FE: V0 = 254, F01E: I += V0
+        let chip8 = run(&[0xA3, 0x00, 0x60, 0xFE, 0xF0, 0x1E], 3);
+        assert_eq!(chip8.processor.i, 0x3FE);
+
+        // 600A: V0 = 10, F029: I = address of the sprite for digit A
+        let chip8 = run(&[0x60, 0x0A, 0xF0, 0x29], 2);
+        assert_eq!(chip8.processor.i, 50);
+
+        // 60FE: V0 = 254, A300: I = 0x300, F033: store BCD of V0
+        let chip8 = run(&[0x60, 0xFE, 0xA3, 0x00, 0xF0, 0x33], 3);
+        assert_eq!(chip8.bus.memory[0x300], 2);
+        assert_eq!(chip8.bus.memory[0x301], 5);
+        assert_eq!(chip8.bus.memory[0x302], 4);
+
+        // 6011, 6122: V0, V1 = 0x11, 0x22, A300: I = 0x300, F155: store
+        // V0 and V1, A300: I = 0x300, 6000, 6100: clear, F165: load them back
+        let rom = [
+            0x60, 0x11, 0x61, 0x22, 0xA3, 0x00, 0xF1, 0x55, 0xA3, 0x00, 0x60, 0x00, 0x61, 0x00,
+            0xF1, 0x65,
+        ];
+        let chip8 = run(&rom, 8);
+        assert_eq!(chip8.bus.memory[0x300], 0x11);
+        assert_eq!(chip8.bus.memory[0x301], 0x22);
+        assert_eq!(chip8.processor.v[..2], [0x11, 0x22]);
+        assert_eq!(chip8.processor.i, 0x302);
+    }
+
+    #[test]
+    fn test_timers() {
+        // 6020: V0 = 32, F015: delay = V0, F018: sound = V0, F107: V1 = delay
+        let chip8 = run(&[0x60, 0x20, 0xF0, 0x15, 0xF0, 0x18, 0xF1, 0x07], 4);
+        assert!(chip8.processor.v[1] >= 31);
+        assert!(
+            chip8
+                .bus
+                .clock
+                .sound_timer
+                .load(core::sync::atomic::Ordering::SeqCst)
+                >= 31
+        );
+    }
+
+    #[test]
+    fn test_audio() {
+        // A208: I = 0x208, F002: load pattern, 6070: V0 = 0x70, F03A: pitch = V0
+        let mut rom = vec![0xA2, 0x08, 0xF0, 0x02, 0x60, 0x70, 0xF0, 0x3A];
+        rom.extend(0..16);
+        let chip8 = run(&rom, 4);
+        let pattern: Vec<u8> = (0..16).collect();
+        assert_eq!(chip8.bus.audio.pattern.unwrap().as_slice(), pattern);
+        assert_eq!(chip8.bus.audio.pitch, 0x70);
+    }
+
+    #[test]
+    fn test_xochip_instructions() {
+        // 6011, 6122, 6233: V0 to V2 = 0x11, 0x22, 0x33, A300: I = 0x300,
+        // 5022: store V0 to V2, 5203: load V2 down to V0 back
+        let rom = [
+            0x60, 0x11, 0x61, 0x22, 0x62, 0x33, 0xA3, 0x00, 0x50, 0x22, 0x52, 0x03,
+        ];
+        let chip8 = run(&rom, 6);
+        let memory = &chip8.bus.memory;
+        assert_eq!(
+            [memory[0x300], memory[0x301], memory[0x302]],
+            [0x11, 0x22, 0x33]
+        );
+        assert_eq!(chip8.processor.v[..3], [0x33, 0x22, 0x11]);
+        // I is left unchanged
+        assert_eq!(chip8.processor.i, 0x300);
+
+        // F000 0ABC: I = 0xABC, F201: select the second plane
+        let chip8 = run(&[0xF0, 0x00, 0x0A, 0xBC, 0xF2, 0x01], 2);
+        assert_eq!(chip8.processor.i, 0xABC);
+        assert_eq!(chip8.processor.pc, 0x206);
+        assert_eq!(chip8.bus.graphics.selected_planes(), 2);
+
+        // F000 F000: I = 0xF000, the address is not an instruction itself
+        let chip8 = run(&[0xF0, 0x00, 0xF0, 0x00], 1);
+        assert_eq!(chip8.processor.i, 0xF000);
+        assert_eq!(chip8.processor.pc, 0x204);
+
+        // 3000: skip if V0 == 0, over F000 1234 and its address
+        let chip8 = run(&[0x30, 0x00, 0xF0, 0x00, 0x12, 0x34], 1);
+        assert_eq!(chip8.processor.pc, 0x206);
+
+        // 00FD: exit the interpreter
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_data(vec![0x00, 0xFD]).unwrap();
+        assert_eq!(chip8.step(), Ok(StepResult::Loop));
+        assert_eq!(chip8.processor.pc, 0x200);
+    }
+
+    #[test]
+    fn test_keys() {
+        // 6005: V0 = 5, E09E: skip if key 5 is pressed, E0A1: skip if not
+        let rom = [0x60, 0x05, 0xE0, 0x9E, 0x00, 0xE0, 0xE0, 0xA1];
+        let mut chip8 = run(&rom, 1);
+        chip8.update_key_state(0x5, true);
+        chip8.step().unwrap();
+        assert_eq!(chip8.processor.pc, 0x206);
+        chip8.step().unwrap();
+        assert_eq!(chip8.processor.pc, 0x208);
+
+        // F20A: wait for a key and store it in V2, while the timers keep
+        // ticking
+        let mut chip8 = run(&[0xF2, 0x0A], 1);
+        chip8.bus.clock.delay_timer = 3;
+        assert_eq!(chip8.step(), Ok(StepResult::WaitingForKey));
+        chip8.tick_timers();
+        chip8.tick_timers();
+        assert_eq!(chip8.step(), Ok(StepResult::WaitingForKey));
+        assert_eq!(chip8.timers(), (1, 0));
+        chip8.update_key_state(0xB, true);
+        chip8.step().unwrap();
+        assert_eq!(chip8.processor.v[2], 0xB);
+    }
+
+    #[test]
+    fn test_vblank_wait() {
+        // F029: I = sprite for digit 0, D005: draw it twice
+        let mut chip8 = Chip8::new();
+        chip8.processor.quirks.vblank_wait = true;
+        chip8
+            .load_rom_data(vec![0xF0, 0x29, 0xD0, 0x05, 0xD0, 0x05])
+            .unwrap();
+        chip8.step().unwrap();
+
+        // Steps never raise the vblank interrupt, only timer ticks do, and
+        // each of them lets a single sprite through
+        assert_eq!(chip8.step(), Ok(StepResult::WaitingForVblank));
+        chip8.tick_timers();
+        assert_eq!(chip8.step(), Ok(StepResult::Continue));
+        assert_eq!(chip8.step(), Ok(StepResult::WaitingForVblank));
+        assert_eq!(chip8.processor.pc, 0x204);
+        chip8.tick_timers();
+        assert_eq!(chip8.step(), Ok(StepResult::Continue));
+        assert_eq!(chip8.processor.pc, 0x206);
+    }
+
+    #[test]
+    fn test_draw() {
+        // F029: I = sprite for digit 0, D005: draw it twice, then 00E0
+        let rom = [0xF0, 0x29, 0xD0, 0x05, 0xD0, 0x05, 0x00, 0xE0];
+        let mut chip8 = run(&rom, 2);
+        assert!(chip8.bus.graphics.as_rgb8().iter().any(|&c| c != 0));
+        assert_eq!(chip8.processor.v[0xF], 0);
+
+        // Drawing the same sprite again erases it and reports a collision
+        chip8.step().unwrap();
+        assert_eq!(chip8.processor.v[0xF], 1);
+        assert!(chip8.bus.graphics.as_rgb8().iter().all(|&c| c == 0));
+
+        chip8.bus.graphics.draw_byte(0, 0, 0xFF);
+        chip8.step().unwrap();
+        assert!(chip8.bus.graphics.as_rgb8().iter().all(|&c| c == 0));
+    }
+
+    #[test]
+    fn test_scroll() {
+        // F029: I = sprite for digit 0, D005: draw it, 00C2: scroll down 2,
+        // 00FB: scroll right 4, 00FC: scroll left 4, 00D2: scroll up 2
+        let rom = [
+            0xF0, 0x29, 0xD0, 0x05, 0x00, 0xC2, 0x00, 0xFB, 0x00, 0xFC, 0x00, 0xD2,
+        ];
+        let mut chip8 = run(&rom, 4);
+        let graphics = &chip8.bus.graphics;
+        assert_eq!((graphics.pixel(4, 2), graphics.pixel(0, 0)), (1, 0));
+
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        let drawn = run(&rom, 2).bus.graphics;
+        assert_eq!(chip8.bus.graphics, drawn);
+    }
+
+    #[test]
+    fn test_index_overflow() {
+        // AFFF: I = 0xFFF, 6002: V0 = 2, 6F07: VF = 7, F01E: I += V0
+        let rom = [0xAF, 0xFF, 0x60, 0x02, 0x6F, 0x07, 0xF0, 0x1E];
+        let chip8 = run(&rom, 4);
+        assert_eq!((chip8.processor.i, chip8.processor.v[0xF]), (0x001, 7));
+
+        // With the quirk, the overflow is reported in VF
+        let mut chip8 = Chip8::new();
+        chip8.processor.quirks.index_overflow = true;
+        chip8.load_rom_data(rom.to_vec()).unwrap();
+        for _ in 0..4 {
+            chip8.step().unwrap();
+        }
+        assert_eq!((chip8.processor.i, chip8.processor.v[0xF]), (0x001, 1));
+        chip8.processor.pc = 0x206;
+        chip8.step().unwrap();
+        assert_eq!((chip8.processor.i, chip8.processor.v[0xF]), (0x003, 0));
+    }
+
+    #[test]
+    fn test_clip_quirk() {
+        // 603E: V0 = 62, 611F: V1 = 31, F229: I = sprite for digit V2 = 0
+        // (0xF0, 0x90, ...), D012: draw its first two rows at (62, 31)
+        let rom = [0x60, 0x3E, 0x61, 0x1F, 0xF2, 0x29, 0xD0, 0x12];
+        let chip8 = run(&rom, 4);
+        let graphics = &chip8.bus.graphics;
+        assert_eq!((graphics.pixel(62, 31), graphics.pixel(63, 31)), (1, 1));
+        assert_eq!((graphics.pixel(0, 31), graphics.pixel(62, 0)), (0, 0));
+
+        // Without clipping, the sprite wraps around to the opposite edges
+        let mut chip8 = Chip8::new();
+        chip8.set_variant(Variant::XoChip);
+        chip8.load_rom_data(rom.to_vec()).unwrap();
+        for _ in 0..4 {
+            chip8.step().unwrap();
+        }
+        let graphics = &chip8.bus.graphics;
+        assert_eq!((graphics.pixel(0, 31), graphics.pixel(1, 31)), (1, 1));
+        assert_eq!((graphics.pixel(62, 0), graphics.pixel(1, 0)), (1, 1));
+        assert_eq!(graphics.pixel(0, 0), 0);
+    }
+
+    #[test]
+    fn test_hires() {
+        // 1260: enter hires mode, skipping the interpreter patch, then at
+        // 0x2C0: 613F: V1 = 63, F029: I = sprite for digit 0, D011: draw its
+        // first row at (0, 63), 0230: clear
+        let mut rom = vec![0x12, 0x60];
+        rom.resize(0xC0, 0);
+        rom.extend([0x61, 0x3F, 0xF0, 0x29, 0xD0, 0x11, 0x02, 0x30]);
+        let mut chip8 = Chip8::new();
+        chip8.set_variant(Variant::HiresChip8);
+        chip8.load_rom_data(rom.clone()).unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.processor.pc, memory::HIRES_START);
+        assert!(chip8.bus.graphics.is_hires());
+
+        // the variant waits for the vblank interrupt before drawing
+        chip8.tick_timers();
+        for _ in 0..3 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.bus.graphics.pixel(0, 63), 1);
+        chip8.step().unwrap();
+        assert!(chip8.bus.graphics.iter().all(|(_, _, index)| index == 0));
+
+        // Without the quirk, 1260 is a plain jump
+        chip8.reset_keep_rom().unwrap();
+        assert!(!chip8.bus.graphics.is_hires());
+        chip8.set_variant(Variant::Chip8);
+        chip8.step().unwrap();
+        assert_eq!(chip8.processor.pc, 0x260);
+    }
+
+    #[test]
+    fn test_updates_display() {
+        // Exactly the opcodes that can change the display are reported
+        for opcode in 0..=0xFFFF {
+            let mut processor = Cpu::new();
+            let mut bus = Bus::default();
+            bus.graphics.set_hires(true);
+            bus.graphics.draw_byte(8, 8, 0xFF);
+            bus.memory[0x300] = 0xFF;
+            processor.i = 0x300;
+            processor.v[0] = 8;
+            let before = bus.graphics;
+            let result = processor.process_opcode(opcode, &mut bus);
+            // selecting the planes of the next drawing leaves the pixels alone
+            bus.graphics.select_planes(before.selected_planes());
+            if bus.graphics != before {
+                assert!(updates_display(opcode), "{opcode:04X}");
+            }
+            if updates_display(opcode) {
+                assert!(result.is_ok(), "{opcode:04X}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_all_opcodes() {
+        // Every opcode either executes or raises an error, without panicking
+        for opcode in 0..=0xFFFF {
+            let mut processor = Cpu::new();
+            let mut bus = Bus::default();
+            processor.sp = 1;
+            match processor.process_opcode(opcode, &mut bus) {
+                Ok(_) | Err(Chip8Error::InvalidOpcode { .. }) => {}
+                Err(err) => panic!("{opcode:04X} raised {err}"),
+            }
+        }
+    }
+}
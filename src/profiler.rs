@@ -0,0 +1,160 @@
+//! This module counts how often every address is executed, read and written,
+//! so ROM developers can find the hot spots of their programs.
+//!
+//! Frontends use these counts to overlay a heatmap on the disassembly and
+//! memory views, and to list the hottest instructions. Profiling is disabled
+//! by default, since counting slows down every step.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+
+/// Execution and memory access counts per address.
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    enabled: bool,
+
+    /// How often the instruction at every address was executed.
+    executions: BTreeMap<usize, u64>,
+
+    /// How often every address was read by `Dxyn` or `Fx65`.
+    reads: BTreeMap<usize, u64>,
+
+    /// How often every address was written by `Fx33` or `Fx55`.
+    writes: BTreeMap<usize, u64>,
+}
+
+impl Profiler {
+    /// Creates a new, disabled [`Profiler`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether the profiler is counting.
+    #[must_use]
+    pub const fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Starts or stops counting. The counts so far are kept.
+    pub const fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Discards all counts.
+    pub fn clear(&mut self) {
+        self.executions.clear();
+        self.reads.clear();
+        self.writes.clear();
+    }
+
+    /// Counts the execution of the given opcode at `pc`, along with the memory
+    /// it accessed through the index register `i`. Addresses wrap around at
+    /// `memory_size`.
+    ///
+    /// Mega-Chip sprites are not counted as reads, since their size depends
+    /// on state outside of the opcode.
+    pub fn record(&mut self, pc: usize, opcode: usize, i: usize, memory_size: usize) {
+        *self.executions.entry(pc).or_default() += 1;
+
+        let x = (opcode & 0x0F00) >> 8;
+        let y = (opcode & 0x00F0) >> 4;
+        let (counts, len) = match opcode & 0xF0FF {
+            0xF033 => (&mut self.writes, 3),
+            0xF055 => (&mut self.writes, x + 1),
+            0xF065 => (&mut self.reads, x + 1),
+            _ if opcode & 0xF00F == 0x5002 => (&mut self.writes, x.abs_diff(y) + 1),
+            _ if opcode & 0xF00F == 0x5003 => (&mut self.reads, x.abs_diff(y) + 1),
+            _ if opcode & 0xF000 == 0xD000 => (&mut self.reads, opcode & 0xF),
+            _ => return,
+        };
+        for address in i..i + len {
+            *counts.entry(address % memory_size).or_default() += 1;
+        }
+    }
+
+    /// Returns how often the instruction at the given address was executed.
+    #[must_use]
+    pub fn executions(&self, address: usize) -> u64 {
+        self.executions.get(&address).copied().unwrap_or_default()
+    }
+
+    /// Returns how often the given address was read.
+    #[must_use]
+    pub fn reads(&self, address: usize) -> u64 {
+        self.reads.get(&address).copied().unwrap_or_default()
+    }
+
+    /// Returns how often the given address was written.
+    #[must_use]
+    pub fn writes(&self, address: usize) -> u64 {
+        self.writes.get(&address).copied().unwrap_or_default()
+    }
+
+    /// Returns up to `count` of the most executed addresses along with their
+    /// execution counts, the hottest first.
+    #[must_use]
+    pub fn hottest(&self, count: usize) -> Vec<(usize, u64)> {
+        let mut hottest: Vec<_> = self.executions.iter().map(|(&a, &c)| (a, c)).collect();
+        hottest.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hottest.truncate(count);
+        hottest
+    }
+
+    /// Returns the execution heat of `len` addresses starting at `start`,
+    /// scaled from `0.0` for never to `1.0` for the most executed address.
+    #[must_use]
+    pub fn execution_heatmap(&self, start: usize, len: usize) -> Vec<f32> {
+        heatmap(start, len, |address| self.executions(address))
+    }
+
+    /// Returns the memory access heat of `len` addresses starting at `start`,
+    /// counting both reads and writes, scaled like
+    /// [`Profiler::execution_heatmap`].
+    #[must_use]
+    pub fn access_heatmap(&self, start: usize, len: usize) -> Vec<f32> {
+        heatmap(start, len, |address| {
+            self.reads(address) + self.writes(address)
+        })
+    }
+}
+
+/// Scales the counts of the given address range relative to its maximum.
+#[allow(clippy::cast_precision_loss)] // heat is only displayed
+fn heatmap(start: usize, len: usize, count: impl Fn(usize) -> u64) -> Vec<f32> {
+    let counts: Vec<u64> = (start..start + len).map(count).collect();
+    let max = counts.iter().copied().max().unwrap_or_default().max(1);
+    counts
+        .into_iter()
+        .map(|count| count as f32 / max as f32)
+        .collect()
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::Chip8;
+
+    #[test]
+    fn test_profile_loop() {
+        let mut chip8 = Chip8::new();
+        chip8.profiler.set_enabled(true);
+        // A300: I = 0x300, F255: store V0-V2, A300: I = 0x300, F065: load V0,
+        // 1200: jump to start
+        chip8
+            .load_rom_data(vec![
+                0xA3, 0x00, 0xF2, 0x55, 0xA3, 0x00, 0xF0, 0x65, 0x12, 0x00,
+            ])
+            .unwrap();
+        for _ in 0..10 {
+            chip8.step().unwrap();
+        }
+
+        let profiler = &chip8.profiler;
+        assert_eq!(profiler.executions(0x200), 2);
+        assert_eq!(profiler.executions(0x208), 2);
+        assert_eq!(profiler.writes(0x302), 2);
+        assert_eq!(profiler.writes(0x303), 0);
+        assert_eq!(profiler.reads(0x300), 2);
+        assert_eq!(profiler.hottest(1), vec![(0x200, 2)]);
+        assert_eq!(profiler.access_heatmap(0x300, 4), vec![1.0, 0.5, 0.5, 0.0]);
+    }
+}
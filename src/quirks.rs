@@ -0,0 +1,298 @@
+//! This module provides the configurable behaviour differences ("quirks")
+//! between the interpreters that Chip8 programs were written for.
+//!
+//! The original COSMAC VIP interpreter, CHIP-48 on the HP-48 calculators,
+//! SUPER-CHIP and XO-CHIP disagree on the details of a handful of
+//! instructions. Programs written for one of them may misbehave on another, so
+//! the [`Quirks`] can either be toggled individually or picked as a whole
+//! through a [`Variant`] profile.
+
+use core::fmt;
+
+/// How the `Fx55` and `Fx65` instructions change the index register.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MemoryIncrement {
+    /// `I` is incremented by `x + 1`, as on the COSMAC VIP.
+    #[default]
+    XPlusOne,
+
+    /// `I` is incremented by `x`, as on CHIP-48.
+    X,
+
+    /// `I` is left unchanged, as on SUPER-CHIP.
+    Unchanged,
+}
+
+/// The default maximum depth of the call stack, matching the 16 levels of the
+/// original interpreters.
+pub const DEFAULT_STACK_DEPTH: usize = 16;
+
+/// How deep subroutine calls may nest before `2nnn` raises a
+/// [`crate::error::Chip8Error::StackOverflow`] error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StackDepth {
+    /// At most the given amount of nested calls.
+    Limited(usize),
+
+    /// Any amount of nested calls, for programs that rely on deeper stacks
+    /// than the hardware offered.
+    Unlimited,
+}
+
+impl Default for StackDepth {
+    fn default() -> Self {
+        Self::Limited(DEFAULT_STACK_DEPTH)
+    }
+}
+
+impl StackDepth {
+    /// Returns whether a call may be made with the given amount of nested
+    /// calls already on the stack.
+    #[must_use]
+    pub const fn allows(self, depth: usize) -> bool {
+        match self {
+            Self::Limited(max) => depth < max,
+            Self::Unlimited => true,
+        }
+    }
+}
+
+/// The set of quirks the [`super::processor::Cpu`] emulates.
+// Each quirk is an independent toggle.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quirks {
+    /// Whether `8xy6` and `8xyE` copy `Vy` into `Vx` before shifting. When
+    /// disabled, `Vx` is shifted in place.
+    pub shift: bool,
+
+    /// Whether the processor waits for the vertical blank interrupt before
+    /// drawing a sprite. The interrupt is raised by
+    /// [`crate::Chip8::tick_timers`], so at most one sprite is drawn per
+    /// tick, and none at all if the host never ticks the timers.
+    pub vblank_wait: bool,
+
+    /// Whether memory addresses computed from the index register wrap around
+    /// at the end of memory, instead of raising a
+    /// [`crate::error::Chip8Error::MemoryOutOfBounds`] error.
+    pub memory_wrap: bool,
+
+    /// Whether `Bnnn` jumps to `xnn + Vx` instead of `nnn + V0`.
+    pub jump: bool,
+
+    /// Whether `8xy1`, `8xy2` and `8xy3` reset `VF` to `0`.
+    pub vf_reset: bool,
+
+    /// How `Fx55` and `Fx65` change the index register.
+    pub memory_increment: MemoryIncrement,
+
+    /// How deep subroutine calls may nest.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub stack_depth: StackDepth,
+
+    /// Whether `Dxyn` clips sprites at the right and bottom edges of the
+    /// display. When disabled, the parts past an edge wrap around to the
+    /// opposite edge, as in Octo.
+    #[cfg_attr(feature = "serde", serde(default = "Quirks::default_clip_sprites"))]
+    pub clip_sprites: bool,
+
+    /// Whether `Fx1E` sets `VF` to `1` when `I` overflows past the end of
+    /// memory, and to `0` otherwise, as the Amiga interpreter did.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub index_overflow: bool,
+
+    /// Whether a `1260` jump at the start of the program enters the 64x64
+    /// two-page hires mode, in which `0230` clears the display.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub hires: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Quirks {
+    /// Creates the default set of quirks: shifts operate on `Vx`, `VF` is
+    /// reset by logic instructions, and `I` is incremented by `x + 1`.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            shift: false,
+            vblank_wait: false,
+            memory_wrap: false,
+            jump: false,
+            vf_reset: true,
+            memory_increment: MemoryIncrement::XPlusOne,
+            stack_depth: StackDepth::Limited(DEFAULT_STACK_DEPTH),
+            clip_sprites: Self::default_clip_sprites(),
+            index_overflow: false,
+            hires: false,
+        }
+    }
+
+    /// Returns whether sprites are clipped by default, which is the case on
+    /// all original interpreters. Also used when deserializing quirks that
+    /// were stored without the setting.
+    const fn default_clip_sprites() -> bool {
+        true
+    }
+}
+
+/// A Chip8 interpreter whose quirks can be emulated. Only the quirks differ
+/// between the profiles; the extended instruction sets of SUPER-CHIP and
+/// XO-CHIP are not part of a profile.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Variant {
+    /// The original interpreter of the COSMAC VIP.
+    #[default]
+    Chip8,
+
+    /// The original interpreter with the two-page hires patch, which shows
+    /// 64x64 pixels.
+    HiresChip8,
+
+    /// CHIP-48, the interpreter for the HP-48 graphing calculators.
+    Chip48,
+
+    /// SUPER-CHIP 1.1.
+    SuperChip,
+
+    /// XO-CHIP, as implemented by Octo.
+    XoChip,
+}
+
+impl Variant {
+    /// All variants, in the order they should be offered to the user.
+    pub const ALL: [Self; 5] = [
+        Self::Chip8,
+        Self::HiresChip8,
+        Self::Chip48,
+        Self::SuperChip,
+        Self::XoChip,
+    ];
+
+    /// Returns the display name of the variant.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Chip8 => "CHIP-8",
+            Self::HiresChip8 => "CHIP-8 HIRES",
+            Self::Chip48 => "CHIP-48",
+            Self::SuperChip => "SCHIP",
+            Self::XoChip => "XO-CHIP",
+        }
+    }
+
+    /// Returns the quirks of the variant.
+    #[must_use]
+    pub const fn quirks(self) -> Quirks {
+        match self {
+            Self::Chip8 => Quirks {
+                shift: true,
+                vblank_wait: true,
+                memory_wrap: false,
+                jump: false,
+                vf_reset: true,
+                memory_increment: MemoryIncrement::XPlusOne,
+                stack_depth: StackDepth::Limited(DEFAULT_STACK_DEPTH),
+                clip_sprites: true,
+                index_overflow: false,
+                hires: false,
+            },
+            Self::HiresChip8 => Quirks {
+                hires: true,
+                ..Self::Chip8.quirks()
+            },
+            Self::Chip48 => Quirks {
+                shift: false,
+                vblank_wait: false,
+                memory_wrap: false,
+                jump: true,
+                vf_reset: false,
+                memory_increment: MemoryIncrement::X,
+                stack_depth: StackDepth::Limited(DEFAULT_STACK_DEPTH),
+                clip_sprites: true,
+                index_overflow: false,
+                hires: false,
+            },
+            Self::SuperChip => Quirks {
+                shift: false,
+                vblank_wait: false,
+                memory_wrap: false,
+                jump: true,
+                vf_reset: false,
+                memory_increment: MemoryIncrement::Unchanged,
+                stack_depth: StackDepth::Limited(DEFAULT_STACK_DEPTH),
+                clip_sprites: true,
+                index_overflow: false,
+                hires: false,
+            },
+            Self::XoChip => Quirks {
+                shift: true,
+                vblank_wait: false,
+                memory_wrap: true,
+                jump: false,
+                vf_reset: false,
+                memory_increment: MemoryIncrement::XPlusOne,
+                stack_depth: StackDepth::Limited(DEFAULT_STACK_DEPTH),
+                clip_sprites: false,
+                index_overflow: false,
+                hires: false,
+            },
+        }
+    }
+
+    /// Returns the variant whose quirks match the given quirks exactly, if
+    /// any. Frontends use this to show which profile is active after single
+    /// quirks were toggled.
+    #[must_use]
+    pub fn from_quirks(quirks: Quirks) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|variant| variant.quirks() == quirks)
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    #[test]
+    fn test_from_quirks() {
+        for variant in Variant::ALL {
+            assert_eq!(Variant::from_quirks(variant.quirks()), Some(variant));
+        }
+        assert_eq!(Variant::from_quirks(Quirks::new()), None);
+    }
+
+    #[test]
+    fn test_chip48() {
+        // 6104: V1 = 4, B210: jump to 0x210 + V1, since x = 2 uses V2 = 0
+        let mut chip8 = Chip8::new();
+        chip8.set_variant(Variant::Chip48);
+        chip8.load_rom_data(vec![0x61, 0x04, 0xB2, 0x10]).unwrap();
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.processor.pc, 0x210);
+
+        // A300: I = 0x300, F255: store V0 to V2, leaving I = 0x302
+        chip8.reset_and_load(vec![0xA3, 0x00, 0xF2, 0x55]).unwrap();
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.processor.i, 0x302);
+        assert_eq!(chip8.processor.quirks, Variant::Chip48.quirks());
+    }
+}
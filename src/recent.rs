@@ -0,0 +1,138 @@
+//! This module keeps track of recently opened ROM files, so frontends can
+//! offer a "Recent ROMs" list and reload the current ROM from disk.
+//!
+//! With the `persistence` feature enabled, the list can be stored in and
+//! loaded from a TOML file.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "persistence")]
+use std::{fs, io};
+
+/// The default amount of ROM paths kept by [`RecentRoms`].
+pub const DEFAULT_RECENT_ROMS: usize = 10;
+
+/// A most-recently-used list of ROM file paths.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecentRoms {
+    /// The ROM paths, with the most recently opened one at the front.
+    paths: VecDeque<PathBuf>,
+    /// The maximum amount of paths to keep.
+    capacity: usize,
+}
+
+impl Default for RecentRoms {
+    fn default() -> Self {
+        Self::new(DEFAULT_RECENT_ROMS)
+    }
+}
+
+impl RecentRoms {
+    /// Creates a new, empty [`RecentRoms`] list that keeps at most `capacity`
+    /// paths.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            paths: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Marks the given path as the most recently opened ROM. If the path is
+    /// already in the list it is moved to the front; otherwise the oldest path
+    /// is discarded once the list is full.
+    pub fn push(&mut self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        self.paths.retain(|p| *p != path);
+        self.paths.push_front(path);
+        self.paths.truncate(self.capacity);
+    }
+
+    /// Removes the given path from the list, e.g. because the file no longer
+    /// exists.
+    pub fn remove(&mut self, path: &Path) {
+        self.paths.retain(|p| p != path);
+    }
+
+    /// Returns the most recently opened ROM, which is the one a "Reload"
+    /// action should read again.
+    #[must_use]
+    pub fn current(&self) -> Option<&Path> {
+        self.paths.front().map(PathBuf::as_path)
+    }
+
+    /// Returns an iterator over the ROM paths, from most to least recent.
+    pub fn iter(&self) -> impl Iterator<Item = &Path> {
+        self.paths.iter().map(PathBuf::as_path)
+    }
+
+    /// Returns the amount of paths in the list.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Returns whether the list is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Removes all paths from the list.
+    pub fn clear(&mut self) {
+        self.paths.clear();
+    }
+
+    /// Loads a [`RecentRoms`] list from the TOML file at the given path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a valid list.
+    #[cfg(feature = "persistence")]
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        toml::from_str(&contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Saves the [`RecentRoms`] list as TOML to the file at the given path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the list cannot be serialized or the file cannot
+    /// be written.
+    #[cfg(feature = "persistence")]
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let contents =
+            toml::to_string(self).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(path, contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push() {
+        let mut recent = RecentRoms::new(2);
+        recent.push("pong.ch8");
+        recent.push("tetris.ch8");
+        assert_eq!(recent.current(), Some(Path::new("tetris.ch8")));
+
+        // Re-opening a ROM moves it to the front without duplicating it
+        recent.push("pong.ch8");
+        assert_eq!(
+            recent.iter().collect::<Vec<_>>(),
+            [Path::new("pong.ch8"), Path::new("tetris.ch8")]
+        );
+
+        // The oldest ROM is dropped once the list is full
+        recent.push("brix.ch8");
+        assert_eq!(
+            recent.iter().collect::<Vec<_>>(),
+            [Path::new("brix.ch8"), Path::new("pong.ch8")]
+        );
+    }
+}
@@ -0,0 +1,189 @@
+//! This module provides a recorder that captures the display whenever it
+//! changes and exports the recording as an animated PNG (APNG).
+
+use std::time::Duration;
+use std::{fs, io, path::Path};
+
+use crate::graphics::{self, Framebuffer};
+
+/// The default maximum amount of frames captured per second.
+pub const DEFAULT_MAX_FPS: u32 = 30;
+
+/// A captured display frame.
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    /// The display contents.
+    buffer: Framebuffer,
+    /// The time at which the frame was captured, relative to the start of the
+    /// recording.
+    timestamp: Duration,
+}
+
+/// Records display frames and exports them as an animation.
+#[derive(Debug)]
+pub struct Recorder {
+    /// The captured frames, in chronological order.
+    frames: Vec<Frame>,
+    /// The maximum amount of frames captured per second.
+    max_fps: u32,
+    /// Whether frames are currently being captured.
+    recording: bool,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_FPS)
+    }
+}
+
+impl Recorder {
+    /// Creates a new, stopped [`Recorder`] that captures at most `max_fps`
+    /// frames per second.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_fps` is `0`.
+    #[must_use]
+    pub fn new(max_fps: u32) -> Self {
+        assert!(max_fps > 0, "max_fps must not be 0");
+        Self {
+            frames: Vec::new(),
+            max_fps,
+            recording: false,
+        }
+    }
+
+    /// Discards any previous recording and starts capturing frames.
+    pub fn start(&mut self) {
+        self.frames.clear();
+        self.recording = true;
+    }
+
+    /// Stops capturing frames. The captured frames are kept until the next
+    /// [`Recorder::start`].
+    pub const fn stop(&mut self) {
+        self.recording = false;
+    }
+
+    /// Returns whether frames are currently being captured.
+    #[must_use]
+    pub const fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// Returns the amount of captured frames.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns whether no frames have been captured.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Captures the given display at `timestamp`, measured from the start of
+    /// the recording. The frame is skipped if the recorder is stopped, the
+    /// display did not change since the last captured frame, or capturing it
+    /// would exceed the maximum frame rate.
+    ///
+    /// # Returns
+    ///
+    /// [`true`] if the frame was captured.
+    pub fn capture(&mut self, buffer: &Framebuffer, timestamp: Duration) -> bool {
+        if !self.recording {
+            return false;
+        }
+        if let Some(last) = self.frames.last() {
+            if last.buffer == *buffer
+                || timestamp.saturating_sub(last.timestamp) < self.min_frame_time()
+            {
+                return false;
+            }
+        }
+        self.frames.push(Frame {
+            buffer: *buffer,
+            timestamp,
+        });
+        true
+    }
+
+    /// Saves the captured frames as an animated PNG to the given path, scaling
+    /// every pixel up to a `scale` x `scale` square. Each frame is shown until
+    /// the next one was captured.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no frames were captured, the display changed its
+    /// height during the recording, or the file cannot be written.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the scaled image or the recording is too large for a PNG
+    /// image.
+    pub fn save_apng(&self, path: impl AsRef<Path>, scale: usize) -> io::Result<()> {
+        if self.frames.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no frames were recorded",
+            ));
+        }
+        let width = u32::try_from(graphics::WIDTH * scale).expect("image width fits into u32");
+        let height = u32::try_from(self.frames[0].buffer.height() * scale)
+            .expect("image height fits into u32");
+        let frame_count = u32::try_from(self.frames.len()).expect("frame count fits into u32");
+
+        let file = io::BufWriter::new(fs::File::create(path)?);
+        let mut encoder = png::Encoder::new(file, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_animated(frame_count, 0)?;
+        let mut writer = encoder.write_header()?;
+
+        for (i, frame) in self.frames.iter().enumerate() {
+            let delay = self.frames.get(i + 1).map_or_else(
+                || self.min_frame_time(),
+                |next| next.timestamp.saturating_sub(frame.timestamp),
+            );
+            let delay_ms = u16::try_from(delay.as_millis()).unwrap_or(u16::MAX);
+            writer.set_frame_delay(delay_ms, 1000)?;
+            writer.write_image_data(&frame.buffer.to_rgba(scale))?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Returns the minimum time between two captured frames.
+    fn min_frame_time(&self) -> Duration {
+        Duration::from_secs(1) / self.max_fps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture() {
+        let mut recorder = Recorder::new(10);
+        let mut buffer = Framebuffer::new();
+
+        // Nothing is captured while stopped
+        assert!(!recorder.capture(&buffer, Duration::ZERO));
+
+        recorder.start();
+        assert!(recorder.capture(&buffer, Duration::ZERO));
+
+        // Unchanged frames are skipped
+        assert!(!recorder.capture(&buffer, Duration::from_millis(200)));
+
+        // Frames exceeding the frame rate are skipped
+        buffer.draw_byte(0, 0, 0xFF);
+        assert!(!recorder.capture(&buffer, Duration::from_millis(50)));
+        assert!(recorder.capture(&buffer, Duration::from_millis(100)));
+
+        recorder.stop();
+        assert_eq!(recorder.len(), 2);
+    }
+}
@@ -0,0 +1,307 @@
+//! This module provides a differential fuzzer, which runs random programs on
+//! the [`Chip8`] core and on a deliberately simple reference interpreter and
+//! reports where their registers diverge.
+//!
+//! The [`Reference`] interpreter is written straight from the instruction
+//! set description, independently of [`crate::processor`], and only covers
+//! the instructions that compute something: loads, arithmetic, logic,
+//! shifts, skips and `Fx33`/`Fx55`/`Fx65`. Generated programs run straight
+//! through without jumps, and keep the index register well inside memory.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{
+    memory::{Memory, PROGRAM_START},
+    quirks::{MemoryIncrement, Quirks},
+    rng::Rng,
+    Chip8,
+};
+
+/// The registers compared after every instruction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Registers {
+    /// The V0 to VF registers.
+    pub v: [u8; 16],
+    /// The index register.
+    pub i: usize,
+    /// The program counter.
+    pub pc: usize,
+}
+
+/// A minimal interpreter for the instructions generated by [`generate`].
+#[derive(Debug, Clone)]
+pub struct Reference {
+    /// The current registers.
+    pub registers: Registers,
+    /// The memory, holding the program at [`PROGRAM_START`].
+    memory: Vec<u8>,
+    /// The quirks to follow.
+    quirks: Quirks,
+}
+
+impl Reference {
+    /// Creates a reference interpreter with the given program loaded. The
+    /// interpreter area holds the same font as the core.
+    #[must_use]
+    pub fn new(program: &[u8], quirks: Quirks) -> Self {
+        let interpreter = Memory::new();
+        let mut memory: Vec<u8> = (0..4096).map(|address| interpreter[address]).collect();
+        memory[PROGRAM_START..PROGRAM_START + program.len()].copy_from_slice(program);
+        Self {
+            registers: Registers {
+                pc: PROGRAM_START,
+                ..Registers::default()
+            },
+            memory,
+            quirks,
+        }
+    }
+
+    /// Executes the instruction at the program counter.
+    pub fn step(&mut self) {
+        let Registers { v, i, pc } = &mut self.registers;
+        let opcode = u16::from_be_bytes([self.memory[*pc], self.memory[*pc + 1]]);
+        let x = usize::from(opcode >> 8 & 0xF);
+        let y = usize::from(opcode >> 4 & 0xF);
+        let nn = opcode.to_be_bytes()[1];
+        let mut next = *pc + 2;
+        let skip = next + 2;
+        match opcode >> 12 {
+            0x3 if v[x] == nn => next = skip,
+            0x4 if v[x] != nn => next = skip,
+            0x5 if v[x] == v[y] => next = skip,
+            0x9 if v[x] != v[y] => next = skip,
+            0x6 => v[x] = nn,
+            0x7 => v[x] = v[x].wrapping_add(nn),
+            0x8 => self.alu(opcode & 0xF, x, y),
+            0xA => *i = usize::from(opcode & 0xFFF),
+            0xF => self.misc(nn, x),
+            _ => {}
+        }
+        self.registers.pc = next;
+    }
+
+    /// Executes the `8xyN` instruction with the given `n`.
+    fn alu(&mut self, n: u16, x: usize, y: usize) {
+        let v = &mut self.registers.v;
+        let (vx, vy) = (v[x], v[y]);
+        // the flag is written last, so it wins if x is F
+        let flag = match n {
+            0x0 => {
+                v[x] = vy;
+                None
+            }
+            0x1..=0x3 => {
+                v[x] = match n {
+                    0x1 => vx | vy,
+                    0x2 => vx & vy,
+                    _ => vx ^ vy,
+                };
+                self.quirks.vf_reset.then_some(0)
+            }
+            0x4 => {
+                v[x] = vx.wrapping_add(vy);
+                Some(u8::from(u16::from(vx) + u16::from(vy) > 0xFF))
+            }
+            0x5 => {
+                v[x] = vx.wrapping_sub(vy);
+                Some(u8::from(vx >= vy))
+            }
+            0x7 => {
+                v[x] = vy.wrapping_sub(vx);
+                Some(u8::from(vy >= vx))
+            }
+            0x6 | 0xE => {
+                let value = if self.quirks.shift { vy } else { vx };
+                if n == 0x6 {
+                    v[x] = value >> 1;
+                    Some(value & 1)
+                } else {
+                    v[x] = value << 1;
+                    Some(value >> 7)
+                }
+            }
+            _ => None,
+        };
+        if let Some(flag) = flag {
+            v[0xF] = flag;
+        }
+    }
+
+    /// Executes the `FxNN` instruction with the given `nn`.
+    fn misc(&mut self, nn: u8, x: usize) {
+        let Registers { v, i, .. } = &mut self.registers;
+        let increment = match self.quirks.memory_increment {
+            MemoryIncrement::XPlusOne => x + 1,
+            MemoryIncrement::X => x,
+            MemoryIncrement::Unchanged => 0,
+        };
+        match nn {
+            0x33 => {
+                self.memory[*i] = v[x] / 100;
+                self.memory[*i + 1] = v[x] / 10 % 10;
+                self.memory[*i + 2] = v[x] % 10;
+            }
+            0x55 => {
+                self.memory[*i..=*i + x].copy_from_slice(&v[..=x]);
+                *i += increment;
+            }
+            0x65 => {
+                v[..=x].copy_from_slice(&self.memory[*i..=*i + x]);
+                *i += increment;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Generates a random program of `len` instructions that [`Reference`]
+/// can execute.
+#[must_use]
+pub fn generate(rng: &mut Rng, len: usize) -> Vec<u8> {
+    const ALU: [u8; 9] = [0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0xE];
+    const MISC: [u8; 3] = [0x33, 0x55, 0x65];
+
+    let mut program = Vec::with_capacity(len * 2);
+    for _ in 0..len {
+        let x = rng.next_u8() & 0xF;
+        let y = rng.next_u8() & 0xF;
+        let nn = rng.next_u8();
+        let instruction = match rng.next_u8() % 9 {
+            0 => [0x30 | x, nn],
+            1 => [0x40 | x, nn],
+            2 => [0x50 | x, y << 4],
+            3 => [0x90 | x, y << 4],
+            4 => [0x60 | x, nn],
+            5 => [0x70 | x, nn],
+            // the index register stays between 0x300 and 0x6FF, leaving
+            // room for the increments of Fx55 and Fx65
+            6 => [0xA3 + (nn & 0x3), rng.next_u8()],
+            7 => [0xF0 | x, MISC[usize::from(nn) % MISC.len()]],
+            _ => [0x80 | x, y << 4 | ALU[usize::from(nn) % ALU.len()]],
+        };
+        program.extend(instruction);
+    }
+    program
+}
+
+/// A difference between the [`Chip8`] core and the [`Reference`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// The program that was run.
+    pub program: Vec<u8>,
+    /// The address of the instruction after which the registers differed.
+    pub pc: usize,
+    /// The opcode of that instruction.
+    pub opcode: u16,
+    /// The registers according to the reference.
+    pub expected: Registers,
+    /// The registers according to the core.
+    pub actual: Registers,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04X} at {:#06X}:", self.opcode, self.pc)?;
+        for x in 0..16 {
+            let (expected, actual) = (self.expected.v[x], self.actual.v[x]);
+            if expected != actual {
+                write!(f, " V{x:X} {expected:02X} != {actual:02X}")?;
+            }
+        }
+        if self.expected.i != self.actual.i {
+            write!(f, " I {:04X} != {:04X}", self.expected.i, self.actual.i)?;
+        }
+        if self.expected.pc != self.actual.pc {
+            write!(f, " PC {:04X} != {:04X}", self.expected.pc, self.actual.pc)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs the program on the [`Chip8`] core and the [`Reference`] side by side
+/// and returns the first divergence, if any.
+///
+/// # Panics
+///
+/// Panics if the program is empty, or does not fit into memory.
+#[must_use]
+pub fn compare(program: &[u8], quirks: Quirks) -> Option<Divergence> {
+    let mut chip8 = Chip8::builder()
+        .rom(program)
+        .quirks(quirks)
+        .build()
+        .expect("program fits into memory");
+    chip8.history.set_depth(0);
+    let mut reference = Reference::new(program, quirks);
+    let end = PROGRAM_START + program.len();
+
+    while reference.registers.pc < end {
+        let pc = reference.registers.pc;
+        let opcode =
+            u16::from_be_bytes([program[pc - PROGRAM_START], program[pc - PROGRAM_START + 1]]);
+        reference.step();
+        // errors show up as registers that were not updated
+        let _ = chip8.step();
+        let actual = Registers {
+            v: chip8.processor.v,
+            i: chip8.processor.i,
+            pc: chip8.processor.pc,
+        };
+        if actual != reference.registers {
+            return Some(Divergence {
+                program: program.to_vec(),
+                pc,
+                opcode,
+                expected: reference.registers,
+                actual,
+            });
+        }
+    }
+    None
+}
+
+/// Generates `count` random programs of `len` instructions from the given
+/// seed, and returns the divergences found while running them with the given
+/// quirks.
+#[must_use]
+pub fn fuzz(seed: u64, count: usize, len: usize, quirks: Quirks) -> Vec<Divergence> {
+    let mut rng = Rng::new(seed);
+    (0..count)
+        .filter_map(|_| compare(&generate(&mut rng, len), quirks))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Variant;
+
+    #[test]
+    fn test_fuzz() {
+        for variant in Variant::ALL {
+            let divergences = fuzz(42, 200, 32, variant.quirks());
+            assert!(
+                divergences.is_empty(),
+                "{}: {}",
+                variant.name(),
+                divergences[0]
+            );
+        }
+    }
+
+    #[test]
+    fn test_divergence() {
+        // 6005: V0 = 5, 8015: V0 -= V1
+        let program = [0x60, 0x05, 0x80, 0x15];
+        let mut reference = Reference::new(&program, Quirks::default());
+        reference.step();
+        reference.step();
+        assert_eq!(
+            (reference.registers.v[0], reference.registers.v[0xF]),
+            (5, 1)
+        );
+        assert_eq!(compare(&program, Quirks::default()), None);
+    }
+}
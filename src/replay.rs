@@ -0,0 +1,282 @@
+//! This module provides recording and playback of keypad input, e.g. for
+//! tool-assisted runs or reproducing bug reports.
+//!
+//! Every key state change is stored together with the amount of instructions
+//! executed before it, and fed back at exactly the same instruction during
+//! playback. Combined with the seed of the random number generator this makes
+//! a run reproducible, as long as the timers are ticked at the same
+//! instructions too, e.g. by [`crate::clock::FixedStep`]. With the
+//! `persistence` feature
+//! enabled, a [`Movie`] can be stored in and loaded from a TOML file.
+
+use alloc::vec::Vec;
+#[cfg(feature = "persistence")]
+use std::{fs, io, path::Path};
+
+use crate::input::Input;
+
+/// A single key state change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeyEvent {
+    /// The amount of instructions executed before the change.
+    pub instruction: u64,
+    /// The key code of the changed key.
+    pub key_code: u8,
+    /// Whether the key was pressed ([`true`]) or released ([`false`]).
+    pub pressed: bool,
+}
+
+/// A recorded run: the seed of the random number generator and all key state
+/// changes, in chronological order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Movie {
+    /// The seed of the random number generator the run started with. Stored
+    /// as a string, since TOML integers cannot hold every [`u64`].
+    #[cfg_attr(feature = "serde", serde(with = "seed"))]
+    pub seed: u64,
+    /// The recorded key state changes.
+    pub events: Vec<KeyEvent>,
+}
+
+impl Movie {
+    /// Creates an empty [`Movie`] for a run started with the given seed.
+    #[must_use]
+    pub const fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            events: Vec::new(),
+        }
+    }
+
+    /// Parses a [`Movie`] from a TOML string.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the string is not a valid movie.
+    #[cfg(feature = "persistence")]
+    pub fn from_toml(toml: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(toml)
+    }
+
+    /// Serializes the [`Movie`] into a TOML string.
+    ///
+    /// # Panics
+    ///
+    /// Never panics in practice, since a [`Movie`] always serializes to TOML.
+    #[cfg(feature = "persistence")]
+    #[must_use]
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("movie is always serializable")
+    }
+
+    /// Loads a [`Movie`] from the TOML file at the given path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a valid movie.
+    #[cfg(feature = "persistence")]
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Self::from_toml(&contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Saves the [`Movie`] as TOML to the file at the given path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    #[cfg(feature = "persistence")]
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_toml())
+    }
+}
+
+/// Serializes the seed of a [`Movie`] as a string.
+#[cfg(feature = "serde")]
+mod seed {
+    use alloc::string::{String, ToString};
+
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    // `serde(with)` passes the field by reference.
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    pub fn serialize<S: Serializer>(seed: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&seed.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Whether input is being recorded or played back.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Input is neither recorded nor played back.
+    #[default]
+    Idle,
+    /// Key state changes are appended to the [`Movie`].
+    Recording,
+    /// Key state changes are fed from the [`Movie`], and live input is ignored.
+    Playing,
+}
+
+/// Records or plays back the input of a [`super::Chip8`].
+#[derive(Debug, Default)]
+pub struct Replay {
+    /// The movie being recorded or played back.
+    movie: Movie,
+    /// Whether input is being recorded or played back.
+    mode: Mode,
+    /// The amount of instructions executed since recording or playback
+    /// started.
+    instruction: u64,
+    /// The index of the next event to play back.
+    next: usize,
+}
+
+impl Replay {
+    /// Discards the current movie and starts recording a new one for a run
+    /// started with the given seed.
+    pub fn record(&mut self, seed: u64) {
+        *self = Self {
+            movie: Movie::new(seed),
+            mode: Mode::Recording,
+            ..Self::default()
+        };
+    }
+
+    /// Starts playing back the given movie.
+    pub fn play(&mut self, movie: Movie) {
+        *self = Self {
+            movie,
+            mode: Mode::Playing,
+            ..Self::default()
+        };
+    }
+
+    /// Stops recording or playback. The movie is kept, so a recording can be
+    /// saved afterwards.
+    pub const fn stop(&mut self) {
+        self.mode = Mode::Idle;
+    }
+
+    /// Returns whether input is being recorded or played back.
+    #[must_use]
+    pub const fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Returns the movie being recorded or played back.
+    #[must_use]
+    pub const fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    /// Returns the amount of instructions executed since recording or
+    /// playback started.
+    #[must_use]
+    pub const fn instruction(&self) -> u64 {
+        self.instruction
+    }
+
+    /// Returns whether every event of the movie was played back.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.mode == Mode::Playing && self.next >= self.movie.events.len()
+    }
+
+    /// Handles a live key state change.
+    ///
+    /// # Returns
+    ///
+    /// Whether the change should be applied, which is not the case during
+    /// playback.
+    pub(crate) fn key(&mut self, key_code: u8, pressed: bool) -> bool {
+        match self.mode {
+            Mode::Idle => true,
+            Mode::Recording => {
+                self.movie.events.push(KeyEvent {
+                    instruction: self.instruction,
+                    key_code,
+                    pressed,
+                });
+                true
+            }
+            Mode::Playing => false,
+        }
+    }
+
+    /// Feeds the key state changes that are due before the next instruction
+    /// into `input`, and counts the instruction.
+    pub(crate) fn advance(&mut self, input: &mut Input) {
+        if self.mode == Mode::Playing {
+            while let Some(event) = self.movie.events.get(self.next) {
+                if event.instruction > self.instruction {
+                    break;
+                }
+                input.update(event.key_code, event.pressed);
+                self.next += 1;
+            }
+        }
+        if self.mode != Mode::Idle {
+            self.instruction += 1;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    #[cfg(feature = "persistence")]
+    use super::*;
+    use crate::Chip8;
+
+    #[test]
+    fn test_record_and_play() {
+        // F00A: wait for a key, C1FF: V1 = random, 1204: loop
+        let rom = vec![0xF0, 0x0A, 0xC1, 0xFF, 0x12, 0x04];
+
+        let mut chip8 = Chip8::new();
+        chip8.record(rom.clone()).unwrap();
+        for _ in 0..5 {
+            chip8.step().unwrap();
+        }
+        chip8.update_key_state(0x7, true);
+        for _ in 0..5 {
+            chip8.step().unwrap();
+        }
+        chip8.replay.stop();
+        let (v0, v1) = (chip8.processor.v[0], chip8.processor.v[1]);
+        let movie = chip8.replay.movie().clone();
+        assert_eq!(movie.events.len(), 1);
+
+        let mut chip8 = Chip8::new();
+        chip8.play(rom, movie).unwrap();
+        // Live input is ignored during playback
+        chip8.update_key_state(0x3, true);
+        for _ in 0..10 {
+            chip8.step().unwrap();
+        }
+        assert!(chip8.replay.is_finished());
+        assert_eq!(chip8.processor.v[0], v0);
+        assert_eq!(chip8.processor.v[1], v1);
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_toml_round_trip() {
+        let movie = Movie {
+            seed: u64::MAX,
+            events: vec![KeyEvent {
+                instruction: 42,
+                key_code: 0xA,
+                pressed: true,
+            }],
+        };
+        assert_eq!(Movie::from_toml(&movie.to_toml()).unwrap(), movie);
+    }
+}
@@ -0,0 +1,78 @@
+//! This module provides the seedable random number generator used by the
+//! `Cxnn` instruction.
+//!
+//! Using a small, serializable generator instead of the operating system's
+//! entropy source makes runs reproducible: two systems started with the same
+//! seed and the same input produce the same random numbers.
+
+/// A `SplitMix64` pseudo-random number generator.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rng {
+    /// The seed the generator was created with.
+    seed: u64,
+    /// The current state of the generator.
+    state: u64,
+}
+
+impl Rng {
+    /// Creates a new [`Rng`] from the given seed.
+    #[must_use]
+    pub const fn new(seed: u64) -> Self {
+        Self { seed, state: seed }
+    }
+
+    /// Creates a new [`Rng`] seeded from the operating system's entropy
+    /// source.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entropy source is unavailable.
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn from_entropy() -> Self {
+        let mut seed = [0; 8];
+        getrandom::getrandom(&mut seed).expect("entropy source is available");
+        Self::new(u64::from_le_bytes(seed))
+    }
+
+    /// Returns the seed the generator was created with.
+    #[must_use]
+    pub const fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the next random [`u64`].
+    pub const fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns the next random [`u8`].
+    #[allow(clippy::cast_possible_truncation)]
+    pub const fn next_u8(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_deterministic() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let mut c = Rng::new(43);
+
+        let a: Vec<u8> = (0..16).map(|_| a.next_u8()).collect();
+        let b: Vec<u8> = (0..16).map(|_| b.next_u8()).collect();
+        let c: Vec<u8> = (0..16).map(|_| c.next_u8()).collect();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+}
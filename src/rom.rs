@@ -0,0 +1,524 @@
+//! This module inspects ROM files before they are run, for a "ROM Info"
+//! panel or an `info` command.
+//!
+//! Besides the size and [`crate::roms::hash`] of a ROM, a [`RomInfo`] lists
+//! the instruction set extensions used by the ROM, so the right
+//! [`Variant`] can be suggested. To avoid mistaking sprite data for
+//! instructions, only code reachable from the entry point is examined. The
+//! jump and call targets of the ROM also hint at its load address, e.g.
+//! [`memory::ETI_660_START`] for ROMs written for the ETI-660.
+//!
+//! With the `persistence` feature enabled, Octo-style [`Metadata`] is read
+//! from a JSON file next to the ROM, in the format used by Octo and the CHIP-8
+//! archive.
+
+use std::{collections::BTreeSet, fmt};
+#[cfg(feature = "persistence")]
+use std::{fs, io, path::Path};
+
+#[cfg(feature = "persistence")]
+use crate::{
+    graphics::Rgb,
+    quirks::{MemoryIncrement, Quirks},
+};
+use crate::{memory, quirks::Variant, roms};
+
+/// An instruction set extension beyond the original Chip8 instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Extension {
+    /// The scrolling, high resolution and flag register instructions of
+    /// SUPER-CHIP.
+    SuperChip,
+    /// The bit planes, audio and long index instructions of XO-CHIP.
+    XoChip,
+    /// The color display and sampled sound instructions of Mega-Chip.
+    MegaChip,
+    /// The 64x64 display of the two-page hires patch, which ROMs enter
+    /// through a `1260` jump at their start.
+    Hires,
+}
+
+impl Extension {
+    /// Returns the extension an opcode belongs to, or [`None`] if it is a
+    /// Chip8 instruction or invalid.
+    #[must_use]
+    pub const fn of(opcode: u16) -> Option<Self> {
+        let n = opcode & 0xF;
+        let nn = opcode & 0xFF;
+        Some(match opcode >> 12 {
+            0x0 if opcode == 0x0010 || opcode == 0x0011 => Self::MegaChip,
+            0x0 if opcode & 0xFFF0 == 0x00C0 && n != 0 => Self::SuperChip,
+            0x0 if opcode >= 0x00FB && opcode <= 0x00FF => Self::SuperChip,
+            0x0 if opcode & 0xFFF0 == 0x00D0 => Self::XoChip,
+            0x5 if n == 2 || n == 3 => Self::XoChip,
+            0xD if n == 0 => Self::SuperChip,
+            0xF if opcode == 0xF000 || opcode == 0xF002 => Self::XoChip,
+            0xF if nn == 0x01 || nn == 0x3A => Self::XoChip,
+            0xF if nn == 0x30 || nn == 0x75 || nn == 0x85 => Self::SuperChip,
+            _ => return None,
+        })
+    }
+
+    /// Returns whether the original platform of the given variant executes
+    /// the instructions of the extension. The core runs them under every
+    /// variant, except `00FE`, `00FF` and `Fx30`, which need the 128x64
+    /// SUPER-CHIP display. The Mega-Chip extensions are enabled
+    /// independently, see [`crate::Chip8::set_megachip`].
+    #[must_use]
+    pub const fn supported_by(self, variant: Variant) -> bool {
+        matches!(
+            (self, variant),
+            (Self::SuperChip, Variant::SuperChip | Variant::XoChip)
+                | (Self::XoChip, Variant::XoChip)
+                | (Self::Hires, Variant::HiresChip8)
+        )
+    }
+
+    /// Returns the display name of the extension.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::SuperChip => "SCHIP",
+            Self::XoChip => "XO-CHIP",
+            Self::MegaChip => "Mega-Chip",
+            Self::Hires => "CHIP-8 HIRES",
+        }
+    }
+}
+
+impl fmt::Display for Extension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Information about a ROM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomInfo {
+    /// The size of the ROM in bytes.
+    pub size: usize,
+
+    /// The SHA-1 hash of the ROM.
+    pub hash: String,
+
+    /// The address the ROM most likely expects to be loaded at, see
+    /// [`detect_load_address`].
+    pub load_address: usize,
+
+    /// The instruction set extensions used by the reachable code of the ROM.
+    pub extensions: BTreeSet<Extension>,
+
+    /// The Octo-style metadata found next to the ROM, if any.
+    #[cfg(feature = "persistence")]
+    pub metadata: Option<Metadata>,
+}
+
+impl RomInfo {
+    /// Inspects the given ROM data.
+    #[must_use]
+    pub fn new(data: &[u8]) -> Self {
+        let load_address = detect_load_address(data);
+        let hires = is_hires(data, load_address);
+        let mut extensions: BTreeSet<_> = reachable(data, load_address)
+            .into_iter()
+            .filter_map(|address| opcode_at(data, load_address, address))
+            .filter_map(Extension::of)
+            .collect();
+        if hires {
+            extensions.insert(Extension::Hires);
+        }
+        Self {
+            size: data.len(),
+            hash: roms::hash(data),
+            load_address,
+            extensions,
+            #[cfg(feature = "persistence")]
+            metadata: None,
+        }
+    }
+
+    /// Inspects the ROM file at the given path, along with the Octo-style
+    /// metadata in the JSON file of the same name, if there is one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM or an existing metadata file cannot be
+    /// read, or if the metadata is invalid.
+    #[cfg(feature = "persistence")]
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let mut info = Self::new(&fs::read(path)?);
+        info.metadata = match fs::read_to_string(path.with_extension("json")) {
+            Ok(json) => Some(
+                Metadata::from_json(&json)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
+            ),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+        Ok(info)
+    }
+
+    /// Returns whether the ROM fits into the memory of the original Chip8.
+    /// Larger ROMs require the Mega-Chip extensions.
+    #[must_use]
+    pub const fn fits_memory(&self) -> bool {
+        self.size <= memory::MAX_ROM_SIZE
+    }
+
+    /// Returns the variant the ROM most likely expects: the platform given by
+    /// its metadata, or else the most capable variant whose extensions it
+    /// uses.
+    #[must_use]
+    pub fn suggested_variant(&self) -> Variant {
+        #[cfg(feature = "persistence")]
+        if let Some(variant) = self.metadata.as_ref().and_then(Metadata::variant) {
+            return variant;
+        }
+        if self.extensions.contains(&Extension::Hires) {
+            Variant::HiresChip8
+        } else if self.extensions.contains(&Extension::XoChip) {
+            Variant::XoChip
+        } else if self.extensions.contains(&Extension::SuperChip) {
+            Variant::SuperChip
+        } else {
+            Variant::Chip8
+        }
+    }
+
+    /// Returns whether the ROM needs the Mega-Chip extensions to be enabled.
+    #[must_use]
+    pub fn needs_megachip(&self) -> bool {
+        self.extensions.contains(&Extension::MegaChip) || !self.fits_memory()
+    }
+}
+
+impl fmt::Display for RomInfo {
+    /// Formats the information as lines of `Field: value`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        #[cfg(feature = "persistence")]
+        if let Some(metadata) = &self.metadata {
+            if let Some(title) = &metadata.title {
+                writeln!(f, "Title: {title}")?;
+            }
+            if !metadata.authors.is_empty() {
+                writeln!(f, "Authors: {}", metadata.authors.join(", "))?;
+            }
+        }
+        writeln!(f, "Size: {} bytes", self.size)?;
+        writeln!(f, "SHA-1: {}", self.hash)?;
+        if self.load_address != memory::PROGRAM_START {
+            writeln!(f, "Load address: {:#05X}", self.load_address)?;
+        }
+        let extensions: Vec<_> = self.extensions.iter().map(|e| e.name()).collect();
+        if extensions.is_empty() {
+            writeln!(f, "Extensions: none")?;
+        } else {
+            writeln!(f, "Extensions: {}", extensions.join(", "))?;
+        }
+        write!(f, "Suggested variant: {}", self.suggested_variant())?;
+        if self.needs_megachip() {
+            write!(f, "\nRequires Mega-Chip")?;
+        }
+        Ok(())
+    }
+}
+
+/// Guesses the address the ROM expects to be loaded at.
+///
+/// Returns [`memory::ETI_660_START`] if none of the jump and call targets of
+/// the ROM lie below it but some lie within the ROM when loaded there, and
+/// [`memory::PROGRAM_START`] otherwise.
+#[must_use]
+pub fn detect_load_address(data: &[u8]) -> usize {
+    let targets: Vec<usize> = data
+        .chunks_exact(2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .filter(|op| matches!(op >> 12, 0x1 | 0x2))
+        .map(|op| usize::from(op & 0x0FFF))
+        .collect();
+    let eti_660 = memory::ETI_660_START..memory::ETI_660_START + data.len();
+    let below = targets
+        .iter()
+        .any(|&target| (memory::PROGRAM_START..memory::ETI_660_START).contains(&target));
+    if !below && targets.iter().any(|target| eti_660.contains(target)) {
+        memory::ETI_660_START
+    } else {
+        memory::PROGRAM_START
+    }
+}
+
+/// Returns whether the ROM loaded at `start` enters the hires mode of the
+/// two-page hires patch through a `1260` jump at its start.
+pub(crate) fn is_hires(data: &[u8], start: usize) -> bool {
+    start == memory::PROGRAM_START && data.starts_with(&[0x12, 0x60])
+}
+
+/// Returns the opcode at `address` of the ROM loaded at `start`, or [`None`]
+/// if the address lies outside the ROM.
+pub(crate) fn opcode_at(data: &[u8], start: usize, address: usize) -> Option<u16> {
+    let offset = address.checked_sub(start)?;
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+/// Collects the addresses of the instructions of the ROM loaded at `start`
+/// that are reachable from its entry point, by following all jumps, calls
+/// and skips. The program of a hires ROM starts behind the interpreter
+/// patch, which is not followed.
+pub(crate) fn reachable(data: &[u8], start: usize) -> BTreeSet<usize> {
+    let opcode = |address| opcode_at(data, start, address);
+    let entry = if is_hires(data, start) {
+        memory::HIRES_START
+    } else {
+        start
+    };
+
+    let mut visited = BTreeSet::new();
+    let mut pending = vec![entry];
+    while let Some(address) = pending.pop() {
+        if visited.contains(&address) {
+            continue;
+        }
+        let Some(op) = opcode(address) else {
+            continue;
+        };
+        visited.insert(address);
+
+        // `F000 nnnn` is followed by a 16 bit address
+        let next = address + if op == 0xF000 { 4 } else { 2 };
+        let nnn = usize::from(op & 0x0FFF);
+        match op >> 12 {
+            0x0 if op == 0x00EE || op == 0x00FD => {}
+            0x1 => pending.push(nnn),
+            0x2 => pending.extend([nnn, next]),
+            // The target of `Bnnn` depends on a register
+            0xB => {}
+            0x3 | 0x4 | 0x5 | 0x9 | 0xE => {
+                let skipped = if opcode(next) == Some(0xF000) { 4 } else { 2 };
+                pending.extend([next, next + skipped]);
+            }
+            _ => pending.push(next),
+        }
+    }
+    visited
+}
+
+/// Metadata describing a ROM and the settings it expects, as written by Octo.
+#[cfg(feature = "persistence")]
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(default)]
+pub struct Metadata {
+    /// The title of the ROM.
+    pub title: Option<String>,
+
+    /// The authors of the ROM.
+    pub authors: Vec<String>,
+
+    /// A description of the ROM.
+    #[serde(alias = "desc")]
+    pub description: Option<String>,
+
+    /// The platform the ROM was written for, e.g. `schip`.
+    pub platform: Option<String>,
+
+    /// The emulator settings the ROM expects.
+    pub options: OctoOptions,
+}
+
+/// The emulator settings of Octo. Unset options keep the setting of the
+/// emulator.
+#[cfg(feature = "persistence")]
+#[allow(clippy::struct_excessive_bools)] // Mirrors the Octo format.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct OctoOptions {
+    /// The amount of instructions executed per frame.
+    pub tickrate: Option<u64>,
+    /// The color of set pixels, as `#RRGGBB`.
+    pub fill_color: Option<String>,
+    /// The color of unset pixels, as `#RRGGBB`.
+    pub background_color: Option<String>,
+    /// Whether `8xy6` and `8xyE` shift `Vx` in place.
+    pub shift_quirks: Option<bool>,
+    /// Whether `Fx55` and `Fx65` leave `I` unchanged.
+    pub load_store_quirk: Option<bool>,
+    /// Whether `Bnnn` jumps to `nnn + Vx`.
+    pub jump_quirks: Option<bool>,
+    /// Whether `Dxyn` waits for the vertical blank.
+    pub v_blank_quirks: Option<bool>,
+    /// Whether `8xy1`, `8xy2` and `8xy3` reset `VF`.
+    pub logic_quirks: Option<bool>,
+    /// Whether `Dxyn` clips sprites at the edges of the display.
+    pub clip_quirks: Option<bool>,
+}
+
+#[cfg(feature = "persistence")]
+impl Metadata {
+    /// Parses [`Metadata`] from a JSON string.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the string is not a valid metadata object.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Returns the variant of the platform, if it is a known one.
+    #[must_use]
+    pub fn variant(&self) -> Option<Variant> {
+        match self.platform.as_deref()?.to_ascii_lowercase().as_str() {
+            "chip8" | "chip-8" => Some(Variant::Chip8),
+            "chip48" | "chip-48" => Some(Variant::Chip48),
+            "schip" | "superchip" => Some(Variant::SuperChip),
+            "xochip" | "xo-chip" => Some(Variant::XoChip),
+            _ => None,
+        }
+    }
+
+    /// Returns the given quirks with the quirk options applied.
+    #[must_use]
+    pub const fn quirks(&self, mut quirks: Quirks) -> Quirks {
+        let options = &self.options;
+        if let Some(shift) = options.shift_quirks {
+            quirks.shift = !shift;
+        }
+        if let Some(load_store) = options.load_store_quirk {
+            quirks.memory_increment = if load_store {
+                MemoryIncrement::Unchanged
+            } else {
+                MemoryIncrement::XPlusOne
+            };
+        }
+        if let Some(jump) = options.jump_quirks {
+            quirks.jump = jump;
+        }
+        if let Some(vblank_wait) = options.v_blank_quirks {
+            quirks.vblank_wait = vblank_wait;
+        }
+        if let Some(vf_reset) = options.logic_quirks {
+            quirks.vf_reset = vf_reset;
+        }
+        if let Some(clip_sprites) = options.clip_quirks {
+            quirks.clip_sprites = clip_sprites;
+        }
+        quirks
+    }
+
+    /// Returns the amount of instructions per second matching the tick rate,
+    /// if set.
+    #[must_use]
+    pub fn ips(&self) -> Option<u64> {
+        self.options.tickrate.map(|tickrate| tickrate * 60)
+    }
+
+    /// Returns the color of set pixels, if set and valid.
+    #[must_use]
+    pub fn foreground(&self) -> Option<Rgb> {
+        self.options.fill_color.as_deref().and_then(parse_color)
+    }
+
+    /// Returns the color of unset pixels, if set and valid.
+    #[must_use]
+    pub fn background(&self) -> Option<Rgb> {
+        self.options
+            .background_color
+            .as_deref()
+            .and_then(parse_color)
+    }
+}
+
+/// Parses a color written as `#RRGGBB`.
+#[cfg(feature = "persistence")]
+fn parse_color(color: &str) -> Option<Rgb> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    let [_, red, green, blue] = value.to_be_bytes();
+    Some(Rgb::from_array([red, green, blue]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_extensions() {
+        // 00FF: high resolution, 2206: call 0x206, 1204: loop, 00EE: return,
+        // followed by sprite data that looks like an XO-CHIP instruction
+        let rom = [0x00, 0xFF, 0x22, 0x06, 0x12, 0x04, 0x00, 0xEE, 0xF0, 0x01];
+        let info = RomInfo::new(&rom);
+        assert_eq!(info.size, 10);
+        assert_eq!(
+            info.extensions.iter().copied().collect::<Vec<_>>(),
+            vec![Extension::SuperChip]
+        );
+        assert_eq!(info.suggested_variant(), Variant::SuperChip);
+        assert!(!info.needs_megachip());
+        assert!(info.to_string().contains("Extensions: SCHIP"));
+
+        let info = RomInfo::new(&[0x00, 0xE0, 0x12, 0x02]);
+        assert_eq!(info.suggested_variant(), Variant::Chip8);
+        assert_eq!(info.load_address, memory::PROGRAM_START);
+
+        // 1260: enter hires mode, followed by the interpreter patch, which is
+        // not scanned, and 00FF at the start of the program
+        let mut rom = vec![0x12, 0x60];
+        rom.resize(0xC0, 0xF0);
+        rom.extend([0x00, 0xFF]);
+        let info = RomInfo::new(&rom);
+        assert_eq!(
+            info.extensions.iter().copied().collect::<Vec<_>>(),
+            vec![Extension::SuperChip, Extension::Hires]
+        );
+        assert_eq!(info.suggested_variant(), Variant::HiresChip8);
+    }
+
+    #[test]
+    fn test_detect_load_address() {
+        // 00FF: high resolution, 2606: call 0x606, 1604: loop, 00EE: return
+        let rom = [0x00, 0xFF, 0x26, 0x06, 0x16, 0x04, 0x00, 0xEE];
+        let info = RomInfo::new(&rom);
+        assert_eq!(info.load_address, memory::ETI_660_START);
+        assert!(info.extensions.contains(&Extension::SuperChip));
+        assert!(info.to_string().contains("Load address: 0x600"));
+
+        // A large ROM loaded at 0x200 also jumps above 0x600
+        assert_eq!(
+            detect_load_address(&[0x12, 0x02, 0x16, 0x00]),
+            memory::PROGRAM_START
+        );
+    }
+
+    #[cfg(feature = "persistence")]
+    #[test]
+    fn test_octo_metadata() {
+        let metadata = Metadata::from_json(
+            r##"{
+                "title": "Example",
+                "authors": ["Someone"],
+                "platform": "xochip",
+                "options": {
+                    "tickrate": 20,
+                    "fillColor": "#FF6600",
+                    "shiftQuirks": true,
+                    "loadStoreQuirk": true,
+                    "clipQuirks": false
+                }
+            }"##,
+        )
+        .unwrap();
+        assert_eq!(metadata.variant(), Some(Variant::XoChip));
+        assert_eq!(metadata.ips(), Some(1200));
+        assert_eq!(
+            metadata.foreground(),
+            Some(Rgb::from_array([0xFF, 0x66, 0x00]))
+        );
+        let quirks = metadata.quirks(Variant::Chip8.quirks());
+        assert!(!quirks.shift);
+        assert_eq!(quirks.memory_increment, MemoryIncrement::Unchanged);
+        assert!(!quirks.clip_sprites);
+    }
+}
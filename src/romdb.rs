@@ -0,0 +1,260 @@
+//! This module provides a database of known ROMs and the settings they play
+//! best with, so loading a ROM can select the right interpreter variant,
+//! speed and key bindings automatically.
+//!
+//! ROMs are identified by their [`crate::roms::hash`], so renamed files are
+//! still recognized. A starter database ships with the crate, and users can
+//! add or replace entries in `romdb.toml` in the platform configuration
+//! directory.
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config, keymap::Keymap, quirks::Variant, roms, runner::Chip8Runner, theme::Palette,
+    touch::TouchLayout,
+};
+
+/// The name of the file holding the user's entries, inside the configuration
+/// directory of [`config::Config::path`].
+pub const USER_FILE_NAME: &str = "romdb.toml";
+
+/// The database that ships with the crate.
+const BUILTIN_DATABASE: &str = include_str!("../roms/database.toml");
+
+/// The preferred settings of a ROM.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RomEntry {
+    /// The title of the ROM.
+    pub name: String,
+
+    /// The interpreter variant whose quirks the ROM expects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<Variant>,
+
+    /// The amount of instructions per second the ROM plays best at.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ips: Option<u64>,
+
+    /// The key bindings suited to the ROM.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keymap: Option<Keymap>,
+
+    /// The colors chosen for the ROM, overriding the configured ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub palette: Option<Palette>,
+
+    /// The key codes the ROM uses, so an on-screen keypad can show only
+    /// those, see [`crate::touch::TouchLayout::for_keys`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub touch_keys: Option<Vec<u8>>,
+}
+
+impl RomEntry {
+    /// Applies the variant, speed and colors of the entry to the given
+    /// [`Chip8Runner`], if set. The key bindings are left to the frontend.
+    pub fn apply(&self, runner: &mut Chip8Runner) {
+        if let Some(variant) = self.variant {
+            runner.chip8.set_variant(variant);
+        }
+        if let Some(ips) = self.ips {
+            runner.set_ips(ips);
+        }
+        if let Some(palette) = self.palette {
+            runner.chip8.bus.graphics.set_palette(palette);
+        }
+    }
+
+    /// Returns the layout of the on-screen keypad for the ROM: the keys in
+    /// [`RomEntry::touch_keys`], or the full keypad.
+    #[must_use]
+    pub fn touch_layout(&self) -> TouchLayout {
+        let keys = self
+            .touch_keys
+            .iter()
+            .flatten()
+            .fold(0, |keys, &key| keys | 1 << (key & 0xF));
+        TouchLayout::for_keys(keys)
+    }
+}
+
+/// A database of [`RomEntry`]s, keyed by ROM hash.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct RomDatabase {
+    entries: BTreeMap<String, RomEntry>,
+}
+
+impl RomDatabase {
+    /// Creates an empty [`RomDatabase`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the starter database that ships with the crate.
+    ///
+    /// # Panics
+    ///
+    /// Never panics in practice, since the starter database is valid TOML.
+    #[must_use]
+    pub fn builtin() -> Self {
+        Self::from_toml(BUILTIN_DATABASE).expect("builtin database is valid")
+    }
+
+    /// Returns the starter database with the user's entries from
+    /// [`RomDatabase::user_path`] merged in.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an existing user database cannot be read or is
+    /// invalid.
+    pub fn load_with_user_entries() -> io::Result<Self> {
+        let mut database = Self::builtin();
+        if let Some(path) = Self::user_path() {
+            match Self::load(path) {
+                Ok(user) => database.merge(user),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(database)
+    }
+
+    /// Returns the path of the user database in the platform configuration
+    /// directory, or [`None`] if the platform has none.
+    #[must_use]
+    pub fn user_path() -> Option<PathBuf> {
+        config::Config::path().and_then(|path| Some(path.parent()?.join(USER_FILE_NAME)))
+    }
+
+    /// Returns the entry of the ROM with the given hash, if any.
+    #[must_use]
+    pub fn get(&self, rom_hash: &str) -> Option<&RomEntry> {
+        self.entries.get(rom_hash)
+    }
+
+    /// Returns the entry of the given ROM data, if any.
+    #[must_use]
+    pub fn lookup(&self, data: &[u8]) -> Option<&RomEntry> {
+        self.get(&roms::hash(data))
+    }
+
+    /// Adds or replaces the entry of the ROM with the given hash.
+    pub fn insert(&mut self, rom_hash: impl Into<String>, entry: RomEntry) {
+        self.entries.insert(rom_hash.into(), entry);
+    }
+
+    /// Removes the entry of the ROM with the given hash. Returns the removed
+    /// entry, if any.
+    pub fn remove(&mut self, rom_hash: &str) -> Option<RomEntry> {
+        self.entries.remove(rom_hash)
+    }
+
+    /// Adds all entries of the given database, replacing entries of the same
+    /// ROMs.
+    pub fn merge(&mut self, other: Self) {
+        self.entries.extend(other.entries);
+    }
+
+    /// Returns an iterator over all ROM hashes and their entries.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &RomEntry)> {
+        self.entries
+            .iter()
+            .map(|(hash, entry)| (hash.as_str(), entry))
+    }
+
+    /// Returns the amount of entries.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the database has no entries.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Parses a [`RomDatabase`] from a TOML string.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the string is not a valid database.
+    pub fn from_toml(toml: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(toml)
+    }
+
+    /// Serializes the [`RomDatabase`] into a TOML string.
+    ///
+    /// # Panics
+    ///
+    /// Never panics in practice, since a [`RomDatabase`] always serializes to
+    /// TOML.
+    #[must_use]
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("database is always serializable")
+    }
+
+    /// Loads a [`RomDatabase`] from the TOML file at the given path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a valid database.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Self::from_toml(&contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Saves the [`RomDatabase`] as TOML to the file at the given path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_toml())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{theme, Chip8};
+
+    #[test]
+    fn test_builtin_roms_are_known() {
+        let database = RomDatabase::builtin();
+        for rom in roms::BUILTIN_ROMS {
+            let entry = database.lookup(rom.data).expect("builtin ROM is missing");
+            assert_eq!(entry.name, rom.name);
+        }
+    }
+
+    #[test]
+    fn test_user_entries() {
+        let rom = roms::find("noise").unwrap();
+        let mut user = RomDatabase::new();
+        user.insert(
+            roms::hash(rom.data),
+            RomEntry {
+                name: "noise".into(),
+                variant: Some(Variant::SuperChip),
+                palette: Some(theme::THEMES[1].palette),
+                ..Default::default()
+            },
+        );
+        let user = RomDatabase::from_toml(&user.to_toml()).unwrap();
+
+        let mut database = RomDatabase::builtin();
+        database.merge(user);
+        let entry = database.lookup(rom.data).unwrap();
+
+        let mut runner = Chip8Runner::new(Chip8::new());
+        entry.apply(&mut runner);
+        assert_eq!(runner.chip8.processor.quirks, Variant::SuperChip.quirks());
+        assert_eq!(runner.chip8.bus.graphics.palette, theme::THEMES[1].palette);
+    }
+}
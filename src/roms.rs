@@ -0,0 +1,102 @@
+//! This module provides a small library of built-in ROMs, so the emulator can
+//! be tried out without hunting for ROM files.
+//!
+//! All built-in ROMs were written for this crate and are in the public domain.
+
+use crate::{error::Chip8Error, Chip8};
+
+/// A ROM that is embedded into the crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuiltinRom {
+    /// The short name used to select the ROM, e.g. from a menu or the
+    /// command line.
+    pub name: &'static str,
+
+    /// A one-line description of what the ROM does.
+    pub description: &'static str,
+
+    /// The ROM data.
+    pub data: &'static [u8],
+}
+
+/// All built-in ROMs.
+pub const BUILTIN_ROMS: &[BuiltinRom] = &[
+    BuiltinRom {
+        name: "font",
+        description: "Draws the 16 built-in font digits and halts",
+        data: include_bytes!("../roms/font.ch8"),
+    },
+    BuiltinRom {
+        name: "keypad",
+        description: "Shows the digit of the last pressed key",
+        data: include_bytes!("../roms/keypad.ch8"),
+    },
+    BuiltinRom {
+        name: "noise",
+        description: "Keeps flipping random pixels on the screen",
+        data: include_bytes!("../roms/noise.ch8"),
+    },
+];
+
+/// Returns the built-in ROM with the given name, if any.
+#[must_use]
+pub fn find(name: &str) -> Option<&'static BuiltinRom> {
+    BUILTIN_ROMS.iter().find(|rom| rom.name == name)
+}
+
+/// Returns the SHA-1 digest of the given ROM data as a lowercase hexadecimal
+/// string. This identifies a ROM independently of its file name.
+#[must_use]
+pub fn hash(data: &[u8]) -> String {
+    sha1_smol::Sha1::from(data).digest().to_string()
+}
+
+impl BuiltinRom {
+    /// Resets the given [`Chip8`] and loads this ROM into it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::RomTooLarge`] if the ROM does not fit into
+    /// memory, which never happens for the built-in ROMs.
+    pub fn load(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        chip8.reset_and_load(self.data.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::StepResult;
+
+    #[test]
+    fn test_font() {
+        let mut chip8 = Chip8::new();
+        find("font").unwrap().load(&mut chip8).unwrap();
+
+        let mut result = Ok(StepResult::Continue);
+        for _ in 0..200 {
+            result = chip8.step();
+            if result != Ok(StepResult::Continue) {
+                break;
+            }
+        }
+        assert_eq!(result, Ok(StepResult::Loop));
+        assert_eq!(chip8.processor.v[0], 16);
+    }
+
+    #[test]
+    fn test_builtin_roms_run() {
+        for rom in BUILTIN_ROMS {
+            let mut chip8 = Chip8::new();
+            rom.load(&mut chip8).unwrap();
+            for _ in 0..100 {
+                assert!(chip8.step().is_ok(), "{} raised an error", rom.name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hash() {
+        assert_eq!(hash(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    }
+}
@@ -0,0 +1,1468 @@
+//! This module provides the rate-limited run loop of the emulator.
+//!
+//! A [`Chip8Runner`] executes instructions of a [`Chip8`] at a configurable
+//! amount of instructions per second (IPS). Frontends call
+//! [`Chip8Runner::update`] regularly (from a dedicated thread or once per
+//! animation frame), and the runner executes as many instructions as are due
+//! since the previous call. Without threads, e.g. on wasm32,
+//! [`Chip8Runner::run_async`] drives the updates from a future instead.
+//!
+//! The runner also ticks the delay and sound timers through
+//! [`Chip8::tick_timers`], interleaved with the instructions at the timer
+//! frequency of the emulated time, unless the frontend ticks them itself, see
+//! [`Chip8Runner::set_manual_timers`].
+//!
+//! On top of that, a speed multiplier scales both the instructions per second
+//! and the timer frequency, which frontends use for fast-forward and
+//! slow-motion.
+
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::ops::ControlFlow;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    mpsc, Arc,
+};
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{path::PathBuf, time::Instant};
+
+use crate::{
+    clock::Clock,
+    control::Controls,
+    error::Chip8Error,
+    events::{EmulatorEvent, EventBus},
+    fault::{CrashReport, Fault},
+    frontend::Chip8Frontend,
+    history::SaveState,
+    hotkeys::{Action, SLOTS},
+    processor::StepResult,
+    stats::{Stats, StatsMeter},
+    timing::{self, Timing},
+    Chip8,
+};
+
+/// The default amount of instructions executed per second.
+pub const DEFAULT_IPS: u64 = 700;
+
+/// The fraction of the speed a runner keeps while throttled in the
+/// background by [`FocusBehavior::Throttle`].
+pub const BACKGROUND_SPEED: f64 = 0.1;
+
+/// The fastest speed [`Action::SpeedUp`] goes up to.
+pub const MAX_SPEED: f64 = 8.0;
+
+/// The slowest speed [`Action::SlowDown`] goes down to.
+pub const MIN_SPEED: f64 = 0.125;
+
+/// The time [`Chip8Runner::run`] sleeps between updates while throttled in
+/// the background.
+const BACKGROUND_SLEEP: Duration = Duration::from_millis(50);
+
+/// The time [`Chip8Runner::run`] sleeps between updates while the program
+/// sleeps until a key press, which keeps the timers ticking at 60 Hz.
+const KEY_SLEEP: Duration = Duration::from_millis(16);
+
+/// What a [`Chip8Runner`] does while the window of the frontend is not
+/// focused, see [`Chip8Runner::set_focused`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FocusBehavior {
+    /// Keep running at full speed.
+    #[default]
+    KeepRunning,
+    /// Pause, and resume once focused again.
+    Pause,
+    /// Slow down to [`BACKGROUND_SPEED`] and update less often.
+    Throttle,
+}
+
+impl FocusBehavior {
+    /// All behaviors, in the order they should be offered to the user.
+    pub const ALL: [Self; 3] = [Self::KeepRunning, Self::Pause, Self::Throttle];
+
+    /// Returns the display name of the behavior.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::KeepRunning => "Keep running",
+            Self::Pause => "Pause",
+            Self::Throttle => "Throttle",
+        }
+    }
+}
+
+/// The longest amount of time that is caught up on in a single update. This
+/// prevents a burst of instructions after the frontend stalled for a while.
+const MAX_CATCH_UP: Duration = Duration::from_millis(100);
+
+/// An event that stops the run loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunnerEvent {
+    /// The program counter ran past the end of memory.
+    End,
+
+    /// The program jumped to its own address at `pc` and will not make any
+    /// further progress.
+    Loop {
+        /// The address of the looping instruction.
+        pc: usize,
+    },
+
+    /// The program raised a [`Chip8Error`].
+    Error(Chip8Error),
+
+    /// The program counter reached the address passed to
+    /// [`Chip8Runner::run_to`], and execution was paused.
+    Reached {
+        /// The reached address.
+        pc: usize,
+    },
+
+    /// The program counter reached a breakpoint added with
+    /// [`Chip8Runner::add_breakpoint`], and execution was paused.
+    Breakpoint {
+        /// The address of the breakpoint.
+        pc: usize,
+    },
+
+    /// An instruction caused an event added with
+    /// [`Chip8Runner::add_event_break`], and execution was paused.
+    Event {
+        /// The address of the instruction that caused the event.
+        pc: usize,
+        /// The event that occurred.
+        event: BreakEvent,
+    },
+}
+
+/// An event that pauses execution once it occurs, so the debugger stops at
+/// interesting moments without knowing their address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BreakEvent {
+    /// The screen was cleared or a sprite was drawn.
+    Draw,
+    /// VF became `1`, e.g. on a sprite collision or an arithmetic carry.
+    FlagSet,
+    /// The sound timer was loaded with a non-zero value, starting the buzzer.
+    SoundStart,
+    /// The program read the given key, by testing it with `Ex9E` or `ExA1`,
+    /// or by receiving it after waiting with `Fx0A`.
+    KeyRead(u8),
+}
+
+impl BreakEvent {
+    /// Returns the watched event caused by executing `opcode`, given the
+    /// values of VF and VX before it was executed.
+    fn detect(
+        events: &BTreeSet<Self>,
+        chip8: &Chip8,
+        opcode: usize,
+        vf: u8,
+        vx: u8,
+    ) -> Option<Self> {
+        let watched = |event| events.contains(&event).then_some(event);
+        let key_read = match opcode & 0xF0FF {
+            0xE09E | 0xE0A1 => watched(Self::KeyRead(vx)),
+            _ => None,
+        };
+        let draw = (opcode == 0x00E0 || opcode & 0xF000 == 0xD000)
+            .then(|| watched(Self::Draw))
+            .flatten();
+        let sound = (opcode & 0xF0FF == 0xF018 && vx > 0)
+            .then(|| watched(Self::SoundStart))
+            .flatten();
+        let flag = (vf != 1 && chip8.processor.v[0xF] == 1)
+            .then(|| watched(Self::FlagSet))
+            .flatten();
+        key_read.or(draw).or(sound).or(flag)
+    }
+}
+
+/// Drives a [`Chip8`] at a configurable speed.
+#[derive(Debug)]
+pub struct Chip8Runner {
+    /// The [`Chip8`] system being driven.
+    pub chip8: Chip8,
+    /// The target amount of instructions per second, shared so it can be
+    /// adjusted from other threads.
+    ips: Arc<AtomicU64>,
+    /// The [`Timing`] model used to pace execution.
+    timing: Timing,
+    /// The multiplier applied to the emulation speed.
+    speed: f64,
+    /// The timer frequency (in Hz) at normal speed.
+    timer_frequency: f64,
+    /// Whether the frontend ticks the timers instead of the runner.
+    manual_timers: bool,
+    /// The share of the timer period that passed since the previous tick.
+    tick_phase: f64,
+    /// The time that is due but not spent on executing instructions yet. This
+    /// is measured in instructions for [`Timing::Flat`] and in microseconds
+    /// for [`Timing::CosmacVip`].
+    budget: f64,
+    /// The channel that [`Fault`]s are reported on, if subscribed.
+    faults: Option<mpsc::Sender<Fault>>,
+    /// The subscribers to the [`EmulatorEvent`]s.
+    events: EventBus,
+    /// The report of the latest error raised by the program.
+    crash_report: Option<CrashReport>,
+    /// The directory crash reports are written to, if any.
+    #[cfg(not(target_arch = "wasm32"))]
+    crash_dir: Option<PathBuf>,
+    /// The address to pause at, set by [`Chip8Runner::run_to`].
+    run_to: Option<usize>,
+    /// The addresses to pause at whenever they are reached.
+    breakpoints: BTreeSet<usize>,
+    /// The events to pause at whenever they occur.
+    break_events: BTreeSet<BreakEvent>,
+    /// The execution statistics.
+    stats: StatsMeter,
+    /// What to do while the window is not focused.
+    focus_behavior: FocusBehavior,
+    /// Whether the window of the frontend is focused.
+    focused: bool,
+    /// Whether execution was paused because the window lost focus.
+    paused_by_focus: bool,
+    /// The save states taken through [`Action::SaveState`], by slot.
+    save_slots: [Option<SaveState>; SLOTS],
+    /// The time of the previous [`Chip8Runner::update`].
+    #[cfg(not(target_arch = "wasm32"))]
+    last_update: Instant,
+    #[cfg(target_arch = "wasm32")]
+    last_update: f64,
+}
+
+impl Chip8Runner {
+    /// Creates a new [`Chip8Runner`] for the given [`Chip8`], running at
+    /// [`DEFAULT_IPS`].
+    #[must_use]
+    pub fn new(chip8: Chip8) -> Self {
+        Self {
+            chip8,
+            ips: Arc::new(AtomicU64::new(DEFAULT_IPS)),
+            timing: Timing::default(),
+            speed: 1.0,
+            timer_frequency: Clock::TIMER_FREQUENCY_HZ,
+            manual_timers: false,
+            tick_phase: 0.0,
+            budget: 0.0,
+            faults: None,
+            events: EventBus::new(),
+            crash_report: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            crash_dir: None,
+            run_to: None,
+            breakpoints: BTreeSet::new(),
+            break_events: BTreeSet::new(),
+            stats: StatsMeter::default(),
+            focus_behavior: FocusBehavior::default(),
+            focused: true,
+            paused_by_focus: false,
+            save_slots: Default::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            last_update: Instant::now(),
+            #[cfg(target_arch = "wasm32")]
+            last_update: js_sys::Date::now(),
+        }
+    }
+
+    /// Returns the target amount of instructions per second.
+    #[must_use]
+    pub fn ips(&self) -> u64 {
+        self.ips.load(Ordering::SeqCst)
+    }
+
+    /// Sets the target amount of instructions per second.
+    pub fn set_ips(&self, ips: u64) {
+        self.ips.store(ips, Ordering::SeqCst);
+    }
+
+    /// Returns the [`Timing`] model used to pace execution.
+    #[must_use]
+    pub const fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    /// Sets the [`Timing`] model used to pace execution. With
+    /// [`Timing::CosmacVip`], the target instructions per second are ignored.
+    pub const fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+        self.budget = 0.0;
+    }
+
+    /// Returns the multiplier applied to the emulation speed.
+    #[must_use]
+    pub const fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Sets the multiplier applied to the emulation speed. Both the
+    /// instructions per second and the timer frequency are scaled, so `4.0`
+    /// fast-forwards at four times the normal speed and `0.25` runs in slow
+    /// motion.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `speed` is negative or not a number.
+    pub fn set_speed(&mut self, speed: f64) {
+        assert!(speed >= 0.0, "speed must not be negative");
+        log::debug!("speed set to {speed}x");
+        self.speed = speed;
+    }
+
+    /// Returns the timer frequency (in Hz) at normal speed.
+    #[must_use]
+    pub const fn timer_frequency(&self) -> f64 {
+        self.timer_frequency
+    }
+
+    /// Overrides the timer frequency (in Hz) at normal speed, which defaults
+    /// to [`Clock::TIMER_FREQUENCY_HZ`]. A frequency of `0` stops the timers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frequency` is negative or not a number.
+    pub fn set_timer_frequency(&mut self, frequency: f64) {
+        assert!(frequency >= 0.0, "timer frequency must not be negative");
+        self.timer_frequency = frequency;
+    }
+
+    /// Returns whether the frontend ticks the timers itself instead of the
+    /// runner.
+    #[must_use]
+    pub const fn manual_timers(&self) -> bool {
+        self.manual_timers
+    }
+
+    /// Leaves ticking the timers to the frontend, which then calls
+    /// [`Chip8::tick_timers`] itself, e.g. once per `requestAnimationFrame`.
+    /// Otherwise the runner ticks them in step with the emulated time.
+    pub const fn set_manual_timers(&mut self, manual: bool) {
+        self.manual_timers = manual;
+    }
+
+    /// Returns the speed multiplier, reduced while throttled in the
+    /// background.
+    fn effective_speed(&self) -> f64 {
+        if self.is_throttled() {
+            self.speed * BACKGROUND_SPEED
+        } else {
+            self.speed
+        }
+    }
+
+    /// Returns what the runner does while the window is not focused.
+    #[must_use]
+    pub const fn focus_behavior(&self) -> FocusBehavior {
+        self.focus_behavior
+    }
+
+    /// Sets what the runner does while the window is not focused. Takes
+    /// effect the next time the focus changes.
+    pub const fn set_focus_behavior(&mut self, behavior: FocusBehavior) {
+        self.focus_behavior = behavior;
+    }
+
+    /// Tells the runner whether the window of the frontend is focused, and
+    /// applies the [`FocusBehavior`]. Execution paused by a focus loss is
+    /// resumed once focused again, but a manual pause is kept.
+    pub fn set_focused(&mut self, focused: bool) {
+        if focused == self.focused {
+            return;
+        }
+        if focused {
+            self.focused = true;
+            if std::mem::take(&mut self.paused_by_focus) {
+                self.resume();
+            }
+            return;
+        }
+        self.focused = false;
+        match self.focus_behavior {
+            FocusBehavior::KeepRunning | FocusBehavior::Throttle => {}
+            FocusBehavior::Pause => {
+                if !self.chip8.controls.is_paused() {
+                    self.pause();
+                    self.paused_by_focus = true;
+                }
+            }
+        }
+    }
+
+    /// Returns whether the window of the frontend is focused.
+    #[must_use]
+    pub const fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    /// Returns whether the runner is throttled because the window is not
+    /// focused.
+    #[must_use]
+    pub const fn is_throttled(&self) -> bool {
+        !self.focused && matches!(self.focus_behavior, FocusBehavior::Throttle)
+    }
+
+    /// Returns whether the runner saves power while the program waits for a
+    /// key press.
+    #[must_use]
+    pub const fn power_saving(&self) -> bool {
+        self.chip8.idle.is_enabled()
+    }
+
+    /// Starts or stops saving power while the program waits for a key press,
+    /// either blocked in `Fx0A` or spinning in an idle loop detected by
+    /// [`Chip8::idle`]. Meanwhile, each update only executes a single
+    /// instruction and a thread driving the runner sleeps longer, until a
+    /// key is pressed.
+    pub const fn set_power_saving(&mut self, enabled: bool) {
+        self.chip8.idle.set_enabled(enabled);
+    }
+
+    /// Returns whether the program sleeps until a key press while
+    /// [`Chip8Runner::power_saving`] is enabled.
+    #[must_use]
+    pub const fn is_sleeping(&self) -> bool {
+        self.chip8.idle.idle_pc().is_some()
+            || self.chip8.idle.is_enabled() && self.chip8.bus.input.waiting()
+    }
+
+    /// Carries out the given hotkey [`Action`]. Save states are kept in the
+    /// runner, one per slot.
+    ///
+    /// # Returns
+    ///
+    /// [`false`] if the action is up to the frontend, which is the case for
+    /// [`Action::ToggleMute`] and [`Action::ToggleMode`], if the slot to load
+    /// is empty, or if the ROM cannot be reloaded on [`Action::Reset`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the action refers to a slot beyond [`SLOTS`].
+    pub fn perform(&mut self, action: Action) -> bool {
+        match action {
+            Action::TogglePause => {
+                if self.chip8.controls.is_paused() {
+                    self.resume();
+                } else {
+                    self.pause();
+                }
+            }
+            Action::Step => {
+                self.pause();
+                self.chip8.controls.step();
+            }
+            Action::SaveState(slot) => {
+                self.save_slots[slot] = Some(self.chip8.save_state());
+                log::info!("saved the state to slot {slot}");
+            }
+            Action::LoadState(slot) => {
+                let Some(state) = &self.save_slots[slot] else {
+                    return false;
+                };
+                self.chip8.load_state(state);
+                log::info!("loaded the state from slot {slot}");
+            }
+            Action::SpeedUp => self.set_speed((self.speed * 2.0).min(MAX_SPEED)),
+            Action::SlowDown => self.set_speed((self.speed / 2.0).max(MIN_SPEED)),
+            Action::Reset => {
+                if self.chip8.reset_keep_rom().is_err() {
+                    return false;
+                }
+                self.resume();
+            }
+            Action::ToggleMute | Action::ToggleMode => return false,
+        }
+        true
+    }
+
+    /// Returns the save state in the given slot, if any.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `slot` is not below [`SLOTS`].
+    #[must_use]
+    pub const fn save_slot(&self, slot: usize) -> Option<&SaveState> {
+        self.save_slots[slot].as_ref()
+    }
+
+    /// Puts the given save state into the given slot, or empties the slot if
+    /// [`None`] is given, e.g. to restore slots kept across restarts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `slot` is not below [`SLOTS`].
+    pub fn set_save_slot(&mut self, slot: usize, state: Option<SaveState>) {
+        self.save_slots[slot] = state;
+    }
+
+    /// Returns a shared handle to the target amount of instructions per
+    /// second, e.g. for a speed slider running on another thread.
+    #[must_use]
+    pub fn ips_handle(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.ips)
+    }
+
+    /// Returns a clone of the [`Controls`] handle of the driven [`Chip8`].
+    #[must_use]
+    pub fn controls(&self) -> Controls {
+        self.chip8.controls.clone()
+    }
+
+    /// Subscribes to the [`Fault`]s raised by the program. Whenever a step
+    /// raises a [`Chip8Error`], execution is paused and a [`Fault`] is sent on
+    /// the returned channel, so a frontend can decide whether to reset the
+    /// system or skip the faulting instruction. Subscribing again replaces
+    /// the previous channel.
+    pub fn subscribe_faults(&mut self) -> mpsc::Receiver<Fault> {
+        let (sender, receiver) = mpsc::channel();
+        self.faults = Some(sender);
+        receiver
+    }
+
+    /// Subscribes to the [`EmulatorEvent`]s published while the runner
+    /// executes instructions and loads ROMs, so a frontend can e.g. present
+    /// the display only once it changed. Every subscriber receives every
+    /// event.
+    pub fn subscribe_events(&mut self) -> mpsc::Receiver<EmulatorEvent> {
+        self.events.subscribe()
+    }
+
+    /// Returns the [`CrashReport`] of the latest error raised by the
+    /// program, e.g. for a "Save crash report" button in an error dialog.
+    #[must_use]
+    pub const fn crash_report(&self) -> Option<&CrashReport> {
+        self.crash_report.as_ref()
+    }
+
+    /// Writes a [`CrashReport`] into the given directory whenever the program
+    /// raises an error, or stops writing them if [`None`] is given. A program
+    /// running into the same error at the same address again does not write
+    /// another report.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_crash_dir(&mut self, dir: Option<PathBuf>) {
+        self.crash_dir = dir;
+    }
+
+    /// Resets the system and loads the given ROM data, see
+    /// [`Chip8::reset_and_load`], and publishes [`EmulatorEvent::RomLoaded`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM does not fit into memory.
+    pub fn load_rom(&mut self, data: Vec<u8>) -> Result<(), Chip8Error> {
+        let size = data.len();
+        self.chip8.reset_and_load(data)?;
+        log::info!("loaded a ROM of {size} bytes");
+        self.events.publish(EmulatorEvent::RomLoaded);
+        self.events.publish(EmulatorEvent::DisplayUpdated);
+        Ok(())
+    }
+
+    /// Returns the execution statistics, e.g. to show them in an overlay or
+    /// graph them.
+    #[must_use]
+    pub const fn stats(&self) -> Stats {
+        self.stats.stats()
+    }
+
+    /// Counts a frame drawn by the frontend in the [`Stats`]. Call this
+    /// whenever the display is presented.
+    pub const fn record_frame(&mut self) {
+        self.stats.frame();
+    }
+
+    /// Discards the execution statistics.
+    pub fn reset_stats(&mut self) {
+        self.stats.reset();
+    }
+
+    /// Pauses execution.
+    pub fn pause(&self) {
+        log::debug!("paused");
+        self.chip8.controls.pause();
+    }
+
+    /// Resumes execution at the current speed.
+    pub fn resume(&mut self) {
+        log::debug!("resumed");
+        self.restart_clock();
+        self.chip8.controls.resume();
+    }
+
+    /// Resumes execution at the given amount of instructions per second.
+    pub fn run_at(&mut self, ips: u64) {
+        self.set_ips(ips);
+        self.resume();
+    }
+
+    /// Resumes execution until the program counter reaches the given
+    /// address, e.g. the line under the cursor in a disassembly view. The
+    /// address acts as a temporary breakpoint: once reached, execution is
+    /// paused, [`RunnerEvent::Reached`] is raised and the address is
+    /// forgotten.
+    pub fn run_to(&mut self, address: usize) {
+        self.run_to = Some(address);
+        self.resume();
+    }
+
+    /// Returns the address passed to [`Chip8Runner::run_to`] that was not
+    /// reached yet, if any.
+    #[must_use]
+    pub const fn run_to_address(&self) -> Option<usize> {
+        self.run_to
+    }
+
+    /// Forgets the address passed to [`Chip8Runner::run_to`] without pausing.
+    pub const fn cancel_run_to(&mut self) {
+        self.run_to = None;
+    }
+
+    /// Adds a breakpoint at the given address. Once the program counter
+    /// reaches it while running, execution is paused and
+    /// [`RunnerEvent::Breakpoint`] is raised. Returns whether the breakpoint
+    /// is new.
+    pub fn add_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.insert(address)
+    }
+
+    /// Removes the breakpoint at the given address. Returns whether there was
+    /// one.
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    /// Returns an iterator over the addresses of all breakpoints, in
+    /// ascending order.
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Removes all breakpoints.
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Adds a breakpoint on the given event. Once it occurs while running,
+    /// execution is paused after the instruction that caused it and
+    /// [`RunnerEvent::Event`] is raised. Returns whether the breakpoint is
+    /// new.
+    pub fn add_event_break(&mut self, event: BreakEvent) -> bool {
+        self.break_events.insert(event)
+    }
+
+    /// Removes the breakpoint on the given event. Returns whether there was
+    /// one.
+    pub fn remove_event_break(&mut self, event: BreakEvent) -> bool {
+        self.break_events.remove(&event)
+    }
+
+    /// Returns an iterator over all events execution pauses at.
+    pub fn event_breaks(&self) -> impl Iterator<Item = BreakEvent> + '_ {
+        self.break_events.iter().copied()
+    }
+
+    /// Removes all breakpoints on events.
+    pub fn clear_event_breaks(&mut self) {
+        self.break_events.clear();
+    }
+
+    /// Immediately executes up to `n` instructions, regardless of whether
+    /// execution is paused.
+    ///
+    /// # Returns
+    ///
+    /// The [`RunnerEvent`] that stopped execution early, if any.
+    pub fn step_n(&mut self, n: u32) -> Option<RunnerEvent> {
+        self.observe(|runner| {
+            for _ in 0..n {
+                let result = runner.chip8.step();
+                if let Some(event) = runner.handle(result) {
+                    return Some(event);
+                }
+            }
+            None
+        })
+    }
+
+    /// Immediately executes the instructions of a single frame, regardless of
+    /// whether execution is paused. A frame is one tick of the timers at the
+    /// timer frequency of normal speed, e.g. 1/60th of a second, which is the
+    /// granularity most programs are written for.
+    ///
+    /// With [`Timing::Flat`] the frame holds the target instructions per
+    /// second divided by the timer frequency, with [`Timing::CosmacVip`] the
+    /// instructions that fit into the frame on the COSMAC VIP. At least one
+    /// instruction is executed. The frame ends early once the program waits
+    /// for a key press.
+    ///
+    /// # Returns
+    ///
+    /// The [`RunnerEvent`] that stopped execution early, if any.
+    pub fn step_frame(&mut self) -> Option<RunnerEvent> {
+        self.observe(Self::execute_frame)
+    }
+
+    /// Executes the instructions of a single frame, see
+    /// [`Chip8Runner::step_frame`].
+    fn execute_frame(&mut self) -> Option<RunnerEvent> {
+        // stopped timers leave the frame at its usual length
+        let stopped = self.timer_frequency <= 0.0;
+        let frequency = if stopped {
+            Clock::TIMER_FREQUENCY_HZ
+        } else {
+            self.timer_frequency
+        };
+        let frame = 1.0 / frequency;
+        let mut budget = match self.timing {
+            #[allow(clippy::cast_precision_loss)]
+            Timing::Flat => (frame * self.ips() as f64).round().max(1.0),
+            Timing::CosmacVip => frame * 1_000_000.0,
+        };
+        loop {
+            budget -= match self.timing {
+                Timing::Flat => 1.0,
+                Timing::CosmacVip => {
+                    let opcode = self.chip8.current_opcode().unwrap_or_default();
+                    f64::from(timing::cosmac_vip_cost(opcode))
+                }
+            };
+            let result = self.chip8.step();
+            let waiting = matches!(
+                result,
+                Ok(StepResult::WaitingForKey | StepResult::WaitingForVblank)
+            );
+            if let Some(event) = self.handle(result) {
+                return Some(event);
+            }
+            if waiting || budget <= 0.0 {
+                break;
+            }
+        }
+        if !self.manual_timers && !stopped {
+            self.chip8.tick_timers();
+        }
+        None
+    }
+
+    /// Executes all instructions that are due since the previous update.
+    ///
+    /// # Returns
+    ///
+    /// The [`RunnerEvent`] that stopped execution, if any.
+    pub fn update(&mut self) -> Option<RunnerEvent> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let elapsed = {
+            let now = Instant::now();
+            now.duration_since(std::mem::replace(&mut self.last_update, now))
+        };
+        #[cfg(target_arch = "wasm32")]
+        let elapsed = {
+            let now = js_sys::Date::now();
+            Duration::from_secs_f64((now - std::mem::replace(&mut self.last_update, now)) / 1000.0)
+        };
+        self.advance(elapsed)
+    }
+
+    /// Runs a single displayed frame with the given [`Chip8Frontend`]: applies
+    /// the key state of the frontend, executes all instructions that are due
+    /// since the previous update, counts the frame in the [`Stats`] and then
+    /// lets the frontend draw the display and set the buzzer.
+    ///
+    /// # Returns
+    ///
+    /// The [`RunnerEvent`] that stopped execution, if any.
+    pub fn run_frame(&mut self, frontend: &mut impl Chip8Frontend) -> Option<RunnerEvent> {
+        #[allow(clippy::cast_possible_truncation)] // there are 16 keys
+        for (key_code, pressed) in frontend.keys().into_iter().enumerate() {
+            let key_code = key_code as u8;
+            if self.chip8.bus.input.is_key_pressed(key_code) != pressed {
+                self.chip8.update_key_state(key_code, pressed);
+            }
+        }
+        let event = self.update();
+        self.record_frame();
+        let bus = &self.chip8.bus;
+        frontend.draw(&bus.graphics);
+        frontend.beep(bus.clock.sound_timer.load(Ordering::SeqCst) > 0);
+        event
+    }
+
+    /// Executes the instructions that are due within `elapsed` time with the
+    /// current [`Timing`], scaled by the speed. While paused, only explicitly requested steps are
+    /// executed.
+    ///
+    /// # Returns
+    ///
+    /// The [`RunnerEvent`] that stopped execution, if any.
+    pub fn advance(&mut self, elapsed: Duration) -> Option<RunnerEvent> {
+        self.observe(|runner| runner.advance_scaled(elapsed))
+    }
+
+    /// Executes the instructions that are due within `elapsed` time, see
+    /// [`Chip8Runner::advance`].
+    fn advance_scaled(&mut self, elapsed: Duration) -> Option<RunnerEvent> {
+        let scaled = elapsed.min(MAX_CATCH_UP).as_secs_f64() * self.effective_speed();
+        let flow = self.advance_timed(scaled);
+        self.stats.advance(elapsed, self.ips());
+        match flow {
+            ControlFlow::Continue(()) => None,
+            ControlFlow::Break(event) => {
+                self.budget = 0.0;
+                event
+            }
+        }
+    }
+
+    /// Executes the instructions due within `elapsed` seconds of emulated
+    /// time, and ticks the timers at the timer frequency in between, unless
+    /// the frontend ticks them. The timers keep ticking while the program
+    /// sleeps until a key press, but not while paused.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn advance_timed(&mut self, elapsed: f64) -> ControlFlow<Option<RunnerEvent>> {
+        let frequency = self.timer_frequency;
+        if self.manual_timers || frequency <= 0.0 {
+            return self.advance_instructions(elapsed);
+        }
+        let phase = elapsed.mul_add(frequency, self.tick_phase);
+        let ticks = phase.floor();
+        for _ in 0..ticks as u64 {
+            let until_tick = (1.0 - self.tick_phase) / frequency;
+            let flow = self.advance_instructions(until_tick);
+            // the timers stand still while paused, even if no instruction
+            // was due to notice it
+            if self.chip8.controls.is_paused() {
+                return flow;
+            }
+            if let ControlFlow::Break(Some(event)) = flow {
+                return ControlFlow::Break(Some(event));
+            }
+            self.chip8.tick_timers();
+            self.tick_phase = 0.0;
+        }
+        let rest = (phase - ticks - self.tick_phase) / frequency;
+        self.tick_phase = phase - ticks;
+        self.advance_instructions(rest)
+    }
+
+    /// Executes the instructions due within `elapsed` seconds with the
+    /// current [`Timing`].
+    fn advance_instructions(&mut self, elapsed: f64) -> ControlFlow<Option<RunnerEvent>> {
+        match self.timing {
+            Timing::Flat => self.advance_flat(elapsed),
+            Timing::CosmacVip => self.advance_cosmac_vip(elapsed),
+        }
+    }
+
+    /// Executes the instructions due within `elapsed` seconds at the target
+    /// instructions per second.
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn advance_flat(&mut self, elapsed: f64) -> ControlFlow<Option<RunnerEvent>> {
+        self.budget += elapsed * self.ips() as f64;
+        let due = self.budget.floor();
+        self.budget -= due;
+
+        for _ in 0..due as u64 {
+            self.execute()?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Executes the instructions that fit within `elapsed` seconds according
+    /// to their COSMAC VIP execution time.
+    fn advance_cosmac_vip(&mut self, elapsed: f64) -> ControlFlow<Option<RunnerEvent>> {
+        self.budget += elapsed * 1_000_000.0;
+        loop {
+            let opcode = self.chip8.current_opcode().unwrap_or_default();
+            let cost = f64::from(timing::cosmac_vip_cost(opcode));
+            if self.budget < cost {
+                return ControlFlow::Continue(());
+            }
+            self.budget -= cost;
+            self.execute()?;
+        }
+    }
+
+    /// Executes a single instruction, unless execution is paused. Breaks if
+    /// execution is paused, the instruction raised a [`RunnerEvent`], or the
+    /// program sleeps until a key press, see [`Chip8Runner::is_sleeping`].
+    fn execute(&mut self) -> ControlFlow<Option<RunnerEvent>> {
+        let (pc, opcode, vf) = (
+            self.chip8.processor.pc,
+            self.chip8.current_opcode().unwrap_or_default(),
+            self.chip8.processor.v[0xF],
+        );
+        let vx = self.chip8.processor.v[(opcode >> 8) & 0xF];
+        let received_key = self.chip8.bus.input.peek_response().map(|r| r.key_code);
+        let Some(result) = self.chip8.try_step() else {
+            // paused without any pending steps
+            return ControlFlow::Break(None);
+        };
+        let executed = matches!(result, Ok(StepResult::Continue | StepResult::Loop));
+        if let Some(event) = self.handle(result) {
+            return ControlFlow::Break(Some(event));
+        }
+        if executed && !self.break_events.is_empty() {
+            let event = received_key
+                .map(BreakEvent::KeyRead)
+                .filter(|event| self.break_events.contains(event))
+                .or_else(|| BreakEvent::detect(&self.break_events, &self.chip8, opcode, vf, vx));
+            if let Some(event) = event {
+                log::debug!("break on {event:?} at {pc:#05X}");
+                self.chip8.controls.pause();
+                return ControlFlow::Break(Some(RunnerEvent::Event { pc, event }));
+            }
+        }
+        let pc = self.chip8.processor.pc;
+        if self.run_to == Some(pc) {
+            self.run_to = None;
+            log::debug!("reached {pc:#05X}");
+            self.chip8.controls.pause();
+            return ControlFlow::Break(Some(RunnerEvent::Reached { pc }));
+        }
+        if self.breakpoints.contains(&pc) {
+            log::debug!("breakpoint at {pc:#05X}");
+            self.chip8.controls.pause();
+            return ControlFlow::Break(Some(RunnerEvent::Breakpoint { pc }));
+        }
+        if self.is_sleeping() {
+            // nothing changes until a key is pressed, so the rest of the
+            // frame is skipped to save power
+            return ControlFlow::Break(None);
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Runs the emulator on the current thread until a [`RunnerEvent`] stops
+    /// it. Pausing through [`Chip8Runner::controls`] keeps this loop idle
+    /// rather than returning. While throttled in the background or sleeping
+    /// until a key press, the loop wakes up less often.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn run(&mut self) -> RunnerEvent {
+        self.restart_clock();
+        loop {
+            if let Some(event) = self.update() {
+                return event;
+            }
+            std::thread::sleep(self.idle_interval());
+        }
+    }
+
+    /// Runs the emulator as a future until a [`RunnerEvent`] stops it, like
+    /// [`Chip8Runner::run`] but without blocking a thread, so it also works
+    /// on single-threaded targets like wasm32. Between updates, the future
+    /// awaits `sleep` with the time to wait, which comes from the async
+    /// runtime, e.g. `tokio::time::sleep` on desktop or
+    /// [`crate::webtimer::sleep`] in the browser:
+    ///
+    /// ```ignore
+    /// let event = runner.run_async(tokio::time::sleep).await;
+    /// ```
+    pub async fn run_async<F: Future<Output = ()>>(
+        &mut self,
+        mut sleep: impl FnMut(Duration) -> F,
+    ) -> RunnerEvent {
+        self.restart_clock();
+        loop {
+            if let Some(event) = self.update() {
+                return event;
+            }
+            sleep(self.idle_interval()).await;
+        }
+    }
+
+    /// Returns how long a thread or future driving the runner should sleep
+    /// between updates, which is longer while throttled in the background or
+    /// sleeping until a key press.
+    pub(crate) const fn idle_interval(&self) -> Duration {
+        if self.is_throttled() {
+            BACKGROUND_SLEEP
+        } else if self.is_sleeping() {
+            KEY_SLEEP
+        } else {
+            Duration::from_millis(1)
+        }
+    }
+
+    /// Discards any time that passed since the previous update.
+    pub(crate) fn restart_clock(&mut self) {
+        self.budget = 0.0;
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.last_update = Instant::now();
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.last_update = js_sys::Date::now();
+        }
+    }
+
+    /// Runs `execute` and publishes the [`EmulatorEvent`]s it caused: changes
+    /// of the display and the buzzer, and the [`RunnerEvent`] it returned.
+    fn observe(
+        &mut self,
+        execute: impl FnOnce(&mut Self) -> Option<RunnerEvent>,
+    ) -> Option<RunnerEvent> {
+        if !self.events.has_subscribers() {
+            return execute(self);
+        }
+        let sounding =
+            |runner: &Self| runner.chip8.bus.clock.sound_timer.load(Ordering::SeqCst) > 0;
+        let (display, sound) = (self.chip8.bus.graphics, sounding(self));
+        let event = execute(self);
+        if self.chip8.bus.graphics != display {
+            self.events.publish(EmulatorEvent::DisplayUpdated);
+        }
+        match (sound, sounding(self)) {
+            (false, true) => self.events.publish(EmulatorEvent::SoundStarted),
+            (true, false) => self.events.publish(EmulatorEvent::SoundStopped),
+            _ => {}
+        }
+        if let Some(event) = event {
+            self.events.publish(event.into());
+        }
+        event
+    }
+
+    /// Captures the [`CrashReport`] of the given error, and writes it into the
+    /// crash directory unless it repeats the previous one.
+    fn report_crash(&mut self, err: Chip8Error) {
+        let report = CrashReport::capture(&self.chip8, err);
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(dir) = self.crash_dir.as_ref().filter(|_| {
+            self.crash_report
+                .as_ref()
+                .is_none_or(|previous| !previous.same_fault(&report))
+        }) {
+            match report.save_to(dir) {
+                Ok(path) => log::error!("wrote a crash report to {}", path.display()),
+                Err(err) => log::error!("cannot write a crash report: {err}"),
+            }
+        }
+        self.crash_report = Some(report);
+    }
+
+    /// Translates the result of a step into the [`RunnerEvent`] it raises, if
+    /// any.
+    fn handle(&mut self, result: Result<StepResult, Chip8Error>) -> Option<RunnerEvent> {
+        if let Ok(StepResult::Continue | StepResult::Loop) = result {
+            self.stats.instruction();
+        }
+        match result {
+            Ok(StepResult::Continue | StepResult::WaitingForKey | StepResult::WaitingForVblank) => {
+                None
+            }
+            Ok(StepResult::Loop) => {
+                let pc = self.chip8.processor.pc;
+                log::debug!("the program loops at {pc:#05X}");
+                Some(RunnerEvent::Loop { pc })
+            }
+            Ok(StepResult::End) => {
+                log::debug!("the program ran past the end of memory");
+                Some(RunnerEvent::End)
+            }
+            Err(err) => {
+                log::warn!("{err}");
+                self.report_crash(err);
+                if let Some(faults) = &self.faults {
+                    self.chip8.controls.pause();
+                    if faults.send(Fault::capture(&self.chip8, err)).is_err() {
+                        // the receiver was dropped
+                        self.faults = None;
+                    }
+                }
+                Some(RunnerEvent::Error(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advance() {
+        let mut chip8 = Chip8::new();
+        // 7001: V0 += 1, 1200: jump to 0x200
+        chip8.load_rom_data(vec![0x70, 0x01, 0x12, 0x00]).unwrap();
+        let mut runner = Chip8Runner::new(chip8);
+        runner.set_ips(100);
+
+        // 100 IPS for 100ms executes 10 instructions, 5 of them adds
+        assert_eq!(runner.advance(Duration::from_millis(100)), None);
+        assert_eq!(runner.chip8.processor.v[0], 5);
+
+        // Nothing is executed while paused, except for requested steps
+        runner.pause();
+        runner.controls().step();
+        assert_eq!(runner.advance(Duration::from_millis(100)), None);
+        assert_eq!(runner.chip8.processor.v[0], 6);
+    }
+
+    #[test]
+    fn test_loop_event() {
+        let mut chip8 = Chip8::new();
+        // 6001: V0 = 1, 1202: jump to self
+        chip8.load_rom_data(vec![0x60, 0x01, 0x12, 0x02]).unwrap();
+        let mut runner = Chip8Runner::new(chip8);
+
+        assert_eq!(runner.step_n(10), Some(RunnerEvent::Loop { pc: 0x202 }));
+        assert_eq!(runner.chip8.processor.v[0], 1);
+    }
+
+    #[test]
+    fn test_events() {
+        let mut runner = Chip8Runner::new(Chip8::new());
+        let events = runner.subscribe_events();
+        // A000: I = 0x000, D001: draw, 6005: V0 = 5, F018: sound = V0,
+        // 1208: jump to self
+        let rom = vec![0xA0, 0x00, 0xD0, 0x01, 0x60, 0x05, 0xF0, 0x18, 0x12, 0x08];
+        runner.load_rom(rom).unwrap();
+        runner.step_n(10);
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            [
+                EmulatorEvent::RomLoaded,
+                EmulatorEvent::DisplayUpdated,
+                EmulatorEvent::DisplayUpdated,
+                EmulatorEvent::SoundStarted,
+                EmulatorEvent::Ended,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_async() {
+        use std::task::{Context, Poll, Waker};
+
+        let mut chip8 = Chip8::new();
+        // 7001: V0 += 1, 3005: skip if V0 == 5, 1200: jump to 0x200,
+        // 1206: jump to self
+        chip8
+            .load_rom_data(vec![0x70, 0x01, 0x30, 0x05, 0x12, 0x00, 0x12, 0x06])
+            .unwrap();
+        let mut runner = Chip8Runner::new(chip8);
+        let mut sleeps = 0;
+        let event = {
+            let mut future = std::pin::pin!(runner.run_async(|_| {
+                sleeps += 1;
+                std::future::ready(())
+            }));
+            let mut context = Context::from_waker(Waker::noop());
+            loop {
+                if let Poll::Ready(event) = future.as_mut().poll(&mut context) {
+                    break event;
+                }
+            }
+        };
+        assert_eq!(event, RunnerEvent::Loop { pc: 0x206 });
+        assert!(sleeps > 0);
+    }
+
+    #[test]
+    fn test_fault_channel() {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_data(vec![0x80, 0x08]).unwrap();
+        let mut runner = Chip8Runner::new(chip8);
+        let faults = runner.subscribe_faults();
+        let dir = std::env::temp_dir().join(format!("chip8-runner-{}", std::process::id()));
+        runner.set_crash_dir(Some(dir.clone()));
+
+        let event = runner.step_n(1);
+        let fault = faults.try_recv().unwrap();
+        assert_eq!(event, Some(RunnerEvent::Error(fault.error)));
+        assert_eq!(fault.pc, 0x200);
+        assert!(runner.controls().is_paused());
+        assert_eq!(runner.crash_report().unwrap().fault.pc, 0x200);
+
+        // Running into the same error again writes no further report
+        runner.resume();
+        runner.step_n(1);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_speed() {
+        let mut chip8 = Chip8::new();
+        // 7001: V0 += 1, 1200: jump to 0x200
+        chip8.load_rom_data(vec![0x70, 0x01, 0x12, 0x00]).unwrap();
+        let mut runner = Chip8Runner::new(chip8);
+        runner.set_ips(100);
+        runner.set_timer_frequency(50.0);
+        runner.chip8.bus.clock.delay_timer = 60;
+
+        // At double speed, 100ms executes 20 instructions and 10 ticks
+        runner.set_speed(2.0);
+        assert_eq!(runner.advance(Duration::from_millis(100)), None);
+        assert_eq!(runner.chip8.processor.v[0], 10);
+        assert_eq!(runner.chip8.timers().0, 50);
+
+        // In slow motion, 100ms executes 5 instructions and 2.5 ticks
+        runner.set_speed(0.5);
+        assert_eq!(runner.advance(Duration::from_millis(100)), None);
+        assert_eq!(runner.chip8.timers().0, 48);
+
+        // Timers ticked by the frontend are left alone
+        runner.set_manual_timers(true);
+        assert_eq!(runner.advance(Duration::from_millis(100)), None);
+        assert_eq!(runner.chip8.timers().0, 48);
+
+        // Paused timers stand still, even with no instruction due
+        runner.set_manual_timers(false);
+        runner.set_ips(1);
+        runner.pause();
+        assert_eq!(runner.advance(Duration::from_millis(100)), None);
+        assert_eq!(runner.chip8.timers().0, 48);
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut chip8 = Chip8::new();
+        // 7001: V0 += 1, 1200: jump to 0x200
+        chip8.load_rom_data(vec![0x70, 0x01, 0x12, 0x00]).unwrap();
+        let mut runner = Chip8Runner::new(chip8);
+        runner.set_ips(100);
+
+        for _ in 0..10 {
+            runner.record_frame();
+            assert_eq!(runner.advance(Duration::from_millis(100)), None);
+        }
+        let stats = runner.stats();
+        assert_eq!(stats.instructions, 100);
+        assert_eq!(stats.frames, 10);
+        assert_eq!(stats.target_ips, 100);
+        assert_eq!(stats.running_time, Duration::from_secs(1));
+        assert!((stats.ips - 100.0).abs() < 1.0);
+        assert!((stats.fps - 10.0).abs() < 1.0);
+
+        runner.reset_stats();
+        assert_eq!(runner.stats(), Stats::default());
+    }
+
+    #[test]
+    fn test_run_to() {
+        let mut chip8 = Chip8::new();
+        // 7001: V0 += 1, 1200: jump to 0x200
+        chip8.load_rom_data(vec![0x70, 0x01, 0x12, 0x00]).unwrap();
+        let mut runner = Chip8Runner::new(chip8);
+        runner.set_ips(100);
+        runner.pause();
+
+        // Running to the current address runs a full loop iteration
+        runner.run_to(0x200);
+        assert_eq!(
+            runner.advance(Duration::from_millis(100)),
+            Some(RunnerEvent::Reached { pc: 0x200 })
+        );
+        assert_eq!(runner.chip8.processor.v[0], 1);
+        assert!(runner.controls().is_paused());
+        assert_eq!(runner.run_to_address(), None);
+
+        runner.run_to(0x202);
+        runner.cancel_run_to();
+        assert_eq!(runner.advance(Duration::from_millis(100)), None);
+        assert_eq!(runner.chip8.processor.v[0], 6);
+
+        // Breakpoints stay until removed
+        assert!(runner.add_breakpoint(0x202));
+        for v0 in 7..9 {
+            runner.resume();
+            assert_eq!(
+                runner.advance(Duration::from_millis(100)),
+                Some(RunnerEvent::Breakpoint { pc: 0x202 })
+            );
+            assert_eq!(runner.chip8.processor.v[0], v0);
+        }
+        assert!(runner.remove_breakpoint(0x202));
+        assert_eq!(runner.breakpoints().count(), 0);
+    }
+
+    #[test]
+    fn test_event_breaks() {
+        let mut chip8 = Chip8::new();
+        // 6005: V0 = 5, E09E: skip if key 5 pressed, 00E0: clear,
+        // 6F01: VF = 1, F018: sound timer = V0, 1200: jump to 0x200
+        let rom = vec![
+            0x60, 0x05, 0xE0, 0x9E, 0x00, 0xE0, 0x6F, 0x01, 0xF0, 0x18, 0x12, 0x00,
+        ];
+        chip8.load_rom_data(rom).unwrap();
+        let mut runner = Chip8Runner::new(chip8);
+        runner.set_ips(100);
+        for event in [
+            BreakEvent::KeyRead(5),
+            BreakEvent::Draw,
+            BreakEvent::FlagSet,
+            BreakEvent::SoundStart,
+        ] {
+            assert!(runner.add_event_break(event));
+        }
+
+        for (pc, event) in [
+            (0x202, BreakEvent::KeyRead(5)),
+            (0x204, BreakEvent::Draw),
+            (0x206, BreakEvent::FlagSet),
+            (0x208, BreakEvent::SoundStart),
+        ] {
+            runner.resume();
+            assert_eq!(
+                runner.advance(Duration::from_millis(100)),
+                Some(RunnerEvent::Event { pc, event })
+            );
+        }
+        runner.clear_event_breaks();
+        assert_eq!(runner.event_breaks().count(), 0);
+    }
+
+    #[test]
+    fn test_perform() {
+        let mut chip8 = Chip8::new();
+        // 7001: V0 += 1, 1200: jump to 0x200
+        chip8.load_rom_data(vec![0x70, 0x01, 0x12, 0x00]).unwrap();
+        let mut runner = Chip8Runner::new(chip8);
+
+        // Stepping pauses and executes a single instruction
+        assert!(runner.perform(Action::Step));
+        assert_eq!(runner.advance(Duration::from_millis(100)), None);
+        assert!(runner.controls().is_paused());
+        assert_eq!(runner.chip8.processor.v[0], 1);
+        assert!(runner.perform(Action::TogglePause));
+        assert!(!runner.controls().is_paused());
+
+        // Save states are kept per slot
+        assert!(!runner.perform(Action::LoadState(0)));
+        assert!(runner.perform(Action::SaveState(0)));
+        runner.step_n(2);
+        assert_eq!(runner.chip8.processor.v[0], 2);
+        assert!(runner.perform(Action::LoadState(0)));
+        assert_eq!(runner.chip8.processor.v[0], 1);
+        assert!(runner.save_slot(1).is_none());
+
+        for _ in 0..8 {
+            runner.perform(Action::SpeedUp);
+        }
+        assert!((runner.speed() - MAX_SPEED).abs() < f64::EPSILON);
+        assert!(!runner.perform(Action::ToggleMute));
+
+        // Resetting restarts the program without losing the ROM
+        assert!(runner.perform(Action::Reset));
+        assert_eq!(runner.chip8.processor.v[0], 0);
+        runner.step_n(1);
+        assert_eq!(runner.chip8.processor.v[0], 1);
+    }
+
+    #[test]
+    fn test_focus() {
+        let mut chip8 = Chip8::new();
+        // 7001: V0 += 1, 1200: jump to 0x200
+        chip8.load_rom_data(vec![0x70, 0x01, 0x12, 0x00]).unwrap();
+        let mut runner = Chip8Runner::new(chip8);
+        runner.set_ips(100);
+
+        // Throttled, 100ms only execute a tenth of the instructions
+        runner.set_focus_behavior(FocusBehavior::Throttle);
+        runner.set_focused(false);
+        assert!(runner.is_throttled());
+        assert_eq!(runner.advance(Duration::from_millis(100)), None);
+        assert_eq!(runner.chip8.processor.v[0], 1);
+        runner.set_focused(true);
+        assert!(!runner.is_throttled());
+
+        // Pausing on focus loss resumes on focus, but keeps a manual pause
+        runner.set_focus_behavior(FocusBehavior::Pause);
+        runner.set_focused(false);
+        assert!(runner.controls().is_paused());
+        runner.set_focused(true);
+        assert!(!runner.controls().is_paused());
+        runner.pause();
+        runner.set_focused(false);
+        runner.set_focused(true);
+        assert!(runner.controls().is_paused());
+    }
+
+    #[test]
+    fn test_power_saving() {
+        let mut chip8 = Chip8::new();
+        // F00A: wait for a key into V0, 7101: V1 += 1, 1200: jump to 0x200
+        chip8
+            .load_rom_data(vec![0xF0, 0x0A, 0x71, 0x01, 0x12, 0x00])
+            .unwrap();
+        let mut runner = Chip8Runner::new(chip8);
+        runner.set_ips(100);
+        runner.set_power_saving(true);
+
+        // Blocked in Fx0A, the runner sleeps after the first instruction
+        assert_eq!(runner.advance(Duration::from_millis(100)), None);
+        assert!(runner.is_sleeping());
+        assert_eq!(runner.idle_interval(), KEY_SLEEP);
+
+        // A key press wakes it up
+        runner.chip8.press(0x5);
+        assert!(!runner.is_sleeping());
+        assert_eq!(runner.advance(Duration::from_millis(40)), None);
+        assert_eq!(runner.chip8.processor.v[..2], [5, 1]);
+        assert!(runner.is_sleeping());
+    }
+
+    #[test]
+    fn test_step_frame() {
+        let mut chip8 = Chip8::new();
+        // 7001: V0 += 1, 1200: jump to 0x200
+        chip8.load_rom_data(vec![0x70, 0x01, 0x12, 0x00]).unwrap();
+        let mut runner = Chip8Runner::new(chip8);
+        runner.pause();
+
+        // 600 IPS at 60 Hz is 10 instructions per frame, 5 of them adds
+        runner.set_ips(600);
+        assert_eq!(runner.step_frame(), None);
+        assert_eq!(runner.chip8.processor.v[0], 5);
+
+        // A frame on the COSMAC VIP holds 111 loop iterations of 150us, plus
+        // the add that crosses the end of the frame
+        runner.set_timing(Timing::CosmacVip);
+        assert_eq!(runner.step_frame(), None);
+        assert_eq!(runner.chip8.processor.v[0], 5 + 112);
+
+        // The frame ends once the program waits for a key
+        // F00A: wait for a key
+        runner.chip8.reset_and_load(vec![0xF0, 0x0A]).unwrap();
+        assert_eq!(runner.step_frame(), None);
+        assert_eq!(runner.chip8.processor.pc, 0x202);
+        assert_eq!(runner.step_frame(), None);
+        assert_eq!(runner.chip8.processor.pc, 0x202);
+
+        // Stopped timers keep the frame at its usual length
+        // 6005: V0 = 5, F015: DT = V0, 7001: V0 += 1, 1204: jump to 0x204
+        runner
+            .chip8
+            .reset_and_load(vec![0x60, 0x05, 0xF0, 0x15, 0x70, 0x01, 0x12, 0x04])
+            .unwrap();
+        runner.set_timing(Timing::Flat);
+        runner.set_timer_frequency(0.0);
+        assert_eq!(runner.step_frame(), None);
+        assert_eq!(runner.chip8.processor.v[0], 5 + 4);
+        assert_eq!(runner.chip8.bus.clock.delay_timer, 5);
+    }
+
+    #[test]
+    fn test_cosmac_vip_timing() {
+        let mut chip8 = Chip8::new();
+        // 6001: V0 = 1 (27us), 7001: V0 += 1 (45us), 1200: jump to 0x200 (105us)
+        chip8
+            .load_rom_data(vec![0x60, 0x01, 0x70, 0x01, 0x12, 0x00])
+            .unwrap();
+        let mut runner = Chip8Runner::new(chip8);
+        runner.set_timing(Timing::CosmacVip);
+
+        assert_eq!(runner.advance(Duration::from_micros(100)), None);
+        assert_eq!(runner.chip8.processor.pc, 0x204);
+        assert_eq!(runner.advance(Duration::from_micros(100)), None);
+        assert_eq!(runner.chip8.processor.pc, 0x200);
+    }
+}
@@ -0,0 +1,484 @@
+//! This module provides scripting hooks through the embedded [Rhai] scripting
+//! language, for automated ROM testing, bots and custom HUDs without
+//! recompiling the emulator.
+//!
+//! A script defines any of the following callbacks, which a [`ScriptHost`]
+//! calls while it drives a [`Chip8`]:
+//!
+//! | Callback                  | Called                                       |
+//! |---------------------------|----------------------------------------------|
+//! | `init()`                  | once, when the script is loaded              |
+//! | `on_step(pc, opcode)`     | after every executed instruction             |
+//! | `on_draw()`               | after every instruction that changed the display |
+//! | `on_key(key, pressed)`    | before a key state change is applied         |
+//! | `on_write(addr, value)`   | after an instruction wrote a byte to memory  |
+//!
+//! Callbacks read and change the machine through the functions `v(x)`,
+//! `set_v(x, value)`, `i()`, `set_i(value)`, `pc()`, `set_pc(addr)`, `sp()`,
+//! `delay_timer()`, `set_delay_timer(value)`, `sound_timer()`,
+//! `set_sound_timer(value)`, `peek(addr)`, `poke(addr, value)`, `key(key)`,
+//! `press(key)`, `release(key)` and `pause()`. The per-ROM
+//! [`crate::storage::Storage`] is read with `load_data(name)`, which returns
+//! an array of bytes or `()`, and written with `save_data(name, bytes)`.
+//! Callbacks share an object map bound
+//! to `this`, which keeps state between calls. Lines printed with `print`
+//! are collected by [`ScriptHost::take_output`].
+//!
+//! [Rhai]: https://rhai.rs
+
+use std::{cell::RefCell, fmt, fs, io, path::Path, rc::Rc, sync::atomic::Ordering};
+
+use rhai::{
+    Array, CallFnOptions, Dynamic, Engine, EvalAltResult, FuncArgs, Map, ParseError, Scope, AST,
+    INT,
+};
+
+use crate::{
+    error::Chip8Error,
+    keymap::KEY_COUNT,
+    memory::Memory,
+    processor::{self, StepResult},
+    storage::Storage,
+    Chip8,
+};
+
+/// The maximum amount of operations a single callback may perform, so a
+/// runaway script cannot hang the emulator.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// An error raised while loading or running a script.
+#[derive(Debug)]
+pub enum ScriptError {
+    /// The script file could not be read.
+    Io(io::Error),
+
+    /// The script failed to compile or raised an error in a callback.
+    Script(Box<EvalAltResult>),
+
+    /// The emulated program raised an error.
+    Chip8(Chip8Error),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "cannot read script: {err}"),
+            Self::Script(err) => write!(f, "script error: {err}"),
+            Self::Chip8(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+impl From<Box<EvalAltResult>> for ScriptError {
+    fn from(err: Box<EvalAltResult>) -> Self {
+        Self::Script(err)
+    }
+}
+
+impl From<ParseError> for ScriptError {
+    fn from(err: ParseError) -> Self {
+        Self::Script(err.into())
+    }
+}
+
+impl From<Chip8Error> for ScriptError {
+    fn from(err: Chip8Error) -> Self {
+        Self::Chip8(err)
+    }
+}
+
+/// The machine state that callbacks work on. The registers are copied from
+/// the [`Chip8`] before a callback and written back afterwards, while its
+/// memory and storage are swapped in for the duration of the callback, so
+/// `peek` and `poke` work on them directly.
+#[derive(Debug, Default)]
+struct Machine {
+    v: [u8; 16],
+    i: usize,
+    pc: usize,
+    sp: usize,
+    delay_timer: u8,
+    sound_timer: u8,
+    memory: Memory,
+    keys: [bool; KEY_COUNT],
+    /// The key state changes requested by a callback.
+    key_changes: Vec<(u8, bool)>,
+    /// Whether a callback requested to pause.
+    pause: bool,
+    /// The per-ROM storage.
+    storage: Storage,
+}
+
+impl Machine {
+    /// Takes over the state of the given [`Chip8`].
+    fn load(&mut self, chip8: &mut Chip8) {
+        let cpu = &chip8.processor;
+        self.v = cpu.v;
+        self.i = cpu.i;
+        self.pc = cpu.pc;
+        self.sp = cpu.sp;
+        self.delay_timer = chip8.bus.clock.delay_timer;
+        self.sound_timer = chip8.bus.clock.sound_timer.load(Ordering::SeqCst);
+        std::mem::swap(&mut self.memory, &mut chip8.bus.memory);
+        #[allow(clippy::cast_possible_truncation)] // there are 16 keys
+        for (key_code, pressed) in self.keys.iter_mut().enumerate() {
+            *pressed = chip8.bus.input.is_key_pressed(key_code as u8);
+        }
+        std::mem::swap(&mut self.storage, &mut chip8.storage);
+    }
+
+    /// Hands the state back to the given [`Chip8`].
+    fn store(&mut self, chip8: &mut Chip8) {
+        let cpu = &mut chip8.processor;
+        cpu.v = self.v;
+        cpu.i = self.i;
+        cpu.pc = self.pc;
+        chip8.bus.clock.delay_timer = self.delay_timer;
+        chip8
+            .bus
+            .clock
+            .sound_timer
+            .store(self.sound_timer, Ordering::SeqCst);
+        std::mem::swap(&mut self.memory, &mut chip8.bus.memory);
+        std::mem::swap(&mut self.storage, &mut chip8.storage);
+        for (key_code, pressed) in self.key_changes.drain(..) {
+            chip8.update_key_state(key_code, pressed);
+        }
+    }
+}
+
+/// Converts a script integer into an index below `len`.
+fn index(value: INT, len: usize, what: &str) -> Result<usize, Box<EvalAltResult>> {
+    usize::try_from(value)
+        .ok()
+        .filter(|&index| index < len)
+        .ok_or_else(|| format!("invalid {what} {value}").into())
+}
+
+/// Converts a script integer into a byte.
+fn byte(value: INT) -> Result<u8, Box<EvalAltResult>> {
+    u8::try_from(value).map_err(|_| format!("{value} does not fit into a byte").into())
+}
+
+/// Converts an address or other machine value into a script integer.
+#[allow(clippy::cast_possible_wrap)] // machine values are far below 2^63
+const fn int(value: usize) -> INT {
+    value as INT
+}
+
+/// Runs a script's callbacks while driving a [`Chip8`].
+pub struct ScriptHost {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    /// The object map bound to `this` in every callback.
+    state: Dynamic,
+    machine: Rc<RefCell<Machine>>,
+    output: Rc<RefCell<Vec<String>>>,
+}
+
+impl fmt::Debug for ScriptHost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScriptHost")
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ScriptHost {
+    /// Compiles the given script and calls its `init` callback on the given
+    /// [`Chip8`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the script does not compile or `init` fails.
+    pub fn new(source: &str, chip8: &mut Chip8) -> Result<Self, ScriptError> {
+        let machine = Rc::new(RefCell::new(Machine::default()));
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let lines = output.clone();
+        engine.on_print(move |line| lines.borrow_mut().push(line.to_string()));
+        register_api(&mut engine, &machine);
+
+        let ast = engine.compile(source)?;
+        let mut host = Self {
+            engine,
+            ast,
+            scope: Scope::new(),
+            state: Dynamic::from_map(Map::new()),
+            machine,
+            output,
+        };
+        host.call(chip8, "init", ())?;
+        Ok(host)
+    }
+
+    /// Reads and compiles the script file at the given path, see
+    /// [`ScriptHost::new`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, the script does not
+    /// compile or `init` fails.
+    pub fn load(path: impl AsRef<Path>, chip8: &mut Chip8) -> Result<Self, ScriptError> {
+        let source = fs::read_to_string(path).map_err(ScriptError::Io)?;
+        Self::new(&source, chip8)
+    }
+
+    /// Executes one instruction cycle of the given [`Chip8`] and calls the
+    /// callbacks it triggers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the instruction or a callback fails.
+    pub fn step(&mut self, chip8: &mut Chip8) -> Result<StepResult, ScriptError> {
+        let (pc, i) = (chip8.processor.pc, chip8.processor.i);
+        let opcode = chip8.current_opcode();
+        let result = chip8.step()?;
+        let Some(opcode) = opcode.filter(|_| result != StepResult::WaitingForKey) else {
+            return Ok(result);
+        };
+
+        self.call(chip8, "on_step", (int(pc), int(opcode)))?;
+        let x = (opcode & 0x0F00) >> 8;
+        let y = (opcode & 0x00F0) >> 4;
+        if processor::updates_display(opcode) {
+            self.call(chip8, "on_draw", ())?;
+        }
+        let written = match opcode & 0xF0FF {
+            0xF033 => 3,
+            0xF055 => x + 1,
+            _ if opcode & 0xF00F == 0x5002 => x.abs_diff(y) + 1,
+            _ => 0,
+        };
+        for addr in i..i + written {
+            let addr = addr % chip8.bus.memory.len();
+            let value = chip8.bus.memory[addr];
+            self.call(chip8, "on_write", (int(addr), INT::from(value)))?;
+        }
+        Ok(result)
+    }
+
+    /// Calls the `on_key` callback and then applies the key state change to
+    /// the given [`Chip8`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the callback fails.
+    pub fn update_key_state(
+        &mut self,
+        chip8: &mut Chip8,
+        key_code: u8,
+        pressed: bool,
+    ) -> Result<(), ScriptError> {
+        self.call(chip8, "on_key", (INT::from(key_code), pressed))?;
+        chip8.update_key_state(key_code, pressed);
+        Ok(())
+    }
+
+    /// Returns whether a callback called `pause()` since the previous call,
+    /// and resets the request.
+    pub fn take_pause_request(&mut self) -> bool {
+        std::mem::take(&mut self.machine.borrow_mut().pause)
+    }
+
+    /// Returns the lines printed by the script since the previous call.
+    pub fn take_output(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.output.borrow_mut())
+    }
+
+    /// Calls the callback with the given name, if the script defines it.
+    fn call(
+        &mut self,
+        chip8: &mut Chip8,
+        name: &str,
+        args: impl FuncArgs,
+    ) -> Result<(), ScriptError> {
+        if !self.ast.iter_functions().any(|f| f.name == name) {
+            return Ok(());
+        }
+        self.machine.borrow_mut().load(chip8);
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .rewind_scope(false)
+            .bind_this_ptr(&mut self.state);
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut self.scope,
+            &self.ast,
+            name,
+            args,
+        );
+        self.machine.borrow_mut().store(chip8);
+        result.map(drop).map_err(ScriptError::from)
+    }
+}
+
+/// Registers the functions that give scripts access to the machine.
+fn register_api(engine: &mut Engine, machine: &Rc<RefCell<Machine>>) {
+    let m = machine.clone();
+    engine.register_fn("v", move |x: INT| -> Result<INT, Box<EvalAltResult>> {
+        Ok(INT::from(m.borrow().v[index(x, 16, "register")?]))
+    });
+    let m = machine.clone();
+    engine.register_fn(
+        "set_v",
+        move |x: INT, value: INT| -> Result<(), Box<EvalAltResult>> {
+            m.borrow_mut().v[index(x, 16, "register")?] = byte(value)?;
+            Ok(())
+        },
+    );
+    let m = machine.clone();
+    engine.register_fn("i", move || int(m.borrow().i));
+    let m = machine.clone();
+    engine.register_fn(
+        "set_i",
+        move |value: INT| -> Result<(), Box<EvalAltResult>> {
+            m.borrow_mut().i = index(value, 0x1_0000, "index")?;
+            Ok(())
+        },
+    );
+    let m = machine.clone();
+    engine.register_fn("pc", move || int(m.borrow().pc));
+    let m = machine.clone();
+    engine.register_fn(
+        "set_pc",
+        move |addr: INT| -> Result<(), Box<EvalAltResult>> {
+            let mut machine = m.borrow_mut();
+            machine.pc = index(addr, machine.memory.len(), "address")?;
+            Ok(())
+        },
+    );
+    let m = machine.clone();
+    engine.register_fn("sp", move || int(m.borrow().sp));
+    let m = machine.clone();
+    engine.register_fn("delay_timer", move || INT::from(m.borrow().delay_timer));
+    let m = machine.clone();
+    engine.register_fn(
+        "set_delay_timer",
+        move |value: INT| -> Result<(), Box<EvalAltResult>> {
+            m.borrow_mut().delay_timer = byte(value)?;
+            Ok(())
+        },
+    );
+    let m = machine.clone();
+    engine.register_fn("sound_timer", move || INT::from(m.borrow().sound_timer));
+    let m = machine.clone();
+    engine.register_fn(
+        "set_sound_timer",
+        move |value: INT| -> Result<(), Box<EvalAltResult>> {
+            m.borrow_mut().sound_timer = byte(value)?;
+            Ok(())
+        },
+    );
+    let m = machine.clone();
+    engine.register_fn(
+        "peek",
+        move |addr: INT| -> Result<INT, Box<EvalAltResult>> {
+            let machine = m.borrow();
+            Ok(INT::from(
+                machine.memory[index(addr, machine.memory.len(), "address")?],
+            ))
+        },
+    );
+    let m = machine.clone();
+    engine.register_fn(
+        "poke",
+        move |addr: INT, value: INT| -> Result<(), Box<EvalAltResult>> {
+            let mut machine = m.borrow_mut();
+            let addr = index(addr, machine.memory.len(), "address")?;
+            machine.memory[addr] = byte(value)?;
+            Ok(())
+        },
+    );
+    let m = machine.clone();
+    engine.register_fn("key", move |key: INT| -> Result<bool, Box<EvalAltResult>> {
+        Ok(m.borrow().keys[index(key, KEY_COUNT, "key")?])
+    });
+    for (name, pressed) in [("press", true), ("release", false)] {
+        let m = machine.clone();
+        engine.register_fn(name, move |key: INT| -> Result<(), Box<EvalAltResult>> {
+            let key_code = byte(key)?;
+            index(key, KEY_COUNT, "key")?;
+            m.borrow_mut().key_changes.push((key_code, pressed));
+            Ok(())
+        });
+    }
+    let m = machine.clone();
+    engine.register_fn("pause", move || m.borrow_mut().pause = true);
+    register_storage_api(engine, machine);
+}
+
+/// Registers the functions reading and writing the per-ROM storage.
+fn register_storage_api(engine: &mut Engine, machine: &Rc<RefCell<Machine>>) {
+    let m = machine.clone();
+    engine.register_fn("load_data", move |name: &str| {
+        m.borrow().storage.get(name).map_or(Dynamic::UNIT, |bytes| {
+            bytes
+                .iter()
+                .map(|&byte| Dynamic::from_int(INT::from(byte)))
+                .collect::<Array>()
+                .into()
+        })
+    });
+    let m = machine.clone();
+    engine.register_fn(
+        "save_data",
+        move |name: &str, bytes: Array| -> Result<(), Box<EvalAltResult>> {
+            let bytes = bytes
+                .into_iter()
+                .map(|value| byte(value.as_int()?))
+                .collect::<Result<_, _>>()?;
+            m.borrow_mut().storage.set(name, bytes);
+            Ok(())
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_callbacks() {
+        let script = r"
+            fn init() { this.steps = 0; this.writes = []; }
+            fn on_step(pc, opcode) {
+                this.steps += 1;
+                if pc == 0x204 { set_v(1, v(0) + 1); press(5); }
+            }
+            fn on_write(addr, value) { this.writes.push(value); }
+            fn on_draw() {
+                print(`draw at ${pc()}`);
+                pause();
+                save_data(`score`, [v(0), v(1)]);
+                poke(0x400, peek(0x302) + 1);
+            }
+        ";
+        // 6007: V0 = 7, A300: I = 0x300, F133: BCD of V1 at I, 00E0: clear
+        let mut chip8 = Chip8::new();
+        chip8
+            .load_rom_data(vec![0x60, 0x07, 0xA3, 0x00, 0xF1, 0x33, 0x00, 0xE0])
+            .unwrap();
+        let mut host = ScriptHost::new(script, &mut chip8).unwrap();
+        for _ in 0..4 {
+            host.step(&mut chip8).unwrap();
+        }
+
+        // The BCD of V1 was written after on_step changed it
+        assert_eq!(chip8.processor.v[1], 8);
+        assert!(chip8.bus.input.is_key_pressed(5));
+        assert_eq!(chip8.bus.memory[0x302], 0);
+        assert_eq!(chip8.bus.memory[0x400], 1);
+        assert_eq!(host.take_output(), ["draw at 520"]);
+        assert!(host.take_pause_request());
+        assert_eq!(chip8.storage.get("score"), Some(&[7, 8][..]));
+
+        let state = host.state.read_lock::<Map>().unwrap();
+        assert_eq!(state["steps"].as_int().unwrap(), 4);
+        assert_eq!(state["writes"].to_string(), "[0, 0, 0]");
+    }
+}
@@ -0,0 +1,342 @@
+//! This module runs the bundled test ROMs under every [`Variant`] and checks
+//! the results, so the interpreter can be verified without any external test
+//! ROMs.
+//!
+//! The test ROMs are written in Octo and live in `roms/selftest`. Each one
+//! runs a series of checks and draws one cell per check onto the display,
+//! eight cells per row: a check mark if the check passed, or if the quirk it
+//! probes is present, and a cross otherwise. After a headless [`run`], the
+//! cells are read back from the framebuffer and compared with what the
+//! [`Quirks`] of the variant demand.
+
+use std::fmt::{self, Write as _};
+
+use crate::{
+    graphics::Framebuffer,
+    headless::{run, ExitReason, HeadlessOptions},
+    octo,
+    quirks::{MemoryIncrement, Quirks, Variant},
+    rng::Rng,
+    Chip8,
+};
+
+/// The sprite a test ROM draws for a passed check.
+const PASS_GLYPH: [u8; 5] = [0x01, 0x02, 0x84, 0x48, 0x30];
+
+/// The sprite a test ROM draws for a failed check.
+const FAIL_GLYPH: [u8; 5] = [0x88, 0x50, 0x20, 0x50, 0x88];
+
+/// The amount of cells in a row of the display.
+const CELLS_PER_ROW: usize = 8;
+
+/// The height of a cell, including a blank row below the glyph.
+const CELL_HEIGHT: usize = 6;
+
+/// The amount of instructions a test ROM may take before it is stopped.
+const MAX_INSTRUCTIONS: u64 = 100_000;
+
+/// A single check of a [`Suite`].
+#[derive(Debug, Clone, Copy)]
+pub struct Check {
+    /// The name of the check, usually the instruction or quirk it covers.
+    pub name: &'static str,
+
+    /// Returns whether the ROM should draw a check mark under the given
+    /// quirks.
+    expected: fn(&Quirks) -> bool,
+}
+
+/// A test ROM with the checks it runs, in the order of its cells.
+#[derive(Debug, Clone, Copy)]
+pub struct Suite {
+    /// The name of the suite.
+    pub name: &'static str,
+
+    /// The Octo source of the test ROM.
+    source: &'static str,
+
+    /// The checks the ROM runs.
+    pub checks: &'static [Check],
+}
+
+/// Returns `true` regardless of the quirks, for checks every variant passes.
+const fn always(_: &Quirks) -> bool {
+    true
+}
+
+/// Creates a [`Check`] every variant passes.
+const fn check(name: &'static str) -> Check {
+    Check {
+        name,
+        expected: always,
+    }
+}
+
+/// All bundled test suites.
+pub const SUITES: &[Suite] = &[
+    Suite {
+        name: "opcodes",
+        source: include_str!("../roms/selftest/opcodes.8o"),
+        checks: &[
+            check("6xnn 7xnn"),
+            check("8xy0"),
+            check("8xy1"),
+            check("8xy2"),
+            check("8xy3"),
+            check("8xy4"),
+            check("8xy5"),
+            check("8xy7"),
+            check("8xy6"),
+            check("8xyE"),
+            check("2nnn 00EE"),
+            check("skips"),
+            check("Fx55 Fx65"),
+            check("Fx33"),
+            check("Fx1E"),
+            check("Fx29"),
+            check("timers"),
+            check("Cxnn"),
+            check("Dxyn collision"),
+        ],
+    },
+    Suite {
+        name: "flags",
+        source: include_str!("../roms/selftest/flags.8o"),
+        checks: &[
+            check("8xy4"),
+            check("8xy4 VF operand"),
+            check("8xy5"),
+            check("8xy5 VF operand"),
+            check("8xy7"),
+            check("8xy7 VF operand"),
+            check("8xy6"),
+            check("8xy6 VF operand"),
+            check("8xyE"),
+            check("8xyE VF operand"),
+        ],
+    },
+    Suite {
+        name: "quirks",
+        source: include_str!("../roms/selftest/quirks.8o"),
+        checks: &[
+            Check {
+                name: "jump",
+                expected: |quirks| quirks.jump,
+            },
+            Check {
+                name: "vf_reset",
+                expected: |quirks| quirks.vf_reset,
+            },
+            Check {
+                name: "shift",
+                expected: |quirks| quirks.shift,
+            },
+            Check {
+                name: "memory_increment x+1",
+                expected: |quirks| quirks.memory_increment == MemoryIncrement::XPlusOne,
+            },
+            Check {
+                name: "memory_increment x",
+                expected: |quirks| quirks.memory_increment == MemoryIncrement::X,
+            },
+            Check {
+                name: "clip_sprites",
+                expected: |quirks| quirks.clip_sprites,
+            },
+            Check {
+                name: "index_overflow",
+                expected: |quirks| quirks.index_overflow,
+            },
+        ],
+    },
+];
+
+/// The outcome of a [`Check`] under a [`Variant`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The ROM drew the expected result.
+    Pass,
+    /// The ROM drew the opposite result.
+    Fail,
+    /// The ROM drew no result, e.g. because it stopped early.
+    Missing,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Pass => "pass",
+            Self::Fail => "FAIL",
+            Self::Missing => "-",
+        })
+    }
+}
+
+/// The outcomes of all checks of a suite under one variant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuiteResult {
+    /// The variant the suite ran under.
+    pub variant: Variant,
+    /// Why the test ROM stopped.
+    pub reason: ExitReason,
+    /// The outcome of every check, in the order of [`Suite::checks`].
+    pub outcomes: Vec<Outcome>,
+}
+
+/// Runs the given suite under the given variant.
+///
+/// # Panics
+///
+/// Panics if the test ROM of the suite does not assemble, which the tests of
+/// this module rule out.
+#[must_use]
+pub fn run_suite(suite: &Suite, variant: Variant) -> SuiteResult {
+    let rom = octo::assemble(suite.source).expect("test ROMs always assemble");
+    let mut chip8 = Chip8::new_with_rng(Rng::new(0));
+    chip8.set_variant(variant);
+    let reason = match chip8.load_rom_data(rom) {
+        Ok(()) => {
+            let options = HeadlessOptions {
+                max_instructions: Some(MAX_INSTRUCTIONS),
+                exit_on_loop: true,
+                ..HeadlessOptions::default()
+            };
+            run(&mut chip8, &options).reason
+        }
+        Err(err) => ExitReason::Error(err),
+    };
+
+    let quirks = variant.quirks();
+    let outcomes = suite
+        .checks
+        .iter()
+        .enumerate()
+        .map(
+            |(index, check)| match read_cell(&chip8.bus.graphics, index) {
+                Some(result) if result == (check.expected)(&quirks) => Outcome::Pass,
+                Some(_) => Outcome::Fail,
+                None => Outcome::Missing,
+            },
+        )
+        .collect();
+    SuiteResult {
+        variant,
+        reason,
+        outcomes,
+    }
+}
+
+/// Reads the cell with the given index from the display. Returns whether it
+/// holds a check mark, or [`None`] if it holds neither glyph.
+fn read_cell(graphics: &Framebuffer, index: usize) -> Option<bool> {
+    let x = index % CELLS_PER_ROW * 8;
+    let y = index / CELLS_PER_ROW * CELL_HEIGHT;
+    if y + PASS_GLYPH.len() > graphics.height() {
+        return None;
+    }
+    let rows: Vec<u8> = (0..PASS_GLYPH.len())
+        .map(|row| {
+            (0..8).fold(0, |byte, col| {
+                byte << 1 | u8::from(graphics.pixel(x + col, y + row) != 0)
+            })
+        })
+        .collect();
+    if rows == PASS_GLYPH {
+        Some(true)
+    } else if rows == FAIL_GLYPH {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// The results of running all [`SUITES`] under all variants.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// The results of every suite, in the order of [`SUITES`], under every
+    /// variant, in the order of [`Variant::ALL`].
+    pub results: Vec<Vec<SuiteResult>>,
+}
+
+impl Report {
+    /// Runs all [`SUITES`] under all variants.
+    #[must_use]
+    pub fn run() -> Self {
+        let results = SUITES
+            .iter()
+            .map(|suite| {
+                Variant::ALL
+                    .iter()
+                    .map(|&variant| run_suite(suite, variant))
+                    .collect()
+            })
+            .collect();
+        Self { results }
+    }
+
+    /// Returns whether every check passed under every variant.
+    #[must_use]
+    pub fn passed(&self) -> bool {
+        self.results
+            .iter()
+            .flatten()
+            .all(|result| result.outcomes.iter().all(|&o| o == Outcome::Pass))
+    }
+}
+
+/// Prints the report as a matrix of checks by variants, followed by the
+/// suites that stopped on an error.
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = SUITES
+            .iter()
+            .flat_map(|suite| {
+                suite
+                    .checks
+                    .iter()
+                    .map(|c| suite.name.len() + c.name.len() + 2)
+            })
+            .max()
+            .unwrap_or(0);
+        write!(f, "{:width$}", "")?;
+        for variant in Variant::ALL {
+            write!(f, "  {variant}")?;
+        }
+        writeln!(f)?;
+
+        for (suite, results) in SUITES.iter().zip(&self.results) {
+            for (index, check) in suite.checks.iter().enumerate() {
+                let mut line = format!("{:width$}", format!("{}: {}", suite.name, check.name));
+                for result in results {
+                    let column = result.variant.name().len();
+                    write!(line, "  {:column$}", result.outcomes[index])?;
+                }
+                writeln!(f, "{}", line.trim_end())?;
+            }
+        }
+
+        for (suite, results) in SUITES.iter().zip(&self.results) {
+            for result in results {
+                if !result.reason.is_success() {
+                    writeln!(
+                        f,
+                        "{} stopped under {}: {}",
+                        suite.name, result.variant, result.reason
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selftest() {
+        let report = Report::run();
+        assert!(report.passed(), "{report}");
+    }
+}
@@ -0,0 +1,198 @@
+//! This module manages several emulators running side by side, e.g. in the
+//! tabs of a frontend.
+//!
+//! Every [`Session`] owns its own [`Chip8Runner`], so speed, quirks, pause
+//! state and input are independent. This allows comparing the behaviour of a
+//! ROM under two quirk profiles. Keyboard input is routed to the active
+//! session only, while all sessions keep running.
+
+use crate::{
+    runner::{Chip8Runner, RunnerEvent},
+    Chip8,
+};
+
+/// A named emulator.
+#[derive(Debug)]
+pub struct Session {
+    /// The name shown on the tab of the session.
+    pub name: String,
+
+    /// The runner driving the emulator of the session.
+    pub runner: Chip8Runner,
+}
+
+impl Session {
+    /// Creates a new [`Session`] with the given name, running the given
+    /// [`Chip8`].
+    #[must_use]
+    pub fn new(name: impl Into<String>, chip8: Chip8) -> Self {
+        Self {
+            name: name.into(),
+            runner: Chip8Runner::new(chip8),
+        }
+    }
+}
+
+/// A list of [`Session`]s, one of which is active.
+#[derive(Debug, Default)]
+pub struct Sessions {
+    /// The open sessions, in tab order.
+    sessions: Vec<Session>,
+    /// The index of the active session.
+    active: usize,
+}
+
+impl Sessions {
+    /// Creates an empty [`Sessions`] list.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the given session after all others and activates it. Returns its
+    /// index.
+    pub fn open(&mut self, session: Session) -> usize {
+        self.sessions.push(session);
+        self.active = self.sessions.len() - 1;
+        self.active
+    }
+
+    /// Removes the session at the given index and returns it. The active
+    /// session stays active, unless it was the removed one, in which case its
+    /// neighbour is activated.
+    pub fn close(&mut self, index: usize) -> Option<Session> {
+        if index >= self.sessions.len() {
+            return None;
+        }
+        let session = self.sessions.remove(index);
+        if self.active > index || self.active == self.sessions.len() {
+            self.active = self.active.saturating_sub(1);
+        }
+        Some(session)
+    }
+
+    /// Returns the session at the given index.
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&Session> {
+        self.sessions.get(index)
+    }
+
+    /// Returns the session at the given index mutably.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Session> {
+        self.sessions.get_mut(index)
+    }
+
+    /// Returns the index of the active session.
+    #[must_use]
+    pub const fn active_index(&self) -> usize {
+        self.active
+    }
+
+    /// Activates the session at the given index. Returns whether it exists.
+    pub const fn set_active(&mut self, index: usize) -> bool {
+        if index < self.sessions.len() {
+            self.active = index;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the active session, or [`None`] if no session is open.
+    #[must_use]
+    pub fn active(&self) -> Option<&Session> {
+        self.sessions.get(self.active)
+    }
+
+    /// Returns the active session mutably, or [`None`] if no session is open.
+    pub fn active_mut(&mut self) -> Option<&mut Session> {
+        self.sessions.get_mut(self.active)
+    }
+
+    /// Forwards a key press or release to the active session.
+    pub fn update_key_state(&mut self, key_code: u8, pressed: bool) {
+        if let Some(session) = self.active_mut() {
+            session.runner.chip8.update_key_state(key_code, pressed);
+        }
+    }
+
+    /// Executes the instructions that are due in every session.
+    ///
+    /// # Returns
+    ///
+    /// The index of every session that raised a [`RunnerEvent`], together
+    /// with the event.
+    pub fn update(&mut self) -> Vec<(usize, RunnerEvent)> {
+        self.sessions
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, session)| Some((index, session.runner.update()?)))
+            .collect()
+    }
+
+    /// Returns an iterator over all sessions, in tab order.
+    pub fn iter(&self) -> impl Iterator<Item = &Session> {
+        self.sessions.iter()
+    }
+
+    /// Returns a mutable iterator over all sessions, in tab order.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Session> {
+        self.sessions.iter_mut()
+    }
+
+    /// Returns the amount of open sessions.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Returns whether no session is open.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Variant;
+
+    #[test]
+    fn test_sessions() {
+        let mut sessions = Sessions::new();
+        assert!(sessions.active().is_none());
+
+        for variant in [Variant::Chip8, Variant::SuperChip] {
+            let mut chip8 = Chip8::new();
+            chip8.set_variant(variant);
+            sessions.open(Session::new(variant.name(), chip8));
+        }
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions.active().unwrap().name, "SCHIP");
+
+        // Input only reaches the active session
+        sessions.update_key_state(0x5, true);
+        assert!(!sessions
+            .get(0)
+            .unwrap()
+            .runner
+            .chip8
+            .bus
+            .input
+            .is_key_pressed(0x5));
+        assert!(sessions
+            .get(1)
+            .unwrap()
+            .runner
+            .chip8
+            .bus
+            .input
+            .is_key_pressed(0x5));
+
+        // Closing the active session activates its neighbour
+        assert!(sessions.close(1).is_some());
+        assert_eq!(sessions.active().unwrap().name, "CHIP-8");
+        assert!(!sessions.set_active(1));
+    }
+}
@@ -0,0 +1,105 @@
+//! This module stores per-ROM data, such as labels or cheats, in JSON sidecar
+//! files next to the ROM.
+//!
+//! A sidecar records the [`crate::roms::hash`] of the ROM it was saved for, so
+//! it is ignored once the ROM changes.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// The contents of a sidecar file.
+#[derive(Serialize, Deserialize)]
+struct Sidecar<T> {
+    /// The hash of the ROM the data belongs to.
+    rom_hash: String,
+
+    /// The data stored for the ROM.
+    data: T,
+}
+
+/// Returns the path of the sidecar file with the given extension for the ROM
+/// at the given path, i.e. the ROM path with its extension replaced.
+#[must_use]
+pub fn path(rom_path: impl AsRef<Path>, extension: &str) -> PathBuf {
+    rom_path.as_ref().with_extension(extension)
+}
+
+/// Deserializes the data of the ROM with the given hash from a sidecar JSON
+/// string. Returns [`None`] if the sidecar belongs to a different ROM.
+///
+/// # Errors
+///
+/// Returns an error if the string is not a valid sidecar.
+pub fn from_json<T: DeserializeOwned>(
+    json: &str,
+    rom_hash: &str,
+) -> Result<Option<T>, serde_json::Error> {
+    let sidecar: Sidecar<T> = serde_json::from_str(json)?;
+    Ok((sidecar.rom_hash == rom_hash).then_some(sidecar.data))
+}
+
+/// Serializes the data into a sidecar JSON string for the ROM with the given
+/// hash.
+///
+/// # Errors
+///
+/// Returns an error if the data cannot be represented as JSON.
+pub fn to_json<T: Serialize>(data: &T, rom_hash: &str) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(&Sidecar {
+        rom_hash: rom_hash.into(),
+        data,
+    })
+}
+
+/// Loads the data of the ROM at the given path from its sidecar file.
+///
+/// Returns the default value if there is no sidecar file with the given
+/// extension, or if it was saved for a different version of the ROM.
+///
+/// # Errors
+///
+/// Returns an error if the ROM or an existing sidecar cannot be read, or if
+/// the sidecar is invalid.
+pub fn load<T: DeserializeOwned + Default>(
+    rom_path: impl AsRef<Path>,
+    extension: &str,
+) -> io::Result<T> {
+    let rom_hash = crate::roms::hash(&fs::read(&rom_path)?);
+    let json = match fs::read_to_string(path(rom_path, extension)) {
+        Ok(json) => json,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(err) => return Err(err),
+    };
+    from_json(&json, &rom_hash)
+        .map(Option::unwrap_or_default)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Saves the data to the sidecar file with the given extension of the ROM at
+/// the given path.
+///
+/// # Errors
+///
+/// Returns an error if the ROM cannot be read or the sidecar cannot be
+/// written.
+pub fn save<T: Serialize>(data: &T, rom_path: impl AsRef<Path>, extension: &str) -> io::Result<()> {
+    let rom_hash = crate::roms::hash(&fs::read(&rom_path)?);
+    let json = to_json(data, &rom_hash).map_err(io::Error::other)?;
+    fs::write(path(rom_path, extension), json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rom_hash_mismatch() {
+        let json = to_json(&vec![1, 2, 3], "abc").unwrap();
+        assert_eq!(from_json(&json, "abc").unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(from_json::<Vec<u8>>(&json, "def").unwrap(), None);
+    }
+}
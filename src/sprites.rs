@@ -0,0 +1,115 @@
+//! This module interprets memory as a stack of 8xN sprites, the way `Dxyn`
+//! would draw it.
+//!
+//! Frontends use this to render a sprite viewer at an adjustable address,
+//! letting ROM developers verify their sprite data and explore the graphics of
+//! existing ROMs.
+
+use std::fmt;
+
+use crate::{graphics::Rgb, memory::Memory};
+
+/// The width of a sprite in pixels.
+pub const SPRITE_WIDTH: usize = 8;
+
+/// A sprite read from memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sprite {
+    /// The address of the first row of the sprite.
+    pub address: usize,
+
+    /// The rows of the sprite, one byte per row with the most significant bit
+    /// being the leftmost pixel.
+    pub rows: Vec<u8>,
+}
+
+impl Sprite {
+    /// Returns the height of the sprite in pixels.
+    #[must_use]
+    pub const fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Returns whether the pixel at the given position is set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is outside of the sprite.
+    #[must_use]
+    pub fn is_set(&self, x: usize, y: usize) -> bool {
+        assert!(x < SPRITE_WIDTH, "pixel is outside of the sprite");
+        self.rows[y] & (0x80 >> x) != 0
+    }
+
+    /// Returns the sprite as a flat array of RGBA values, where every pixel is
+    /// scaled up to a `scale` x `scale` square.
+    #[must_use]
+    pub fn to_rgba(&self, scale: usize, foreground: Rgb, background: Rgb) -> Vec<u8> {
+        let mut data = Vec::with_capacity(SPRITE_WIDTH * self.height() * scale * scale * 4);
+        for y in 0..self.height() {
+            for _ in 0..scale {
+                for x in 0..SPRITE_WIDTH {
+                    let color = if self.is_set(x, y) {
+                        foreground
+                    } else {
+                        background
+                    };
+                    for _ in 0..scale {
+                        data.extend_from_slice(&[color.red, color.green, color.blue, 0xFF]);
+                    }
+                }
+            }
+        }
+        data
+    }
+}
+
+impl fmt::Display for Sprite {
+    /// Formats the sprite as text, with `#` for set and `.` for unset pixels.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for y in 0..self.height() {
+            for x in 0..SPRITE_WIDTH {
+                f.write_str(if self.is_set(x, y) { "#" } else { "." })?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads up to `count` consecutive sprites of `height` rows each, starting at
+/// the given address. Reading stops early at the end of memory.
+#[must_use]
+pub fn rip(memory: &Memory, address: usize, height: usize, count: usize) -> Vec<Sprite> {
+    (0..count)
+        .map(|i| address + i * height)
+        .map_while(|address| {
+            let rows = memory.read_slice(address, height).ok()?;
+            Some(Sprite {
+                address,
+                rows: rows.to_vec(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rip_font() {
+        // The font digits are stored as 5 byte sprites at the start of memory
+        let memory = Memory::new();
+        let sprites = rip(&memory, 0, 5, 2);
+        assert_eq!(sprites.len(), 2);
+        assert_eq!(sprites[1].address, 5);
+        assert_eq!(
+            sprites[0].to_string(),
+            "####....\n#..#....\n#..#....\n#..#....\n####....\n"
+        );
+
+        // Reading stops at the end of memory
+        assert_eq!(rip(&memory, memory.len() - 12, 5, 4).len(), 2);
+    }
+}
@@ -0,0 +1,100 @@
+//! This module provides execution statistics of a
+//! [`super::runner::Chip8Runner`], so frontends can show whether the emulator
+//! keeps up with its target speed.
+//!
+//! Totals are counted since the runner was created or the statistics were
+//! reset. The achieved instructions and frames per second are measured over a
+//! sliding window of [`SAMPLE_WINDOW`] and updated once the window is full.
+
+use std::{fmt, time::Duration};
+
+/// The amount of time over which the rates of a [`Stats`] are measured.
+pub const SAMPLE_WINDOW: Duration = Duration::from_secs(1);
+
+/// A snapshot of the execution statistics.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    /// The total amount of executed instructions.
+    pub instructions: u64,
+
+    /// The total amount of frames drawn by the frontend.
+    pub frames: u64,
+
+    /// The total amount of wall-clock time the runner was updated for.
+    pub running_time: Duration,
+
+    /// The target amount of instructions per second.
+    pub target_ips: u64,
+
+    /// The amount of instructions per second achieved during the last
+    /// [`SAMPLE_WINDOW`].
+    pub ips: f64,
+
+    /// The amount of frames per second drawn during the last
+    /// [`SAMPLE_WINDOW`].
+    pub fps: f64,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.0} / {} IPS, {:.0} FPS, {} instructions",
+            self.ips, self.target_ips, self.fps, self.instructions
+        )
+    }
+}
+
+/// Counts instructions and frames and measures their rates.
+#[derive(Debug, Default)]
+pub(crate) struct StatsMeter {
+    /// The statistics measured so far.
+    stats: Stats,
+    /// The time elapsed in the current window.
+    window: Duration,
+    /// The instructions executed in the current window.
+    window_instructions: u64,
+    /// The frames drawn in the current window.
+    window_frames: u64,
+}
+
+impl StatsMeter {
+    /// Returns the statistics measured so far.
+    pub(crate) const fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Counts an executed instruction.
+    pub(crate) const fn instruction(&mut self) {
+        self.stats.instructions += 1;
+        self.window_instructions += 1;
+    }
+
+    /// Counts a drawn frame.
+    pub(crate) const fn frame(&mut self) {
+        self.stats.frames += 1;
+        self.window_frames += 1;
+    }
+
+    /// Accounts for `elapsed` wall-clock time, updating the rates once the
+    /// window is full.
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn advance(&mut self, elapsed: Duration, target_ips: u64) {
+        self.stats.running_time += elapsed;
+        self.stats.target_ips = target_ips;
+        self.window += elapsed;
+        if self.window >= SAMPLE_WINDOW {
+            let seconds = self.window.as_secs_f64();
+            self.stats.ips = self.window_instructions as f64 / seconds;
+            self.stats.fps = self.window_frames as f64 / seconds;
+            self.window = Duration::ZERO;
+            self.window_instructions = 0;
+            self.window_frames = 0;
+        }
+    }
+
+    /// Discards all statistics.
+    pub(crate) fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
@@ -0,0 +1,224 @@
+//! This module provides a per-ROM storage area for save data, so homebrew can
+//! keep high scores or progress between sessions.
+//!
+//! The [`Storage`] is a set of named byte strings that scripts and frontends
+//! can read and write. A ROM can additionally reserve a memory region through
+//! [`Storage::set_region`]: its contents are stored under [`REGION_KEY`] by
+//! [`crate::Chip8::sync_storage`] and written back into memory whenever the
+//! ROM is loaded again.
+//!
+//! With the `persistence` feature enabled, the [`Storage`] of a ROM can be
+//! kept in a JSON sidecar file next to it. The sidecar records the hash of the
+//! ROM, so it is ignored once the ROM changes.
+
+use alloc::collections::BTreeMap;
+use alloc::{string::String, vec::Vec};
+use core::ops::Range;
+#[cfg(feature = "persistence")]
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::memory::Memory;
+#[cfg(feature = "persistence")]
+use crate::sidecar;
+
+/// The extension of the sidecar file holding the storage of a ROM.
+#[cfg(feature = "persistence")]
+pub const SIDECAR_EXTENSION: &str = "sav";
+
+/// The key the contents of the reserved memory region are stored under.
+pub const REGION_KEY: &str = "memory";
+
+/// A set of named byte strings stored for a ROM.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Storage {
+    /// The memory region mirrored into the storage, if any.
+    #[cfg_attr(feature = "serde", serde(default))]
+    region: Option<Range<usize>>,
+
+    /// The stored values by name.
+    values: BTreeMap<String, Vec<u8>>,
+
+    /// Whether the values changed since [`Storage::take_changed`] was last
+    /// called.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    changed: bool,
+}
+
+impl Storage {
+    /// Creates an empty [`Storage`] without a memory region.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value stored under the given name.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&[u8]> {
+        self.values.get(key).map(Vec::as_slice)
+    }
+
+    /// Stores a value under the given name, replacing the previous one.
+    pub fn set(&mut self, key: &str, value: Vec<u8>) {
+        if self.get(key) != Some(&value) {
+            self.values.insert(key.into(), value);
+            self.changed = true;
+        }
+    }
+
+    /// Removes the value stored under the given name, returning it.
+    pub fn remove(&mut self, key: &str) -> Option<Vec<u8>> {
+        let value = self.values.remove(key);
+        self.changed |= value.is_some();
+        value
+    }
+
+    /// Returns an iterator over the stored names and values, ordered by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.values
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_slice()))
+    }
+
+    /// Returns the memory region mirrored into the storage, if any.
+    #[must_use]
+    pub fn region(&self) -> Option<Range<usize>> {
+        self.region.clone()
+    }
+
+    /// Sets the memory region mirrored into the storage, or stops mirroring
+    /// memory if [`None`] is given. A stored region is kept until it is
+    /// captured again.
+    pub fn set_region(&mut self, region: Option<Range<usize>>) {
+        self.changed |= self.region != region;
+        self.region = region;
+    }
+
+    /// Returns whether the storage changed since the last call, and resets
+    /// that state, so a frontend knows when to save it.
+    pub fn take_changed(&mut self) -> bool {
+        core::mem::take(&mut self.changed)
+    }
+
+    /// Stores the contents of the memory region under [`REGION_KEY`]. Parts
+    /// of the region outside of memory are ignored.
+    pub(crate) fn capture(&mut self, memory: &Memory) {
+        if let Some(region) = self.region() {
+            let end = region.end.min(memory.len());
+            let bytes = (region.start..end).map(|addr| memory[addr]).collect();
+            self.set(REGION_KEY, bytes);
+        }
+    }
+
+    /// Writes the value stored under [`REGION_KEY`] back into the memory
+    /// region. Bytes beyond the region or outside of memory are ignored.
+    pub(crate) fn restore(&self, memory: &mut Memory) {
+        let (Some(region), Some(bytes)) = (self.region(), self.get(REGION_KEY)) else {
+            return;
+        };
+        let end = region.end.min(memory.len());
+        for (addr, &byte) in (region.start..end).zip(bytes) {
+            memory[addr] = byte;
+        }
+    }
+
+    /// Returns the path of the sidecar file for the ROM at the given path,
+    /// i.e. the ROM path with its extension replaced by
+    /// [`SIDECAR_EXTENSION`].
+    #[cfg(feature = "persistence")]
+    #[must_use]
+    pub fn sidecar_path(rom_path: impl AsRef<Path>) -> PathBuf {
+        sidecar::path(rom_path, SIDECAR_EXTENSION)
+    }
+
+    /// Deserializes the [`Storage`] of the ROM with the given hash from a
+    /// sidecar JSON string. Returns [`None`] if the sidecar belongs to a
+    /// different ROM.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the string is not a valid sidecar.
+    #[cfg(feature = "persistence")]
+    pub fn from_json(json: &str, rom_hash: &str) -> Result<Option<Self>, serde_json::Error> {
+        sidecar::from_json(json, rom_hash)
+    }
+
+    /// Serializes the [`Storage`] into a sidecar JSON string for the ROM with
+    /// the given hash.
+    ///
+    /// # Panics
+    ///
+    /// Never panics in practice, since a [`Storage`] always serializes to
+    /// JSON.
+    #[cfg(feature = "persistence")]
+    #[must_use]
+    pub fn to_json(&self, rom_hash: &str) -> String {
+        sidecar::to_json(self, rom_hash).expect("storage is always serializable")
+    }
+
+    /// Loads the [`Storage`] of the ROM at the given path from its sidecar
+    /// file. Returns an empty storage if there is no sidecar file, or if it
+    /// was saved for a different version of the ROM.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM or an existing sidecar cannot be read, or
+    /// if the sidecar is invalid.
+    #[cfg(feature = "persistence")]
+    pub fn load_for_rom(rom_path: impl AsRef<Path>) -> io::Result<Self> {
+        sidecar::load(rom_path, SIDECAR_EXTENSION)
+    }
+
+    /// Saves the [`Storage`] to the sidecar file of the ROM at the given
+    /// path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM cannot be read or the sidecar cannot be
+    /// written.
+    #[cfg(feature = "persistence")]
+    pub fn save_for_rom(&self, rom_path: impl AsRef<Path>) -> io::Result<()> {
+        sidecar::save(self, rom_path, SIDECAR_EXTENSION)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    #[test]
+    fn test_storage() {
+        let mut chip8 = Chip8::new();
+        let mut storage = Storage::new();
+        storage.set("name", b"AAA".to_vec());
+        storage.set_region(Some(0xE00..0xE02));
+        storage.set(REGION_KEY, vec![1, 2]);
+        assert!(storage.take_changed());
+
+        // The region is written into memory when the ROM is loaded
+        chip8.set_storage(storage);
+        chip8.load_rom_data(vec![0x12, 0x00]).unwrap();
+        assert_eq!((chip8.bus.memory[0xE00], chip8.bus.memory[0xE01]), (1, 2));
+
+        // and survives a reset after being captured
+        chip8.bus.memory[0xE01] = 7;
+        chip8.sync_storage();
+        assert!(chip8.storage.take_changed());
+        chip8.sync_storage();
+        assert!(!chip8.storage.take_changed());
+        chip8.reset_keep_rom().unwrap();
+        assert_eq!(chip8.bus.memory[0xE01], 7);
+        assert_eq!(chip8.storage.get("name"), Some(&b"AAA"[..]));
+
+        #[cfg(feature = "persistence")]
+        {
+            let json = chip8.storage.to_json("hash");
+            let storage = Storage::from_json(&json, "hash").unwrap();
+            assert_eq!(storage.as_ref(), Some(&chip8.storage));
+        }
+    }
+}
@@ -0,0 +1,293 @@
+//! This module runs a [`Chip8Runner`] on a supervised background thread.
+//!
+//! A [`Supervisor`] owns the thread driving the emulator, so frontends never
+//! detach it. The thread publishes every change of the display through a
+//! triple buffer, see [`Supervisor::display`], and receives key events
+//! through a channel, so drawing and input never wait for the emulator. The
+//! runner itself is shared behind a mutex that is only held for a single
+//! update at a time, for everything else a frontend needs. The thread stops
+//! when the program raises a [`RunnerEvent`], and [`Supervisor::load_rom`]
+//! starts a new one, so loading a ROM works after a program ended. Dropping
+//! the supervisor signals the thread to shut down and joins it. While the
+//! program sleeps until a key press, the thread sleeps longer, and
+//! [`Supervisor::update_key_state`] wakes it up.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    error::Chip8Error,
+    graphics::Framebuffer,
+    runner::{Chip8Runner, RunnerEvent},
+    triple_buffer,
+};
+
+/// The ends of the display and key channels owned by the thread.
+#[derive(Debug)]
+struct ThreadIo {
+    /// Publishes the display whenever it changed.
+    display: triple_buffer::Writer<Framebuffer>,
+    /// The display published last.
+    published: Framebuffer,
+    /// Receives key events, by key code and whether the key is pressed.
+    keys: mpsc::Receiver<(u8, bool)>,
+}
+
+/// Drives a shared [`Chip8Runner`] on a background thread.
+#[derive(Debug)]
+pub struct Supervisor {
+    /// The runner, shared with the thread.
+    runner: Arc<Mutex<Chip8Runner>>,
+    /// Set to ask the thread to stop.
+    shutdown: Arc<AtomicBool>,
+    /// The running thread, if started. It hands its ends of the channels
+    /// back when it finishes.
+    thread: Option<JoinHandle<ThreadIo>>,
+    /// The ends of the channels of the thread while it is stopped.
+    io: Option<ThreadIo>,
+    /// Receives the display published by the thread.
+    display: triple_buffer::Reader<Framebuffer>,
+    /// Sends key events to the thread.
+    keys: mpsc::Sender<(u8, bool)>,
+    /// Sends the events that stopped a thread.
+    event_sender: mpsc::Sender<RunnerEvent>,
+    /// Receives the events that stopped a thread.
+    events: mpsc::Receiver<RunnerEvent>,
+}
+
+impl Supervisor {
+    /// Creates a new [`Supervisor`] and starts driving the given runner.
+    #[must_use]
+    pub fn spawn(runner: Chip8Runner) -> Self {
+        let (event_sender, events) = mpsc::channel();
+        let (io, display, keys) = ThreadIo::new(&runner);
+        let mut supervisor = Self {
+            runner: Arc::new(Mutex::new(runner)),
+            shutdown: Arc::new(AtomicBool::new(false)),
+            thread: None,
+            io: Some(io),
+            display,
+            keys,
+            event_sender,
+            events,
+        };
+        supervisor.start();
+        supervisor
+    }
+
+    /// Locks the runner, e.g. to inspect or change the system. Keep the lock
+    /// short, as the thread cannot execute instructions meanwhile. Drawing
+    /// and key input should go through [`Supervisor::display`] and
+    /// [`Supervisor::update_key_state`] instead, which never wait for the
+    /// thread.
+    pub fn lock(&self) -> MutexGuard<'_, Chip8Runner> {
+        // A panic on the thread leaves the runner usable
+        self.runner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Queues an update of the state of a key, see
+    /// [`crate::Chip8::update_key_state`], which the thread applies before
+    /// its next update, and wakes the thread up in case the program sleeps
+    /// until a key press. Key events sent while the thread is stopped are
+    /// applied once it starts again.
+    pub fn update_key_state(&self, key_code: u8, pressed: bool) {
+        // The receiver lives as long as the supervisor
+        let _ = self.keys.send((key_code, pressed));
+        self.wake();
+    }
+
+    /// Returns the display as of the latest change, without waiting for the
+    /// thread.
+    pub fn display(&mut self) -> &Framebuffer {
+        self.display.read()
+    }
+
+    /// Returns whether the display changed since the previous call of
+    /// [`Supervisor::display`], so a frontend can skip presenting it
+    /// otherwise.
+    #[must_use]
+    pub fn display_changed(&self) -> bool {
+        self.display.has_update()
+    }
+
+    /// Wakes the thread up if it sleeps between updates, e.g. after
+    /// forwarding input through [`Supervisor::lock`].
+    pub fn wake(&self) {
+        if let Some(thread) = &self.thread {
+            thread.thread().unpark();
+        }
+    }
+
+    /// Returns whether the thread is driving the runner.
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        self.thread
+            .as_ref()
+            .is_some_and(|thread| !thread.is_finished())
+    }
+
+    /// Returns the next event that stopped the thread, if any.
+    #[must_use]
+    pub fn try_event(&self) -> Option<RunnerEvent> {
+        self.events.try_recv().ok()
+    }
+
+    /// Starts driving the runner again if the thread stopped, e.g. after
+    /// handling a [`RunnerEvent`] by resetting the program.
+    pub fn restart(&mut self) {
+        if !self.is_running() {
+            self.stop();
+            self.start();
+        }
+    }
+
+    /// Stops the thread, resets the system, loads the given ROM data and
+    /// starts a new thread.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM does not fit into memory. The thread stays
+    /// stopped in that case.
+    pub fn load_rom(&mut self, data: Vec<u8>) -> Result<(), Chip8Error> {
+        self.stop();
+        self.lock().load_rom(data)?;
+        self.start();
+        Ok(())
+    }
+
+    /// Signals the thread to stop and waits for it to finish the current
+    /// update.
+    pub fn stop(&mut self) {
+        let Some(thread) = self.thread.take() else {
+            return;
+        };
+        self.shutdown.store(true, Ordering::SeqCst);
+        match thread.join() {
+            Ok(io) => self.io = Some(io),
+            Err(_) => log::error!("the emulator thread panicked"),
+        }
+    }
+
+    /// Starts a new thread driving the runner.
+    fn start(&mut self) {
+        self.shutdown.store(false, Ordering::SeqCst);
+        self.lock().restart_clock();
+        let runner = Arc::clone(&self.runner);
+        let shutdown = Arc::clone(&self.shutdown);
+        let events = self.event_sender.clone();
+        let mut io = self.io.take().unwrap_or_else(|| {
+            // The ends of the channels went down with the previous thread
+            let (io, display, keys) = ThreadIo::new(&self.lock());
+            (self.display, self.keys) = (display, keys);
+            io
+        });
+        self.thread = Some(thread::spawn(move || {
+            while !shutdown.load(Ordering::SeqCst) {
+                let (event, idle) = {
+                    let mut runner = runner.lock().unwrap_or_else(PoisonError::into_inner);
+                    for (key_code, pressed) in io.keys.try_iter() {
+                        runner.chip8.update_key_state(key_code, pressed);
+                    }
+                    let event = runner.update();
+                    io.publish(&runner.chip8.bus.graphics);
+                    (event, runner.idle_interval())
+                };
+                if let Some(event) = event {
+                    // The supervisor may be gone already
+                    let _ = events.send(event);
+                    break;
+                }
+                thread::park_timeout(idle);
+            }
+            io
+        }));
+    }
+}
+
+impl ThreadIo {
+    /// Creates the channels of a thread driving the given runner, and
+    /// returns the ends of the thread and of the supervisor.
+    fn new(
+        runner: &Chip8Runner,
+    ) -> (
+        Self,
+        triple_buffer::Reader<Framebuffer>,
+        mpsc::Sender<(u8, bool)>,
+    ) {
+        let published = runner.chip8.bus.graphics;
+        let (display, reader) = triple_buffer::channel(published);
+        let (sender, keys) = mpsc::channel();
+        let io = Self {
+            display,
+            published,
+            keys,
+        };
+        (io, reader, sender)
+    }
+
+    /// Publishes the given display if it changed since it was published
+    /// last.
+    fn publish(&mut self, graphics: &Framebuffer) {
+        if *graphics != self.published {
+            self.published = *graphics;
+            self.display.publish(*graphics);
+        }
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::Chip8;
+
+    #[test]
+    fn test_supervisor() {
+        let mut chip8 = Chip8::new();
+        // 1200: jump to itself
+        chip8.load_rom_data(vec![0x12, 0x00]).unwrap();
+        let mut supervisor = Supervisor::spawn(Chip8Runner::new(chip8));
+
+        // The program ends in a loop, which stops the thread
+        let event = supervisor.events.recv_timeout(Duration::from_secs(5));
+        assert_eq!(event, Ok(RunnerEvent::Loop { pc: 0x200 }));
+        supervisor.thread.take().unwrap().join().unwrap();
+        assert!(!supervisor.is_running());
+
+        // Loading a ROM starts a new thread
+        // 7001: V0 += 1, 1200: jump to 0x200
+        supervisor.load_rom(vec![0x70, 0x01, 0x12, 0x00]).unwrap();
+        assert!(supervisor.is_running());
+        supervisor.stop();
+        assert!(!supervisor.is_running());
+        assert_eq!(supervisor.try_event(), None);
+
+        // Key events reach the thread and the drawn digit is published
+        // F00A: V0 = key, F029: I = digit V0, D005: draw it, 1206: jump to
+        // itself
+        let rom = vec![0xF0, 0x0A, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06];
+        supervisor.load_rom(rom).unwrap();
+        assert!(!supervisor.display_changed());
+        while !supervisor.lock().chip8.bus.input.waiting() {
+            std::thread::yield_now();
+        }
+        supervisor.update_key_state(1, true);
+        let event = supervisor.events.recv_timeout(Duration::from_secs(5));
+        assert_eq!(event, Ok(RunnerEvent::Loop { pc: 0x206 }));
+        assert!(supervisor.display_changed());
+        assert_eq!(supervisor.display().pixel(3, 1), 1);
+        assert!(!supervisor.display_changed());
+    }
+}
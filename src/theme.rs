@@ -0,0 +1,134 @@
+//! This module provides color themes for the display.
+//!
+//! A [`Palette`] holds one color per palette index of the
+//! [`crate::graphics::Framebuffer`]: the background, the foreground, and the
+//! two colors only XO-CHIP programs drawing into both bit planes show. The
+//! preset [`THEMES`] mimic classic displays or aid accessibility: the high
+//! contrast themes keep all four colors far apart in brightness, and the
+//! colorblind safe theme takes its plane colors from the Okabe-Ito palette,
+//! which stays distinguishable with every common color vision deficiency. A
+//! palette can be stored per ROM in its [`crate::romdb::RomEntry`].
+
+use crate::graphics::{Rgb, COLOR_COUNT, DEFAULT_BACKGROUND, DEFAULT_FOREGROUND};
+
+/// The colors of the palette indices: background, foreground, second plane
+/// and both planes.
+pub type Palette = [Rgb; COLOR_COUNT];
+
+/// A named preset [`Palette`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// The name shown to the user.
+    pub name: &'static str,
+
+    /// The colors of the theme.
+    pub palette: Palette,
+}
+
+/// Shorthand for defining the preset colors.
+const fn rgb(red: u8, green: u8, blue: u8) -> Rgb {
+    Rgb { red, green, blue }
+}
+
+/// The default palette: white on black, with gray for the second plane.
+pub const DEFAULT_PALETTE: Palette = [
+    DEFAULT_BACKGROUND,
+    DEFAULT_FOREGROUND,
+    rgb(0xAA, 0xAA, 0xAA),
+    rgb(0x55, 0x55, 0x55),
+];
+
+/// The preset themes, in the order they should be offered to the user.
+pub const THEMES: [Theme; 8] = [
+    Theme {
+        name: "Classic",
+        palette: DEFAULT_PALETTE,
+    },
+    Theme {
+        name: "Green phosphor",
+        palette: [
+            rgb(0x0A, 0x14, 0x0A),
+            rgb(0x33, 0xFF, 0x66),
+            rgb(0x1A, 0x99, 0x3D),
+            rgb(0x99, 0xFF, 0xB3),
+        ],
+    },
+    Theme {
+        name: "Amber",
+        palette: [
+            rgb(0x14, 0x0C, 0x00),
+            rgb(0xFF, 0xB0, 0x00),
+            rgb(0x99, 0x66, 0x00),
+            rgb(0xFF, 0xD8, 0x80),
+        ],
+    },
+    Theme {
+        name: "LCD",
+        palette: [
+            rgb(0x9B, 0xBC, 0x0F),
+            rgb(0x0F, 0x38, 0x0F),
+            rgb(0x8B, 0xAC, 0x0F),
+            rgb(0x30, 0x62, 0x30),
+        ],
+    },
+    Theme {
+        name: "Octo",
+        palette: [
+            rgb(0x99, 0x66, 0x00),
+            rgb(0xFF, 0xCC, 0x00),
+            rgb(0xFF, 0x66, 0x00),
+            rgb(0x66, 0x22, 0x00),
+        ],
+    },
+    Theme {
+        name: "High contrast",
+        palette: [
+            rgb(0x00, 0x00, 0x00),
+            rgb(0xFF, 0xFF, 0xFF),
+            rgb(0xFF, 0xFF, 0x00),
+            rgb(0x00, 0xFF, 0xFF),
+        ],
+    },
+    Theme {
+        name: "High contrast light",
+        palette: [
+            rgb(0xFF, 0xFF, 0xFF),
+            rgb(0x00, 0x00, 0x00),
+            rgb(0x00, 0x00, 0xCC),
+            rgb(0x99, 0x00, 0x00),
+        ],
+    },
+    Theme {
+        name: "Colorblind safe",
+        palette: [
+            rgb(0x00, 0x00, 0x00),
+            rgb(0xFF, 0xFF, 0xFF),
+            rgb(0x56, 0xB4, 0xE9),
+            rgb(0xE6, 0x9F, 0x00),
+        ],
+    },
+];
+
+/// Returns the preset theme with the given name, compared
+/// case-insensitively.
+#[must_use]
+pub fn find(name: &str) -> Option<&'static Theme> {
+    THEMES
+        .iter()
+        .find(|theme| theme.name.eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::Framebuffer;
+
+    #[test]
+    fn test_themes() {
+        assert_eq!(find("amber").map(|theme| theme.name), Some("Amber"));
+        assert!(find("Sepia").is_none());
+        assert!(find("colorblind safe").is_some());
+        // The first theme matches the default colors of the display
+        assert_eq!(THEMES[0].palette, Framebuffer::new().palette);
+    }
+}
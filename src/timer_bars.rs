@@ -0,0 +1,172 @@
+//! This module prepares the delay and sound timers for a timer panel, so
+//! timing-sensitive programs can be debugged without printing the state.
+//!
+//! Every [`TimerBar`] remembers the value its timer was last loaded with and
+//! reports the share left of it, so a bar starts full whenever the program
+//! sets the timer and decays to empty at 60 Hz. Loads are told apart by their
+//! [`TimerLoad::count`], so loading a timer with its current value or a
+//! smaller one fills the bar as well. The beep indicator of [`TimerBars`]
+//! stays lit for at least [`BEEP_HOLD_FRAMES`] frames, so even beeps shorter
+//! than a frame can be seen.
+
+use crate::{clock::TimerLoad, Chip8};
+
+/// The minimum amount of frames the beep indicator stays lit.
+pub const BEEP_HOLD_FRAMES: u32 = 6;
+
+/// The state of one timer, shown as a decaying bar.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimerBar {
+    /// The current value of the timer.
+    value: u8,
+    /// The value the timer was last loaded with.
+    loaded: u8,
+    /// The [`TimerLoad::count`] of the last load.
+    loads: u32,
+}
+
+impl TimerBar {
+    /// Records the current value and the most recent load of the timer. A
+    /// new load fills the bar again. Returns whether the timer was loaded
+    /// since the previous update.
+    pub const fn update(&mut self, value: u8, load: TimerLoad) -> bool {
+        let reloaded = load.count != self.loads;
+        if reloaded {
+            self.loaded = load.value;
+            self.loads = load.count;
+        }
+        self.value = value;
+        reloaded
+    }
+
+    /// Returns the current value of the timer.
+    #[must_use]
+    pub const fn value(&self) -> u8 {
+        self.value
+    }
+
+    /// Returns the value the timer was last loaded with.
+    #[must_use]
+    pub const fn loaded(&self) -> u8 {
+        self.loaded
+    }
+
+    /// Returns the length of the bar, from `0.0` for an expired timer to
+    /// `1.0` right after it was loaded. A timer set by other means than a
+    /// load, e.g. by a debugger, shows a full bar.
+    #[must_use]
+    pub fn level(&self) -> f32 {
+        if self.loaded == 0 {
+            0.0
+        } else {
+            (f32::from(self.value) / f32::from(self.loaded)).min(1.0)
+        }
+    }
+}
+
+/// The bars of the delay and sound timers and the beep indicator.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimerBars {
+    /// The bar of the delay timer.
+    pub delay: TimerBar,
+    /// The bar of the sound timer.
+    pub sound: TimerBar,
+    /// The amount of frames the beep indicator stays lit.
+    beep_frames: u32,
+}
+
+impl TimerBars {
+    /// Creates empty timer bars.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the given timer values and their most recent loads. Call
+    /// this once per displayed frame. A sound timer loaded with a non-zero
+    /// value since the previous frame lights the beep indicator, even if it
+    /// already expired.
+    pub const fn update(&mut self, (delay, sound): (u8, u8), loads: (TimerLoad, TimerLoad)) {
+        self.delay.update(delay, loads.0);
+        let beeped = self.sound.update(sound, loads.1) && loads.1.value > 0;
+        if sound > 0 || beeped {
+            self.beep_frames = BEEP_HOLD_FRAMES;
+        } else {
+            self.beep_frames = self.beep_frames.saturating_sub(1);
+        }
+    }
+
+    /// Records the timers of the given [`Chip8`], see [`TimerBars::update`].
+    pub fn capture(&mut self, chip8: &Chip8) {
+        let clock = &chip8.bus.clock;
+        self.update(chip8.timers(), (clock.delay_load, clock.sound_load));
+    }
+
+    /// Returns whether the beep indicator is lit: the buzzer sounds, or
+    /// sounded during the last [`BEEP_HOLD_FRAMES`] frames.
+    #[must_use]
+    pub const fn beeping(&self) -> bool {
+        self.beep_frames > 0
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    /// Returns the loads after loading a timer `count` times, lastly with
+    /// `value`.
+    const fn load(value: u8, count: u32) -> TimerLoad {
+        TimerLoad { value, count }
+    }
+
+    #[test]
+    fn test_timer_bars() {
+        let mut bars = TimerBars::new();
+        assert!((bars.delay.level()).abs() < f32::EPSILON);
+
+        // Loading fills the bar, which then decays
+        bars.update((60, 2), (load(60, 1), load(2, 1)));
+        bars.update((30, 1), (load(60, 1), load(2, 1)));
+        assert!((bars.delay.level() - 0.5).abs() < f32::EPSILON);
+        assert_eq!(bars.delay.loaded(), 60);
+
+        // Reloading with a smaller value fills the bar again
+        bars.update((20, 0), (load(20, 2), load(2, 1)));
+        assert!((bars.delay.level() - 1.0).abs() < f32::EPSILON);
+        assert_eq!(bars.delay.loaded(), 20);
+
+        // So does reloading with the current value
+        bars.update((19, 0), (load(20, 2), load(2, 1)));
+        bars.update((19, 0), (load(19, 3), load(2, 1)));
+        assert!((bars.delay.level() - 1.0).abs() < f32::EPSILON);
+
+        // The beep stays visible for a few frames after the buzzer stopped
+        assert!(bars.beeping());
+        for _ in 0..BEEP_HOLD_FRAMES {
+            bars.update((0, 0), (load(19, 3), load(2, 1)));
+        }
+        assert!(!bars.beeping());
+        assert!((bars.sound.level()).abs() < f32::EPSILON);
+
+        // A beep that expired before the frame is still seen
+        bars.update((0, 0), (load(19, 3), load(1, 2)));
+        assert!(bars.beeping());
+    }
+
+    #[test]
+    fn test_capture() {
+        let mut chip8 = Chip8::new();
+        // 6001: V0 = 1, F018: sound = V0
+        chip8.load_rom_data(vec![0x60, 0x01, 0xF0, 0x18]).unwrap();
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        chip8.tick_timers();
+
+        let mut bars = TimerBars::new();
+        bars.capture(&chip8);
+        assert_eq!(bars.sound.value(), 0);
+        assert_eq!(bars.sound.loaded(), 1);
+        assert!(bars.beeping());
+    }
+}
@@ -0,0 +1,64 @@
+//! This module describes how long instructions take to execute.
+//!
+//! The [`super::runner::Chip8Runner`] can either run at a flat amount of
+//! instructions per second or emulate the timing of the original COSMAC VIP
+//! interpreter.
+
+/// The timing model used to pace instruction execution.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Timing {
+    /// Every instruction takes the same amount of time, determined by the
+    /// target instructions per second.
+    #[default]
+    Flat,
+
+    /// Every instruction takes roughly as long as it did in the COSMAC VIP
+    /// interpreter, see [`cosmac_vip_cost`].
+    CosmacVip,
+}
+
+/// Returns the approximate execution time, in microseconds, of the given opcode
+/// on the COSMAC VIP.
+///
+/// The values are averages; the real interpreter's timing also depends on the
+/// operands, e.g. the amount of registers stored by `Fx55` or the position of a
+/// sprite drawn by `Dxyn`.
+#[must_use]
+pub const fn cosmac_vip_cost(opcode: usize) -> u32 {
+    match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => 109,
+            _ => 105,
+        },
+        0x1000 | 0x2000 | 0xB000 => 105,
+        0x3000 | 0x4000 | 0xA000 => 55,
+        0x5000 | 0x9000 | 0xE000 => 73,
+        0x6000 => 27,
+        0x7000 => 45,
+        0x8000 => 200,
+        0xC000 => 164,
+        0xD000 => 22_734,
+        _ => match opcode & 0x00FF {
+            0x1E => 86,
+            0x29 => 91,
+            0x33 => 927,
+            0x55 | 0x65 => 605,
+            _ => 45,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosmac_vip_cost() {
+        assert_eq!(cosmac_vip_cost(0x00E0), 109);
+        assert_eq!(cosmac_vip_cost(0x6A02), 27);
+        assert_eq!(cosmac_vip_cost(0xD015), 22_734);
+        assert_eq!(cosmac_vip_cost(0xF233), 927);
+        assert_eq!(cosmac_vip_cost(0xF015), 45);
+    }
+}
@@ -0,0 +1,140 @@
+//! This module provides the on-screen keypad of touch screen frontends.
+//!
+//! A [`TouchLayout`] arranges the keys like the COSMAC VIP hex keypad, see
+//! [`KEYPAD`], but can be limited to the keys a ROM actually uses: rows and
+//! columns without any of them are dropped, so e.g. `5`, `7`, `8` and `9`
+//! form a compact direction pad. A [`TouchPad`] follows every finger
+//! separately, so several keys can be held at once and sliding a finger
+//! moves the press from one key to the next.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use crate::keymap::KEYPAD;
+
+/// The arrangement of the keys of an on-screen keypad.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TouchLayout {
+    /// The amount of cells per row.
+    columns: usize,
+    /// The key code of every cell row by row, or [`None`] for a gap.
+    cells: Vec<Option<u8>>,
+}
+
+impl Default for TouchLayout {
+    fn default() -> Self {
+        Self::for_keys(u16::MAX)
+    }
+}
+
+impl TouchLayout {
+    /// Creates the layout of the full keypad.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a layout showing only the given keys, one bit per key with
+    /// key `0` in the lowest bit. Every key keeps its place relative to the
+    /// others. Shows the full keypad if no key is given.
+    #[must_use]
+    pub fn for_keys(keys: u16) -> Self {
+        let keys = if keys == 0 { u16::MAX } else { keys };
+        let shown = |key: u8| keys & 1 << key != 0;
+        let rows: Vec<_> = KEYPAD
+            .iter()
+            .filter(|row| row.iter().any(|&key| shown(key)))
+            .collect();
+        let columns: Vec<_> = (0..KEYPAD[0].len())
+            .filter(|&column| KEYPAD.iter().any(|row| shown(row[column])))
+            .collect();
+        let cells = rows
+            .iter()
+            .flat_map(|row| columns.iter().map(|&column| row[column]))
+            .map(|key| shown(key).then_some(key))
+            .collect();
+        Self {
+            columns: columns.len(),
+            cells,
+        }
+    }
+
+    /// Returns the amount of cells per row.
+    #[must_use]
+    pub const fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// Returns the key code of every cell row by row, or [`None`] for a gap.
+    #[must_use]
+    pub fn cells(&self) -> &[Option<u8>] {
+        &self.cells
+    }
+}
+
+/// The keys held by the fingers on an on-screen keypad.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TouchPad {
+    /// The key under every finger, by touch identifier.
+    touches: BTreeMap<i32, u8>,
+}
+
+impl TouchPad {
+    /// Creates a [`TouchPad`] without any fingers on it.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the finger with the given touch identifier being over the
+    /// given key, or over no key if [`None`] is given. Use this for both
+    /// `touchstart` and `touchmove` events.
+    pub fn touch(&mut self, id: i32, key: Option<u8>) {
+        match key {
+            Some(key) => self.touches.insert(id, key),
+            None => self.touches.remove(&id),
+        };
+    }
+
+    /// Records the finger with the given touch identifier being lifted, e.g.
+    /// on `touchend` and `touchcancel` events.
+    pub fn release(&mut self, id: i32) {
+        self.touches.remove(&id);
+    }
+
+    /// Returns the held keys, one bit per key with key `0` in the lowest bit.
+    #[must_use]
+    pub fn keys(&self) -> u16 {
+        self.touches
+            .values()
+            .fold(0, |keys, &key| keys | 1 << (key & 0xF))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_touch() {
+        assert_eq!(TouchLayout::new().cells().len(), 16);
+
+        // 5, 7, 8 and 9 form a direction pad with gaps beside the 5
+        let layout = TouchLayout::for_keys(1 << 5 | 1 << 7 | 1 << 8 | 1 << 9);
+        assert_eq!(layout.columns(), 3);
+        assert_eq!(
+            layout.cells(),
+            [None, Some(5), None, Some(7), Some(8), Some(9)]
+        );
+
+        // Two fingers hold two keys, and sliding moves the press
+        let mut pad = TouchPad::new();
+        pad.touch(1, Some(7));
+        pad.touch(2, Some(5));
+        assert_eq!(pad.keys(), 1 << 5 | 1 << 7);
+        pad.touch(1, Some(8));
+        pad.release(2);
+        assert_eq!(pad.keys(), 1 << 8);
+        pad.touch(1, None);
+        assert_eq!(pad.keys(), 0);
+    }
+}
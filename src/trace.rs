@@ -0,0 +1,151 @@
+//! This module provides machine-readable execution traces and state dumps,
+//! so external scripts can diff runs of this emulator against reference
+//! implementations to find where they diverge.
+//!
+//! A [`TraceEntry`] captures the state right before an instruction is
+//! executed. It is written as one line per instruction, either as text or,
+//! with the `persistence` feature enabled, as a JSON object (JSON Lines).
+
+use std::fmt;
+use std::sync::atomic::Ordering;
+
+use crate::Chip8;
+
+/// The output format of a trace.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// One human-readable line per instruction.
+    #[default]
+    Text,
+    /// One JSON object per instruction. Requires the `persistence` feature.
+    Json,
+}
+
+impl TraceFormat {
+    /// All trace formats, in the order they are offered.
+    pub const ALL: [Self; 2] = [Self::Text, Self::Json];
+
+    /// Returns the name of the format, as accepted by `--trace-format`.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Json => "json",
+        }
+    }
+
+    /// Returns the format with the given name, if any.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.name() == name)
+    }
+}
+
+/// The state of the machine right before an instruction is executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TraceEntry {
+    /// The address of the instruction.
+    pub pc: usize,
+    /// The opcode of the instruction, or `0` if the program counter points
+    /// outside of memory.
+    pub opcode: usize,
+    /// The V0 to VF registers.
+    pub v: [u8; 16],
+    /// The index register.
+    pub i: usize,
+    /// The stack pointer.
+    pub sp: usize,
+    /// The delay timer.
+    pub dt: u8,
+    /// The sound timer.
+    pub st: u8,
+}
+
+impl TraceEntry {
+    /// Captures the state of the given [`Chip8`] before its next instruction.
+    #[must_use]
+    pub fn capture(chip8: &Chip8) -> Self {
+        let cpu = &chip8.processor;
+        Self {
+            pc: cpu.pc,
+            opcode: chip8.current_opcode().unwrap_or_default(),
+            v: cpu.v,
+            i: cpu.i,
+            sp: cpu.sp,
+            dt: chip8.bus.clock.delay_timer,
+            st: chip8.bus.clock.sound_timer.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Formats the entry as a single line in the given format. Without the
+    /// `persistence` feature, JSON falls back to text.
+    ///
+    /// # Panics
+    ///
+    /// Never panics in practice, since trace entries always serialize to
+    /// JSON.
+    #[must_use]
+    pub fn format(&self, format: TraceFormat) -> String {
+        match format {
+            #[cfg(feature = "persistence")]
+            TraceFormat::Json => {
+                serde_json::to_string(self).expect("trace entries are always serializable")
+            }
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04X} {:04X}", self.pc, self.opcode)?;
+        for (x, value) in self.v.iter().enumerate() {
+            write!(f, " V{x:X}={value:02X}")?;
+        }
+        write!(
+            f,
+            " I={:04X} SP={} DT={:02X} ST={:02X}",
+            self.i, self.sp, self.dt, self.st
+        )
+    }
+}
+
+/// Serializes the full machine state of the given [`Chip8`] into a JSON
+/// string: registers, stack, timers, memory, display and quirks.
+///
+/// # Panics
+///
+/// Never panics in practice, since the machine state always serializes to
+/// JSON.
+#[cfg(feature = "persistence")]
+#[must_use]
+pub fn dump_state(chip8: &Chip8) -> String {
+    serde_json::to_string(chip8).expect("machine state is always serializable")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace() {
+        let mut chip8 = Chip8::new();
+        // 6005: V0 = 5, A123: I = 0x123
+        chip8.load_rom_data(vec![0x60, 0x05, 0xA1, 0x23]).unwrap();
+        chip8.step().unwrap();
+
+        let entry = TraceEntry::capture(&chip8);
+        assert_eq!((entry.pc, entry.opcode, entry.v[0]), (0x202, 0xA123, 5));
+        assert!(entry
+            .format(TraceFormat::Text)
+            .starts_with("0202 A123 V0=05 V1=00"));
+        #[cfg(feature = "persistence")]
+        {
+            let json = entry.format(TraceFormat::Json);
+            assert_eq!(serde_json::from_str::<TraceEntry>(&json).unwrap(), entry);
+            let state: serde_json::Value = serde_json::from_str(&dump_state(&chip8)).unwrap();
+            assert_eq!(state["processor"]["pc"], 0x202);
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! This module provides a triple buffer, which hands the latest value from
+//! one thread to another without either of them ever waiting.
+//!
+//! The [`Writer`] and the [`Reader`] each own one of three buffers, and the
+//! third one is shared between them through an atomic index. Publishing
+//! swaps the written buffer with the shared one, and reading swaps the shared
+//! buffer with the read one if it holds a newer value. Values published
+//! between two reads are skipped, so the reader always sees the latest one,
+//! which is what a display running at a lower rate than the emulator needs.
+
+use std::{
+    cell::UnsafeCell,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+};
+
+/// Marks the shared buffer as holding a value the reader did not see yet.
+const FRESH: u8 = 0b100;
+
+/// Masks the buffer index out of the shared state.
+const INDEX: u8 = 0b011;
+
+/// The state shared between a [`Writer`] and a [`Reader`].
+#[derive(Debug)]
+struct Shared<T> {
+    /// The three buffers.
+    buffers: [UnsafeCell<T>; 3],
+    /// The index of the shared buffer, and [`FRESH`] if it is newer than
+    /// the read buffer.
+    back: AtomicU8,
+}
+
+// The writer, the reader and `back` always own three different buffers, so
+// no buffer is ever accessed from two threads at once.
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+/// The sending half of a triple buffer, see [`channel`].
+#[derive(Debug)]
+pub struct Writer<T> {
+    /// The state shared with the reader.
+    shared: Arc<Shared<T>>,
+    /// The index of the buffer owned by the writer.
+    index: u8,
+}
+
+/// The receiving half of a triple buffer, see [`channel`].
+#[derive(Debug)]
+pub struct Reader<T> {
+    /// The state shared with the writer.
+    shared: Arc<Shared<T>>,
+    /// The index of the buffer owned by the reader.
+    index: u8,
+}
+
+/// Creates a triple buffer holding `initial`, and returns its two halves.
+pub fn channel<T: Clone>(initial: T) -> (Writer<T>, Reader<T>) {
+    let shared = Arc::new(Shared {
+        buffers: [
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial.clone()),
+            UnsafeCell::new(initial),
+        ],
+        back: AtomicU8::new(2),
+    });
+    let writer = Writer {
+        shared: Arc::clone(&shared),
+        index: 0,
+    };
+    (writer, Reader { shared, index: 1 })
+}
+
+impl<T> Writer<T> {
+    /// Publishes the given value, replacing any value the reader did not see
+    /// yet.
+    pub fn publish(&mut self, value: T) {
+        // SAFETY: the buffer at `self.index` is owned by the writer
+        unsafe { *self.shared.buffers[usize::from(self.index)].get() = value };
+        let back = self.shared.back.swap(self.index | FRESH, Ordering::AcqRel);
+        self.index = back & INDEX;
+    }
+}
+
+impl<T> Reader<T> {
+    /// Returns whether a value was published since the previous read.
+    #[must_use]
+    pub fn has_update(&self) -> bool {
+        self.shared.back.load(Ordering::Acquire) & FRESH != 0
+    }
+
+    /// Returns the latest published value.
+    pub fn read(&mut self) -> &T {
+        if self.has_update() {
+            let back = self.shared.back.swap(self.index, Ordering::AcqRel);
+            self.index = back & INDEX;
+        }
+        // SAFETY: the buffer at `self.index` is owned by the reader
+        unsafe { &*self.shared.buffers[usize::from(self.index)].get() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn test_triple_buffer() {
+        let (mut writer, mut reader) = channel(0);
+        assert!(!reader.has_update());
+        assert_eq!(*reader.read(), 0);
+
+        // Only the latest value is read
+        writer.publish(1);
+        writer.publish(2);
+        assert!(reader.has_update());
+        assert_eq!(*reader.read(), 2);
+        assert!(!reader.has_update());
+        assert_eq!(*reader.read(), 2);
+
+        // Values never go back in time across threads
+        let thread = thread::spawn(move || {
+            for value in 3..10_000 {
+                writer.publish(value);
+            }
+        });
+        let mut previous = 2;
+        while !thread.is_finished() || reader.has_update() {
+            let value = *reader.read();
+            assert!(value >= previous);
+            previous = value;
+        }
+        thread.join().unwrap();
+        assert_eq!(previous, 9_999);
+    }
+}
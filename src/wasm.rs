@@ -0,0 +1,1884 @@
+//! This module provides the browser frontend of the emulator, exported to
+//! JavaScript through `wasm-bindgen`.
+//!
+//! A web page creates a [`WebEmulator`], passes the bytes of an uploaded ROM
+//! file to [`WebEmulator::load_rom`], forwards `keydown`/`keyup` events and
+//! calls [`WebEmulator::frame`] from `requestAnimationFrame`. After every
+//! frame, [`WebEmulator::framebuffer`] returns the pixels to draw on a canvas.
+//!
+//! On touch devices the page can draw an on-screen keypad laid out by
+//! [`WebEmulator::keypad_layout`] in [`WebEmulator::keypad_columns`] columns,
+//! which known ROMs limit to the keys they use. Every `touchstart` and
+//! `touchmove` goes to [`WebEmulator::touch`] with the identifier of the
+//! finger and the key under it, every `touchend` to
+//! [`WebEmulator::touch_end`], so several keys can be held at once. The page
+//! highlights the keys reported by [`WebEmulator::keypad_state`].
+//!
+//! The debugger panels are laid out by a [`DockLayout`]: the page places
+//! every open panel into [`WebEmulator::panel_area`] and the display into
+//! [`WebEmulator::display_area`]. In player mode, set with
+//! [`WebEmulator::set_play_mode`] or the mode hotkey, the display gets the
+//! whole canvas. The layout and the mode of every ROM are saved with the
+//! other settings.
+//!
+//! Pages that bring their own frontend can use [`JsChip8`] instead, exported
+//! as `Chip8`, which only wraps the interpreter core.
+//!
+//! To start a game from a shared link, the page checks
+//! [`WebEmulator::rom_url`] on startup, shows a loading indicator while
+//! awaiting [`fetch_rom`] and passes the result to [`WebEmulator::load_rom`].
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    audio::{Synth, Waveform},
+    cheats::{Cheat, Cheats, Target},
+    config::{Config, WindowLayout, MAX_UI_SCALE, MIN_UI_SCALE},
+    coverage::Coverage,
+    disassembler::{self, Syntax},
+    display::{self, DisplayOptions, Filter, Phosphor, Scaling, Viewport},
+    flags::RplFlags,
+    gamepad::{Button, GamepadMap},
+    graphics,
+    hotkeys::{Action, Hotkeys, SLOTS},
+    i18n::Language,
+    keymap::{Keymap, KEY_COUNT},
+    labels::Labels,
+    layout::{Dock, DockLayout, Panel},
+    logging::{self, LogFilter},
+    megachip::{self, MegaChip},
+    netplay::{Lockstep, Message, PROTOCOL_VERSION},
+    octo,
+    processor::StepResult,
+    quirks::{StackDepth, Variant},
+    rng::Rng,
+    rom::{self, RomInfo},
+    romdb::RomDatabase,
+    roms,
+    runner::{BreakEvent, Chip8Runner, FocusBehavior},
+    sprites,
+    storage::Storage,
+    theme,
+    timer_bars::TimerBars,
+    touch::{TouchLayout, TouchPad},
+    trace::{self, TraceEntry, TraceFormat},
+    webaudio::WebAudio,
+    webrom, webstorage, Chip8,
+};
+
+/// A Chip8 emulator running in the browser.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct WebEmulator {
+    runner: Chip8Runner,
+    keymap: Keymap,
+    hotkeys: Hotkeys,
+    gamepad_map: GamepadMap,
+    rom_database: RomDatabase,
+    rom_hash: String,
+    rom_info: String,
+    synth: Synth,
+    web_audio: WebAudio,
+    touch_layout: TouchLayout,
+    touch_pad: TouchPad,
+    netplay: Option<Lockstep>,
+    display: DisplayOptions,
+    phosphor: Phosphor,
+    syntax: Syntax,
+    layout: DockLayout,
+    language: Language,
+    ui_scale: f32,
+    timer_bars: TimerBars,
+}
+
+impl Default for WebEmulator {
+    fn default() -> Self {
+        let mut chip8 = Chip8::new();
+        // the diff views compare with the state before the last instruction
+        chip8.history.set_depth(1);
+        Self {
+            runner: Chip8Runner::new(chip8),
+            keymap: Keymap::default(),
+            hotkeys: Hotkeys::default(),
+            gamepad_map: GamepadMap::default(),
+            rom_database: RomDatabase::builtin(),
+            rom_hash: String::new(),
+            rom_info: String::new(),
+            synth: Synth::new(),
+            web_audio: WebAudio::new(),
+            touch_layout: TouchLayout::new(),
+            touch_pad: TouchPad::new(),
+            netplay: None,
+            display: DisplayOptions::default(),
+            phosphor: Phosphor::default(),
+            syntax: Syntax::default(),
+            layout: DockLayout::default(),
+            language: Language::default(),
+            ui_scale: 1.0,
+            timer_bars: TimerBars::new(),
+        }
+    }
+}
+
+// `wasm-bindgen` cannot export `const fn`s.
+#[allow(clippy::missing_const_for_fn)]
+#[wasm_bindgen]
+impl WebEmulator {
+    /// Creates a new [`WebEmulator`] without a ROM loaded.
+    #[wasm_bindgen(constructor)]
+    #[must_use]
+    pub fn new() -> Self {
+        // further emulators on the page share the logger of the first one
+        let _ = logging::init(LogFilter::new(log::LevelFilter::Info));
+        Self::default()
+    }
+
+    /// Resets the emulator and loads the given ROM bytes, e.g. read from a
+    /// file input. The labels, comments, cheats and gamepad bindings are
+    /// discarded unless the same ROM is loaded again. The variant, speed and
+    /// key bindings of known ROMs are selected automatically.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM does not fit into memory.
+    pub fn load_rom(&mut self, data: &[u8]) -> Result<(), JsError> {
+        self.runner.load_rom(data.to_vec())?;
+        self.phosphor.reset();
+        self.runner.resume();
+        let rom_hash = roms::hash(data);
+        if rom_hash != self.rom_hash {
+            self.runner.chip8.labels = Labels::new();
+            self.runner.chip8.cheats = Cheats::new();
+            self.gamepad_map = GamepadMap::new();
+            self.rom_hash = rom_hash;
+            self.restore_save_slots();
+            self.layout.restore_mode(&self.rom_hash);
+        }
+        self.rom_info = RomInfo::new(data).to_string();
+        self.touch_layout = TouchLayout::new();
+        if let Some(entry) = self.rom_database.get(&self.rom_hash) {
+            entry.apply(&mut self.runner);
+            if let Some(keymap) = &entry.keymap {
+                self.keymap = keymap.clone();
+            }
+            self.touch_layout = entry.touch_layout();
+        }
+        Ok(())
+    }
+
+    /// Returns the URL of the ROM named by the `rom` query parameter of the
+    /// page, e.g. `?rom=https://example.com/pong.ch8`, if any.
+    #[must_use]
+    pub fn rom_url(&self) -> Option<String> {
+        webrom::rom_url()
+    }
+
+    /// Assembles the given Octo source and loads the result like
+    /// [`WebEmulator::load_rom`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error with the offending line if the source fails to
+    /// assemble, or if the ROM does not fit into memory.
+    pub fn load_octo(&mut self, source: &str) -> Result<(), JsError> {
+        let rom = octo::assemble(source)?;
+        self.load_rom(&rom)
+    }
+
+    /// Sets the address the next ROM is loaded at, e.g. `0x600` for ETI-660
+    /// ROMs, see [`Chip8::set_load_address`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the address lies before `0x200` or outside of
+    /// memory.
+    pub fn set_load_address(&mut self, addr: usize) -> Result<(), JsError> {
+        Ok(self.runner.chip8.set_load_address(addr)?)
+    }
+
+    /// Returns the address the loaded ROM most likely expects to be loaded
+    /// at, so the page can offer to load it there instead.
+    #[must_use]
+    pub fn detected_load_address(&self) -> usize {
+        rom::detect_load_address(self.runner.chip8.rom())
+    }
+
+    /// Returns a description of the loaded ROM for a "ROM Info" panel: its
+    /// size, hash, load address hint, instruction set extensions and
+    /// suggested variant.
+    #[must_use]
+    pub fn rom_info(&self) -> String {
+        self.rom_info.clone()
+    }
+
+    /// Returns the title of the loaded ROM if it is a known ROM, or an empty
+    /// string otherwise.
+    #[must_use]
+    pub fn rom_name(&self) -> String {
+        self.rom_database
+            .get(&self.rom_hash)
+            .map(|entry| entry.name.clone())
+            .unwrap_or_default()
+    }
+
+    /// Returns the SHA-1 hash of the loaded ROM, e.g. to key its labels in
+    /// `localStorage`.
+    #[must_use]
+    pub fn rom_hash(&self) -> String {
+        self.rom_hash.clone()
+    }
+
+    /// Handles a `keydown` event with the given `KeyboardEvent.key`. Returns
+    /// whether the key is bound, so the page can call `preventDefault`.
+    pub fn key_down(&mut self, key: &str) -> bool {
+        self.update_key(key, true)
+    }
+
+    /// Restarts the loaded ROM, see [`Chip8::reset_keep_rom`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM no longer fits into memory.
+    pub fn reset(&mut self) -> Result<(), JsError> {
+        self.runner.chip8.reset_keep_rom()?;
+        self.phosphor.reset();
+        self.runner.resume();
+        Ok(())
+    }
+
+    /// Restarts the loaded ROM like a power cycle, with a freshly seeded
+    /// random number generator, see [`Chip8::power_cycle`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM no longer fits into memory.
+    pub fn hard_reset(&mut self) -> Result<(), JsError> {
+        self.runner.chip8.power_cycle(Rng::from_entropy().seed())?;
+        self.phosphor.reset();
+        self.runner.resume();
+        Ok(())
+    }
+
+    /// Handles a `keydown` event with the given `KeyboardEvent.key` and
+    /// `KeyboardEvent.shiftKey` as a hotkey, see [`Hotkeys`]. Returns the name
+    /// of the action if the key is bound to one, so the page can call
+    /// `preventDefault`.
+    pub fn hotkey(&mut self, key: &str, shift: bool) -> Option<String> {
+        let action = self.hotkeys.action(key, shift)?;
+        if action == Action::ToggleMute {
+            self.synth.muted = !self.synth.muted;
+        } else if action == Action::ToggleMode {
+            self.set_play_mode(!self.layout.play_mode);
+        } else {
+            self.runner.perform(action);
+        }
+        if let Action::SaveState(slot) = action {
+            self.persist_save_slot(slot);
+        }
+        Some(action.to_string())
+    }
+
+    /// Applies the settings saved in `localStorage` by
+    /// [`WebEmulator::save_settings`]: key bindings, hotkeys, colors, quirks,
+    /// speed, sound, display options, panel layout, language and
+    /// accessibility options. Returns whether settings were
+    /// saved before.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `localStorage` is not available or the saved
+    /// settings are invalid.
+    pub fn load_settings(&mut self) -> Result<bool, JsValue> {
+        let Some(config) = webstorage::load_config()? else {
+            return Ok(false);
+        };
+        config.apply(&mut self.runner);
+        self.keymap = config.keymap.clone();
+        self.hotkeys = config.hotkeys.clone();
+        self.synth = config.synth();
+        self.display = config.display;
+        self.syntax = config.syntax;
+        self.language = config.language;
+        self.ui_scale = config.ui_scale();
+        self.layout = config.window.dock;
+        Ok(true)
+    }
+
+    /// Saves the current settings into `localStorage`, so they survive a
+    /// page reload. Call this whenever the user changed a setting.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `localStorage` is not available or full.
+    pub fn save_settings(&self) -> Result<(), JsValue> {
+        let mut config = Config {
+            window: WindowLayout {
+                dock: self.layout.clone(),
+                ..WindowLayout::default()
+            },
+            keymap: self.keymap.clone(),
+            hotkeys: self.hotkeys.clone(),
+            volume: self.synth.volume,
+            waveform: self.synth.waveform,
+            tone_frequency: self.synth.frequency,
+            key_clicks: self.synth.key_clicks,
+            display: self.display,
+            syntax: self.syntax,
+            language: self.language,
+            ui_scale: self.ui_scale,
+            ..Config::default()
+        };
+        config.capture(&self.runner);
+        webstorage::save_config(&config)
+    }
+
+    /// Handles a `keyup` event with the given `KeyboardEvent.key`. Returns
+    /// whether the key is bound, so the page can call `preventDefault`.
+    pub fn key_up(&mut self, key: &str) -> bool {
+        self.update_key(key, false)
+    }
+
+    /// Presses the given Chip8 key, e.g. when an on-screen key is tapped.
+    pub fn press_key(&mut self, key_code: u8) {
+        if usize::from(key_code) < KEY_COUNT {
+            self.set_key_state(key_code, true);
+        }
+    }
+
+    /// Releases the given Chip8 key, e.g. when an on-screen key is no longer
+    /// touched.
+    pub fn release_key(&mut self, key_code: u8) {
+        if usize::from(key_code) < KEY_COUNT {
+            self.set_key_state(key_code, false);
+        }
+    }
+
+    /// Sets the state of all 16 keys from a bitmask, where bit `n` is set if
+    /// key `n` is pressed, e.g. from a polled gamepad or touch overlay.
+    pub fn set_keys(&mut self, keys: u16) {
+        for key_code in 0..16 {
+            let pressed = keys & 1 << key_code != 0;
+            if self.runner.chip8.bus.input.is_key_pressed(key_code) != pressed {
+                self.set_key_state(key_code, pressed);
+            }
+        }
+    }
+
+    /// Returns the key codes of the on-screen keypad, row by row, with
+    /// `255` for gaps.
+    #[must_use]
+    pub fn keypad_layout(&self) -> Vec<u8> {
+        let cells = self.touch_layout.cells();
+        cells.iter().map(|key| key.unwrap_or(u8::MAX)).collect()
+    }
+
+    /// Returns the amount of keys per row of the on-screen keypad.
+    #[must_use]
+    pub fn keypad_columns(&self) -> usize {
+        self.touch_layout.columns()
+    }
+
+    /// Limits the on-screen keypad to the given key codes, e.g. chosen by
+    /// the user for an unknown ROM. Shows the full keypad if none are given.
+    pub fn set_touch_keys(&mut self, keys: &[u8]) {
+        let keys = keys.iter().fold(0, |keys, &key| keys | 1 << (key & 0xF));
+        self.touch_layout = TouchLayout::for_keys(keys);
+    }
+
+    /// Records the finger with the given touch identifier being over the
+    /// given key, or over a gap if `undefined` is given, on `touchstart` and
+    /// `touchmove` events.
+    pub fn touch(&mut self, id: i32, key_code: Option<u8>) {
+        let before = self.touch_pad.keys();
+        self.touch_pad.touch(id, key_code);
+        self.apply_touches(before);
+    }
+
+    /// Records the finger with the given touch identifier being lifted, on
+    /// `touchend` and `touchcancel` events.
+    pub fn touch_end(&mut self, id: i32) {
+        let before = self.touch_pad.keys();
+        self.touch_pad.release(id);
+        self.apply_touches(before);
+    }
+
+    /// Returns the state of all 16 keys, indexed by key code: `1` if the key
+    /// is pressed, `0` otherwise.
+    #[must_use]
+    pub fn keypad_state(&self) -> Vec<u8> {
+        (0..16)
+            .map(|key_code| u8::from(self.runner.chip8.bus.input.is_key_pressed(key_code)))
+            .collect()
+    }
+
+    /// Rebinds a Chip8 key to the given `KeyboardEvent.key`.
+    pub fn rebind(&mut self, key_code: u8, key: &str) {
+        if usize::from(key_code) < KEY_COUNT {
+            self.keymap.rebind(key_code, key);
+        }
+    }
+
+    /// Executes all instructions that are due since the previous frame. This
+    /// should be called from `requestAnimationFrame`.
+    ///
+    /// Returns a description of the event that stopped the program (an error,
+    /// the end of memory or an infinite loop), or `undefined` if it keeps
+    /// running.
+    pub fn frame(&mut self) -> Option<String> {
+        let event = self.runner.update().map(|event| format!("{event:?}"));
+        self.runner.record_frame();
+        self.timer_bars.capture(&self.runner.chip8);
+        self.queue_audio();
+        if self.phosphor.is_enabled() {
+            let rgb = self.display_rgb();
+            self.phosphor.apply(&rgb);
+        }
+        event
+    }
+
+    /// Returns the values of the delay and sound timers as `[delay, sound]`,
+    /// as of the last frame.
+    #[must_use]
+    pub fn timer_values(&self) -> Vec<u8> {
+        vec![self.timer_bars.delay.value(), self.timer_bars.sound.value()]
+    }
+
+    /// Returns the lengths of the delay and sound timer bars of a Timers
+    /// panel as `[delay, sound]`, each from `0.0` to `1.0` of the value the
+    /// timer was last loaded with.
+    #[must_use]
+    pub fn timer_levels(&self) -> Vec<f32> {
+        vec![self.timer_bars.delay.level(), self.timer_bars.sound.level()]
+    }
+
+    /// Returns whether the beep indicator of a Timers panel is lit. It stays
+    /// lit for a few frames after the buzzer stopped, so short beeps show.
+    #[must_use]
+    pub fn beeping(&self) -> bool {
+        self.timer_bars.beeping()
+    }
+
+    /// Pauses execution whenever the named event occurs: `draw`, `flag`
+    /// (VF becomes 1) or `sound` (the sound timer is started). Returns
+    /// whether the event exists.
+    pub fn set_event_break(&mut self, name: &str, enabled: bool) -> bool {
+        let event = match name {
+            "draw" => BreakEvent::Draw,
+            "flag" => BreakEvent::FlagSet,
+            "sound" => BreakEvent::SoundStart,
+            _ => return false,
+        };
+        if enabled {
+            self.runner.add_event_break(event);
+        } else {
+            self.runner.remove_event_break(event);
+        }
+        true
+    }
+
+    /// Pauses execution whenever the program reads the given key.
+    pub fn set_key_break(&mut self, key_code: u8, enabled: bool) {
+        if enabled {
+            self.runner.add_event_break(BreakEvent::KeyRead(key_code));
+        } else {
+            self.runner
+                .remove_event_break(BreakEvent::KeyRead(key_code));
+        }
+    }
+
+    /// Returns a one-line summary of the execution statistics, e.g. for an
+    /// overlay.
+    #[must_use]
+    pub fn stats(&self) -> String {
+        self.runner.stats().to_string()
+    }
+
+    /// Tells the emulator whether the page is visible and focused, e.g. from
+    /// `visibilitychange`, `focus` and `blur` events.
+    pub fn set_focused(&mut self, focused: bool) {
+        self.runner.set_focused(focused);
+    }
+
+    /// Returns the names of the behaviors while the page is not focused.
+    #[must_use]
+    pub fn focus_behaviors(&self) -> Vec<String> {
+        FocusBehavior::ALL
+            .iter()
+            .map(|b| b.name().to_string())
+            .collect()
+    }
+
+    /// Selects the behavior with the given name while the page is not
+    /// focused. Returns whether the behavior exists.
+    pub fn set_focus_behavior(&mut self, name: &str) -> bool {
+        let Some(behavior) = FocusBehavior::ALL.into_iter().find(|b| b.name() == name) else {
+            return false;
+        };
+        self.runner.set_focus_behavior(behavior);
+        true
+    }
+
+    /// Returns the amount of instructions per second achieved during the last
+    /// second, e.g. to graph it against [`WebEmulator::ips`].
+    #[must_use]
+    pub fn achieved_ips(&self) -> f64 {
+        self.runner.stats().ips
+    }
+
+    /// Returns the amount of frames per second drawn during the last second.
+    #[must_use]
+    pub fn fps(&self) -> f64 {
+        self.runner.stats().fps
+    }
+
+    /// Returns the display as RGB pixels, row by row, with phosphor
+    /// persistence applied if enabled.
+    #[must_use]
+    pub fn framebuffer(&self) -> Vec<u8> {
+        let rgb = self.display_rgb();
+        let glow = self.phosphor.output();
+        if self.phosphor.is_enabled() && glow.len() == rgb.len() {
+            glow.to_vec()
+        } else {
+            rgb
+        }
+    }
+
+    /// Sets the amount of frames turned-off pixels take to fade out, which
+    /// reduces flicker, e.g. for a slider. 0 disables the effect.
+    pub fn set_phosphor_frames(&mut self, frames: u32) {
+        self.phosphor.frames = frames;
+    }
+
+    /// Returns the width of the display in pixels, which changes when a
+    /// program switches into Mega-Chip mode.
+    #[must_use]
+    pub fn width(&self) -> usize {
+        self.megachip().map_or(graphics::WIDTH, |_| megachip::WIDTH)
+    }
+
+    /// Returns the height of the display in pixels, which changes when a
+    /// program switches into Mega-Chip mode or the two-page hires mode.
+    #[must_use]
+    pub fn height(&self) -> usize {
+        self.megachip().map_or_else(
+            || self.runner.chip8.bus.graphics.height(),
+            |_| megachip::HEIGHT,
+        )
+    }
+
+    /// Returns the display as RGBA pixels, row by row, with every pixel scaled
+    /// up to a `scale` x `scale` square and the selected filter applied.
+    #[must_use]
+    pub fn scaled_framebuffer(&self, scale: usize) -> Vec<u8> {
+        self.display
+            .render_rgb(&self.framebuffer(), self.width(), scale.max(1))
+    }
+
+    /// Returns the rectangle of a canvas of the given size that the display
+    /// should be drawn into with the selected scaling mode, as
+    /// `[x, y, width, height]`.
+    #[must_use]
+    pub fn viewport(&self, canvas_width: u32, canvas_height: u32) -> Vec<u32> {
+        #[allow(clippy::cast_possible_truncation)] // displays are small
+        let viewport = self.display.viewport(
+            self.width() as u32,
+            self.height() as u32,
+            canvas_width,
+            canvas_height,
+        );
+        vec![viewport.x, viewport.y, viewport.width, viewport.height]
+    }
+
+    /// Returns the rectangle of the given area of a canvas that the display
+    /// should be drawn into, e.g. a panel next to the debugger panels, as
+    /// `[x, y, width, height]`.
+    #[must_use]
+    pub fn viewport_in(&self, x: u32, y: u32, width: u32, height: u32) -> Vec<u32> {
+        let area = Viewport {
+            x,
+            y,
+            width,
+            height,
+        };
+        #[allow(clippy::cast_possible_truncation)] // displays are small
+        let viewport = self
+            .display
+            .viewport_in(self.width() as u32, self.height() as u32, area);
+        vec![viewport.x, viewport.y, viewport.width, viewport.height]
+    }
+
+    /// Returns the size of the drawing buffer of a canvas laid out at the
+    /// given CSS size, as `[width, height]`, given `window.devicePixelRatio`.
+    /// Sizing the canvas like this keeps the display sharp on high-DPI
+    /// screens; [`WebEmulator::viewport`] expects the sizes in these device
+    /// pixels.
+    #[must_use]
+    pub fn canvas_size(
+        &self,
+        css_width: u32,
+        css_height: u32,
+        device_pixel_ratio: f64,
+    ) -> Vec<u32> {
+        vec![
+            display::to_physical(css_width, device_pixel_ratio),
+            display::to_physical(css_height, device_pixel_ratio),
+        ]
+    }
+
+    /// Returns the codes of the languages the user interface can be shown in,
+    /// e.g. to fill a language menu together with [`WebEmulator::language_name`].
+    #[must_use]
+    pub fn languages(&self) -> Vec<String> {
+        Language::ALL.iter().map(|l| l.code().to_string()).collect()
+    }
+
+    /// Returns the name of the language with the given code in the language
+    /// itself, or an empty string if it is not supported.
+    #[must_use]
+    pub fn language_name(&self, code: &str) -> String {
+        Language::from_code(code)
+            .map(|language| language.name().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Returns the code of the selected language.
+    #[must_use]
+    pub fn language(&self) -> String {
+        self.language.code().to_string()
+    }
+
+    /// Selects the language with the given code, e.g. `navigator.language`
+    /// on first start. Returns whether the language is supported.
+    pub fn set_language(&mut self, code: &str) -> bool {
+        let Some(language) = Language::from_code(code) else {
+            return false;
+        };
+        self.language = language;
+        true
+    }
+
+    /// Returns the given English label, e.g. a name returned by
+    /// [`WebEmulator::panels`], in the selected language. Names passed back
+    /// to the emulator, e.g. to [`WebEmulator::set_scaling`], stay English.
+    #[must_use]
+    pub fn translate(&self, text: &str) -> String {
+        self.language.translate(text).to_string()
+    }
+
+    /// Returns the names of the debugger panels, e.g. to fill a Window menu.
+    #[must_use]
+    pub fn panels(&self) -> Vec<String> {
+        Panel::ALL.iter().map(|p| p.name().to_string()).collect()
+    }
+
+    /// Returns the names of the places a panel can be docked to.
+    #[must_use]
+    pub fn docks(&self) -> Vec<String> {
+        Dock::ALL.iter().map(|d| d.name().to_string()).collect()
+    }
+
+    /// Returns the name of the dock of the panel with the given name, if the
+    /// panel exists.
+    #[must_use]
+    pub fn panel_dock(&self, panel: &str) -> Option<String> {
+        let panel = Panel::from_name(panel)?;
+        Some(self.layout.state(panel).dock.name().to_string())
+    }
+
+    /// Moves the panel with the given name to the dock with the given name,
+    /// e.g. when it is dropped on another edge or detached. Returns whether
+    /// both exist.
+    pub fn set_panel_dock(&mut self, panel: &str, dock: &str) -> bool {
+        let (Some(panel), Some(dock)) = (Panel::from_name(panel), Dock::from_name(dock)) else {
+            return false;
+        };
+        self.layout.set_dock(panel, dock);
+        true
+    }
+
+    /// Returns whether the panel with the given name is shown. No panel is
+    /// shown in play mode.
+    #[must_use]
+    pub fn is_panel_visible(&self, panel: &str) -> bool {
+        Panel::from_name(panel).is_some_and(|panel| self.layout.is_visible(panel))
+    }
+
+    /// Opens or closes the panel with the given name. Returns whether the
+    /// panel exists.
+    pub fn set_panel_open(&mut self, panel: &str, open: bool) -> bool {
+        let Some(panel) = Panel::from_name(panel) else {
+            return false;
+        };
+        self.layout.set_open(panel, open);
+        true
+    }
+
+    /// Returns whether play mode hides all debugger panels.
+    #[must_use]
+    pub fn play_mode(&self) -> bool {
+        self.layout.play_mode
+    }
+
+    /// Switches to player mode, which hides all debugger panels so the page
+    /// only shows the display, [`WebEmulator::keypad_hints`] and the speed,
+    /// or back to developer mode, e.g. from a View menu. The mode is
+    /// remembered for the loaded ROM once the settings are saved.
+    pub fn set_play_mode(&mut self, play_mode: bool) {
+        self.layout.set_play_mode_for(&self.rom_hash, play_mode);
+    }
+
+    /// Returns a hint for every key of the on-screen keypad, i.e. the keys
+    /// the loaded ROM uses if known, as `"<key>: <binding>"`, e.g. `"5: W"`.
+    /// Unbound keys are left out.
+    #[must_use]
+    pub fn keypad_hints(&self) -> Vec<String> {
+        self.touch_layout
+            .cells()
+            .iter()
+            .flatten()
+            .map(|&key| (key, self.keymap.binding(key)))
+            .filter(|(_, binding)| !binding.is_empty())
+            .map(|(key, binding)| format!("{key:X}: {binding}"))
+            .collect()
+    }
+
+    /// Returns the area of a canvas of the given size left to the display
+    /// by the docked panels, as `[x, y, width, height]`. Pass it on to
+    /// [`WebEmulator::viewport_in`].
+    #[must_use]
+    pub fn display_area(&self, canvas_width: u32, canvas_height: u32) -> Vec<u32> {
+        let area = self.layout.arrange(canvas_width, canvas_height).display;
+        vec![area.x, area.y, area.width, area.height]
+    }
+
+    /// Returns the area of a canvas of the given size the panel with the
+    /// given name is docked into, as `[x, y, width, height]`, or an empty
+    /// array if the panel is hidden or floating.
+    #[must_use]
+    pub fn panel_area(&self, panel: &str, canvas_width: u32, canvas_height: u32) -> Vec<u32> {
+        Panel::from_name(panel)
+            .and_then(|panel| {
+                self.layout
+                    .arrange(canvas_width, canvas_height)
+                    .panel(panel)
+            })
+            .map_or_else(Vec::new, |area| {
+                vec![area.x, area.y, area.width, area.height]
+            })
+    }
+
+    /// Returns the names of the preset color themes, e.g. to fill a View menu.
+    #[must_use]
+    pub fn themes(&self) -> Vec<String> {
+        theme::THEMES
+            .iter()
+            .map(|theme| theme.name.to_string())
+            .collect()
+    }
+
+    /// Switches the display to the preset theme with the given name. Returns
+    /// whether the theme exists.
+    pub fn set_theme(&mut self, name: &str) -> bool {
+        let Some(theme) = theme::find(name) else {
+            return false;
+        };
+        self.runner.chip8.bus.graphics.set_palette(theme.palette);
+        true
+    }
+
+    /// Sets the color of the given palette index: 0 for the background, 1
+    /// for the foreground, 2 and 3 for XO-CHIP plane colors. Returns whether
+    /// the index exists.
+    pub fn set_color(&mut self, index: usize, red: u8, green: u8, blue: u8) -> bool {
+        let Some(color) = self.runner.chip8.bus.graphics.palette.get_mut(index) else {
+            return false;
+        };
+        *color = graphics::Rgb::from_array([red, green, blue]);
+        true
+    }
+
+    /// Returns the names of the scaling modes, e.g. to fill a View menu.
+    #[must_use]
+    pub fn scaling_modes(&self) -> Vec<String> {
+        Scaling::ALL.iter().map(|s| s.name().to_string()).collect()
+    }
+
+    /// Selects the scaling mode with the given name. Returns whether the mode
+    /// exists.
+    pub fn set_scaling(&mut self, name: &str) -> bool {
+        let Some(scaling) = Scaling::ALL.into_iter().find(|s| s.name() == name) else {
+            return false;
+        };
+        self.display.scaling = scaling;
+        true
+    }
+
+    /// Returns the names of the display filters, e.g. to fill a View menu.
+    #[must_use]
+    pub fn filters(&self) -> Vec<String> {
+        Filter::ALL.iter().map(|f| f.name().to_string()).collect()
+    }
+
+    /// Selects the display filter with the given name. Returns whether the
+    /// filter exists.
+    pub fn set_filter(&mut self, name: &str) -> bool {
+        let Some(filter) = Filter::ALL.into_iter().find(|f| f.name() == name) else {
+            return false;
+        };
+        self.display.filter = filter;
+        true
+    }
+
+    /// Enables or disables the Mega-Chip extensions for the next loaded ROM.
+    pub fn set_megachip(&mut self, enabled: bool) {
+        self.runner.chip8.set_megachip(enabled);
+    }
+
+    /// Returns whether execution is paused.
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.runner.controls().is_paused()
+    }
+
+    /// Toggles between the paused and running state.
+    pub fn toggle_execution(&mut self) {
+        if self.is_paused() {
+            self.runner.resume();
+        } else {
+            self.runner.pause();
+        }
+    }
+
+    /// Executes a single instruction while paused.
+    pub fn step(&self) {
+        self.runner.controls().step();
+    }
+
+    /// Runs until the program counter reaches the given address, e.g. the
+    /// line that was right-clicked in the disassembly, and pauses there.
+    pub fn run_to(&mut self, address: usize) {
+        self.runner.run_to(address);
+    }
+
+    /// Executes the instructions of a single 60Hz frame while paused.
+    ///
+    /// Returns a description of the event that stopped the program, like
+    /// [`WebEmulator::frame`].
+    pub fn step_frame(&mut self) -> Option<String> {
+        self.runner.step_frame().map(|event| format!("{event:?}"))
+    }
+
+    /// Returns the target amount of instructions per second.
+    #[must_use]
+    pub fn ips(&self) -> u64 {
+        self.runner.ips()
+    }
+
+    /// Sets the target amount of instructions per second.
+    pub fn set_ips(&self, ips: u64) {
+        self.runner.set_ips(ips);
+    }
+
+    /// Returns the multiplier applied to the emulation speed.
+    #[must_use]
+    pub fn speed(&self) -> f64 {
+        self.runner.speed()
+    }
+
+    /// Sets the multiplier applied to the emulation speed, e.g. `4.0` while a
+    /// fast-forward key is held or `0.25` for slow motion. Negative values are
+    /// ignored.
+    pub fn set_speed(&mut self, speed: f64) {
+        if speed >= 0.0 {
+            self.runner.set_speed(speed);
+        }
+    }
+
+    /// Overrides the timer frequency (in Hz) at normal speed. Negative values
+    /// are ignored.
+    pub fn set_timer_frequency(&mut self, frequency: f64) {
+        if frequency >= 0.0 {
+            self.runner.set_timer_frequency(frequency);
+        }
+    }
+
+    /// Switches between timers ticked by the runner in step with the
+    /// emulated time and timers ticked by [`WebEmulator::tick_60hz`], e.g.
+    /// once per `requestAnimationFrame`.
+    pub fn set_manual_timers(&mut self, manual: bool) {
+        self.runner.set_manual_timers(manual);
+    }
+
+    /// Decrements the delay and sound timers once and raises the vblank
+    /// interrupt.
+    pub fn tick_60hz(&mut self) {
+        self.runner.chip8.tick_timers();
+    }
+
+    /// Returns the greeting the host of a netplay session sends over the
+    /// WebSocket, announcing the seed and the loaded ROM.
+    #[must_use]
+    pub fn netplay_hello(&self, seed: u64) -> String {
+        Message::Hello {
+            version: PROTOCOL_VERSION,
+            seed,
+            rom_hash: self.rom_hash.clone(),
+        }
+        .to_string()
+    }
+
+    /// Restarts the given ROM with the given seed and starts a netplay
+    /// session, executing `instructions_per_frame` instructions per frame
+    /// with an input delay of `delay` frames. From now on,
+    /// [`WebEmulator::netplay_frame`] drives the emulator instead of
+    /// [`WebEmulator::frame`], and key presses are shared with the remote
+    /// player.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM does not fit into memory.
+    pub fn start_netplay(
+        &mut self,
+        data: &[u8],
+        seed: u64,
+        instructions_per_frame: u32,
+        delay: u64,
+    ) -> Result<(), JsError> {
+        self.runner.chip8.set_seed(seed);
+        self.load_rom(data)?;
+        self.runner.pause();
+        self.netplay = Some(Lockstep::new(instructions_per_frame, delay));
+        Ok(())
+    }
+
+    /// Ends the netplay session. The emulator keeps its state but stays
+    /// paused.
+    pub fn stop_netplay(&mut self) {
+        self.netplay = None;
+    }
+
+    /// Handles a line received from the remote player. Returns whether it was
+    /// a valid message.
+    pub fn netplay_receive(&mut self, line: &str) -> bool {
+        match (&mut self.netplay, Message::parse(line)) {
+            (Some(lockstep), Some(message)) => lockstep.receive(&message),
+            _ => false,
+        }
+    }
+
+    /// Returns the lines to send to the remote player.
+    pub fn netplay_outgoing(&mut self) -> Vec<String> {
+        self.netplay
+            .as_mut()
+            .map(|lockstep| {
+                lockstep
+                    .take_outgoing()
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Executes the next netplay frame, if the keys of the remote player for
+    /// it arrived. Returns whether the frame was executed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no netplay session is running or the program
+    /// raised an error.
+    pub fn netplay_frame(&mut self) -> Result<bool, JsError> {
+        let lockstep = self
+            .netplay
+            .as_mut()
+            .ok_or_else(|| JsError::new("no netplay session"))?;
+        Ok(lockstep.step_frame(&mut self.runner.chip8)?)
+    }
+
+    /// Starts playing the buzzer through Web Audio, which browsers only allow
+    /// after a user interaction. Call this from the first click or key
+    /// handler; [`WebEmulator::frame`] then queues the sound by itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the browser does not support Web Audio.
+    pub fn unlock_audio(&mut self) -> Result<(), JsValue> {
+        self.web_audio.unlock()
+    }
+
+    /// Returns whether Web Audio is unlocked and playing, e.g. to hide a
+    /// "click to enable sound" hint.
+    #[must_use]
+    pub fn audio_unlocked(&self) -> bool {
+        self.web_audio.is_unlocked()
+    }
+
+    /// Returns `count` mono samples of the buzzer at the given sample rate,
+    /// e.g. to fill the buffer of an `AudioWorkletProcessor` instead of
+    /// using [`WebEmulator::unlock_audio`]. The samples are silent while the
+    /// sound timer is zero.
+    #[must_use]
+    pub fn audio_samples(&mut self, count: usize, sample_rate: u32) -> Vec<f32> {
+        let bus = &self.runner.chip8.bus;
+        let playing = bus
+            .clock
+            .sound_timer
+            .load(std::sync::atomic::Ordering::SeqCst)
+            > 0;
+        let mut samples = vec![0.0; count];
+        self.synth
+            .fill(&mut samples, sample_rate, &bus.audio, playing);
+        samples
+    }
+
+    /// Returns the names of the buzzer waveforms, e.g. to fill a dropdown.
+    #[must_use]
+    pub fn waveforms(&self) -> Vec<String> {
+        Waveform::ALL.iter().map(|w| w.name().to_string()).collect()
+    }
+
+    /// Switches the buzzer to the waveform with the given name. Returns
+    /// whether the waveform exists.
+    pub fn set_waveform(&mut self, name: &str) -> bool {
+        let Some(waveform) = Waveform::ALL.into_iter().find(|w| w.name() == name) else {
+            return false;
+        };
+        self.synth.waveform = waveform;
+        true
+    }
+
+    /// Sets the frequency of the buzzer tone in Hz. Non-positive values are
+    /// ignored.
+    pub fn set_tone_frequency(&mut self, frequency: f32) {
+        if frequency > 0.0 {
+            self.synth.frequency = frequency;
+        }
+    }
+
+    /// Returns the volume of the buzzer, from `0.0` for muted to `1.0`.
+    #[must_use]
+    pub fn volume(&self) -> f32 {
+        self.synth.volume
+    }
+
+    /// Sets the volume of the buzzer, clamped to `0.0` for muted to `1.0`.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.synth.volume = volume.clamp(0.0, 1.0);
+    }
+
+    /// Returns whether key presses are confirmed with a short click.
+    #[must_use]
+    pub fn key_clicks(&self) -> bool {
+        self.synth.key_clicks
+    }
+
+    /// Enables or disables a short click on every key press, as an audible
+    /// cue for users who cannot see the keypad well.
+    pub fn set_key_clicks(&mut self, enabled: bool) {
+        self.synth.key_clicks = enabled;
+    }
+
+    /// Returns the factor the page should scale its user interface by, on
+    /// top of `window.devicePixelRatio`.
+    #[must_use]
+    pub fn ui_scale(&self) -> f32 {
+        self.ui_scale
+    }
+
+    /// Sets the factor the page scales its user interface by, e.g. from a
+    /// slider in the settings, clamped to the supported range.
+    pub fn set_ui_scale(&mut self, scale: f32) {
+        self.ui_scale = scale.clamp(MIN_UI_SCALE, MAX_UI_SCALE);
+    }
+
+    /// Handles a button of a standard gamepad changing state, given its index
+    /// in `Gamepad.buttons`. Returns whether the button is bound to a Chip8
+    /// key.
+    pub fn gamepad_button(&mut self, index: usize, pressed: bool) -> bool {
+        let Some(key_code) =
+            Button::from_standard_index(index).and_then(|b| self.gamepad_map.key_code(b))
+        else {
+            return false;
+        };
+        self.set_key_state(key_code, pressed);
+        true
+    }
+
+    /// Binds the button with the given index in `Gamepad.buttons` to a Chip8
+    /// key code. Unknown buttons and invalid key codes are ignored.
+    pub fn bind_gamepad_button(&mut self, index: usize, key_code: u8) {
+        if let Some(button) = Button::from_standard_index(index) {
+            if usize::from(key_code) < KEY_COUNT {
+                self.gamepad_map.bind(button, key_code);
+            }
+        }
+    }
+
+    /// Returns the gamepad bindings of the loaded ROM as a sidecar JSON
+    /// string, to store in the browser.
+    #[must_use]
+    pub fn gamepad_json(&self) -> String {
+        self.gamepad_map.to_json(&self.rom_hash)
+    }
+
+    /// Replaces the gamepad bindings with those of a sidecar JSON string.
+    /// Returns whether the sidecar was valid and belongs to the loaded ROM.
+    pub fn load_gamepad_json(&mut self, json: &str) -> bool {
+        match GamepadMap::from_json(json, &self.rom_hash) {
+            Ok(Some(map)) => {
+                self.gamepad_map = map;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the names of the interpreter variants whose quirks can be
+    /// emulated, e.g. to fill a dropdown.
+    #[must_use]
+    pub fn variants(&self) -> Vec<String> {
+        Variant::ALL.iter().map(ToString::to_string).collect()
+    }
+
+    /// Switches to the quirks of the variant with the given name. Returns
+    /// whether the variant exists.
+    pub fn set_variant(&mut self, name: &str) -> bool {
+        let Some(variant) = Variant::ALL.into_iter().find(|v| v.name() == name) else {
+            return false;
+        };
+        self.runner.chip8.set_variant(variant);
+        true
+    }
+
+    /// Limits how deep subroutine calls may nest, or lifts the limit if
+    /// `depth` is `0`.
+    pub fn set_stack_depth(&mut self, depth: usize) {
+        self.runner.chip8.processor.quirks.stack_depth = if depth == 0 {
+            StackDepth::Unlimited
+        } else {
+            StackDepth::Limited(depth)
+        };
+    }
+
+    /// Returns `count` sprites of `height` rows starting at `address`, stacked
+    /// on top of each other as RGBA pixels and scaled up by `scale`. The image
+    /// is `8 * scale` pixels wide.
+    #[must_use]
+    pub fn sprite_sheet(
+        &self,
+        address: usize,
+        height: usize,
+        count: usize,
+        scale: usize,
+    ) -> Vec<u8> {
+        let graphics = &self.runner.chip8.bus.graphics;
+        sprites::rip(&self.runner.chip8.bus.memory, address, height, count)
+            .iter()
+            .flat_map(|sprite| sprite.to_rgba(scale, graphics.palette[1], graphics.palette[0]))
+            .collect()
+    }
+
+    /// Returns the values of the V0 to VF registers.
+    #[must_use]
+    pub fn registers(&self) -> Vec<u8> {
+        self.runner.chip8.processor.v.to_vec()
+    }
+
+    /// Returns the program counter.
+    #[must_use]
+    pub fn pc(&self) -> usize {
+        self.runner.chip8.processor.pc
+    }
+
+    /// Returns the index register.
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.runner.chip8.processor.i
+    }
+
+    /// Returns how the most recent instruction used the V0 to VF registers,
+    /// indexed by register: bit 0 is set if it was read, bit 1 if it was
+    /// written and bit 2 if its value changed.
+    #[must_use]
+    pub fn register_diff(&self) -> Vec<u8> {
+        let Some(diff) = self.runner.chip8.diff() else {
+            return vec![0; 16];
+        };
+        (0..16)
+            .map(|x| {
+                let bit = |mask: u16| u8::from(mask & 1 << x != 0);
+                bit(diff.accesses.reads) | bit(diff.accesses.writes) << 1 | bit(diff.registers) << 2
+            })
+            .collect()
+    }
+
+    /// Returns the addresses of the memory bytes changed by the most recent
+    /// instruction.
+    #[must_use]
+    pub fn memory_diff(&self) -> Vec<usize> {
+        self.runner
+            .chip8
+            .diff()
+            .map(|diff| diff.memory)
+            .unwrap_or_default()
+    }
+
+    /// Returns the indices (`y * 64 + x`) of the pixels changed by the most
+    /// recent instruction.
+    #[must_use]
+    pub fn pixel_diff(&self) -> Vec<usize> {
+        self.runner
+            .chip8
+            .diff()
+            .map(|diff| {
+                diff.pixels
+                    .into_iter()
+                    .map(|(x, y)| y * graphics::WIDTH + x)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the full machine state as JSON, e.g. to diff it against a
+    /// reference implementation.
+    #[must_use]
+    pub fn dump_state(&self) -> String {
+        trace::dump_state(&self.runner.chip8)
+    }
+
+    /// Returns the state before the next instruction as a single trace line,
+    /// in the format with the given name (`text` or `json`).
+    #[must_use]
+    pub fn trace_entry(&self, format: &str) -> String {
+        let format = TraceFormat::from_name(format).unwrap_or_default();
+        TraceEntry::capture(&self.runner.chip8).format(format)
+    }
+
+    /// Starts or stops saving power while the program waits for a key press,
+    /// by only executing a single instruction per frame.
+    pub fn set_power_saving(&mut self, enabled: bool) {
+        self.runner.set_power_saving(enabled);
+    }
+
+    /// Returns whether the program sleeps until a key press, so the page can
+    /// request frames less often until the next key event.
+    #[must_use]
+    pub fn is_sleeping(&self) -> bool {
+        self.runner.is_sleeping()
+    }
+
+    /// Starts or stops logging memory reads and writes.
+    pub fn set_memory_log(&mut self, enabled: bool) {
+        self.runner.chip8.memory_log.set_enabled(enabled);
+    }
+
+    /// Only logs accesses to the addresses from `start` up to, but not
+    /// including, `end`. Passing `0` for both logs all addresses.
+    pub fn set_memory_log_filter(&mut self, start: usize, end: usize) {
+        let filter = (start, end) != (0, 0);
+        self.runner
+            .chip8
+            .memory_log
+            .set_filter(filter.then_some(start..end));
+    }
+
+    /// Returns the logged accesses to the addresses from `start` up to, but
+    /// not including, `end`, oldest first, e.g. `0x0204: write 0x0300 =
+    /// 0x07`.
+    #[must_use]
+    pub fn memory_log(&self, start: usize, end: usize) -> Vec<String> {
+        self.runner
+            .chip8
+            .memory_log
+            .in_range(start..end)
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    /// Returns the recently executed instructions, with the most recent one
+    /// first.
+    #[must_use]
+    pub fn trace(&self) -> Vec<String> {
+        let chip8 = &self.runner.chip8;
+        chip8
+            .processor
+            .instructions
+            .iter()
+            .map(|instruction| {
+                let mut line = format!(
+                    "{}  {:04X}  {}",
+                    chip8.labels.format_address(instruction.address),
+                    instruction.opcode,
+                    instruction.text(self.syntax, &chip8.labels)
+                );
+                if let Some(comment) = chip8.labels.comment(instruction.address) {
+                    line.push_str("  ; ");
+                    line.push_str(comment);
+                }
+                line
+            })
+            .collect()
+    }
+
+    /// Returns the report of the latest error raised by the program as text,
+    /// with the registers, the stack, the memory around PC and I and the
+    /// recently executed instructions, so the error dialog of the page can
+    /// offer to save it for a bug report. Returns [`None`] if the program did
+    /// not raise an error yet.
+    #[must_use]
+    pub fn crash_report(&self) -> Option<String> {
+        self.runner.crash_report().map(ToString::to_string)
+    }
+
+    /// Returns the recent log messages at or above the level with the given
+    /// name (`error`, `warn`, `info`, `debug` or `trace`), oldest first, for a
+    /// log console.
+    #[must_use]
+    pub fn log_messages(&self, level: &str) -> Vec<String> {
+        let level = level.parse().unwrap_or(log::LevelFilter::Trace);
+        logging::logger()
+            .console()
+            .entries(level)
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    /// Discards the messages of the log console.
+    pub fn clear_log(&self) {
+        logging::logger().console().clear();
+    }
+
+    /// Returns the levels of the logged messages by module, e.g.
+    /// `info,processor=trace`.
+    #[must_use]
+    pub fn log_filter(&self) -> String {
+        logging::logger().filter().to_string()
+    }
+
+    /// Changes the levels of the logged messages by module, e.g. to
+    /// `info,processor=trace` to trace every executed instruction. Returns
+    /// whether the filter is valid.
+    pub fn set_log_filter(&mut self, filter: &str) -> bool {
+        let Some(filter) = LogFilter::parse(filter) else {
+            return false;
+        };
+        logging::logger().set_filter(filter);
+        true
+    }
+
+    /// Returns the names of the syntaxes instructions can be written in, e.g.
+    /// to fill a dropdown above the trace.
+    #[must_use]
+    pub fn syntaxes(&self) -> Vec<String> {
+        Syntax::ALL.iter().map(|s| s.name().to_string()).collect()
+    }
+
+    /// Selects the syntax of the trace, call stack and profiler by name.
+    /// Returns whether the syntax exists.
+    pub fn set_syntax(&mut self, name: &str) -> bool {
+        let Some(syntax) = Syntax::ALL.into_iter().find(|s| s.name() == name) else {
+            return false;
+        };
+        self.syntax = syntax;
+        true
+    }
+
+    /// Returns the pending subroutine calls, with the most recent call first,
+    /// each described by its disassembled call site and return address.
+    #[must_use]
+    pub fn call_stack(&self) -> Vec<String> {
+        let chip8 = &self.runner.chip8;
+        chip8
+            .call_stack()
+            .iter()
+            .map(|frame| frame.describe(&chip8.labels, self.syntax))
+            .collect()
+    }
+
+    /// Assigns a label to the given address, or removes its label if the name
+    /// is empty.
+    pub fn set_label(&mut self, address: usize, name: &str) {
+        let labels = &mut self.runner.chip8.labels;
+        if name.is_empty() {
+            labels.remove(address);
+        } else {
+            labels.set(address, name);
+        }
+    }
+
+    /// Attaches a comment to the given address, or removes its comment if the
+    /// text is empty.
+    pub fn set_comment(&mut self, address: usize, comment: &str) {
+        let labels = &mut self.runner.chip8.labels;
+        if comment.is_empty() {
+            labels.remove_comment(address);
+        } else {
+            labels.set_comment(address, comment);
+        }
+    }
+
+    /// Returns the labels and comments of the loaded ROM as a sidecar JSON
+    /// string, to store in the browser.
+    #[must_use]
+    pub fn labels_json(&self) -> String {
+        self.runner.chip8.labels.to_json(&self.rom_hash)
+    }
+
+    /// Replaces the labels and comments with those of a sidecar JSON string.
+    /// Returns whether the sidecar was valid and belongs to the loaded ROM.
+    pub fn load_labels_json(&mut self, json: &str) -> bool {
+        match Labels::from_json(json, &self.rom_hash) {
+            Ok(Some(labels)) => {
+                self.runner.chip8.labels = labels;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the RPL user flags as a sidecar JSON string, to store in the
+    /// browser so high scores survive reloading the page.
+    #[must_use]
+    pub fn rpl_flags_json(&self) -> String {
+        self.runner.chip8.bus.flags.to_json(&self.rom_hash)
+    }
+
+    /// Replaces the RPL user flags with those of a sidecar JSON string.
+    /// Returns whether the sidecar was valid and belongs to the loaded ROM.
+    pub fn load_rpl_flags_json(&mut self, json: &str) -> bool {
+        match RplFlags::from_json(json, &self.rom_hash) {
+            Ok(Some(flags)) => {
+                self.runner.chip8.bus.flags = flags;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns whether the program wrote the RPL user flags since the last
+    /// call, so they can be stored again.
+    pub fn rpl_flags_changed(&mut self) -> bool {
+        self.runner.chip8.bus.flags.take_changed()
+    }
+
+    /// Reserves `len` bytes of memory starting at `addr` as the save region
+    /// of the loaded ROM, or releases it if `len` is zero.
+    pub fn set_save_region(&mut self, addr: usize, len: usize) {
+        let region = (len > 0).then(|| addr..addr.saturating_add(len));
+        self.runner.chip8.storage.set_region(region);
+    }
+
+    /// Returns the storage of the loaded ROM as a sidecar JSON string, to
+    /// store in the browser.
+    #[must_use]
+    pub fn storage_json(&self) -> String {
+        self.runner.chip8.storage.to_json(&self.rom_hash)
+    }
+
+    /// Replaces the storage with that of a sidecar JSON string and writes its
+    /// save region into memory. Returns whether the sidecar was valid and
+    /// belongs to the loaded ROM.
+    pub fn load_storage_json(&mut self, json: &str) -> bool {
+        match Storage::from_json(json, &self.rom_hash) {
+            Ok(Some(storage)) => {
+                self.runner.chip8.set_storage(storage);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Stores the save region of the loaded ROM and returns whether the
+    /// storage changed since the last call, so it can be stored again.
+    pub fn storage_changed(&mut self) -> bool {
+        self.runner.chip8.sync_storage();
+        self.runner.chip8.storage.take_changed()
+    }
+
+    /// Adds an enabled cheat freezing the byte at the given memory address at
+    /// `value`.
+    pub fn add_memory_cheat(&mut self, name: &str, address: usize, value: u8) {
+        let cheat = Cheat::new(name, Target::Memory(address), value);
+        self.runner.chip8.cheats.push(cheat);
+    }
+
+    /// Adds an enabled cheat freezing the register `Vx` at `value`.
+    pub fn add_register_cheat(&mut self, name: &str, x: u8, value: u8) {
+        let cheat = Cheat::new(name, Target::Register(x), value);
+        self.runner.chip8.cheats.push(cheat);
+    }
+
+    /// Removes the cheat at the given index of [`WebEmulator::cheats`].
+    pub fn remove_cheat(&mut self, index: usize) {
+        self.runner.chip8.cheats.remove(index);
+    }
+
+    /// Enables or disables the cheat at the given index of
+    /// [`WebEmulator::cheats`].
+    pub fn set_cheat_enabled(&mut self, index: usize, enabled: bool) {
+        self.runner.chip8.cheats.set_enabled(index, enabled);
+    }
+
+    /// Returns a description of every cheat, in the order they were added.
+    #[must_use]
+    pub fn cheats(&self) -> Vec<String> {
+        self.runner
+            .chip8
+            .cheats
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    /// Returns the cheats of the loaded ROM as a sidecar JSON string, to store
+    /// in the browser.
+    #[must_use]
+    pub fn cheats_json(&self) -> String {
+        self.runner.chip8.cheats.to_json(&self.rom_hash)
+    }
+
+    /// Replaces the cheats with those of a sidecar JSON string. Returns
+    /// whether the sidecar was valid and belongs to the loaded ROM.
+    pub fn load_cheats_json(&mut self, json: &str) -> bool {
+        match Cheats::from_json(json, &self.rom_hash) {
+            Ok(Some(cheats)) => {
+                self.runner.chip8.cheats = cheats;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Starts or stops counting executions and memory accesses per address.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.runner.chip8.profiler.set_enabled(enabled);
+    }
+
+    /// Discards the counts of the profiler.
+    pub fn clear_profile(&mut self) {
+        self.runner.chip8.profiler.clear();
+    }
+
+    /// Returns the execution heat of `len` addresses starting at `start`,
+    /// from `0.0` to `1.0`, to overlay on the disassembly view.
+    #[must_use]
+    pub fn execution_heatmap(&self, start: usize, len: usize) -> Vec<f32> {
+        self.runner.chip8.profiler.execution_heatmap(start, len)
+    }
+
+    /// Returns the read and write heat of `len` addresses starting at
+    /// `start`, from `0.0` to `1.0`, to overlay on the memory view.
+    #[must_use]
+    pub fn access_heatmap(&self, start: usize, len: usize) -> Vec<f32> {
+        self.runner.chip8.profiler.access_heatmap(start, len)
+    }
+
+    /// Returns up to `count` of the most executed instructions, the hottest
+    /// first, each with its execution count and disassembly.
+    #[must_use]
+    pub fn hottest_instructions(&self, count: usize) -> Vec<String> {
+        let chip8 = &self.runner.chip8;
+        chip8
+            .profiler
+            .hottest(count)
+            .into_iter()
+            .map(|(address, executions)| {
+                let instruction = chip8
+                    .bus
+                    .memory
+                    .read_opcode(address)
+                    .ok()
+                    .and_then(|opcode| {
+                        disassembler::disassemble_as(opcode, self.syntax, &chip8.labels)
+                    })
+                    .unwrap_or_else(|| "???".into());
+                format!(
+                    "{}  {executions:>10}  {instruction}",
+                    chip8.labels.format_address(address)
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the code and data coverage of `len` bytes starting at `start`
+    /// as JSON, based on the counts of the profiler.
+    #[must_use]
+    pub fn coverage_json(&self, start: usize, len: usize) -> String {
+        Coverage::from_profiler(&self.runner.chip8.profiler, start, len).to_json()
+    }
+}
+
+impl WebEmulator {
+    /// Returns the Mega-Chip state while Mega-Chip mode is active.
+    /// Returns the current display as RGB pixels, without phosphor
+    /// persistence.
+    fn display_rgb(&self) -> Vec<u8> {
+        self.megachip().map_or_else(
+            || self.runner.chip8.bus.graphics.as_rgb8(),
+            MegaChip::as_rgb8,
+        )
+    }
+
+    fn megachip(&self) -> Option<&MegaChip> {
+        self.runner
+            .chip8
+            .bus
+            .megachip
+            .as_ref()
+            .filter(|megachip| megachip.is_active())
+    }
+
+    /// Presses and releases the keys whose touches changed from the given
+    /// held keys.
+    fn apply_touches(&mut self, before: u16) {
+        let after = self.touch_pad.keys();
+        for key_code in 0..16 {
+            if (before ^ after) & 1 << key_code != 0 {
+                self.set_key_state(key_code, after & 1 << key_code != 0);
+            }
+        }
+    }
+
+    /// Keeps the save state in the given slot in `localStorage`, so it
+    /// survives a page reload.
+    fn persist_save_slot(&self, slot: usize) {
+        let Some(state) = self.runner.save_slot(slot) else {
+            return;
+        };
+        if let Err(err) = webstorage::save_save_state(&self.rom_hash, slot, state) {
+            log::warn!("cannot store save state: {err:?}");
+        }
+    }
+
+    /// Replaces the save slots with the ones kept in `localStorage` for the
+    /// loaded ROM.
+    fn restore_save_slots(&mut self) {
+        for slot in 0..SLOTS {
+            let state = webstorage::load_save_state(&self.rom_hash, slot).unwrap_or_else(|err| {
+                log::warn!("cannot load save state: {err:?}");
+                None
+            });
+            self.runner.set_save_slot(slot, state);
+        }
+    }
+
+    /// Queues the sound of the buzzer into Web Audio once it is unlocked.
+    fn queue_audio(&mut self) {
+        let bus = &self.runner.chip8.bus;
+        let playing = bus
+            .clock
+            .sound_timer
+            .load(std::sync::atomic::Ordering::SeqCst)
+            > 0;
+        if let Err(err) = self.web_audio.queue(&mut self.synth, &bus.audio, playing) {
+            log::warn!("cannot queue audio: {err:?}");
+        }
+    }
+
+    /// Updates the state of the Chip8 key bound to the given host key.
+    fn update_key(&mut self, key: &str, pressed: bool) -> bool {
+        let Some(key_code) = self.keymap.key_code(key) else {
+            return false;
+        };
+        self.set_key_state(key_code, pressed);
+        true
+    }
+
+    /// Updates the state of the given Chip8 key, or hands it to the netplay
+    /// session if one is running.
+    fn set_key_state(&mut self, key_code: u8, pressed: bool) {
+        if pressed {
+            self.synth.click();
+        }
+        if let Some(lockstep) = &mut self.netplay {
+            lockstep.update_key_state(key_code, pressed);
+        } else {
+            self.runner.chip8.update_key_state(key_code, pressed);
+        }
+    }
+}
+
+/// Fetches the ROM at the given URL with a CORS request, e.g. the one
+/// returned by [`WebEmulator::rom_url`], and resolves to its bytes.
+///
+/// # Errors
+///
+/// Rejects if the request fails, e.g. because the server does not allow
+/// cross-origin requests, or if the server answers with an error status.
+// JavaScript futures live on the single thread of the page
+#[allow(clippy::future_not_send)]
+#[wasm_bindgen]
+pub async fn fetch_rom(url: String) -> Result<Vec<u8>, JsValue> {
+    webrom::fetch_rom(&url).await
+}
+
+/// The bare interpreter core, exported to JavaScript as `Chip8` for pages
+/// that bring their own frontend.
+///
+/// Unlike [`WebEmulator`], it has no runner, keymap or display options: the
+/// page executes instructions with `step`, ticks the timers with `tick60hz`
+/// once per frame, draws the `framebuffer` and passes the pressed keys to
+/// `setKeys`.
+///
+/// To draw without copying the pixels out of wasm memory, the page calls
+/// `displayView` for an RGBA view over the display and blits it into an
+/// `ImageData` whenever `displayGeneration` changed.
+#[wasm_bindgen(js_name = Chip8)]
+#[derive(Debug)]
+pub struct JsChip8 {
+    chip8: Chip8,
+    on_beep: Option<js_sys::Function>,
+    beeping: bool,
+    /// The RGBA pixels behind `displayView`, allocated for the largest
+    /// display so that they never move.
+    rgba: Vec<u8>,
+    /// The framebuffer `rgba` was last rendered from.
+    rendered: Option<graphics::Framebuffer>,
+    /// Incremented whenever `rgba` changes.
+    generation: u32,
+}
+
+// `wasm-bindgen` cannot export `const fn`s.
+#[allow(clippy::missing_const_for_fn)]
+#[wasm_bindgen(js_class = Chip8)]
+impl JsChip8 {
+    /// Creates a system running the given ROM bytes. The timers only tick
+    /// through `tick60hz`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ROM does not fit into memory.
+    #[wasm_bindgen(constructor)]
+    pub fn new(rom: &[u8]) -> Result<Self, JsError> {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_data(rom.to_vec())?;
+        Ok(Self {
+            chip8,
+            on_beep: None,
+            beeping: false,
+            rgba: vec![0xFF; graphics::MAX_PIXEL_COUNT * 4],
+            rendered: None,
+            generation: 0,
+        })
+    }
+
+    /// Executes up to `n` instructions and returns how many were executed.
+    /// Stops early once the program halts or runs past the end of memory.
+    /// Instructions spent waiting for a key or the vblank interrupt count as
+    /// executed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an instruction cannot be executed.
+    pub fn step(&mut self, n: u32) -> Result<u32, JsError> {
+        for executed in 0..n {
+            let result = self.chip8.step()?;
+            self.update_beep();
+            if matches!(result, StepResult::Loop | StepResult::End) {
+                return Ok(executed + 1);
+            }
+        }
+        Ok(n)
+    }
+
+    /// Decrements the delay and sound timers once and raises the vblank
+    /// interrupt. Call this once per 60Hz frame.
+    #[wasm_bindgen(js_name = tick60hz)]
+    pub fn tick_60hz(&mut self) {
+        self.chip8.tick_timers();
+        self.update_beep();
+    }
+
+    /// Returns the palette index of every pixel, row by row.
+    #[must_use]
+    pub fn framebuffer(&self) -> Vec<u8> {
+        let graphics = &self.chip8.bus.graphics;
+        graphics.iter().map(|(_, _, pixel)| pixel).collect()
+    }
+
+    /// Returns the width of the display in pixels.
+    #[must_use]
+    pub fn width(&self) -> usize {
+        graphics::WIDTH
+    }
+
+    /// Returns the height of the display in pixels, which changes with the
+    /// two-page hires mode.
+    #[must_use]
+    pub fn height(&self) -> usize {
+        self.chip8.bus.graphics.height()
+    }
+
+    /// Renders the display into the RGBA pixels behind `displayView` if it
+    /// changed, and returns the generation of the pixels. The generation
+    /// only changes when the pixels do, so the page can skip drawing
+    /// otherwise.
+    #[wasm_bindgen(js_name = displayGeneration)]
+    pub fn display_generation(&mut self) -> u32 {
+        let graphics = self.chip8.bus.graphics;
+        if self.rendered != Some(graphics) {
+            for (rgba, color) in self.rgba.chunks_exact_mut(4).zip(graphics.colors()) {
+                rgba[..3].copy_from_slice(&color.as_array());
+            }
+            self.rendered = Some(graphics);
+            self.generation = self.generation.wrapping_add(1);
+        }
+        self.generation
+    }
+
+    /// Returns a `Uint8Array` viewing the RGBA pixels of the display in wasm
+    /// memory, `width * height * 4` bytes row by row, after rendering them
+    /// like `displayGeneration`. Wrap it in a `Uint8ClampedArray` over the
+    /// same buffer to create an `ImageData`. The view stays valid across
+    /// frames until the height changes or wasm memory grows, which detaches
+    /// it and leaves it empty.
+    #[wasm_bindgen(js_name = displayView)]
+    pub fn display_view(&mut self) -> js_sys::Uint8Array {
+        self.display_generation();
+        let memory: js_sys::WebAssembly::Memory = wasm_bindgen::memory().unchecked_into();
+        let len = graphics::WIDTH * self.chip8.bus.graphics.height() * 4;
+        // pointers and lengths are 32 bits wide on wasm32
+        #[allow(clippy::cast_possible_truncation)]
+        js_sys::Uint8Array::new_with_byte_offset_and_length(
+            &memory.buffer(),
+            self.rgba.as_ptr() as u32,
+            len as u32,
+        )
+    }
+
+    /// Sets the state of all 16 keys from a bitmask, where bit `n` is set if
+    /// key `n` is pressed.
+    #[wasm_bindgen(js_name = setKeys)]
+    pub fn set_keys(&mut self, mask: u16) {
+        self.chip8.set_keys(mask);
+    }
+
+    /// Registers a callback that is called with `true` when the sound timer
+    /// starts and with `false` when it runs out, e.g. to start and stop an
+    /// oscillator.
+    #[wasm_bindgen(js_name = onBeep)]
+    pub fn on_beep(&mut self, callback: js_sys::Function) {
+        self.on_beep = Some(callback);
+    }
+}
+
+impl JsChip8 {
+    /// Calls the beep callback if the sound started or stopped.
+    fn update_beep(&mut self) {
+        let beeping = self
+            .chip8
+            .bus
+            .clock
+            .sound_timer
+            .load(std::sync::atomic::Ordering::SeqCst)
+            > 0;
+        if beeping != self.beeping {
+            self.beeping = beeping;
+            if let Some(callback) = &self.on_beep {
+                // an exception in the callback must not stop the emulation
+                let _ = callback.call1(&JsValue::NULL, &JsValue::from_bool(beeping));
+            }
+        }
+    }
+}
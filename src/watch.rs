@@ -0,0 +1,125 @@
+//! This module watches the file of the loaded ROM and reloads it whenever it
+//! changes on disk, for a tight edit-assemble-run loop with external tools
+//! such as Octo.
+//!
+//! The directory containing the ROM is watched rather than the file itself,
+//! since assemblers and editors often replace the file instead of writing
+//! into it. Frontends call [`RomWatcher::reload`] regularly, e.g. once per
+//! frame.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::Chip8;
+
+/// Watches a ROM file for changes.
+#[derive(Debug)]
+pub struct RomWatcher {
+    /// The watched ROM file.
+    path: PathBuf,
+    /// The watcher, which stops watching once dropped.
+    _watcher: RecommendedWatcher,
+    /// The file system events of the directory containing the ROM.
+    events: mpsc::Receiver<notify::Result<Event>>,
+}
+
+impl RomWatcher {
+    /// Starts watching the ROM file at the given path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path cannot be resolved or watched.
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().canonicalize()?;
+        let dir = path.parent().unwrap_or(&path).to_path_buf();
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(io::Error::other)?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .map_err(io::Error::other)?;
+        Ok(Self {
+            path,
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    /// Returns the path of the watched ROM file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns whether the ROM file was created or modified since the
+    /// previous call. All pending events are consumed, so a burst of writes
+    /// counts as a single change.
+    #[must_use]
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        for event in self.events.try_iter() {
+            let Ok(event) = event else {
+                continue;
+            };
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                && event.paths.contains(&self.path)
+            {
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Resets the given [`Chip8`] and loads the ROM file again if it changed
+    /// since the previous call. Returns whether it was reloaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the changed file cannot be loaded, e.g. because it
+    /// is still being written. The system is left untouched then, and the
+    /// next change is picked up again.
+    pub fn reload(&self, chip8: &mut Chip8) -> io::Result<bool> {
+        if !self.changed() {
+            return Ok(false);
+        }
+        chip8.load_rom_file(&self.path)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs, thread,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_reload() {
+        let dir = std::env::temp_dir().join(format!("chip8-watch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.ch8");
+        fs::write(&path, [0x60, 0x01]).unwrap();
+
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_file(&path).unwrap();
+        let watcher = RomWatcher::new(&path).unwrap();
+        assert!(!watcher.reload(&mut chip8).unwrap());
+
+        // 6002: V0 = 2
+        fs::write(&path, [0x60, 0x02]).unwrap();
+        let start = Instant::now();
+        while !watcher.reload(&mut chip8).unwrap() {
+            assert!(start.elapsed() < Duration::from_secs(5), "no change seen");
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(chip8.bus.memory[0x201], 0x02);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
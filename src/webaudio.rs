@@ -0,0 +1,107 @@
+//! This module plays the buzzer through Web Audio in the browser.
+//!
+//! Browsers only let a page start audio in response to a user interaction,
+//! so the [`WebAudio`] output stays silent until [`WebAudio::unlock`] was
+//! called from a click or key handler. From then on, every frame queues the
+//! samples of the [`Synth`] a little ahead of the playback position, which
+//! keeps the waveforms and XO-CHIP audio patterns of the desktop frontends.
+
+use wasm_bindgen::JsValue;
+use web_sys::{AudioContext, AudioContextState};
+
+use crate::audio::{Audio, Synth};
+
+/// How far ahead of the playback position samples are queued, in seconds.
+/// This has to cover the time between two frames.
+pub const LOOKAHEAD: f64 = 0.05;
+
+/// The Web Audio output of the buzzer.
+#[derive(Debug, Default)]
+pub struct WebAudio {
+    /// The audio context, created on the first unlock.
+    context: Option<AudioContext>,
+
+    /// The context time the queued samples end at.
+    next_time: f64,
+}
+
+impl WebAudio {
+    /// Creates a locked [`WebAudio`] output.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates or resumes the audio context. This has to be called from the
+    /// handler of a user interaction, e.g. the first click or key press.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the browser does not support Web Audio.
+    pub fn unlock(&mut self) -> Result<(), JsValue> {
+        let context = if let Some(context) = &self.context {
+            context
+        } else {
+            let context = self.context.insert(AudioContext::new()?);
+            log::debug!("created an audio context at {} Hz", context.sample_rate());
+            context
+        };
+        // the context runs once the returned promise resolves, which
+        // `is_unlocked` reports
+        let _ = context.resume()?;
+        Ok(())
+    }
+
+    /// Returns whether audio is unlocked and playing.
+    #[must_use]
+    pub fn is_unlocked(&self) -> bool {
+        self.context
+            .as_ref()
+            .is_some_and(|context| context.state() == AudioContextState::Running)
+    }
+
+    /// Queues the samples of the buzzer up to [`LOOKAHEAD`] seconds ahead of
+    /// the playback position. Does nothing while audio is locked.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the samples cannot be scheduled.
+    pub fn queue(
+        &mut self,
+        synth: &mut Synth,
+        audio: &Audio,
+        playing: bool,
+    ) -> Result<(), JsValue> {
+        let Some(context) = self.context.as_ref().filter(|_| self.is_unlocked()) else {
+            return Ok(());
+        };
+        let now = context.current_time();
+        if self.next_time > 0.0 && self.next_time < now {
+            log::debug!(
+                "audio underrun of {:.1} ms",
+                (now - self.next_time) * 1000.0
+            );
+        }
+        self.next_time = self.next_time.max(now);
+        let sample_rate = context.sample_rate();
+        // the amount of samples is small and positive
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let count = ((now + LOOKAHEAD - self.next_time) * f64::from(sample_rate)) as u32;
+        if count == 0 {
+            return Ok(());
+        }
+
+        let mut samples = vec![0.0; count as usize];
+        // sample rates are whole numbers
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        synth.fill(&mut samples, sample_rate as u32, audio, playing);
+        let buffer = context.create_buffer(1, count, sample_rate)?;
+        buffer.copy_to_channel(&samples, 0)?;
+        let source = context.create_buffer_source()?;
+        source.set_buffer(Some(&buffer));
+        source.connect_with_audio_node(&context.destination())?;
+        source.start_with_when(self.next_time)?;
+        self.next_time += f64::from(count) / f64::from(sample_rate);
+        Ok(())
+    }
+}
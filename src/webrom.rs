@@ -0,0 +1,56 @@
+//! This module loads ROMs from links in the browser frontend, so people can
+//! share a URL that starts a game right away.
+//!
+//! A page URL like `?rom=https://example.com/pong.ch8` names the ROM in the
+//! [`ROM_PARAM`] query parameter. The ROM is then fetched with a CORS
+//! request, so the server hosting it has to allow cross-origin requests.
+
+use js_sys::{ArrayBuffer, Uint8Array};
+use wasm_bindgen::{JsCast, JsError, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{RequestInit, RequestMode, Response};
+
+/// The query parameter holding the URL of the ROM to load.
+pub const ROM_PARAM: &str = "rom";
+
+/// Returns the URL of the ROM named in the query string of the page, if
+/// any.
+#[must_use]
+pub fn rom_url() -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    web_sys::UrlSearchParams::new_with_str(&search)
+        .ok()?
+        .get(ROM_PARAM)
+        .filter(|url| !url.is_empty())
+}
+
+/// Fetches the ROM at the given URL.
+///
+/// # Errors
+///
+/// Returns an error if the request fails, e.g. because the server does not
+/// allow cross-origin requests, or if the server answers with an error
+/// status.
+// JavaScript futures live on the single thread of the page
+#[allow(clippy::future_not_send)]
+pub async fn fetch_rom(url: &str) -> Result<Vec<u8>, JsValue> {
+    let window = web_sys::window().ok_or_else(|| JsError::new("no window"))?;
+    let init = RequestInit::new();
+    init.set_mode(RequestMode::Cors);
+    // the browser hides why a request failed, but a missing CORS header is
+    // by far the most common reason for links to other sites
+    let response = JsFuture::from(window.fetch_with_str_and_init(url, &init))
+        .await
+        .map_err(|_| {
+            JsError::new(&format!(
+                "cannot fetch {url}, the server may not allow cross-origin requests"
+            ))
+        })?;
+    let response: Response = response.dyn_into()?;
+    if !response.ok() {
+        let status = response.status();
+        return Err(JsError::new(&format!("cannot fetch {url}: HTTP {status}")).into());
+    }
+    let buffer: ArrayBuffer = JsFuture::from(response.array_buffer()?).await?.dyn_into()?;
+    Ok(Uint8Array::new(&buffer).to_vec())
+}
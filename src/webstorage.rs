@@ -0,0 +1,79 @@
+//! This module keeps the settings and save states of the browser frontend in
+//! `localStorage`, so they survive page reloads.
+//!
+//! The settings are stored as the TOML of a [`Config`] under [`CONFIG_KEY`],
+//! like the configuration file of the desktop frontends. Save states are
+//! stored as JSON per ROM and slot, see [`save_state_key`].
+
+use wasm_bindgen::{JsError, JsValue};
+
+use crate::{config::Config, history::SaveState};
+
+/// The key the settings are stored under.
+pub const CONFIG_KEY: &str = "chip8-config";
+
+/// Returns the key the save state of the ROM with the given hash is stored
+/// under for the given slot.
+#[must_use]
+pub fn save_state_key(rom_hash: &str, slot: usize) -> String {
+    format!("chip8-state-{rom_hash}-{slot}")
+}
+
+/// Returns the `localStorage` of the page.
+fn local_storage() -> Result<web_sys::Storage, JsValue> {
+    web_sys::window()
+        .ok_or_else(|| JsError::new("no window"))?
+        .local_storage()?
+        .ok_or_else(|| JsError::new("no local storage").into())
+}
+
+/// Loads the settings. Returns [`None`] if none were saved yet.
+///
+/// # Errors
+///
+/// Returns an error if `localStorage` is not available or the stored
+/// settings are invalid.
+pub fn load_config() -> Result<Option<Config>, JsValue> {
+    let Some(toml) = local_storage()?.get_item(CONFIG_KEY)? else {
+        return Ok(None);
+    };
+    Ok(Some(Config::from_toml(&toml).map_err(JsError::from)?))
+}
+
+/// Saves the settings.
+///
+/// # Errors
+///
+/// Returns an error if `localStorage` is not available or full.
+pub fn save_config(config: &Config) -> Result<(), JsValue> {
+    local_storage()?.set_item(CONFIG_KEY, &config.to_toml())
+}
+
+/// Loads the save state of the ROM with the given hash from the given slot.
+/// Returns [`None`] if the slot is empty.
+///
+/// # Errors
+///
+/// Returns an error if `localStorage` is not available or the stored state
+/// is invalid.
+pub fn load_save_state(rom_hash: &str, slot: usize) -> Result<Option<SaveState>, JsValue> {
+    let Some(json) = local_storage()?.get_item(&save_state_key(rom_hash, slot))? else {
+        return Ok(None);
+    };
+    Ok(Some(serde_json::from_str(&json).map_err(JsError::from)?))
+}
+
+/// Saves the save state of the ROM with the given hash into the given slot.
+///
+/// # Errors
+///
+/// Returns an error if `localStorage` is not available or full.
+///
+/// # Panics
+///
+/// Never panics in practice, since a [`SaveState`] always serializes to
+/// JSON.
+pub fn save_save_state(rom_hash: &str, slot: usize, state: &SaveState) -> Result<(), JsValue> {
+    let json = serde_json::to_string(state).expect("save states are always serializable");
+    local_storage()?.set_item(&save_state_key(rom_hash, slot), &json)
+}
@@ -0,0 +1,28 @@
+//! This module provides timers in the browser, so a
+//! [`crate::runner::Chip8Runner`] can run as a future on the single thread of
+//! the page, see [`crate::runner::Chip8Runner::run_async`].
+
+use std::time::Duration;
+
+use js_sys::{Function, Promise};
+use wasm_bindgen_futures::JsFuture;
+
+/// Waits for the given time with `setTimeout`. The browser rounds the time
+/// up to its timer resolution, usually a few milliseconds. Without a window,
+/// e.g. in a worker, it resolves right away.
+// JavaScript futures live on the single thread of the page
+#[allow(clippy::future_not_send)]
+pub async fn sleep(duration: Duration) {
+    let millis = i32::try_from(duration.as_millis()).unwrap_or(i32::MAX);
+    let promise = Promise::new(&mut |resolve: Function, _| {
+        let scheduled = web_sys::window().is_some_and(|window| {
+            window
+                .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, millis)
+                .is_ok()
+        });
+        if !scheduled {
+            let _ = resolve.call0(&resolve);
+        }
+    });
+    let _ = JsFuture::from(promise).await;
+}
@@ -0,0 +1,101 @@
+//! Plays back the recorded runs in `tests/replays` and compares the display
+//! each of them ends with against the recorded one, so refactors of the core
+//! can be checked against the behavior of real programs.
+//!
+//! Every `.toml` file in the directory describes one run: the ROM it loads,
+//! relative to the file, the [`Variant`], the amount of instructions to
+//! execute, the SHA-1 digest of the display text after them, see
+//! [`headless::dump_display`], and the [`Movie`] to play back. The timers tick
+//! every [`INSTRUCTIONS_PER_TICK`] instructions, so every run is
+//! reproducible.
+//!
+//! Only ROMs that may be redistributed belong into the corpus. The popular
+//! games, e.g. the classic Pong, Tetris or Space Invaders ROMs, were
+//! published without a license, so the corpus plays the public domain
+//! built-in ROMs of [`roms`] instead. Between them they draw with the font,
+//! read keys and wait for them, use the random number generator and depend
+//! on the timers and the vblank quirk.
+//!
+//! To add a run, record a [`Movie`] of it and add a file with an empty
+//! `display` digest. The failing test prints the digest and the display the
+//! run ended with, to be checked and filled in.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chip8::{clock::FixedStep, headless, quirks::Variant, replay::Movie, roms, Chip8};
+use serde::Deserialize;
+
+/// The amount of instructions per timer tick, the classic 660 instructions
+/// per second.
+const INSTRUCTIONS_PER_TICK: u32 = 11;
+
+/// A recorded run.
+#[derive(Debug, Deserialize)]
+struct Run {
+    /// The path of the ROM file, relative to the file of the run.
+    rom: PathBuf,
+    /// The variant whose quirks the ROM runs with.
+    #[serde(default)]
+    variant: Variant,
+    /// The amount of instructions to execute, including the ones spent
+    /// waiting for a key or the vblank interrupt.
+    instructions: u64,
+    /// The SHA-1 digest of the display text after the run.
+    display: String,
+    /// The input to play back.
+    movie: Movie,
+}
+
+impl Run {
+    /// Plays back the run with the ROM read from `dir`, and returns the
+    /// display it ended with.
+    fn play(self, dir: &Path) -> Result<String, String> {
+        let rom = fs::read(dir.join(&self.rom))
+            .map_err(|err| format!("cannot read {}: {err}", self.rom.display()))?;
+        let mut chip8 = Chip8::new();
+        chip8.history.set_depth(0);
+        chip8.set_variant(self.variant);
+        chip8.set_time_source(FixedStep::new(INSTRUCTIONS_PER_TICK));
+        chip8.play(rom, self.movie).map_err(|err| err.to_string())?;
+        for _ in 0..self.instructions {
+            chip8.step().map_err(|err| err.to_string())?;
+        }
+        Ok(headless::dump_display(&chip8.bus.graphics))
+    }
+}
+
+#[test]
+fn test_replays() {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/replays");
+    let mut paths: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "the corpus is empty");
+
+    // Every run is checked before failing, so all regressions show at once
+    let mut failures = Vec::new();
+    for path in &paths {
+        let name = path.file_stem().unwrap().to_string_lossy();
+        let run: Run = toml::from_str(&fs::read_to_string(path).unwrap())
+            .unwrap_or_else(|err| panic!("{name} is not a valid run: {err}"));
+        let expected = run.display.clone();
+        match run.play(&dir) {
+            Ok(display) => {
+                let digest = roms::hash(display.as_bytes());
+                if digest != expected {
+                    failures.push(format!(
+                        "{name}: the display {digest} differs from {expected}:\n{display}"
+                    ));
+                }
+            }
+            Err(err) => failures.push(format!("{name}: {err}")),
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}